@@ -0,0 +1,87 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Procedural macros for the Exonum framework.
+
+#![recursion_limit = "128"]
+#![deny(
+    missing_debug_implementations,
+    missing_docs,
+    unsafe_code,
+    bare_trait_objects
+)]
+
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+extern crate syn;
+
+mod schema;
+
+use proc_macro::TokenStream;
+
+/// Derives the methods of a service schema from the declarations of its indexes.
+///
+/// The schema is a struct with a single type parameter `T` and a field of type `T`
+/// holding the database view. Each of the other fields declares an index as
+/// `PhantomData<Index<T, ...>>`, where `Index` is one of the index types from
+/// the `exonum::storage` module. The name of the index is the service name specified
+/// in the `#[exonum(service = "...")]` attribute of the struct and the name of the field,
+/// separated by a dot; it can be overridden with the `#[exonum(name = "...")]` attribute
+/// of the field. An index family is declared with the `#[exonum(family = "...")]` attribute
+/// specifying the type of the index identifiers.
+///
+/// The derive generates:
+///
+/// - `new` and `try_new` constructors accepting the view, which check the types
+///   of the indexes stored in the database, and an `AsMut` implementation for the view.
+/// - An accessor for each index, which has the same name and documentation as the field
+///   and accepts the index identifier for index families.
+/// - A mutable accessor for each index with the `_mut` suffix for `Schema<&mut Fork>`.
+/// - `state_hash` method returning the root hashes of the `ProofMapIndex`,
+///   `ProofListIndex` and `MmrIndex` indexes in the order of their declaration;
+///   index families are not included.
+/// - `check_index_types` method checking that the indexes stored in the database
+///   have the declared types.
+///
+/// # Examples
+///
+/// ```ignore
+/// #[macro_use]
+/// extern crate exonum_derive;
+///
+/// use exonum::crypto::{Hash, PublicKey};
+/// use exonum::storage::{Entry, ProofListIndex, ProofMapIndex};
+///
+/// use std::marker::PhantomData;
+///
+/// #[derive(Debug, Schema)]
+/// #[exonum(service = "cryptocurrency")]
+/// pub struct Schema<T> {
+///     view: T,
+///     /// Wallets by their public keys.
+///     wallets: PhantomData<ProofMapIndex<T, PublicKey, Wallet>>,
+///     /// History of the wallet.
+///     #[exonum(family = "PublicKey")]
+///     wallet_history: PhantomData<ProofListIndex<T, Hash>>,
+///     /// Number of the created wallets.
+///     #[exonum(name = "cryptocurrency.counter")]
+///     wallets_count: PhantomData<Entry<T, u64>>,
+/// }
+/// ```
+#[proc_macro_derive(Schema, attributes(exonum))]
+pub fn schema(input: TokenStream) -> TokenStream {
+    schema::implement_schema(input)
+}
@@ -0,0 +1,398 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use syn::{
+    self, Attribute, Data, DeriveInput, Error, Field, Fields, GenericArgument, GenericParam, Ident,
+    Lit, Meta, NestedMeta, PathArguments, Type,
+};
+
+use std::collections::HashSet;
+
+/// Supported index types: the name of the type in `exonum::storage`, the corresponding
+/// `IndexType` variant and whether the index is Merkelized.
+const INDEX_TYPES: &[(&str, &str, bool)] = &[
+    ("Entry", "Entry", false),
+    ("KeySetIndex", "KeySet", false),
+    ("ListIndex", "List", false),
+    ("SparseListIndex", "SparseList", false),
+    ("MapIndex", "Map", false),
+    ("ProofListIndex", "ProofList", true),
+    ("ProofMapIndex", "ProofMap", true),
+    ("ValueSetIndex", "ValueSet", false),
+    ("MmrIndex", "Mmr", true),
+];
+
+/// Declaration of an index in the schema.
+struct IndexField {
+    field: Ident,
+    docs: Vec<Attribute>,
+    name: String,
+    index: Ident,
+    index_type: Ident,
+    is_merkelized: bool,
+    args: Vec<Type>,
+    family: Option<Type>,
+}
+
+pub fn implement_schema(input: TokenStream) -> TokenStream {
+    let input: DeriveInput = match syn::parse(input) {
+        Ok(input) => input,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    match schema_impl(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn schema_impl(input: &DeriveInput) -> Result<TokenStream2, Error> {
+    let service = match attr_value(&input.attrs, "service")? {
+        Some(service) => service,
+        None => {
+            return Err(Error::new(
+                input.ident.span(),
+                "#[exonum(service = \"...\")] attribute is required",
+            ))
+        }
+    };
+    let view_param = view_param(input)?;
+    let fields = match input.data {
+        Data::Struct(ref data) => match data.fields {
+            Fields::Named(ref fields) => &fields.named,
+            _ => {
+                return Err(Error::new(
+                    input.ident.span(),
+                    "Schema must have named fields",
+                ))
+            }
+        },
+        _ => return Err(Error::new(input.ident.span(), "Schema must be a struct")),
+    };
+
+    let mut view_field = None;
+    let mut indexes = Vec::new();
+    let mut names = HashSet::new();
+    for field in fields {
+        let ident = field.ident.clone().expect("Named field without name");
+        if is_param(&field.ty, &view_param) {
+            if view_field.is_some() {
+                return Err(Error::new(
+                    ident.span(),
+                    "Schema must have a single view field",
+                ));
+            }
+            view_field = Some(ident);
+            continue;
+        }
+        let index = index_field(field, ident, &service, &view_param)?;
+        if !names.insert(index.name.clone()) {
+            return Err(Error::new(
+                index.field.span(),
+                format!("Index name `{}` is used more than once", index.name),
+            ));
+        }
+        indexes.push(index);
+    }
+    let view_field = match view_field {
+        Some(field) => field,
+        None => {
+            return Err(Error::new(
+                input.ident.span(),
+                format!(
+                    "Schema must have a field of type `{}` holding the view",
+                    view_param
+                ),
+            ))
+        }
+    };
+
+    let schema = &input.ident;
+    let index_fields: Vec<_> = indexes.iter().map(|index| &index.field).collect();
+    let getters = indexes
+        .iter()
+        .map(|index| getter(index, &view_param, &view_field, false));
+    let mut_getters = indexes
+        .iter()
+        .map(|index| getter(index, &view_param, &view_field, true));
+    let state_hash = indexes
+        .iter()
+        .filter(|index| index.is_merkelized && index.family.is_none())
+        .map(|index| &index.field);
+    let type_checks = indexes.iter().map(|index| {
+        let name = &index.name;
+        let index_type = &index.index_type;
+        let is_family = index.family.is_some();
+        quote! {
+            ::exonum::storage::check_index_type(
+                view,
+                #name,
+                ::exonum::storage::IndexType::#index_type,
+                #is_family,
+            )?;
+        }
+    });
+
+    Ok(quote! {
+        impl<#view_param> AsMut<#view_param> for #schema<#view_param> {
+            fn as_mut(&mut self) -> &mut #view_param {
+                &mut self.#view_field
+            }
+        }
+
+        impl<#view_param> #schema<#view_param>
+        where
+            #view_param: AsRef<dyn (::exonum::storage::Snapshot)>,
+        {
+            /// Creates a new schema from the database view.
+            ///
+            /// # Panics
+            ///
+            /// If the indexes of the schema stored in the database have other types
+            /// than the declared ones.
+            pub fn new(view: #view_param) -> Self {
+                Self::try_new(view).unwrap_or_else(|e| panic!("{}", e))
+            }
+
+            /// Creates a new schema from the database view, checking that the indexes
+            /// of the schema stored in the database have the declared types.
+            pub fn try_new(view: #view_param) -> Result<Self, ::exonum::storage::Error> {
+                let schema = #schema {
+                    #view_field: view,
+                    #(#index_fields: ::std::marker::PhantomData,)*
+                };
+                schema.check_index_types()?;
+                Ok(schema)
+            }
+
+            #(#getters)*
+
+            /// Returns the root hashes of the Merkelized indexes of the schema.
+            pub fn state_hash(&self) -> Vec<::exonum::crypto::Hash> {
+                vec![#(self.#state_hash().merkle_root()),*]
+            }
+
+            /// Checks that the indexes of the schema, which are stored in the database,
+            /// have the declared types.
+            pub fn check_index_types(&self) -> Result<(), ::exonum::storage::Error> {
+                let view = self.#view_field.as_ref();
+                #(#type_checks)*
+                Ok(())
+            }
+        }
+
+        impl<'a> #schema<&'a mut ::exonum::storage::Fork> {
+            #(#mut_getters)*
+        }
+    })
+}
+
+fn getter(
+    index: &IndexField,
+    view_param: &Ident,
+    view_field: &Ident,
+    is_mut: bool,
+) -> TokenStream2 {
+    let name = &index.name;
+    let storage_index = &index.index;
+    let args = &index.args;
+    let docs = if index.docs.is_empty() {
+        let doc = format!("Returns the `{}` index.", name);
+        quote!(#[doc = #doc])
+    } else {
+        let docs = &index.docs;
+        quote!(#(#docs)*)
+    };
+    let (method, self_ref, view_ref, view_type) = if is_mut {
+        (
+            Ident::new(&format!("{}_mut", index.field), index.field.span()),
+            quote!(&mut self),
+            quote!(&mut self.#view_field),
+            quote!(&mut ::exonum::storage::Fork),
+        )
+    } else {
+        (
+            index.field.clone(),
+            quote!(&self),
+            quote!(&self.#view_field),
+            quote!(&#view_param),
+        )
+    };
+    match index.family {
+        Some(ref id_type) => quote! {
+            #docs
+            pub fn #method(
+                #self_ref,
+                index_id: &#id_type,
+            ) -> ::exonum::storage::#storage_index<#view_type, #(#args),*> {
+                ::exonum::storage::#storage_index::new_in_family(#name, index_id, #view_ref)
+            }
+        },
+        None => quote! {
+            #docs
+            pub fn #method(#self_ref) -> ::exonum::storage::#storage_index<#view_type, #(#args),*> {
+                ::exonum::storage::#storage_index::new(#name, #view_ref)
+            }
+        },
+    }
+}
+
+/// Returns the type parameter of the schema, which is the type of the view.
+fn view_param(input: &DeriveInput) -> Result<Ident, Error> {
+    let params = &input.generics.params;
+    if params.len() == 1 {
+        if let GenericParam::Type(ref param) = params[0] {
+            return Ok(param.ident.clone());
+        }
+    }
+    Err(Error::new(
+        input.ident.span(),
+        "Schema must have a single type parameter for the view",
+    ))
+}
+
+fn is_param(ty: &Type, param: &Ident) -> bool {
+    match *ty {
+        Type::Path(ref path) => path.qself.is_none() && path.path.is_ident(param.clone()),
+        _ => false,
+    }
+}
+
+/// Parses the declaration of an index, which has the `PhantomData<Index<T, ...>>` type.
+fn index_field(
+    field: &Field,
+    ident: Ident,
+    service: &str,
+    view_param: &Ident,
+) -> Result<IndexField, Error> {
+    let invalid_type = || {
+        Error::new(
+            ident.span(),
+            format!(
+                "Index must be declared as `PhantomData<Index<{}, ...>>`, where `Index` \
+                 is an index type from `exonum::storage`",
+                view_param
+            ),
+        )
+    };
+
+    let phantom_args = last_segment_args(&field.ty, "PhantomData").ok_or_else(&invalid_type)?;
+    if phantom_args.len() != 1 {
+        return Err(invalid_type());
+    }
+    let index_ty = &phantom_args[0];
+    let (index, index_type, is_merkelized) = INDEX_TYPES
+        .iter()
+        .filter_map(|&(name, index_type, is_merkelized)| {
+            last_segment_args(index_ty, name).map(|_| (name, index_type, is_merkelized))
+        }).next()
+        .ok_or_else(&invalid_type)?;
+    let mut args = last_segment_args(index_ty, index).ok_or_else(&invalid_type)?;
+    if args.is_empty() || !is_param(&args[0], view_param) {
+        return Err(invalid_type());
+    }
+    args.remove(0);
+
+    let name = match attr_value(&field.attrs, "name")? {
+        Some(name) => name,
+        None => format!("{}.{}", service, ident),
+    };
+    let family = match attr_value(&field.attrs, "family")? {
+        Some(family) => Some(syn::parse_str::<Type>(&family).map_err(|_| {
+            Error::new(
+                ident.span(),
+                format!("Invalid index family key type `{}`", family),
+            )
+        })?),
+        None => None,
+    };
+    let docs = field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("doc"))
+        .cloned()
+        .collect();
+
+    Ok(IndexField {
+        field: ident,
+        docs,
+        name,
+        index: Ident::new(index, Span::call_site()),
+        index_type: Ident::new(index_type, Span::call_site()),
+        is_merkelized,
+        args,
+        family,
+    })
+}
+
+/// Returns the type arguments of the type if the last segment of its path has
+/// the given name.
+fn last_segment_args(ty: &Type, name: &str) -> Option<Vec<Type>> {
+    let path = match *ty {
+        Type::Path(ref path) if path.qself.is_none() => &path.path,
+        _ => return None,
+    };
+    let segment = path.segments.iter().last()?;
+    if segment.ident != name {
+        return None;
+    }
+    match segment.arguments {
+        PathArguments::AngleBracketed(ref args) => args
+            .args
+            .iter()
+            .map(|arg| match *arg {
+                GenericArgument::Type(ref ty) => Some(ty.clone()),
+                _ => None,
+            }).collect(),
+        _ => None,
+    }
+}
+
+/// Returns the value of the `#[exonum(key = "value")]` attribute with the given key.
+fn attr_value(attrs: &[Attribute], key: &str) -> Result<Option<String>, Error> {
+    let mut value = None;
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("exonum")) {
+        let nested = match attr.parse_meta()? {
+            Meta::List(list) => list.nested,
+            meta => {
+                return Err(Error::new_spanned(
+                    meta,
+                    "Expected #[exonum(key = \"value\")]",
+                ))
+            }
+        };
+        for meta in nested {
+            match meta {
+                NestedMeta::Meta(Meta::NameValue(ref pair))
+                    if pair.ident == "service"
+                        || pair.ident == "name"
+                        || pair.ident == "family" =>
+                {
+                    if pair.ident != key {
+                        continue;
+                    }
+                    match pair.lit {
+                        Lit::Str(ref lit) => value = Some(lit.value()),
+                        ref lit => {
+                            return Err(Error::new_spanned(lit, "Expected a string literal"))
+                        }
+                    }
+                }
+                meta => return Err(Error::new_spanned(meta, "Unknown attribute")),
+            }
+        }
+    }
+    Ok(value)
+}
@@ -31,6 +31,11 @@ use {Schema, CRYPTOCURRENCY_SERVICE_ID};
 pub struct WalletQuery {
     /// Public key of the queried wallet.
     pub pub_key: PublicKey,
+    /// Height of the blockchain state to query. The latest state is used if not specified.
+    ///
+    /// The states at past heights are available only if the node records the state history.
+    #[serde(default)]
+    pub height: Option<Height>,
 }
 
 /// Response to an incoming transaction returned by the REST API.
@@ -75,8 +80,11 @@ pub struct PublicApi;
 
 impl PublicApi {
     /// Endpoint for getting a single wallet.
+    ///
+    /// The returned proofs are built against the `state_hash` of the block at the queried
+    /// height, or of the latest block if the height is not specified.
     pub fn wallet_info(state: &ServiceApiState, query: WalletQuery) -> api::Result<WalletInfo> {
-        let snapshot = state.snapshot();
+        let snapshot = state.snapshot_at(query.height)?;
         let general_schema = blockchain::Schema::new(&snapshot);
         let currency_schema = Schema::new(&snapshot);
 
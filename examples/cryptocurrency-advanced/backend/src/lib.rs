@@ -24,6 +24,8 @@
 #[macro_use]
 extern crate exonum;
 #[macro_use]
+extern crate exonum_derive;
+#[macro_use]
 extern crate failure;
 extern crate serde;
 #[macro_use]
@@ -19,66 +19,38 @@ use exonum::{
     storage::{Fork, ProofListIndex, ProofMapIndex, Snapshot},
 };
 
+use std::marker::PhantomData;
+
 use wallet::Wallet;
 use INITIAL_BALANCE;
 
 /// Database schema for the cryptocurrency.
-#[derive(Debug)]
+///
+/// The accessors of the indexes, including the `_mut` variants for `Schema<&mut Fork>`,
+/// and `state_hash` are generated by the `Schema` derive.
+#[derive(Debug, Schema)]
+#[exonum(service = "cryptocurrency")]
 pub struct Schema<T> {
     view: T,
-}
-
-impl<T> AsMut<T> for Schema<T> {
-    fn as_mut(&mut self) -> &mut T {
-        &mut self.view
-    }
+    /// Returns `ProofMapIndex` with wallets.
+    wallets: PhantomData<ProofMapIndex<T, PublicKey, Wallet>>,
+    /// Returns history of the wallet with the given public key.
+    #[exonum(family = "PublicKey")]
+    wallet_history: PhantomData<ProofListIndex<T, Hash>>,
 }
 
 impl<T> Schema<T>
 where
     T: AsRef<dyn Snapshot>,
 {
-    /// Creates a new schema from the database view.
-    pub fn new(view: T) -> Self {
-        Schema { view }
-    }
-
-    /// Returns `ProofMapIndex` with wallets.
-    pub fn wallets(&self) -> ProofMapIndex<&T, PublicKey, Wallet> {
-        ProofMapIndex::new("cryptocurrency.wallets", &self.view)
-    }
-
-    /// Returns history of the wallet with the given public key.
-    pub fn wallet_history(&self, public_key: &PublicKey) -> ProofListIndex<&T, Hash> {
-        ProofListIndex::new_in_family("cryptocurrency.wallet_history", public_key, &self.view)
-    }
-
     /// Returns wallet for the given public key.
     pub fn wallet(&self, pub_key: &PublicKey) -> Option<Wallet> {
         self.wallets().get(pub_key)
     }
-
-    /// Returns the state hash of cryptocurrency service.
-    pub fn state_hash(&self) -> Vec<Hash> {
-        vec![self.wallets().merkle_root()]
-    }
 }
 
 /// Implementation of mutable methods.
 impl<'a> Schema<&'a mut Fork> {
-    /// Returns mutable `ProofMapIndex` with wallets.
-    pub fn wallets_mut(&mut self) -> ProofMapIndex<&mut Fork, PublicKey, Wallet> {
-        ProofMapIndex::new("cryptocurrency.wallets", &mut self.view)
-    }
-
-    /// Returns history for the wallet by the given public key.
-    pub fn wallet_history_mut(
-        &mut self,
-        public_key: &PublicKey,
-    ) -> ProofListIndex<&mut Fork, Hash> {
-        ProofListIndex::new_in_family("cryptocurrency.wallet_history", public_key, &mut self.view)
-    }
-
     /// Increase balance of the wallet and append new record to its history.
     ///
     /// Panics if there is no wallet with given public key.
@@ -25,9 +25,14 @@ extern crate exonum_testkit;
 extern crate serde_json;
 
 use exonum::{
-    api::node::public::explorer::{TransactionQuery, TransactionResponse},
+    api::{
+        self,
+        node::public::explorer::{TransactionQuery, TransactionResponse},
+    },
     crypto::{self, Hash, PublicKey, SecretKey},
+    helpers::Height,
     messages::{self, RawTransaction, Signed},
+    node::StateHistoryConfig,
 };
 use exonum_testkit::{ApiKind, TestKit, TestKitApi, TestKitBuilder};
 
@@ -205,6 +210,39 @@ fn test_unknown_wallet_request() {
     api.assert_no_wallet(tx.author());
 }
 
+#[test]
+fn test_wallet_at_past_height() {
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(Service)
+        .with_state_history(StateHistoryConfig::default())
+        .create();
+    let api = CryptocurrencyApi {
+        inner: testkit.api(),
+    };
+
+    let (tx_alice, key_alice) = api.create_wallet(ALICE_NAME);
+    let (tx_bob, _) = api.create_wallet(BOB_NAME);
+    testkit.create_block();
+    let tx = Transfer::sign(&tx_alice.author(), &tx_bob.author(), 10, 0, &key_alice);
+    api.transfer(&tx);
+    testkit.create_block();
+
+    let wallet = api.get_wallet(tx_alice.author()).unwrap();
+    assert_eq!(wallet.balance(), 90);
+    let wallet = api
+        .get_wallet_at(tx_alice.author(), Some(Height(1)))
+        .unwrap()
+        .unwrap();
+    assert_eq!(wallet.balance(), 100);
+    let wallet = api.get_wallet_at(tx_alice.author(), Some(Height(0))).unwrap();
+    assert!(wallet.is_none());
+
+    match api.get_wallet_at(tx_alice.author(), Some(Height(3))) {
+        Err(api::Error::NotFound(_)) => {}
+        other => panic!("Unexpected result: {:?}", other),
+    }
+}
+
 /// Wrapper for the cryptocurrency service API allowing to easily use it
 /// (compared to `TestKitApi` calls).
 struct CryptocurrencyApi {
@@ -234,12 +272,24 @@ impl CryptocurrencyApi {
     }
 
     fn get_wallet(&self, pub_key: PublicKey) -> Option<Wallet> {
+        self.get_wallet_at(pub_key, None).unwrap()
+    }
+
+    /// Gets the wallet at the given height, checking that the returned block proof
+    /// corresponds to this height.
+    fn get_wallet_at(
+        &self,
+        pub_key: PublicKey,
+        height: Option<Height>,
+    ) -> Result<Option<Wallet>, api::Error> {
         let wallet_info = self
             .inner
             .public(ApiKind::Service("cryptocurrency"))
-            .query(&WalletQuery { pub_key })
-            .get::<WalletInfo>("v1/wallets/info")
-            .unwrap();
+            .query(&WalletQuery { pub_key, height })
+            .get::<WalletInfo>("v1/wallets/info")?;
+        if let Some(height) = height {
+            assert_eq!(wallet_info.block_proof.block.height(), height);
+        }
 
         let to_wallet = wallet_info.wallet_proof.to_wallet.check().unwrap();
         let wallet = to_wallet
@@ -247,7 +297,7 @@ impl CryptocurrencyApi {
             .find(|(ref k, _)| **k == pub_key)
             .and_then(|tuple| tuple.1)
             .cloned();
-        wallet
+        Ok(wallet)
     }
 
     /// Sends a transfer transaction over HTTP and checks the synchronous result.
@@ -267,7 +317,10 @@ impl CryptocurrencyApi {
         let wallet_info: WalletInfo = self
             .inner
             .public(ApiKind::Service("cryptocurrency"))
-            .query(&WalletQuery { pub_key })
+            .query(&WalletQuery {
+                pub_key,
+                height: None,
+            })
             .get("v1/wallets/info")
             .unwrap();
 
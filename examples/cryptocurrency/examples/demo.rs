@@ -53,6 +53,9 @@ fn node_config() -> NodeConfig {
         mempool: Default::default(),
         services_configs: Default::default(),
         database: Default::default(),
+        pruning: Default::default(),
+        state_history: Default::default(),
+        state_sync: Default::default(),
         thread_pool_size: Default::default(),
     }
 }
@@ -24,6 +24,7 @@ use tempdir::TempDir;
 use std::collections::HashSet;
 
 const NAME: &str = "name";
+const BATCH_SIZE: usize = 2_000;
 
 fn generate_random_kv(len: usize) -> Vec<([u8; KEY_SIZE], Vec<u8>)> {
     let mut rng = XorShiftRng::from_seed([100; 16]);
@@ -100,6 +101,58 @@ fn merkle_patricia_table_merge_insert<T: Database>(b: &mut Bencher, db: &T) {
     });
 }
 
+fn merkle_patricia_table_put<T: Database>(b: &mut Bencher, db: &T) {
+    let data = generate_random_kv(BATCH_SIZE);
+
+    b.iter(|| {
+        let mut fork = db.fork();
+        let mut table = ProofMapIndex::new(NAME, &mut fork);
+        for item in &data {
+            table.put(&item.0, item.1.clone());
+        }
+    });
+}
+
+fn merkle_patricia_table_put_many<T: Database>(b: &mut Bencher, db: &T) {
+    let data = generate_random_kv(BATCH_SIZE);
+
+    b.iter(|| {
+        let mut fork = db.fork();
+        let mut table = ProofMapIndex::new(NAME, &mut fork);
+        table.put_many(data.iter().cloned());
+    });
+}
+
+fn fill_merkle_patricia_table<T: Database>(db: &T, data: &[([u8; KEY_SIZE], Vec<u8>)]) {
+    let mut fork = db.fork();
+    ProofMapIndex::new(NAME, &mut fork).put_many(data.iter().cloned());
+    db.merge(fork.into_patch()).unwrap();
+}
+
+fn merkle_patricia_table_remove<T: Database>(b: &mut Bencher, db: &T) {
+    let data = generate_random_kv(BATCH_SIZE);
+    fill_merkle_patricia_table(db, &data);
+
+    b.iter(|| {
+        let mut fork = db.fork();
+        let mut table: ProofMapIndex<_, _, Vec<u8>> = ProofMapIndex::new(NAME, &mut fork);
+        for item in &data {
+            table.remove(&item.0);
+        }
+    });
+}
+
+fn merkle_patricia_table_remove_many<T: Database>(b: &mut Bencher, db: &T) {
+    let data = generate_random_kv(BATCH_SIZE);
+    fill_merkle_patricia_table(db, &data);
+
+    b.iter(|| {
+        let mut fork = db.fork();
+        let mut table: ProofMapIndex<_, _, Vec<u8>> = ProofMapIndex::new(NAME, &mut fork);
+        table.remove_many(data.iter().map(|item| item.0));
+    });
+}
+
 fn create_rocksdb(tempdir: &TempDir) -> RocksDB {
     let options = DbOptions::default();
     RocksDB::open(tempdir.path(), &options).unwrap()
@@ -123,6 +176,30 @@ fn bench_merkle_patricia_table_insertion_fork_rocksdb(b: &mut Bencher) {
     merkle_patricia_table_fork_insert(b, &db);
 }
 
+fn bench_merkle_patricia_table_put_rocksdb(b: &mut Bencher) {
+    let tempdir = TempDir::new("exonum").unwrap();
+    let db = create_rocksdb(&tempdir);
+    merkle_patricia_table_put(b, &db);
+}
+
+fn bench_merkle_patricia_table_put_many_rocksdb(b: &mut Bencher) {
+    let tempdir = TempDir::new("exonum").unwrap();
+    let db = create_rocksdb(&tempdir);
+    merkle_patricia_table_put_many(b, &db);
+}
+
+fn bench_merkle_patricia_table_remove_rocksdb(b: &mut Bencher) {
+    let tempdir = TempDir::new("exonum").unwrap();
+    let db = create_rocksdb(&tempdir);
+    merkle_patricia_table_remove(b, &db);
+}
+
+fn bench_merkle_patricia_table_remove_many_rocksdb(b: &mut Bencher) {
+    let tempdir = TempDir::new("exonum").unwrap();
+    let db = create_rocksdb(&tempdir);
+    merkle_patricia_table_remove_many(b, &db);
+}
+
 pub fn bench_storage(c: &mut Criterion) {
     ::exonum::crypto::init();
 
@@ -135,4 +212,20 @@ pub fn bench_storage(c: &mut Criterion) {
         "insert with merge merkle table",
         bench_merkle_patricia_table_insertion_merge_rocksdb,
     );
+    c.bench_function(
+        "batch insert merkle table with put",
+        bench_merkle_patricia_table_put_rocksdb,
+    );
+    c.bench_function(
+        "batch insert merkle table with put_many",
+        bench_merkle_patricia_table_put_many_rocksdb,
+    );
+    c.bench_function(
+        "batch remove merkle table with remove",
+        bench_merkle_patricia_table_remove_rocksdb,
+    );
+    c.bench_function(
+        "batch remove merkle table with remove_many",
+        bench_merkle_patricia_table_remove_many_rocksdb,
+    );
 }
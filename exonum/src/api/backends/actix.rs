@@ -134,6 +134,14 @@ impl ResponseError for ApiError {
             ApiError::Io(err) => HttpResponse::InternalServerError().body(err.to_string()),
             ApiError::Storage(err) => HttpResponse::InternalServerError().body(err.to_string()),
             ApiError::NotFound(err) => HttpResponse::NotFound().body(err.to_string()),
+            ApiError::Gone(err) => HttpResponse::Gone().body(err.to_string()),
+            ApiError::ServiceUnavailable(err) => {
+                HttpResponse::ServiceUnavailable().body(err.to_string())
+            }
+            ApiError::TooManyRequests(err) => {
+                HttpResponse::build(actix_web::http::StatusCode::TOO_MANY_REQUESTS)
+                    .body(err.to_string())
+            }
             ApiError::Unauthorized => HttpResponse::Unauthorized().finish(),
         }
     }
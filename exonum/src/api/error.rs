@@ -45,6 +45,21 @@ pub enum Error {
     #[fail(display = "Not found: {}", _0)]
     NotFound(String),
 
+    /// Gone. This error occurs when the requested resource existed, but is no longer
+    /// available on this node, e.g., because the corresponding data has been pruned.
+    #[fail(display = "Gone: {}", _0)]
+    Gone(String),
+
+    /// Service unavailable. This error occurs when the node is temporarily unable
+    /// to handle the request, e.g., because the transaction pool is full.
+    #[fail(display = "Service unavailable: {}", _0)]
+    ServiceUnavailable(String),
+
+    /// Too many requests. This error occurs when the client has exceeded its quota,
+    /// e.g., the quota of the transaction pool for a single author.
+    #[fail(display = "Too many requests: {}", _0)]
+    TooManyRequests(String),
+
     /// Internal server error. This type can return any internal server error to the user.
     #[fail(display = "Internal server error: {}", _0)]
     InternalError(failure::Error),
@@ -17,13 +17,21 @@
 //! Private API includes requests that are available only to the blockchain
 //! administrators, e.g. view the list of services on the current node.
 
-use std::{collections::HashMap, net::SocketAddr};
+use actix_web::{self, http, HttpResponse};
+use futures::IntoFuture;
 
-use api::{Error as ApiError, ServiceApiScope, ServiceApiState};
+use std::{collections::HashMap, net::SocketAddr, sync::Arc};
+
+use api::{
+    backends::actix::{FutureResponse, HttpRequest, RawHandler, RequestHandler},
+    Error as ApiError, ServiceApiBackend, ServiceApiScope, ServiceApiState,
+};
 use blockchain::{Service, SharedNodeState};
 use crypto::PublicKey;
+use helpers::metrics;
 use messages::PROTOCOL_MAJOR_VERSION;
 use node::{ConnectInfo, ExternalMessage};
+use storage::index_catalogue;
 
 /// Short information about the service.
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
@@ -100,6 +108,17 @@ struct ConsensusEnabledQuery {
     enabled: bool,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct CheckpointQuery {
+    name: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+struct IndexesQuery {
+    #[serde(default)]
+    counts: bool,
+}
+
 /// Private system API.
 #[derive(Clone, Debug)]
 pub struct SystemApi {
@@ -124,7 +143,10 @@ impl SystemApi {
             .handle_is_consensus_enabled("v1/consensus_enabled", api_scope)
             .handle_set_consensus_enabled("v1/consensus_enabled", api_scope)
             .handle_shutdown("v1/shutdown", api_scope)
-            .handle_rebroadcast("v1/rebroadcast", api_scope);
+            .handle_rebroadcast("v1/rebroadcast", api_scope)
+            .handle_checkpoint("v1/checkpoint", api_scope)
+            .handle_indexes("v1/indexes", api_scope)
+            .handle_metrics("v1/metrics", api_scope);
         api_scope
     }
 
@@ -233,4 +255,48 @@ impl SystemApi {
         });
         self
     }
+
+    /// Creates a checkpoint of the node database with the name specified in the request.
+    /// The checkpoint is created in the `checkpoints_dir` directory of the node API
+    /// configuration and must not exist.
+    fn handle_checkpoint(self, name: &'static str, api_scope: &mut ServiceApiScope) -> Self {
+        api_scope.endpoint_mut(
+            name,
+            move |state: &ServiceApiState, query: CheckpointQuery| {
+                let blockchain = state.blockchain();
+                let path = blockchain
+                    .checkpoint_path(&query.name)
+                    .map_err(|e| ApiError::BadRequest(e.to_string()))?;
+                blockchain.create_checkpoint(path).map_err(ApiError::from)
+            },
+        );
+        self
+    }
+
+    /// Returns the indexes registered in the node database with their types. The numbers
+    /// of entries and the sizes of the indexes are returned only if the `counts` query
+    /// parameter is `true`, since counting reads the whole database.
+    fn handle_indexes(self, name: &'static str, api_scope: &mut ServiceApiScope) -> Self {
+        api_scope.endpoint(name, move |state: &ServiceApiState, query: IndexesQuery| {
+            Ok(index_catalogue(&*state.snapshot(), query.counts))
+        });
+        self
+    }
+
+    /// Returns the node metrics in the Prometheus text format.
+    fn handle_metrics(self, name: &'static str, api_scope: &mut ServiceApiScope) -> Self {
+        let index = move |_: HttpRequest| -> FutureResponse {
+            let response = HttpResponse::Ok()
+                .content_type("text/plain; version=0.0.4")
+                .body(metrics::export_prometheus());
+            Box::new(Ok::<_, actix_web::Error>(response).into_future())
+        };
+
+        api_scope.web_backend().raw_handler(RequestHandler {
+            name: name.to_owned(),
+            method: http::Method::GET,
+            inner: Arc::from(index) as Arc<RawHandler>,
+        });
+        self
+    }
 }
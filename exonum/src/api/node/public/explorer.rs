@@ -28,7 +28,7 @@ use api::{
     websocket::{Server, Session},
     Error as ApiError, ServiceApiBackend, ServiceApiScope, ServiceApiState,
 };
-use blockchain::{Block, SharedNodeState};
+use blockchain::{Block, PoolLimitError, Schema, SharedNodeState};
 use crypto::Hash;
 use explorer::{self, BlockchainExplorer, TransactionInfo};
 use helpers::Height;
@@ -153,6 +153,19 @@ impl ExplorerApi {
             .rev()
             .filter(|block| !query.skip_empty_blocks || !block.is_empty())
             .take(query.count)
+            .collect();
+
+        if query.add_blocks_time {
+            if let Some(block) = blocks.iter().find(|block| explorer.is_pruned(block.height())) {
+                return Err(ApiError::Gone(format!(
+                    "Precommits for the block at height {} have been pruned",
+                    block.height()
+                )));
+            }
+        }
+
+        let blocks: Vec<_> = blocks
+            .into_iter()
             .inspect(|block| {
                 if query.add_blocks_time {
                     times.push(median_precommits_time(&block.precommits()));
@@ -182,9 +195,14 @@ impl ExplorerApi {
         state: &ServiceApiState,
         query: BlockQuery,
     ) -> Result<Option<BlockInfo>, ApiError> {
-        Ok(BlockchainExplorer::new(state.blockchain())
-            .block(query.height)
-            .map(From::from))
+        let explorer = BlockchainExplorer::new(state.blockchain());
+        if explorer.is_pruned(query.height) {
+            return Err(ApiError::Gone(format!(
+                "Contents of the block at height {} have been pruned",
+                query.height
+            )));
+        }
+        Ok(explorer.block(query.height).map(From::from))
     }
 
     /// Searches for a transaction, either committed or uncommitted, by the hash.
@@ -192,13 +210,19 @@ impl ExplorerApi {
         state: &ServiceApiState,
         query: TransactionQuery,
     ) -> Result<TransactionInfo, ApiError> {
-        BlockchainExplorer::new(state.blockchain())
-            .transaction(&query.hash)
-            .ok_or_else(|| {
-                let description = serde_json::to_string(&json!({ "type": "unknown" })).unwrap();
+        let explorer = BlockchainExplorer::new(state.blockchain());
+        explorer.transaction(&query.hash).ok_or_else(|| {
+            if let Some(location) = explorer.pruned_transaction_location(&query.hash) {
+                let description =
+                    serde_json::to_string(&json!({ "type": "pruned", "location": location }))
+                        .unwrap();
                 debug!("{}", description);
-                ApiError::NotFound(description)
-            })
+                return ApiError::Gone(description);
+            }
+            let description = serde_json::to_string(&json!({ "type": "unknown" })).unwrap();
+            debug!("{}", description);
+            ApiError::NotFound(description)
+        })
     }
     /// Adds transaction into unconfirmed tx pool, and broadcast transaction to other nodes.
     pub fn add_transaction(
@@ -213,6 +237,23 @@ impl ExplorerApi {
         let tx_hash = signed.hash();
         let signed = RawTransaction::try_from(Message::deserialize(signed)?)
             .map_err(|_| format_err!("Couldn't deserialize transaction message."))?;
+        let snapshot = state.snapshot();
+        let next_height = Schema::new(&snapshot).height().next();
+        if let Some(expires_at) = state.blockchain().tx_expiration(&signed) {
+            if expires_at <= next_height {
+                return Err(ApiError::BadRequest(format!(
+                    "Transaction has expired at height {}",
+                    expires_at
+                )));
+            }
+        }
+        state
+            .blockchain()
+            .check_pool_limits(&*snapshot, &signed, |_| true)
+            .map_err(|e| match e {
+                PoolLimitError::QuotaExceeded => ApiError::TooManyRequests(e.to_string()),
+                PoolLimitError::PoolIsFull => ApiError::ServiceUnavailable(e.to_string()),
+            })?;
         let _ = state
             .sender()
             .broadcast_transaction(signed)
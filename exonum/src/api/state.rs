@@ -12,8 +12,10 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use blockchain::Blockchain;
+use super::Error as ApiError;
+use blockchain::{Blockchain, Schema};
 use crypto::{PublicKey, SecretKey};
+use helpers::Height;
 use node::ApiSender;
 use storage::Snapshot;
 
@@ -44,6 +46,38 @@ impl ServiceApiState {
         self.blockchain.snapshot()
     }
 
+    /// Creates a read-only snapshot of the blockchain state at the given height, or of
+    /// the current state if the height is not specified.
+    ///
+    /// Intended for the endpoints accepting an optional `height` parameter. Returns
+    /// the `NotFound` error if the height is greater than the current one, and the `Gone`
+    /// error if the state at this height is not available on this node.
+    /// See [`Blockchain::snapshot_at`] for details.
+    ///
+    /// [`Blockchain::snapshot_at`]: ../blockchain/struct.Blockchain.html#method.snapshot_at
+    pub fn snapshot_at(&self, height: Option<Height>) -> Result<Box<dyn Snapshot>, ApiError> {
+        let height = match height {
+            Some(height) => height,
+            None => return Ok(self.snapshot()),
+        };
+        if let Some(snapshot) = self.blockchain.snapshot_at(height) {
+            return Ok(snapshot);
+        }
+
+        let current_height = Schema::new(&self.snapshot()).height();
+        if height > current_height {
+            Err(ApiError::NotFound(format!(
+                "Height {} is greater than the current height {}",
+                height, current_height
+            )))
+        } else {
+            Err(ApiError::Gone(format!(
+                "State at height {} is not available on this node",
+                height
+            )))
+        }
+    }
+
     /// Returns the public key of the current node.
     pub fn public_key(&self) -> &PublicKey {
         &self.blockchain.service_keypair.0
@@ -0,0 +1,204 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Checkpoints of the node database.
+//!
+//! A checkpoint is a directory with a consistent copy of the database, which is created
+//! without stopping the node. The copy is stored in the `db` subdirectory, and the height
+//! and the hash of its latest block are recorded in the `checkpoint.json` file.
+
+use failure;
+use serde_json;
+
+use std::{
+    fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+use super::{state_snapshot::verify_block_precommits, Schema};
+use crypto::{CryptoHash, Hash, PublicKey};
+use helpers::Height;
+use storage::{open_database, Database, DbOptions, StorageMetadata};
+
+const CHECKPOINT_DB_DIR: &str = "db";
+const CHECKPOINT_INFO_FILE: &str = "checkpoint.json";
+
+/// Maximum number of attempts to create a checkpoint between two block commits.
+const MAX_CHECKPOINT_ATTEMPTS: usize = 3;
+
+/// Information about a checkpoint of the database.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CheckpointInfo {
+    /// Height of the latest block in the checkpoint.
+    pub height: Height,
+    /// Hash of the latest block in the checkpoint.
+    pub block_hash: Hash,
+}
+
+fn latest_block(db: &dyn Database) -> Result<CheckpointInfo, failure::Error> {
+    let snapshot = db.snapshot();
+    let schema = Schema::new(&snapshot);
+    let block_hash = schema
+        .block_hashes_by_height()
+        .last()
+        .ok_or_else(|| format_err!("Blockchain is not initialized"))?;
+    Ok(CheckpointInfo {
+        height: schema.height(),
+        block_hash,
+    })
+}
+
+/// Resolves the path of the checkpoint with the given name in the checkpoints directory.
+pub(crate) fn checkpoint_path(dir: &Path, name: &str) -> Result<PathBuf, failure::Error> {
+    let mut components = Path::new(name).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) => Ok(dir.join(name)),
+        _ => bail!("Invalid checkpoint name: {}", name),
+    }
+}
+
+/// Creates a checkpoint of the database in the specified directory, which must not exist.
+///
+/// The database remains available for the node while the checkpoint is being created.
+/// If a block is committed during the creation, the checkpoint is created anew, so that
+/// the recorded height is exact.
+pub fn create_checkpoint(db: &dyn Database, path: &Path) -> Result<CheckpointInfo, failure::Error> {
+    ensure!(
+        !path.exists(),
+        "Checkpoint path {} already exists",
+        path.display()
+    );
+    fs::create_dir_all(path)?;
+    let db_path = path.join(CHECKPOINT_DB_DIR);
+
+    for _ in 0..MAX_CHECKPOINT_ATTEMPTS {
+        let info = latest_block(db)?;
+        db.create_checkpoint(&db_path)?;
+        if latest_block(db)? == info {
+            fs::write(
+                path.join(CHECKPOINT_INFO_FILE),
+                serde_json::to_vec_pretty(&info)?,
+            )?;
+            info!(
+                "Created a checkpoint at height {} in {}",
+                info.height,
+                path.display()
+            );
+            return Ok(info);
+        }
+        fs::remove_dir_all(&db_path)?;
+    }
+    bail!("Unable to create a checkpoint between block commits")
+}
+
+/// Checks that the checkpoint in the specified directory can be opened with the given
+/// options and contains the recorded latest block, approved by the majority of the given
+/// trusted validators.
+pub fn verify_checkpoint(
+    path: &Path,
+    options: &DbOptions,
+    validator_keys: &[PublicKey],
+) -> Result<CheckpointInfo, failure::Error> {
+    let info_file = fs::File::open(path.join(CHECKPOINT_INFO_FILE))?;
+    let info: CheckpointInfo = serde_json::from_reader(info_file)?;
+
+    let options = DbOptions {
+        create_if_missing: false,
+        ..*options
+    };
+    let db = open_database(path.join(CHECKPOINT_DB_DIR), &options)?;
+    let snapshot = db.snapshot();
+    StorageMetadata::read(&snapshot)?;
+    ensure!(
+        latest_block(&*db)? == info,
+        "Latest block of the checkpoint does not match the recorded one"
+    );
+
+    let schema = Schema::new(&snapshot);
+    let proof = schema
+        .block_and_precommits(info.height)
+        .ok_or_else(|| format_err!("Latest block of the checkpoint is missing"))?;
+    ensure!(
+        proof.block.hash() == info.block_hash,
+        "Latest block of the checkpoint is corrupted"
+    );
+    // The genesis block is not approved with precommits.
+    if info.height > Height::zero() {
+        verify_block_precommits(&proof, &info.block_hash, validator_keys)?;
+    }
+    Ok(info)
+}
+
+/// Replaces the database at `db_path` with a copy of the checkpoint in the specified
+/// directory, after verifying the checkpoint against the given trusted validators.
+///
+/// The replaced database is moved to a directory with the `.bak` suffix, which must
+/// not exist. It can be removed once the node is checked to work with the restored
+/// database. The node must be stopped during the restoration.
+pub fn restore_checkpoint(
+    path: &Path,
+    db_path: &Path,
+    options: &DbOptions,
+    validator_keys: &[PublicKey],
+) -> Result<CheckpointInfo, failure::Error> {
+    let info = verify_checkpoint(path, options, validator_keys)?;
+
+    let backup_path = if db_path.exists() {
+        let mut backup_path = db_path.as_os_str().to_owned();
+        backup_path.push(".bak");
+        let backup_path = PathBuf::from(backup_path);
+        ensure!(
+            !backup_path.exists(),
+            "Backup path {} already exists",
+            backup_path.display()
+        );
+        fs::rename(db_path, &backup_path)?;
+        Some(backup_path)
+    } else {
+        None
+    };
+
+    if let Err(e) = copy_dir(&path.join(CHECKPOINT_DB_DIR), db_path) {
+        if db_path.exists() {
+            fs::remove_dir_all(db_path)?;
+        }
+        if let Some(backup_path) = backup_path {
+            fs::rename(backup_path, db_path)?;
+        }
+        return Err(e.into());
+    }
+    if let Some(backup_path) = backup_path {
+        info!("Previous database is moved to {}", backup_path.display());
+    }
+    info!(
+        "Restored the checkpoint at height {} into {}",
+        info.height,
+        db_path.display()
+    );
+    Ok(info)
+}
+
+fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
@@ -12,6 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::BTreeSet;
+
 use super::config::{ConsensusConfig, ValidatorKeys};
 
 /// The initial configuration which is committed into the genesis block.
@@ -27,6 +29,15 @@ pub struct GenesisConfig {
     pub consensus: ConsensusConfig,
     /// List of public keys of validators.
     pub validator_keys: Vec<ValidatorKeys>,
+    /// Names of the services which are not included into the genesis block.
+    ///
+    /// Such services stay inactive until they are deployed with a configuration change,
+    /// which allows nodes of a running network to add a service to their binaries in advance.
+    /// See [`Blockchain::active_services`] for details.
+    ///
+    /// [`Blockchain::active_services`]: struct.Blockchain.html#method.active_services
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub deferred_services: BTreeSet<String>,
 }
 
 impl GenesisConfig {
@@ -44,6 +55,7 @@ impl GenesisConfig {
         Self {
             consensus,
             validator_keys: validator_keys.collect(),
+            deferred_services: BTreeSet::new(),
         }
     }
 }
@@ -0,0 +1,221 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Verification of the integrity of the blockchain database.
+
+use failure;
+
+use std::collections::HashMap;
+
+use super::{
+    schema::{
+        core_auxiliary_value_hashes, BLOCKS, BLOCK_HASHES_BY_HEIGHT, BLOCK_TRANSACTIONS,
+        PRECOMMITS, STATE_HASH_AGGREGATOR,
+    },
+    state_snapshot::verify_block_precommits,
+    BlockProof, Blockchain, Schema,
+};
+use crypto::{CryptoHash, Hash};
+use helpers::Height;
+use storage::{
+    check_merkelized_indexes, Database, IntegrityError, MemoryDB, ProofMapIndex, Snapshot,
+    StorageKey,
+};
+
+impl Blockchain {
+    /// Checks the integrity of the blockchain database and returns the found inconsistencies.
+    ///
+    /// The check recomputes the root hashes of all the `ProofMapIndex` and `ProofListIndex`
+    /// indexes, rebuilds the `state_hash_aggregator` from the table hashes reported by
+    /// the core and [`Service::state_hash`] and compares its root hash with the state hash
+    /// of the latest block. Additionally, the hashes of all the blocks are checked, as well as
+    /// the Merkle roots of the block transactions and the precommits for the heights, which
+    /// are not pruned.
+    ///
+    /// The whole database is read during the check, so the node should be stopped;
+    /// see the `verify-db` maintenance action.
+    ///
+    /// [`Service::state_hash`]: trait.Service.html#tymethod.state_hash
+    pub fn verify_integrity(&self) -> Vec<IntegrityError> {
+        let snapshot = self.snapshot();
+        if Schema::new(&snapshot).block_hashes_by_height().is_empty() {
+            return vec![IntegrityError::new(
+                BLOCK_HASHES_BY_HEIGHT,
+                Vec::new(),
+                "Blockchain is not initialized",
+            )];
+        }
+
+        let state_indexes = self.state_indexes();
+        let mut value_hashes = HashMap::new();
+        for &(_, ref index) in &state_indexes {
+            value_hashes.insert(index.name().to_owned(), index.value_hash());
+        }
+        for (name, value_hash) in core_auxiliary_value_hashes() {
+            value_hashes.insert(name.to_owned(), value_hash);
+        }
+        let (roots, mut errors) = check_merkelized_indexes(&*snapshot, &value_hashes);
+
+        let height = Schema::new(&snapshot).height();
+        let state_hashes = self.collect_state_hashes(&*snapshot, height);
+        for &(ref table_key, ref index) in &state_indexes {
+            let table_hash = state_hashes
+                .iter()
+                .find(|&&(ref key, _)| key == table_key)
+                .map(|&(_, hash)| hash);
+            if let (Some(table_hash), Some(root)) = (table_hash, roots.get(index.name())) {
+                if *root != table_hash {
+                    errors.push(IntegrityError::new(
+                        index.name(),
+                        Vec::new(),
+                        "Root hash does not match the table hash reported by the service",
+                    ));
+                }
+            }
+        }
+
+        errors.extend(check_state_hash(&*snapshot, &state_hashes));
+        errors.extend(check_blocks(&*snapshot));
+        errors
+    }
+}
+
+/// Rebuilds the state hash aggregator and compares it with the stored one and with
+/// the state hash of the latest block.
+fn check_state_hash(
+    snapshot: &dyn Snapshot,
+    state_hashes: &[(Hash, Hash)],
+) -> Vec<IntegrityError> {
+    let mut errors = Vec::new();
+    let schema = Schema::new(snapshot);
+    let stored = schema.state_hash_aggregator();
+
+    // The hashes of the tables of the services, which are no longer active,
+    // are kept in the aggregator.
+    let db = MemoryDB::new();
+    let mut fork = db.fork();
+    let state_hash = {
+        let mut aggregator = ProofMapIndex::new(STATE_HASH_AGGREGATOR, &mut fork);
+        for (key, hash) in stored.iter() {
+            aggregator.put(&key, hash);
+        }
+        for &(key, hash) in state_hashes {
+            if stored.get(&key) != Some(hash) {
+                errors.push(IntegrityError::new(
+                    STATE_HASH_AGGREGATOR,
+                    key.as_ref().to_vec(),
+                    "Table hash does not match the one reported by the service",
+                ));
+            }
+            aggregator.put(&key, hash);
+        }
+        aggregator.merkle_root()
+    };
+
+    let last_block = schema.last_block();
+    if state_hash != *last_block.state_hash() {
+        errors.push(IntegrityError::new(
+            BLOCKS,
+            last_block.hash().as_ref().to_vec(),
+            "State hash of the latest block does not match the rebuilt state hash aggregator",
+        ));
+    }
+    errors
+}
+
+/// Checks the blocks, their transactions and precommits.
+fn check_blocks(snapshot: &dyn Snapshot) -> Vec<IntegrityError> {
+    let mut errors = Vec::new();
+    let schema = Schema::new(snapshot);
+    let blocks = schema.blocks();
+    let mut prev_hash = Hash::zero();
+
+    for (height, block_hash) in schema.block_hashes_by_height().iter().enumerate() {
+        let height = Height(height as u64);
+        let block = match blocks.get(&block_hash) {
+            Some(ref block) if block.hash() == block_hash && block.height() == height => {
+                block.clone()
+            }
+            Some(_) => {
+                errors.push(IntegrityError::new(
+                    BLOCKS,
+                    block_hash.as_ref().to_vec(),
+                    format!("Block does not match its hash or height {}", height),
+                ));
+                continue;
+            }
+            None => {
+                errors.push(IntegrityError::new(
+                    BLOCKS,
+                    block_hash.as_ref().to_vec(),
+                    format!("Block at height {} is missing", height),
+                ));
+                continue;
+            }
+        };
+        if *block.prev_hash() != prev_hash {
+            errors.push(IntegrityError::new(
+                BLOCKS,
+                block_hash.as_ref().to_vec(),
+                "Block does not refer to the previous block",
+            ));
+        }
+        prev_hash = block_hash;
+        if schema.is_pruned(height) {
+            continue;
+        }
+
+        let transactions = schema.block_transactions(height);
+        if transactions.merkle_root() != *block.tx_hash()
+            || transactions.len() != u64::from(block.tx_count())
+        {
+            let mut key = vec![0; height.0.size()];
+            height.0.write(&mut key);
+            errors.push(IntegrityError::new(
+                BLOCK_TRANSACTIONS,
+                key,
+                "Transactions do not match the block",
+            ));
+        }
+
+        if height > Height::zero() {
+            let proof = BlockProof {
+                precommits: schema.precommits(&block_hash).iter().collect(),
+                block,
+            };
+            if let Err(e) = check_precommits(&schema, &proof, &block_hash) {
+                errors.push(IntegrityError::new(
+                    PRECOMMITS,
+                    block_hash.as_ref().to_vec(),
+                    e.to_string(),
+                ));
+            }
+        }
+    }
+    errors
+}
+
+fn check_precommits<T: AsRef<dyn Snapshot>>(
+    schema: &Schema<T>,
+    proof: &BlockProof,
+    block_hash: &Hash,
+) -> Result<(), failure::Error> {
+    let validator_keys: Vec<_> = schema
+        .configuration_by_height(proof.block.height())
+        .validator_keys
+        .iter()
+        .map(|keys| keys.consensus_key)
+        .collect();
+    verify_block_precommits(proof, block_hash, &validator_keys)
+}
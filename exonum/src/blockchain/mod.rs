@@ -34,10 +34,13 @@
 
 pub use self::{
     block::{Block, BlockProof},
+    checkpoint::{create_checkpoint, restore_checkpoint, verify_checkpoint, CheckpointInfo},
     config::{ConsensusConfig, StoredConfiguration, ValidatorKeys},
     genesis::GenesisConfig,
-    schema::{Schema, TxLocation},
+    schema::{PoolLimitError, Schema, TxLocation},
     service::{Service, ServiceContext, SharedNodeState},
+    state_snapshot::export_state,
+    state_sync::{StateChunk, StateIndex, StateSync, STATE_CHUNK_SIZE},
     transaction::{
         ExecutionError, ExecutionResult, Transaction, TransactionContext, TransactionError,
         TransactionErrorType, TransactionMessage, TransactionResult, TransactionSet,
@@ -48,11 +51,13 @@ pub mod config;
 
 use byteorder::{ByteOrder, LittleEndian};
 use failure;
+use serde_json::Value;
 
 use std::{
     collections::{BTreeMap, HashMap},
     error::Error as StdError,
     fmt, iter, mem, panic,
+    path::{Path, PathBuf},
     sync::Arc,
 };
 
@@ -60,13 +65,20 @@ use crypto::{self, CryptoHash, Hash, PublicKey, SecretKey};
 use encoding::Error as MessageError;
 use helpers::{Height, Round, ValidatorId};
 use messages::{Connect, Message, Precommit, ProtocolMessage, RawTransaction, Signed};
-use node::ApiSender;
+use node::{ApiSender, MemoryPoolConfig, PruningConfig, StateHistoryConfig};
 use storage::{self, Database, Error, Fork, Patch, Snapshot};
 
+use self::state_history::PastStateCache;
+
 mod block;
+mod checkpoint;
 mod genesis;
+mod integrity;
 mod schema;
 mod service;
+mod state_history;
+mod state_snapshot;
+mod state_sync;
 #[macro_use]
 mod transaction;
 #[cfg(test)]
@@ -75,6 +87,9 @@ mod tests;
 /// Id of core service table family.
 pub const CORE_SERVICE: u16 = 0;
 
+/// The maximum number of heights pruned after a single block commit.
+const MAX_PRUNED_HEIGHTS_PER_COMMIT: u64 = 100;
+
 /// Exonum blockchain instance with a certain services set and data storage.
 ///
 /// Only nodes with an identical set of services and genesis block can be combined
@@ -85,6 +100,11 @@ pub struct Blockchain {
     #[doc(hidden)]
     pub service_keypair: (PublicKey, SecretKey),
     pub(crate) api_sender: ApiSender,
+    pruning: Option<PruningConfig>,
+    mempool: MemoryPoolConfig,
+    state_history: Option<StateHistoryConfig>,
+    past_state_cache: Arc<PastStateCache>,
+    checkpoints_dir: Option<PathBuf>,
 }
 
 impl Blockchain {
@@ -113,9 +133,158 @@ impl Blockchain {
             service_map: Arc::new(service_map),
             service_keypair: (service_public_key, service_secret_key),
             api_sender,
+            pruning: None,
+            mempool: MemoryPoolConfig::default(),
+            state_history: None,
+            past_state_cache: Arc::new(PastStateCache::default()),
+            checkpoints_dir: None,
         }
     }
 
+    /// Enables pruning of the historical block contents with the given configuration.
+    /// See [`PruningConfig`] for details.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `keep_heights` is zero.
+    ///
+    /// [`PruningConfig`]: ../node/struct.PruningConfig.html
+    pub fn set_pruning_config(&mut self, config: PruningConfig) {
+        assert!(
+            config.keep_heights > 0,
+            "Pruning should keep the contents of at least one latest block"
+        );
+        self.pruning = Some(config);
+    }
+
+    /// Sets the limits of the transaction pool. See [`MemoryPoolConfig`] for details.
+    ///
+    /// [`MemoryPoolConfig`]: ../node/struct.MemoryPoolConfig.html
+    pub fn set_mempool_config(&mut self, config: MemoryPoolConfig) {
+        self.mempool = config;
+    }
+
+    /// Enables recording of the state history with the given configuration, which allows
+    /// to read the blockchain state at past heights. See [`StateHistoryConfig`] for details.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `keep_heights` is zero.
+    ///
+    /// [`StateHistoryConfig`]: ../node/struct.StateHistoryConfig.html
+    pub fn set_state_history_config(&mut self, config: StateHistoryConfig) {
+        assert!(
+            config.keep_heights != Some(0),
+            "State history should keep the state at least at the current height"
+        );
+        self.state_history = Some(config);
+    }
+
+    /// Returns a read-only snapshot of the blockchain state after the block at the given
+    /// height has been committed.
+    ///
+    /// The states at past heights are available only if the state history is enabled,
+    /// starting from the height at which it has been enabled and within the number
+    /// of heights kept according to [`StateHistoryConfig`]. The height should not be more
+    /// than `max_snapshot_depth` heights below the current one. Returns `None` if the state
+    /// at the given height is not available or the height is greater than the current one.
+    ///
+    /// The node-local data, such as the transaction pool, is not a part of the history
+    /// and is returned in its current state.
+    ///
+    /// [`StateHistoryConfig`]: ../node/struct.StateHistoryConfig.html
+    pub fn snapshot_at(&self, height: Height) -> Option<Box<dyn Snapshot>> {
+        // The history kept for the rollback is not exposed if the state history is disabled.
+        let max_depth = self.state_history.map_or(0, |config| {
+            config
+                .max_snapshot_depth
+                .unwrap_or(StateHistoryConfig::DEFAULT_MAX_SNAPSHOT_DEPTH)
+        });
+        state_history::snapshot_at(
+            &*self.db,
+            height,
+            Some(max_depth),
+            Some(&self.past_state_cache),
+        )
+    }
+
+    /// Rolls the database back to the given height by reverting the changes made by the blocks
+    /// committed after it, using the recorded state history as reverse patches.
+    ///
+    /// The history is always recorded for the latest `StateHistoryConfig::MIN_KEEP_HEIGHTS`
+    /// heights; the blocks below them can be rolled back only if the history for them is kept
+    /// according to the [`StateHistoryConfig`]. The later blocks are removed together with
+    /// their precommits, transaction results and locations. The transactions of the removed
+    /// blocks are returned to the pool with their priority and expiration height.
+    ///
+    /// The node should be stopped during the rollback; after the restart, it synchronizes
+    /// the removed blocks with its peers.
+    ///
+    /// [`StateHistoryConfig`]: ../node/struct.StateHistoryConfig.html
+    pub fn rollback_blocks(&self, height: Height) -> Result<(), failure::Error> {
+        state_history::rollback_blocks(self, height)
+    }
+
+    /// Creates a checkpoint of the blockchain database in the specified directory, which
+    /// must not exist. The checkpoint can be restored with [`restore_checkpoint`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database does not support checkpoints (e.g., `MemoryDB`)
+    /// or the checkpoint cannot be written.
+    ///
+    /// [`restore_checkpoint`]: fn.restore_checkpoint.html
+    pub fn create_checkpoint<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> Result<CheckpointInfo, failure::Error> {
+        checkpoint::create_checkpoint(&*self.db, path.as_ref())
+    }
+
+    /// Sets the directory, in which the named checkpoints are created.
+    /// See [`checkpoint_path`] for details.
+    ///
+    /// [`checkpoint_path`]: #method.checkpoint_path
+    pub fn set_checkpoints_dir<P: Into<PathBuf>>(&mut self, dir: P) {
+        self.checkpoints_dir = Some(dir.into());
+    }
+
+    /// Returns the path of the checkpoint with the given name in the directory set with
+    /// [`set_checkpoints_dir`]. The name must be a single path component, e.g., it cannot
+    /// be `..` or an absolute path, so that the checkpoint cannot be created outside
+    /// of the directory.
+    ///
+    /// [`set_checkpoints_dir`]: #method.set_checkpoints_dir
+    pub fn checkpoint_path(&self, name: &str) -> Result<PathBuf, failure::Error> {
+        let dir = self
+            .checkpoints_dir
+            .as_ref()
+            .ok_or_else(|| format_err!("Checkpoints directory is not configured"))?;
+        checkpoint::checkpoint_path(dir, name)
+    }
+
+    /// Checks whether the transaction fits into the limits of the transaction pool.
+    ///
+    /// Returns the hash of the transaction which needs to be evicted from the pool
+    /// to free space for the new one, if any. Only transactions satisfying `can_evict`
+    /// are considered for eviction.
+    pub fn check_pool_limits<F>(
+        &self,
+        snapshot: &dyn Snapshot,
+        tx: &Signed<RawTransaction>,
+        can_evict: F,
+    ) -> Result<Option<Hash>, PoolLimitError>
+    where
+        F: FnMut(&Hash) -> bool,
+    {
+        Schema::new(snapshot).check_pool_limits(
+            &tx.author(),
+            self.mempool.max_pool_size,
+            self.mempool.max_txs_per_author,
+            can_evict,
+        )
+    }
+
     /// Recreates the blockchain to reuse with a sandbox.
     #[doc(hidden)]
     pub fn clone_with_api_sender(&self, api_sender: ApiSender) -> Self {
@@ -131,6 +300,36 @@ impl Blockchain {
         &self.service_map
     }
 
+    /// Returns the services active at the given height in the increasing order of
+    /// their identifiers.
+    ///
+    /// Services included into the genesis block are always active. A service listed in
+    /// [`GenesisConfig::deferred_services`] is deployed with a configuration change:
+    /// it becomes active starting from the `actual_from` height of a configuration, which
+    /// contains its `service_name` in the `services` section. The service is initialized
+    /// at this height before the transactions of the block are executed.
+    ///
+    /// Transactions of inactive services are not accepted, their hooks are not called, and
+    /// their tables are not included into the blockchain state hash.
+    ///
+    /// [`GenesisConfig::deferred_services`]: struct.GenesisConfig.html#structfield.deferred_services
+    pub fn active_services(&self, snapshot: &dyn Snapshot, height: Height) -> Vec<&dyn Service> {
+        let schema = Schema::new(snapshot);
+        let genesis_config = schema.configuration_by_height(Height::zero());
+        let config = schema.configuration_by_height(height);
+
+        let mut services: Vec<_> = self
+            .service_map
+            .values()
+            .filter(|service| {
+                let name = service.service_name();
+                genesis_config.services.contains_key(name) || config.services.contains_key(name)
+            }).map(|service| service.as_ref())
+            .collect();
+        services.sort_by_key(|service| service.service_id());
+        services
+    }
+
     /// Creates a read-only snapshot of the current storage state.
     pub fn snapshot(&self) -> Box<dyn Snapshot> {
         self.db.snapshot()
@@ -156,6 +355,37 @@ impl Blockchain {
         service.tx_from_raw(raw)
     }
 
+    /// Returns the [priority] of the given transaction, or zero if the transaction
+    /// cannot be deserialized.
+    ///
+    /// [priority]: trait.Transaction.html#method.priority
+    pub fn tx_priority(&self, tx: &Signed<RawTransaction>) -> u64 {
+        self.tx_from_raw(tx.payload().clone())
+            .map(|tx| tx.priority())
+            .unwrap_or(0)
+    }
+
+    /// Returns the [expiration height] of the given transaction, or `None` if the transaction
+    /// does not expire or cannot be deserialized.
+    ///
+    /// [expiration height]: trait.Transaction.html#method.expires_at
+    pub fn tx_expiration(&self, tx: &Signed<RawTransaction>) -> Option<Height> {
+        self.tx_from_raw(tx.payload().clone())
+            .ok()
+            .and_then(|tx| tx.expires_at())
+    }
+
+    /// Adds the transaction into the persistent pool, taking into account its priority
+    /// and expiration height.
+    #[doc(hidden)]
+    pub fn add_transaction_into_pool(&self, fork: &mut Fork, tx: Signed<RawTransaction>) {
+        let (priority, expires_at) = match self.tx_from_raw(tx.payload().clone()) {
+            Ok(parsed) => (parsed.priority(), parsed.expires_at()),
+            Err(_) => (0, None),
+        };
+        Schema::new(fork).add_tracked_transaction_into_pool(tx, priority, expires_at);
+    }
+
     /// Commits changes from the patch to the blockchain storage.
     /// See [`Fork`](../storage/struct.Fork.html) for details.
     pub fn merge(&mut self, patch: Patch) -> Result<(), Error> {
@@ -192,6 +422,7 @@ impl Blockchain {
             .is_empty();
         if has_genesis_block {
             self.assert_storage_version();
+            self.assert_no_pending_migrations();
         } else {
             self.initialize_metadata();
             self.create_genesis_block(cfg)?;
@@ -225,6 +456,102 @@ impl Blockchain {
         }
     }
 
+    /// Checks that the data of all the active services corresponds to their schema versions.
+    ///
+    /// # Panics
+    ///
+    /// Panics if some of the services need to be migrated or the stored data has a newer
+    /// version than the one supported by the service.
+    fn assert_no_pending_migrations(&self) {
+        let pending = self.pending_migrations();
+        if !pending.is_empty() {
+            let services: Vec<_> = pending
+                .iter()
+                .map(|(service, version)| {
+                    format!(
+                        "{} ({} -> {})",
+                        service.service_name(),
+                        version,
+                        service.schema_version()
+                    )
+                }).collect();
+            panic!(
+                "Service data needs to be migrated: {}. Run the `migrate` maintenance action.",
+                services.join(", ")
+            );
+        }
+    }
+
+    /// Returns the active services which data has to be migrated together with the versions
+    /// of their stored data.
+    ///
+    /// The data of the services initialized before the schema versions were introduced
+    /// is considered to have the version `0`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the stored data of a service has a newer version than the one supported
+    /// by the service.
+    pub fn pending_migrations(&self) -> Vec<(&dyn Service, u32)> {
+        let snapshot = self.snapshot();
+        let schema = Schema::new(&snapshot);
+        let versions = schema.service_schema_versions();
+        self.active_services(&*snapshot, schema.height())
+            .into_iter()
+            .filter_map(|service| {
+                let stored = versions
+                    .get(&service.service_name().to_owned())
+                    .unwrap_or(0);
+                let current = service.schema_version();
+                if stored > current {
+                    panic!(
+                        "Data of service {} has schema version {}, which is newer than \
+                         the supported version {}",
+                        service.service_name(),
+                        stored,
+                        current
+                    );
+                }
+                if stored < current {
+                    Some((service, stored))
+                } else {
+                    None
+                }
+            }).collect()
+    }
+
+    /// Migrates the data of the services to their current schema versions.
+    ///
+    /// Migration steps of all the services are applied in a single patch, so the storage is
+    /// left intact if any of them fails. The node must be stopped during the migration.
+    pub fn migrate(&mut self) -> Result<(), failure::Error> {
+        let pending = self.pending_migrations();
+        if pending.is_empty() {
+            info!("No pending migrations");
+            return Ok(());
+        }
+
+        let mut fork = self.fork();
+        for (service, stored) in pending {
+            let name = service.service_name();
+            for version in stored..service.schema_version() {
+                info!("Migrating service {} from schema version {}", name, version);
+                service
+                    .migrate(&mut fork, version)
+                    .map_err(|e| format_err!("Migration of service {} failed: {}", name, e))?;
+                Schema::new(&mut fork)
+                    .service_schema_versions_mut()
+                    .put(&name.to_owned(), version + 1);
+            }
+        }
+        // The past states have the previous schema versions, so they cannot be restored
+        // on top of the migrated data.
+        state_history::clear_state_history(&mut fork);
+        self.db.merge_sync(fork.into_patch())?;
+        info!("Migrations applied successfully");
+        Ok(())
+    }
+
     /// Creates and commits the genesis block with the given genesis configuration.
     fn create_genesis_block(&mut self, cfg: GenesisConfig) -> Result<(), Error> {
         let mut config_propose = StoredConfiguration {
@@ -239,8 +566,11 @@ impl Blockchain {
             let mut fork = self.fork();
             // Update service tables
             for (_, service) in self.service_map.iter() {
-                let cfg = service.initialize(&mut fork);
                 let name = service.service_name();
+                if cfg.deferred_services.contains(name) {
+                    continue;
+                }
+                let cfg = initialize_service(service.as_ref(), &mut fork);
                 if config_propose.services.contains_key(name) {
                     panic!(
                         "Services already contain service with '{}' name, please change it",
@@ -286,6 +616,35 @@ impl Blockchain {
         crypto::hash(&vec)
     }
 
+    /// Collects hashes of the Merkelized tables of the core and the services active
+    /// at the given height, keyed by `service_table_unique_key`.
+    pub(crate) fn collect_state_hashes(
+        &self,
+        snapshot: &dyn Snapshot,
+        height: Height,
+    ) -> Vec<(Hash, Hash)> {
+        let schema = Schema::new(snapshot);
+
+        let vec_core_state = schema.core_state_hash();
+        let mut state_hashes = Vec::new();
+
+        for (idx, core_table_hash) in vec_core_state.into_iter().enumerate() {
+            let key = Self::service_table_unique_key(CORE_SERVICE, idx);
+            state_hashes.push((key, core_table_hash));
+        }
+
+        for service in self.active_services(snapshot, height) {
+            let service_id = service.service_id();
+            let vec_service_state = service.state_hash(snapshot);
+            for (idx, service_table_hash) in vec_service_state.into_iter().enumerate() {
+                let key = Self::service_table_unique_key(service_id, idx);
+                state_hashes.push((key, service_table_hash));
+            }
+        }
+
+        state_hashes
+    }
+
     /// Executes the given transactions from the pool.
     /// Then collects the resulting changes from the current storage state and returns them
     /// with the hash of the resulting block.
@@ -301,46 +660,42 @@ impl Blockchain {
         let block_hash = {
             // Get last hash.
             let last_hash = self.last_hash();
+            let active_services = self.active_services(&fork, height);
+            // Initialize services deployed at this height.
+            if height > Height(0) {
+                let previous_services = self.active_services(&fork, height.previous());
+                for service in &active_services {
+                    let service_id = service.service_id();
+                    if previous_services.iter().all(|s| s.service_id() != service_id) {
+                        info!(
+                            "Deploying service {} at height {}",
+                            service.service_name(),
+                            height
+                        );
+                        initialize_service(*service, &mut fork);
+                    }
+                }
+            }
+
             // Save & execute transactions.
             for (index, hash) in tx_hashes.iter().enumerate() {
-                self.execute_transaction(*hash, height, index, &mut fork)
+                self.execute_transaction(*hash, height, index, &active_services, &mut fork)
                     // Execution could fail if the transaction
                     // cannot be deserialized or it isn't in the pool.
                     .expect("Transaction execution error.");
             }
 
             // Invoke execute method for all services.
-            for service in self.service_map.values() {
+            for service in &active_services {
                 // Skip execution for genesis block.
                 if height > Height(0) {
-                    before_commit(service.as_ref(), &mut fork);
+                    before_commit(*service, &mut fork);
                 }
             }
 
             // Get tx & state hash.
             let (tx_hash, state_hash) = {
-                let state_hashes = {
-                    let schema = Schema::new(&fork);
-
-                    let vec_core_state = schema.core_state_hash();
-                    let mut state_hashes = Vec::new();
-
-                    for (idx, core_table_hash) in vec_core_state.into_iter().enumerate() {
-                        let key = Self::service_table_unique_key(CORE_SERVICE, idx);
-                        state_hashes.push((key, core_table_hash));
-                    }
-
-                    for service in self.service_map.values() {
-                        let service_id = service.service_id();
-                        let vec_service_state = service.state_hash(&fork);
-                        for (idx, service_table_hash) in vec_service_state.into_iter().enumerate() {
-                            let key = Self::service_table_unique_key(service_id, idx);
-                            state_hashes.push((key, service_table_hash));
-                        }
-                    }
-
-                    state_hashes
-                };
+                let state_hashes = self.collect_state_hashes(&fork, height);
 
                 let mut schema = Schema::new(&mut fork);
 
@@ -386,6 +741,7 @@ impl Blockchain {
         tx_hash: Hash,
         height: Height,
         index: usize,
+        active_services: &[&dyn Service],
         fork: &mut Fork,
     ) -> Result<(), failure::Error> {
         let (tx, raw, service_name) = {
@@ -398,9 +754,9 @@ impl Blockchain {
                 ))
             })?;
 
-            let service_name = self
-                .service_map
-                .get(&raw.service_id())
+            let service_name = active_services
+                .iter()
+                .find(|service| service.service_id() == raw.service_id())
                 .ok_or_else(|| {
                     failure::err_msg(format!(
                         "Service not found. Service id: {}",
@@ -496,19 +852,46 @@ impl Blockchain {
                 schema
                     .transactions_pool_len_index_mut()
                     .set(txs_count - u64::from(txs_in_block));
+                let height = schema.height();
+                let committed_txs: Vec<Hash> = schema.block_transactions(height).iter().collect();
+                for tx_hash in &committed_txs {
+                    schema.untrack_pool_transaction(tx_hash);
+                }
+                let expired = schema.expire_pool_transactions(height.next());
+                if expired > 0 {
+                    info!("Removed {} expired transactions from the pool", expired);
+                }
+
+                if let Some(pruning) = self.pruning {
+                    let height = schema.height();
+                    if height.0 >= pruning.keep_heights {
+                        let horizon = Height(height.0 + 1 - pruning.keep_heights);
+                        schema.prune_below(horizon, MAX_PRUNED_HEIGHTS_PER_COMMIT);
+                    }
+                }
             }
+
+            let height = Schema::new(&fork).height();
+            state_history::record_state_history(
+                &mut fork,
+                &*self.db.snapshot(),
+                height,
+                self.state_history,
+            );
             fork.into_patch()
         };
         self.merge(patch)?;
 
-        // Invokes `after_commit` for each service in order of their identifiers
-        for (service_id, service) in self.service_map.iter() {
+        // Invokes `after_commit` for each active service in order of their identifiers
+        let snapshot = self.snapshot();
+        let height = Schema::new(&snapshot).height();
+        for service in self.active_services(&*snapshot, height) {
             let context = ServiceContext::new(
                 self.service_keypair.0,
                 self.service_keypair.1.clone(),
                 self.api_sender.clone(),
                 self.fork(),
-                *service_id,
+                service.service_id(),
             );
             service.after_commit(&context);
         }
@@ -574,6 +957,15 @@ impl Blockchain {
     }
 }
 
+/// Initializes the service and records the current version of its schema.
+fn initialize_service(service: &dyn Service, fork: &mut Fork) -> Value {
+    let config = service.initialize(fork);
+    Schema::new(fork)
+        .service_schema_versions_mut()
+        .put(&service.service_name().to_owned(), service.schema_version());
+    config
+}
+
 fn before_commit(service: &dyn Service, fork: &mut Fork) {
     fork.checkpoint();
     match panic::catch_unwind(panic::AssertUnwindSafe(|| service.before_commit(fork))) {
@@ -606,6 +998,11 @@ impl Clone for Blockchain {
             service_map: Arc::clone(&self.service_map),
             api_sender: self.api_sender.clone(),
             service_keypair: self.service_keypair.clone(),
+            pruning: self.pruning,
+            mempool: self.mempool.clone(),
+            state_history: self.state_history,
+            past_state_cache: Arc::clone(&self.past_state_cache),
+            checkpoints_dir: self.checkpoints_dir.clone(),
         }
     }
 }
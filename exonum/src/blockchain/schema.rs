@@ -16,14 +16,16 @@ use protobuf::Message as ProtobufMessage;
 
 use std::borrow::Cow;
 
-use super::{config::StoredConfiguration, Block, BlockProof, Blockchain, TransactionResult};
+use super::{
+    config::StoredConfiguration, Block, BlockProof, Blockchain, StateIndex, TransactionResult,
+};
 use crypto::{self, CryptoHash, Hash, PublicKey};
 use encoding::protobuf::{self, ProtobufConvert};
 use helpers::{Height, Round};
 use messages::{Connect, Message, Precommit, RawTransaction, Signed};
 use storage::{
-    Entry, Fork, KeySetIndex, ListIndex, MapIndex, MapProof, ProofListIndex, ProofMapIndex,
-    Snapshot, StorageValue,
+    hash_storage_value, Entry, Fork, KeySetIndex, ListIndex, MapIndex, MapProof, ProofListIndex,
+    ProofMapIndex, Snapshot, StorageKey, StorageValue, ValueHashFn,
 };
 
 /// Defines `&str` constants with given name and value.
@@ -33,7 +35,7 @@ macro_rules! define_names {
             $name:ident => $value:expr;
         )+
     ) => (
-        $(const $name: &str = concat!("core.", $value);)*
+        $(pub(crate) const $name: &str = concat!("core.", $value);)*
     )
 }
 
@@ -42,6 +44,16 @@ define_names!(
     TRANSACTION_RESULTS => "transaction_results";
     TRANSACTIONS_POOL => "transactions_pool";
     TRANSACTIONS_POOL_LEN => "transactions_pool_len";
+    TRANSACTIONS_POOL_AUTHORS => "transactions_pool_authors";
+    TRANSACTIONS_POOL_AUTHORS_BY_LOAD => "transactions_pool_authors_by_load";
+    TRANSACTIONS_POOL_BY_AUTHOR => "transactions_pool_by_author";
+    TRANSACTIONS_POOL_SEQ => "transactions_pool_seq";
+    TRANSACTIONS_POOL_NEXT_SEQ => "transactions_pool_next_seq";
+    TRANSACTIONS_POOL_ORDER => "transactions_pool_order";
+    TRANSACTIONS_POOL_PRIORITIES => "transactions_pool_priorities";
+    TRANSACTIONS_POOL_EXPIRATIONS => "transactions_pool_expirations";
+    TRANSACTIONS_POOL_EXPIRY_QUEUE => "transactions_pool_expiry_queue";
+    EXPIRED_TRANSACTIONS => "expired_transactions";
     TRANSACTIONS_LOCATIONS => "transactions_locations";
     BLOCKS => "blocks";
     BLOCK_HASHES_BY_HEIGHT => "block_hashes_by_height";
@@ -53,8 +65,174 @@ define_names!(
     PEERS_CACHE => "peers_cache";
     CONSENSUS_MESSAGES_CACHE => "consensus_messages_cache";
     CONSENSUS_ROUND => "consensus_round";
+    PRUNING_HORIZON => "pruning_horizon";
+    SERVICE_SCHEMA_VERSIONS => "service_schema_versions";
+    STATE_HISTORY => "state_history";
+    STATE_HISTORY_START => "state_history_start";
 );
 
+/// Indexes with the node-local data, which is not a part of the blockchain state.
+const NODE_LOCAL_INDEXES: &[&str] = &[
+    TRANSACTIONS_POOL,
+    TRANSACTIONS_POOL_LEN,
+    TRANSACTIONS_POOL_AUTHORS,
+    TRANSACTIONS_POOL_AUTHORS_BY_LOAD,
+    TRANSACTIONS_POOL_BY_AUTHOR,
+    TRANSACTIONS_POOL_SEQ,
+    TRANSACTIONS_POOL_NEXT_SEQ,
+    TRANSACTIONS_POOL_ORDER,
+    TRANSACTIONS_POOL_PRIORITIES,
+    TRANSACTIONS_POOL_EXPIRATIONS,
+    TRANSACTIONS_POOL_EXPIRY_QUEUE,
+    EXPIRED_TRANSACTIONS,
+    PEERS_CACHE,
+    CONSENSUS_MESSAGES_CACHE,
+    CONSENSUS_ROUND,
+    STATE_HISTORY,
+    STATE_HISTORY_START,
+];
+
+/// Returns `true` if the index contains only node-local data.
+pub(crate) fn is_node_local_index(name: &str) -> bool {
+    NODE_LOCAL_INDEXES.contains(&name)
+}
+
+/// Returns `true` if the entry of the `core.transactions` index belongs to an unconfirmed
+/// transaction, which is a part of the node-local transaction pool.
+pub(crate) fn is_unconfirmed_transaction_entry(
+    view: &dyn Snapshot,
+    name: &str,
+    key: &[u8],
+) -> bool {
+    name == TRANSACTIONS && !view.contains(TRANSACTIONS_LOCATIONS, key)
+}
+
+/// Returns the Merkelized core indexes in the same order as `Schema::core_state_hash`.
+pub(crate) fn core_state_indexes() -> Vec<StateIndex> {
+    vec![
+        StateIndex::proof_map::<_, StoredConfiguration>(CONFIGS),
+        StateIndex::proof_map::<_, TransactionResult>(TRANSACTION_RESULTS),
+    ]
+}
+
+/// Returns the hash functions for the values of the Merkelized core indexes, which are not
+/// a part of `core_state_indexes`.
+pub(crate) fn core_auxiliary_value_hashes() -> Vec<(&'static str, ValueHashFn)> {
+    vec![
+        (BLOCK_TRANSACTIONS, hash_storage_value::<Hash>),
+        (STATE_HASH_AGGREGATOR, hash_storage_value::<Hash>),
+    ]
+}
+
+/// Reasons for rejecting a transaction due to the limits of the transaction pool.
+///
+/// The limits are specified in [`MemoryPoolConfig`].
+///
+/// [`MemoryPoolConfig`]: ../node/struct.MemoryPoolConfig.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Fail)]
+pub enum PoolLimitError {
+    /// The pool is full, and no transaction can be evicted from it.
+    #[fail(display = "Transaction pool is full")]
+    PoolIsFull,
+    /// The author of the transaction has too many transactions in the pool.
+    #[fail(display = "Transaction author has exceeded the quota of the transaction pool")]
+    QuotaExceeded,
+}
+
+/// Key of the `transactions_pool_order` index. Transactions with a higher priority go first,
+/// and transactions with the same priority are ordered by their arrival to the pool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct PoolOrderKey {
+    priority: u64,
+    seq: u64,
+}
+
+impl StorageKey for PoolOrderKey {
+    fn size(&self) -> usize {
+        16
+    }
+
+    fn write(&self, buffer: &mut [u8]) {
+        // Priority is inverted, so that the keys are sorted by the decreasing priority.
+        (!self.priority).write(&mut buffer[..8]);
+        self.seq.write(&mut buffer[8..]);
+    }
+
+    fn read(buffer: &[u8]) -> Self {
+        Self {
+            priority: !u64::read(&buffer[..8]),
+            seq: u64::read(&buffer[8..]),
+        }
+    }
+}
+
+/// Key of the `transactions_pool_expiry_queue` index. Transactions are ordered by their
+/// expiration height, and transactions with the same height are ordered by their arrival
+/// to the pool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct PoolExpiryKey {
+    height: u64,
+    seq: u64,
+}
+
+impl StorageKey for PoolExpiryKey {
+    fn size(&self) -> usize {
+        16
+    }
+
+    fn write(&self, buffer: &mut [u8]) {
+        self.height.write(&mut buffer[..8]);
+        self.seq.write(&mut buffer[8..]);
+    }
+
+    fn read(buffer: &[u8]) -> Self {
+        Self {
+            height: u64::read(&buffer[..8]),
+            seq: u64::read(&buffer[8..]),
+        }
+    }
+}
+
+/// Key of the index ordering the authors of the transactions in the pool by the number
+/// of their transactions. The number is inverted, so that the authors with more transactions
+/// go first; the authors with the same number of transactions are ordered by their keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct AuthorLoadKey {
+    inverted_count: u64,
+    author: PublicKey,
+}
+
+impl AuthorLoadKey {
+    fn new(author: PublicKey, count: u64) -> Self {
+        Self {
+            inverted_count: !count,
+            author,
+        }
+    }
+
+    fn count(&self) -> u64 {
+        !self.inverted_count
+    }
+}
+
+impl StorageKey for AuthorLoadKey {
+    fn size(&self) -> usize {
+        8 + self.author.size()
+    }
+
+    fn write(&self, buffer: &mut [u8]) {
+        self.inverted_count.write(&mut buffer[..8]);
+        self.author.write(&mut buffer[8..]);
+    }
+
+    fn read(buffer: &[u8]) -> Self {
+        Self {
+            inverted_count: u64::read(&buffer[..8]),
+            author: PublicKey::read(&buffer[8..]),
+        }
+    }
+}
+
 /// Configuration index.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ConfigReference {
@@ -238,6 +416,129 @@ where
         pool.get().unwrap_or(0)
     }
 
+    /// Returns a table that keeps the number of transactions in the pool for each author.
+    pub(crate) fn transactions_pool_authors(&self) -> MapIndex<&T, PublicKey, u64> {
+        MapIndex::new(TRANSACTIONS_POOL_AUTHORS, &self.view)
+    }
+
+    /// Returns a set of the authors of the transactions in the pool ordered by decreasing
+    /// number of their transactions.
+    pub(crate) fn transactions_pool_authors_by_load(&self) -> KeySetIndex<&T, AuthorLoadKey> {
+        KeySetIndex::new(TRANSACTIONS_POOL_AUTHORS_BY_LOAD, &self.view)
+    }
+
+    /// Returns a table that keeps the transactions of the given author in the pool
+    /// in the order they have been added to the pool.
+    pub(crate) fn transactions_pool_by_author(
+        &self,
+        author: &PublicKey,
+    ) -> MapIndex<&T, u64, Hash> {
+        MapIndex::new_in_family(TRANSACTIONS_POOL_BY_AUTHOR, author, &self.view)
+    }
+
+    /// Returns a table that keeps the sequence number of each tracked transaction in the pool.
+    pub(crate) fn transactions_pool_seq(&self) -> MapIndex<&T, Hash, u64> {
+        MapIndex::new(TRANSACTIONS_POOL_SEQ, &self.view)
+    }
+
+    /// Returns an entry that keeps the sequence number of the next transaction added to the pool.
+    pub(crate) fn transactions_pool_next_seq(&self) -> Entry<&T, u64> {
+        Entry::new(TRANSACTIONS_POOL_NEXT_SEQ, &self.view)
+    }
+
+    /// Returns a table that keeps the tracked transactions in the pool in the order
+    /// of their inclusion into proposals.
+    pub(crate) fn transactions_pool_order(&self) -> MapIndex<&T, PoolOrderKey, Hash> {
+        MapIndex::new(TRANSACTIONS_POOL_ORDER, &self.view)
+    }
+
+    /// Returns a table that keeps the priority of each tracked transaction in the pool.
+    pub(crate) fn transactions_pool_priorities(&self) -> MapIndex<&T, Hash, u64> {
+        MapIndex::new(TRANSACTIONS_POOL_PRIORITIES, &self.view)
+    }
+
+    /// Returns a table that keeps the expiration height of each tracked transaction in the pool
+    /// having one.
+    pub(crate) fn transactions_pool_expirations(&self) -> MapIndex<&T, Hash, u64> {
+        MapIndex::new(TRANSACTIONS_POOL_EXPIRATIONS, &self.view)
+    }
+
+    /// Returns a table that keeps the tracked transactions in the pool in the order of their
+    /// expiration.
+    pub(crate) fn transactions_pool_expiry_queue(&self) -> MapIndex<&T, PoolExpiryKey, Hash> {
+        MapIndex::new(TRANSACTIONS_POOL_EXPIRY_QUEUE, &self.view)
+    }
+
+    /// Returns a table that keeps the transactions removed from the pool due to their
+    /// [expiration], along with the height since which they are expired.
+    ///
+    /// [expiration]: trait.Transaction.html#method.expires_at
+    pub fn expired_transactions(&self) -> MapIndex<&T, Hash, u64> {
+        MapIndex::new(EXPIRED_TRANSACTIONS, &self.view)
+    }
+
+    /// Returns at most `limit` hashes of the transactions from the pool in the order of their
+    /// inclusion into proposals: by decreasing [priority], and then in the order of their
+    /// arrival to the pool. Transactions added to the pool by older versions of the node
+    /// go last.
+    ///
+    /// [priority]: trait.Transaction.html#method.priority
+    pub fn transactions_pool_ordered(&self, limit: usize) -> Vec<Hash> {
+        let mut txs: Vec<Hash> = self.transactions_pool_order().values().take(limit).collect();
+        if txs.len() < limit {
+            let tracked = self.transactions_pool_seq();
+            let pool = self.transactions_pool();
+            let untracked = pool.iter().filter(|hash| !tracked.contains(hash));
+            let remaining = limit - txs.len();
+            txs.extend(untracked.take(remaining));
+        }
+        txs
+    }
+
+    /// Returns the number of transactions of the given author in the pool.
+    pub fn transactions_pool_len_by_author(&self, author: &PublicKey) -> u64 {
+        self.transactions_pool_authors().get(author).unwrap_or(0)
+    }
+
+    /// Checks whether a transaction of the given author fits into the pool with the given
+    /// limits. Returns the hash of the transaction which needs to be evicted to free space
+    /// for the new one, if any.
+    ///
+    /// The evicted transaction is the oldest transaction satisfying `can_evict` of the author
+    /// with the most transactions in the pool (the author with the least public key among
+    /// the equal ones). The eviction is allowed only if this author has at least two
+    /// transactions more than the author of the new transaction, so the pool is shared fairly.
+    pub(crate) fn check_pool_limits<F>(
+        &self,
+        author: &PublicKey,
+        max_pool_size: Option<u64>,
+        max_txs_per_author: Option<u64>,
+        can_evict: F,
+    ) -> Result<Option<Hash>, PoolLimitError>
+    where
+        F: FnMut(&Hash) -> bool,
+    {
+        let author_txs = self.transactions_pool_len_by_author(author);
+        if max_txs_per_author.map_or(false, |max| author_txs >= max) {
+            return Err(PoolLimitError::QuotaExceeded);
+        }
+        match max_pool_size {
+            Some(max) if self.transactions_pool_len() >= max => {}
+            _ => return Ok(None),
+        }
+
+        let heaviest = self.transactions_pool_authors_by_load().iter().next();
+        match heaviest {
+            Some(key) if key.count() > author_txs + 1 => self
+                .transactions_pool_by_author(&key.author)
+                .values()
+                .find(can_evict)
+                .map(Some)
+                .ok_or(PoolLimitError::PoolIsFull),
+            _ => Err(PoolLimitError::PoolIsFull),
+        }
+    }
+
     /// Returns a table that keeps the block height and transaction position inside the block for every
     /// transaction hash.
     pub fn transactions_locations(&self) -> MapIndex<&T, Hash, TxLocation> {
@@ -317,12 +618,56 @@ where
             .unwrap_or_else(Round::first)
     }
 
+    /// Returns the height starting from which the block contents are available.
+    ///
+    /// Transaction bodies, precommits and lists of block transactions are removed
+    /// for all blocks below this height if the node runs with pruning enabled.
+    /// Block headers, block hashes and the Merkelized state are never pruned.
+    /// Returns the zero height if nothing has been pruned.
+    pub fn pruning_horizon(&self) -> Height {
+        Entry::new(PRUNING_HORIZON, &self.view)
+            .get()
+            .map_or_else(Height::zero, Height)
+    }
+
+    /// Returns `true` if the contents of the block at the given height have been pruned.
+    pub fn is_pruned(&self, height: Height) -> bool {
+        height < self.pruning_horizon()
+    }
+
+    /// Returns a table that keeps versions of the service data layouts, indexed by
+    /// the service names.
+    ///
+    /// The version is recorded when a service is initialized and updated by the
+    /// service migrations. See [`Service::schema_version`] for details.
+    ///
+    /// [`Service::schema_version`]: trait.Service.html#method.schema_version
+    pub fn service_schema_versions(&self) -> MapIndex<&T, String, u32> {
+        MapIndex::new(SERVICE_SCHEMA_VERSIONS, &self.view)
+    }
+
+    /// Returns a table with the previous values of the entries changed by the block
+    /// at the given height. Used to restore the state at past heights if the state history
+    /// is enabled in the node configuration.
+    pub(crate) fn state_history(&self, height: Height) -> MapIndex<&T, Vec<u8>, Vec<u8>> {
+        MapIndex::new_in_family(STATE_HISTORY, &height.0, &self.view)
+    }
+
+    /// Returns the earliest height, the state at which can be restored from the state history,
+    /// or `None` if the state history is not recorded.
+    pub fn state_history_start(&self) -> Option<Height> {
+        Entry::new(STATE_HISTORY_START, &self.view).get().map(Height)
+    }
+
     /// Returns the block hash for the given height.
     pub fn block_hash_by_height(&self, height: Height) -> Option<Hash> {
         self.block_hashes_by_height().get(height.into())
     }
 
     /// Returns the block for the given height with the proof of its inclusion.
+    ///
+    /// The list of precommits is empty if the block contents have been pruned,
+    /// see [`pruning_horizon`](#method.pruning_horizon).
     pub fn block_and_precommits(&self, height: Height) -> Option<BlockProof> {
         let block_hash = match self.block_hash_by_height(height) {
             None => return None,
@@ -520,6 +865,79 @@ impl<'a> Schema<&'a mut Fork> {
         Entry::new(TRANSACTIONS_POOL_LEN, self.view)
     }
 
+    /// Mutable reference to the [`transactions_pool_authors`][1] index.
+    ///
+    /// [1]: struct.Schema.html#method.transactions_pool_authors
+    fn transactions_pool_authors_mut(&mut self) -> MapIndex<&mut Fork, PublicKey, u64> {
+        MapIndex::new(TRANSACTIONS_POOL_AUTHORS, self.view)
+    }
+
+    /// Mutable reference to the [`transactions_pool_authors_by_load`][1] index.
+    ///
+    /// [1]: struct.Schema.html#method.transactions_pool_authors_by_load
+    fn transactions_pool_authors_by_load_mut(&mut self) -> KeySetIndex<&mut Fork, AuthorLoadKey> {
+        KeySetIndex::new(TRANSACTIONS_POOL_AUTHORS_BY_LOAD, self.view)
+    }
+
+    /// Mutable reference to the [`transactions_pool_by_author`][1] index.
+    ///
+    /// [1]: struct.Schema.html#method.transactions_pool_by_author
+    fn transactions_pool_by_author_mut(
+        &mut self,
+        author: &PublicKey,
+    ) -> MapIndex<&mut Fork, u64, Hash> {
+        MapIndex::new_in_family(TRANSACTIONS_POOL_BY_AUTHOR, author, self.view)
+    }
+
+    /// Mutable reference to the [`transactions_pool_seq`][1] index.
+    ///
+    /// [1]: struct.Schema.html#method.transactions_pool_seq
+    fn transactions_pool_seq_mut(&mut self) -> MapIndex<&mut Fork, Hash, u64> {
+        MapIndex::new(TRANSACTIONS_POOL_SEQ, self.view)
+    }
+
+    /// Mutable reference to the [`transactions_pool_next_seq`][1] index.
+    ///
+    /// [1]: struct.Schema.html#method.transactions_pool_next_seq
+    fn transactions_pool_next_seq_mut(&mut self) -> Entry<&mut Fork, u64> {
+        Entry::new(TRANSACTIONS_POOL_NEXT_SEQ, self.view)
+    }
+
+    /// Mutable reference to the [`transactions_pool_order`][1] index.
+    ///
+    /// [1]: struct.Schema.html#method.transactions_pool_order
+    fn transactions_pool_order_mut(&mut self) -> MapIndex<&mut Fork, PoolOrderKey, Hash> {
+        MapIndex::new(TRANSACTIONS_POOL_ORDER, self.view)
+    }
+
+    /// Mutable reference to the [`transactions_pool_priorities`][1] index.
+    ///
+    /// [1]: struct.Schema.html#method.transactions_pool_priorities
+    fn transactions_pool_priorities_mut(&mut self) -> MapIndex<&mut Fork, Hash, u64> {
+        MapIndex::new(TRANSACTIONS_POOL_PRIORITIES, self.view)
+    }
+
+    /// Mutable reference to the [`transactions_pool_expirations`][1] index.
+    ///
+    /// [1]: struct.Schema.html#method.transactions_pool_expirations
+    fn transactions_pool_expirations_mut(&mut self) -> MapIndex<&mut Fork, Hash, u64> {
+        MapIndex::new(TRANSACTIONS_POOL_EXPIRATIONS, self.view)
+    }
+
+    /// Mutable reference to the [`transactions_pool_expiry_queue`][1] index.
+    ///
+    /// [1]: struct.Schema.html#method.transactions_pool_expiry_queue
+    fn transactions_pool_expiry_queue_mut(&mut self) -> MapIndex<&mut Fork, PoolExpiryKey, Hash> {
+        MapIndex::new(TRANSACTIONS_POOL_EXPIRY_QUEUE, self.view)
+    }
+
+    /// Mutable reference to the [`expired_transactions`][1] index.
+    ///
+    /// [1]: struct.Schema.html#method.expired_transactions
+    fn expired_transactions_mut(&mut self) -> MapIndex<&mut Fork, Hash, u64> {
+        MapIndex::new(EXPIRED_TRANSACTIONS, self.view)
+    }
+
     /// Mutable reference to the [`transactions_locations`][1] index.
     ///
     /// [1]: struct.Schema.html#method.transactions_locations
@@ -597,12 +1015,84 @@ impl<'a> Schema<&'a mut Fork> {
         ListIndex::new(CONSENSUS_MESSAGES_CACHE, self.view)
     }
 
+    /// Mutable reference to the [`service_schema_versions`][1] index.
+    ///
+    /// [1]: struct.Schema.html#method.service_schema_versions
+    pub(crate) fn service_schema_versions_mut(&mut self) -> MapIndex<&mut Fork, String, u32> {
+        MapIndex::new(SERVICE_SCHEMA_VERSIONS, self.view)
+    }
+
+    /// Mutable reference to the [`state_history`][1] index.
+    ///
+    /// [1]: struct.Schema.html#method.state_history
+    pub(crate) fn state_history_mut(
+        &mut self,
+        height: Height,
+    ) -> MapIndex<&mut Fork, Vec<u8>, Vec<u8>> {
+        MapIndex::new_in_family(STATE_HISTORY, &height.0, self.view)
+    }
+
+    /// Saves the earliest height available in the state history.
+    pub(crate) fn set_state_history_start(&mut self, height: Height) {
+        let mut entry: Entry<&mut Fork, u64> = Entry::new(STATE_HISTORY_START, self.view);
+        entry.set(height.0);
+    }
+
+    /// Marks the state history as not recorded.
+    pub(crate) fn remove_state_history_start(&mut self) {
+        let mut entry: Entry<&mut Fork, u64> = Entry::new(STATE_HISTORY_START, self.view);
+        entry.remove();
+    }
+
+    /// Moves the pruning horizon to the given height without removing the block contents.
+    ///
+    /// Used when the contents of the blocks below the given height are not available,
+    /// e.g., after the state has been synchronized from the peers.
+    pub(crate) fn set_pruning_horizon(&mut self, height: Height) {
+        let mut horizon: Entry<&mut Fork, u64> = Entry::new(PRUNING_HORIZON, self.view);
+        horizon.set(height.0);
+    }
+
     /// Saves the given consensus round value into the storage.
     pub(crate) fn set_consensus_round(&mut self, round: Round) {
         let mut entry: Entry<&mut Fork, _> = Entry::new(CONSENSUS_ROUND, self.view);
         entry.set(round);
     }
 
+    /// Removes transaction bodies, precommits and lists of block transactions
+    /// for the blocks below the given height and moves the pruning horizon.
+    ///
+    /// At most `max_heights` heights are pruned per call, so that a node which has just
+    /// enabled pruning catches up gradually instead of producing a single huge patch.
+    /// Does nothing if the given height is not above the current pruning horizon.
+    pub(crate) fn prune_below(&mut self, height: Height, max_heights: u64) {
+        let from = self.pruning_horizon();
+        if height <= from {
+            return;
+        }
+        let to = Height(::std::cmp::min(height.0, from.0 + max_heights));
+
+        for pruned in from.0..to.0 {
+            let pruned = Height(pruned);
+            let block_hash = self
+                .block_hash_by_height(pruned)
+                .unwrap_or_else(|| panic!("Block not found, height: {:?}", pruned));
+            let tx_hashes = self.block_transactions(pruned).iter().collect::<Vec<_>>();
+            {
+                let mut transactions = self.transactions_mut();
+                for tx_hash in &tx_hashes {
+                    transactions.remove(tx_hash);
+                }
+            }
+            self.block_transactions_mut(pruned).clear();
+            self.precommits_mut(&block_hash).clear();
+        }
+
+        let mut horizon: Entry<&mut Fork, u64> = Entry::new(PRUNING_HORIZON, self.view);
+        horizon.set(to.0);
+        info!("Pruned block contents for heights {}..{}", from, to);
+    }
+
     /// Adds a new configuration to the blockchain, which will become actual at
     /// the `actual_from` height in `config_data`.
     pub fn commit_configuration(&mut self, config_data: StoredConfiguration) {
@@ -643,14 +1133,149 @@ impl<'a> Schema<&'a mut Fork> {
     /// Adds transaction into the persistent pool.
     /// This method increment `transactions_pool_len_index`,
     /// be sure to decrement it when transaction committed.
+    ///
+    /// The transaction is added with zero priority and without expiration.
+    /// Use `Blockchain::add_transaction_into_pool` to take them into account.
     #[doc(hidden)]
     pub fn add_transaction_into_pool(&mut self, tx: Signed<RawTransaction>) {
+        self.add_prioritized_transaction_into_pool(tx, 0);
+    }
+
+    /// Adds transaction with the given priority into the persistent pool.
+    /// The priority should be obtained with `Blockchain::tx_priority`.
+    #[doc(hidden)]
+    pub fn add_prioritized_transaction_into_pool(
+        &mut self,
+        tx: Signed<RawTransaction>,
+        priority: u64,
+    ) {
+        self.add_tracked_transaction_into_pool(tx, priority, None);
+    }
+
+    /// Adds transaction with the given priority and expiration height into the persistent pool.
+    pub(crate) fn add_tracked_transaction_into_pool(
+        &mut self,
+        tx: Signed<RawTransaction>,
+        priority: u64,
+        expires_at: Option<Height>,
+    ) {
         self.transactions_pool_mut().insert(tx.hash());
         let x = self.transactions_pool_len_index().get().unwrap_or(0);
         self.transactions_pool_len_index_mut().set(x + 1);
+        self.track_pool_transaction(&tx, priority, expires_at);
+        self.expired_transactions_mut().remove(&tx.hash());
         self.transactions_mut().put(&tx.hash(), tx);
     }
 
+    /// Adds the transaction into the indexes used to order the pool and to enforce its limits.
+    fn track_pool_transaction(
+        &mut self,
+        tx: &Signed<RawTransaction>,
+        priority: u64,
+        expires_at: Option<Height>,
+    ) {
+        let hash = tx.hash();
+        let author = tx.author();
+        let seq = self.transactions_pool_next_seq().get().unwrap_or(0);
+        self.transactions_pool_next_seq_mut().set(seq + 1);
+        self.transactions_pool_seq_mut().put(&hash, seq);
+        self.transactions_pool_priorities_mut().put(&hash, priority);
+        self.transactions_pool_order_mut().put(&PoolOrderKey { priority, seq }, hash);
+        if let Some(height) = expires_at {
+            self.transactions_pool_expirations_mut().put(&hash, height.0);
+            let key = PoolExpiryKey {
+                height: height.0,
+                seq,
+            };
+            self.transactions_pool_expiry_queue_mut().put(&key, hash);
+        }
+        self.transactions_pool_by_author_mut(&author).put(&seq, hash);
+        let count = self.transactions_pool_len_by_author(&author);
+        self.set_pool_author_count(author, count, count + 1);
+    }
+
+    /// Updates the number of transactions of the given author in the pool.
+    fn set_pool_author_count(&mut self, author: PublicKey, old_count: u64, count: u64) {
+        if old_count > 0 {
+            self.transactions_pool_authors_by_load_mut()
+                .remove(&AuthorLoadKey::new(author, old_count));
+        }
+        if count > 0 {
+            self.transactions_pool_authors_mut().put(&author, count);
+            self.transactions_pool_authors_by_load_mut()
+                .insert(AuthorLoadKey::new(author, count));
+        } else {
+            self.transactions_pool_authors_mut().remove(&author);
+        }
+    }
+
+    /// Removes the transaction from the indexes used to order the pool and to enforce its limits.
+    /// Does nothing if the transaction is not tracked, e.g., if it has been added to the pool
+    /// by an older version of the node.
+    pub(crate) fn untrack_pool_transaction(&mut self, hash: &Hash) {
+        let seq = match self.transactions_pool_seq().get(hash) {
+            Some(seq) => seq,
+            None => return,
+        };
+        let author = self
+            .transactions()
+            .get(hash)
+            .expect("Tracked transaction is absent from the transactions table")
+            .author();
+        let priority = self.transactions_pool_priorities().get(hash).unwrap_or(0);
+        self.transactions_pool_seq_mut().remove(hash);
+        self.transactions_pool_priorities_mut().remove(hash);
+        self.transactions_pool_order_mut().remove(&PoolOrderKey { priority, seq });
+        if let Some(height) = self.transactions_pool_expirations().get(hash) {
+            self.transactions_pool_expirations_mut().remove(hash);
+            self.transactions_pool_expiry_queue_mut().remove(&PoolExpiryKey { height, seq });
+        }
+        self.transactions_pool_by_author_mut(&author).remove(&seq);
+        let count = self.transactions_pool_len_by_author(&author);
+        self.set_pool_author_count(author, count, count.saturating_sub(1));
+    }
+
+    /// Removes the transaction from the pool to free space for another transaction.
+    pub(crate) fn evict_transaction(&mut self, hash: &Hash) {
+        self.untrack_pool_transaction(hash);
+        if self.remove_from_pool(hash) {
+            self.transactions_mut().remove(hash);
+        }
+    }
+
+    /// Removes the transactions expiring at the given height or earlier from the pool
+    /// and marks them as expired. Returns the number of the expired transactions.
+    ///
+    /// The bodies of the expired transactions are removed; only their hashes are kept,
+    /// so that the explorer is able to report them as expired.
+    pub(crate) fn expire_pool_transactions(&mut self, height: Height) -> usize {
+        let expired: Vec<(u64, Hash)> = self
+            .transactions_pool_expiry_queue()
+            .iter()
+            .take_while(|(key, _)| key.height <= height.0)
+            .map(|(key, hash)| (key.height, hash))
+            .collect();
+        for (expires_at, hash) in &expired {
+            self.untrack_pool_transaction(hash);
+            if self.remove_from_pool(hash) {
+                self.transactions_mut().remove(hash);
+                self.expired_transactions_mut().put(hash, *expires_at);
+            }
+        }
+        expired.len()
+    }
+
+    /// Removes the transaction from the pool, returning `true` if it has been in the pool.
+    fn remove_from_pool(&mut self, hash: &Hash) -> bool {
+        if !self.transactions_pool().contains(hash) {
+            return false;
+        }
+        self.transactions_pool_mut().remove(hash);
+        let x = self.transactions_pool_len_index().get().unwrap_or(0);
+        self.transactions_pool_len_index_mut().set(x.saturating_sub(1));
+        true
+    }
+
     /// Changes the transaction status from `in_pool`, to `committed`.
     pub(crate) fn commit_transaction(&mut self, hash: &Hash) {
         self.transactions_pool_mut().remove(hash);
@@ -16,6 +16,7 @@
 //! blockchain platforms, Exonum services encapsulate business logic of the blockchain application.
 
 use actix::Addr;
+use failure;
 use serde_json::Value;
 
 use std::{
@@ -27,7 +28,7 @@ use std::{
 
 use super::transaction::Transaction;
 use api::{websocket, ServiceApiBuilder};
-use blockchain::{ConsensusConfig, Schema, StoredConfiguration, ValidatorKeys};
+use blockchain::{ConsensusConfig, Schema, StateIndex, StoredConfiguration, ValidatorKeys};
 use crypto::{Hash, PublicKey, SecretKey};
 use encoding::Error as MessageError;
 use events::network::ConnectedPeerAddr;
@@ -151,6 +152,19 @@ pub trait Service: Send + Sync + 'static {
     /// [2]: struct.Blockchain.html#method.service_table_unique_key
     fn state_hash(&self, snapshot: &dyn Snapshot) -> Vec<Hash>;
 
+    /// Returns the Merkelized indexes of the service in the same order as their root hashes
+    /// are returned by `state_hash`. The indexes listed here can be synchronized by other nodes
+    /// over the network without replaying the blockchain history.
+    ///
+    /// The state of the blockchain cannot be synchronized if a service does not declare
+    /// all the indexes, which root hashes are returned by `state_hash`.
+    ///
+    /// *Default implementation returns an empty vector, which is suitable only for the services
+    /// not influencing the blockchain state.*
+    fn state_indexes(&self) -> Vec<StateIndex> {
+        Vec::new()
+    }
+
     /// Tries to create a `Transaction` from the given raw message.
     ///
     /// Exonum framework only guarantees that `SERVICE_ID` of the message is equal to the
@@ -185,6 +199,38 @@ pub trait Service: Send + Sync + 'static {
         Value::Null
     }
 
+    /// Returns the version of the service data layout expected by this implementation
+    /// of the service.
+    ///
+    /// The version of the data stored in the database is recorded in the core schema when
+    /// the service is initialized. If the stored version is lower than the returned one,
+    /// the node refuses to start until the data is migrated with the `migrate` action
+    /// of the `maintenance` command, which invokes [`migrate`] for each pending step.
+    ///
+    /// *Default implementation returns `0`.*
+    ///
+    /// [`migrate`]: #method.migrate
+    fn schema_version(&self) -> u32 {
+        0
+    }
+
+    /// Migrates the service data from the given version of the layout to the next one.
+    ///
+    /// The method is invoked for each version starting from the stored one up to
+    /// [`schema_version`]` - 1`. Migrations of all the services are applied to the storage
+    /// in a single patch, so nothing is changed if any of the steps fails.
+    ///
+    /// *Default implementation returns an error.*
+    ///
+    /// [`schema_version`]: #method.schema_version
+    fn migrate(&self, fork: &mut Fork, version: u32) -> Result<(), failure::Error> {
+        bail!(
+            "Service {} does not support migration from schema version {}",
+            self.service_name(),
+            version
+        )
+    }
+
     /// A service execution. This method is invoked for each service after execution
     /// of all transactions in the block but before `after_commit` handler.
     ///
@@ -0,0 +1,360 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Historical states of the blockchain.
+//!
+//! The node records the previous values of all the entries changed by each committed block,
+//! except for the node-local data. The state at a past height is obtained by applying
+//! the recorded values on top of the current state in the decreasing order of heights.
+//! The recorded values for a block form a reverse patch, so the history also allows to roll
+//! the database back to a past height. The history is kept for at least the latest
+//! `StateHistoryConfig::MIN_KEEP_HEIGHTS` heights even if the state history is disabled,
+//! so that the latest blocks can always be rolled back.
+//!
+//! Only the changes made by the committed blocks are recorded. The bodies of the transactions
+//! put into the `transactions` table on their arrival to the pool have no history, so they
+//! are present in the state at any past height; the table is not a part of the Merkelized
+//! state. Service data migrations are not recorded either; applying them removes
+//! the recorded history.
+//!
+//! A record is keyed by the index name and the key within the index separated by a zero byte
+//! (index names cannot contain it). A value is prefixed with a tag byte: `0` for the absent
+//! entries and `1` for the existing ones.
+
+use failure;
+
+use std::{
+    cmp,
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use super::{schema::is_node_local_index, Blockchain, Schema};
+use crypto::Hash;
+use helpers::{Height, Round};
+use node::StateHistoryConfig;
+use storage::{Change, Changes, Database, Fork, Patch, PatchedSnapshot, Snapshot};
+
+/// Maximum number of heights, the history for which is removed during one commit.
+const MAX_REMOVED_HEIGHTS_PER_COMMIT: u64 = 100;
+
+const ABSENT_TAG: u8 = 0;
+const PRESENT_TAG: u8 = 1;
+
+fn encode_key(name: &str, key: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(name.len() + 1 + key.len());
+    encoded.extend_from_slice(name.as_bytes());
+    encoded.push(0);
+    encoded.extend_from_slice(key);
+    encoded
+}
+
+fn decode_key(encoded: &[u8]) -> (&str, &[u8]) {
+    let separator = encoded
+        .iter()
+        .position(|&byte| byte == 0)
+        .expect("Invalid state history key");
+    let name = ::std::str::from_utf8(&encoded[..separator]).expect("Invalid index name");
+    (name, &encoded[separator + 1..])
+}
+
+fn encode_value(value: Option<Vec<u8>>) -> Vec<u8> {
+    match value {
+        Some(mut value) => {
+            value.insert(0, PRESENT_TAG);
+            value
+        }
+        None => vec![ABSENT_TAG],
+    }
+}
+
+fn decode_value(encoded: &[u8]) -> Option<Vec<u8>> {
+    match encoded.first() {
+        Some(&PRESENT_TAG) => Some(encoded[1..].to_vec()),
+        _ => None,
+    }
+}
+
+/// Cache of the changes reverting the state to the latest requested past height.
+///
+/// The state at a past height is usually read many times in a row, e.g., chunk by chunk
+/// during the state synchronization, so the changes are collected from the history once
+/// and shared by the snapshots until another height is requested or a block is committed.
+#[derive(Debug, Default)]
+pub(crate) struct PastStateCache {
+    entry: Mutex<Option<PastStateChanges>>,
+}
+
+#[derive(Debug)]
+struct PastStateChanges {
+    height: Height,
+    // Hash of the latest block of the state, on top of which the changes are applied.
+    last_block_hash: Hash,
+    patch: Arc<Patch>,
+}
+
+impl PastStateCache {
+    /// Returns the cached changes for the given height and the latest block,
+    /// or collects and caches them.
+    fn get_or_insert<F>(&self, height: Height, last_block_hash: Hash, collect: F) -> Arc<Patch>
+    where
+        F: FnOnce() -> Patch,
+    {
+        let mut entry = self.entry.lock().unwrap();
+        if let Some(ref changes) = *entry {
+            if changes.height == height && changes.last_block_hash == last_block_hash {
+                return Arc::clone(&changes.patch);
+            }
+        }
+        let patch = Arc::new(collect());
+        *entry = Some(PastStateChanges {
+            height,
+            last_block_hash,
+            patch: Arc::clone(&patch),
+        });
+        patch
+    }
+}
+
+/// Records the previous values of the entries changed in the `fork` by the block
+/// at the given height, and removes the history which is no longer needed according
+/// to `config`. If `config` is `None`, the history is kept only for the latest
+/// `MIN_KEEP_HEIGHTS` heights. The previous values are read from `snapshot`, which must
+/// not contain the changes from the `fork`.
+///
+/// This method should be called after all the changes of the block are made in the fork.
+pub(crate) fn record_state_history(
+    fork: &mut Fork,
+    snapshot: &dyn Snapshot,
+    height: Height,
+    config: Option<StateHistoryConfig>,
+) {
+    let records: Vec<(Vec<u8>, Vec<u8>)> = fork
+        .patch()
+        .iter()
+        .filter(|(name, _)| !is_node_local_index(name))
+        .flat_map(|(name, changes)| {
+            changes.iter().map(move |(key, _)| {
+                let previous = snapshot.get(name, key);
+                (encode_key(name, key), encode_value(previous))
+            })
+        }).collect();
+
+    let mut schema = Schema::new(fork);
+    {
+        let mut history = schema.state_history_mut(height);
+        for (key, value) in records {
+            history.put(&key, value);
+        }
+    }
+
+    let start = schema
+        .state_history_start()
+        .unwrap_or_else(|| Height(height.0.saturating_sub(1)));
+    let keep_heights = match config {
+        Some(config) => config
+            .keep_heights
+            .map(|keep_heights| cmp::max(keep_heights, StateHistoryConfig::MIN_KEEP_HEIGHTS)),
+        None => Some(StateHistoryConfig::MIN_KEEP_HEIGHTS),
+    };
+    let new_start = match keep_heights {
+        Some(keep_heights) if height.0 >= keep_heights => {
+            let horizon = height.0 + 1 - keep_heights;
+            Height(cmp::min(horizon, start.0 + MAX_REMOVED_HEIGHTS_PER_COMMIT))
+        }
+        _ => start,
+    };
+    for removed in start.0 + 1..=new_start.0 {
+        schema.state_history_mut(Height(removed)).clear();
+    }
+    schema.set_state_history_start(new_start);
+}
+
+/// Removes the recorded state history. Called when the database is changed outside
+/// of the blocks, e.g., by service data migrations, since the history becomes incomplete.
+pub(crate) fn clear_state_history(fork: &mut Fork) {
+    let mut schema = Schema::new(fork);
+    if let Some(start) = schema.state_history_start() {
+        let height = schema.height();
+        for cleared in start.0 + 1..=height.0 {
+            schema.state_history_mut(Height(cleared)).clear();
+        }
+        schema.remove_state_history_start();
+        info!("Removed the state history for heights {}..{}", start, height);
+    }
+}
+
+/// Returns a read-only snapshot of the blockchain state at the given height, or `None`
+/// if the state at this height is not available or the height is more than `max_depth`
+/// heights below the current one.
+///
+/// The snapshot keeps in memory all the entries changed after the given height. If `cache`
+/// is specified, these entries are taken from it or collected into it, so that the subsequent
+/// snapshots at the same height share them.
+pub(crate) fn snapshot_at(
+    db: &dyn Database,
+    height: Height,
+    max_depth: Option<u64>,
+    cache: Option<&PastStateCache>,
+) -> Option<Box<dyn Snapshot>> {
+    let snapshot = db.snapshot();
+    let last_block_hash = {
+        let schema = Schema::new(&snapshot);
+        let current_height = schema.height();
+        if height > current_height {
+            return None;
+        }
+        if max_depth.map_or(false, |depth| current_height.0 - height.0 > depth) {
+            return None;
+        }
+        if height < current_height && !is_recorded(&schema, height) {
+            return None;
+        }
+        if height < current_height {
+            Some(schema.last_block().hash())
+        } else {
+            None
+        }
+    };
+    let last_block_hash = match last_block_hash {
+        Some(hash) => hash,
+        None => return Some(snapshot),
+    };
+
+    let patch = {
+        let collect = || previous_values_patch(previous_values(&Schema::new(&snapshot), height));
+        match cache {
+            Some(cache) => cache.get_or_insert(height, last_block_hash, collect),
+            None => Arc::new(collect()),
+        }
+    };
+    Some(Box::new(PatchedSnapshot::new(snapshot, patch)))
+}
+
+/// Rolls the database back to the given height by reverting the changes made by the blocks
+/// committed after it. The state history must be recorded for these blocks.
+///
+/// The later blocks are removed together with their precommits, transaction results
+/// and locations. The transactions of the removed blocks, which were received by the node
+/// before the commit, are returned to the pool with the priority and expiration height
+/// obtained from the services of the blockchain.
+pub(crate) fn rollback_blocks(
+    blockchain: &Blockchain,
+    height: Height,
+) -> Result<(), failure::Error> {
+    let mut fork = blockchain.fork();
+    let (current_height, previous_values, reverted_txs) = {
+        let schema = Schema::new(&fork);
+        let current_height = schema.height();
+        ensure!(
+            height < current_height,
+            "Height {} is not below the current height {}",
+            height,
+            current_height
+        );
+        ensure!(
+            is_recorded(&schema, height),
+            "State history is not recorded for the blocks after height {}",
+            height
+        );
+        let mut reverted_txs = Vec::new();
+        for reverted in height.0 + 1..=current_height.0 {
+            reverted_txs.extend(schema.block_transactions(Height(reverted)).iter());
+        }
+        (current_height, previous_values(&schema, height), reverted_txs)
+    };
+    apply_previous_values(&mut fork, previous_values);
+
+    {
+        let mut schema = Schema::new(&mut fork);
+        for reverted in height.0 + 1..=current_height.0 {
+            schema.state_history_mut(Height(reverted)).clear();
+        }
+        // Consensus messages and the round relate to the heights, which are removed.
+        schema.consensus_messages_cache_mut().clear();
+        schema.set_consensus_round(Round::first());
+    }
+    for tx_hash in &reverted_txs {
+        let tx = {
+            let schema = Schema::new(&fork);
+            if schema.transactions_pool().contains(tx_hash) {
+                continue;
+            }
+            schema.transactions().get(tx_hash)
+        };
+        if let Some(tx) = tx {
+            blockchain.add_transaction_into_pool(&mut fork, tx);
+        }
+    }
+    blockchain.db.merge_sync(fork.into_patch())?;
+    info!(
+        "Rolled back blocks at heights {}..{}, the current height is {}",
+        height.next(),
+        current_height.next(),
+        height
+    );
+    Ok(())
+}
+
+/// Returns `true` if the state history is recorded for all the blocks after the given height.
+fn is_recorded<T: AsRef<dyn Snapshot>>(schema: &Schema<T>, height: Height) -> bool {
+    schema
+        .state_history_start()
+        .map_or(false, |start| height >= start)
+}
+
+/// Collects the values, which the entries changed after the given height had at this height.
+fn previous_values<T: AsRef<dyn Snapshot>>(
+    schema: &Schema<T>,
+    height: Height,
+) -> HashMap<Vec<u8>, Vec<u8>> {
+    // The values recorded for the smaller heights override the ones for the bigger heights.
+    let mut previous_values = HashMap::new();
+    for changed in (height.0 + 1..=schema.height().0).rev() {
+        previous_values.extend(schema.state_history(Height(changed)).iter());
+    }
+    previous_values
+}
+
+/// Converts the previous values into a patch reverting the changes made after their height.
+fn previous_values_patch(previous_values: HashMap<Vec<u8>, Vec<u8>>) -> Patch {
+    let mut changes = HashMap::new();
+    for (key, value) in previous_values {
+        let (name, key) = decode_key(&key);
+        let change = match decode_value(&value) {
+            Some(value) => Change::Put(value),
+            None => Change::Delete,
+        };
+        changes
+            .entry(name.to_owned())
+            .or_insert_with(Changes::new)
+            .insert(key.to_vec(), change);
+    }
+
+    let mut patch = Patch::new();
+    for (name, changes) in changes {
+        patch.insert_changes(name, changes);
+    }
+    patch
+}
+
+fn apply_previous_values(fork: &mut Fork, previous_values: HashMap<Vec<u8>, Vec<u8>>) {
+    for (key, value) in previous_values {
+        let (name, key) = decode_key(&key);
+        match decode_value(&value) {
+            Some(value) => fork.put(name, key.to_vec(), value),
+            None => fork.remove(name, key.to_vec()),
+        }
+    }
+}
@@ -0,0 +1,408 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Export and import of the blockchain state snapshots.
+//!
+//! A state snapshot contains all the entries of the blockchain indexes at a certain
+//! height together with the header of the block at this height and its precommits.
+//! A new node may bootstrap from a snapshot instead of replaying all the blocks starting
+//! from the genesis one. The imported state is checked against the `state_hash` of the block,
+//! which in turn is authenticated by the precommits of the validators.
+//!
+//! The snapshot file has the following layout:
+//!
+//! - magic bytes `EXNMSNAP` and the format version (`u32`, little endian),
+//! - JSON-serialized `BlockProof` of the exported block prefixed with its length,
+//! - a sequence of entries, each being a tag byte `1` followed by the index name,
+//!   the key and the value, all prefixed with their lengths,
+//! - a tag byte `0` denoting the end of entries,
+//! - the hash of all the preceding bytes.
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use failure;
+use serde_json;
+
+use std::{
+    collections::HashSet,
+    io::{self, Read, Write},
+    mem,
+};
+
+use super::{
+    schema::{is_node_local_index, is_unconfirmed_transaction_entry},
+    state_history, BlockProof, Blockchain, Schema,
+};
+use crypto::{CryptoHash, Hash, HashStream, PublicKey, HASH_SIZE};
+use helpers::Height;
+use messages::Precommit;
+use node::State;
+use storage::{self, Database, Fork};
+
+const MAGIC: &[u8] = b"EXNMSNAP";
+const FORMAT_VERSION: u32 = 1;
+const ENTRY_TAG: u8 = 1;
+const END_TAG: u8 = 0;
+
+/// Writes the blockchain state at the given height into `writer`.
+///
+/// The state at a height below the current one is available only if the state history
+/// is recorded for it, see [`StateHistoryConfig`]. Node-local data (the transaction pool,
+/// the peers cache and the consensus messages cache) is not exported. Returns the header
+/// of the block at the given height and its precommits, against which the snapshot will be
+/// verified on import.
+///
+/// [`StateHistoryConfig`]: ../node/struct.StateHistoryConfig.html
+pub fn export_state<W: Write>(
+    db: &dyn Database,
+    height: Height,
+    writer: W,
+) -> Result<BlockProof, failure::Error> {
+    let snapshot = state_history::snapshot_at(db, height, None, None)
+        .ok_or_else(|| format_err!("State at height {} is not available", height))?;
+    let snapshot = snapshot.as_ref();
+    let schema = Schema::new(snapshot);
+    let proof = schema
+        .block_and_precommits(height)
+        .ok_or_else(|| format_err!("Blockchain is not initialized"))?;
+    ensure!(
+        height == Height::zero() || !proof.precommits.is_empty(),
+        "Precommits for the block at height {} are missing",
+        height
+    );
+
+    let mut writer = HashingWriter::new(writer);
+    writer.write_all(MAGIC)?;
+    writer.write_u32::<LittleEndian>(FORMAT_VERSION)?;
+    write_bytes(&mut writer, &serde_json::to_vec(&proof)?)?;
+
+    for name in storage::index_names(snapshot) {
+        if is_node_local_index(&name) {
+            continue;
+        }
+
+        // Keys of the indexes metadata table are index names.
+        let metadata_key = name.as_bytes();
+        if let Some(metadata) = snapshot.get(storage::INDEXES_METADATA_TABLE_NAME, metadata_key) {
+            write_entry(
+                &mut writer,
+                storage::INDEXES_METADATA_TABLE_NAME,
+                metadata_key,
+                &metadata,
+            )?;
+        }
+
+        let mut iter = snapshot.iter(&name, &[]);
+        while let Some((key, value)) = iter.next() {
+            if is_unconfirmed_transaction_entry(snapshot, &name, key) {
+                continue;
+            }
+            write_entry(&mut writer, &name, key, value)?;
+        }
+    }
+
+    writer.write_u8(END_TAG)?;
+    let (mut writer, checksum) = writer.finish();
+    writer.write_all(checksum.as_ref())?;
+    writer.flush()?;
+    Ok(proof)
+}
+
+impl Blockchain {
+    /// Imports the blockchain state from a snapshot created by [`export_state`].
+    ///
+    /// The blockchain must contain only the genesis block, which must coincide with the
+    /// genesis block of the snapshot. The imported state is verified against the `state_hash`
+    /// of the block in the snapshot, and the block is verified against the signed precommits
+    /// of the validators trusted by the local blockchain, i.e., the validators specified in its
+    /// actual configuration. Nothing is written into the storage if any of the checks fails.
+    ///
+    /// The chain of the configuration changes is not verified, so a snapshot taken after
+    /// the validator set has changed cannot be verified against the genesis validators
+    /// and is rejected. Such a snapshot can be imported with [`import_state_with_validators`]
+    /// if the consensus keys of the validators at the snapshot height are known.
+    ///
+    /// Returns the imported block together with its precommits.
+    ///
+    /// [`export_state`]: fn.export_state.html
+    /// [`import_state_with_validators`]: #method.import_state_with_validators
+    pub fn import_state<R: Read>(&mut self, reader: R) -> Result<BlockProof, failure::Error> {
+        self.import_state_impl(reader, None)
+    }
+
+    /// Imports the blockchain state from a snapshot created by [`export_state`], verifying
+    /// the block of the snapshot against the precommits of the given validators, which must
+    /// coincide with the validators of the snapshot at its height. See [`import_state`]
+    /// for details.
+    ///
+    /// [`export_state`]: fn.export_state.html
+    /// [`import_state`]: #method.import_state
+    pub fn import_state_with_validators<R: Read>(
+        &mut self,
+        reader: R,
+        validator_keys: &[PublicKey],
+    ) -> Result<BlockProof, failure::Error> {
+        self.import_state_impl(reader, Some(validator_keys.to_vec()))
+    }
+
+    fn import_state_impl<R: Read>(
+        &mut self,
+        reader: R,
+        trusted_keys: Option<Vec<PublicKey>>,
+    ) -> Result<BlockProof, failure::Error> {
+        let (genesis_hash, validator_keys) = {
+            let snapshot = self.snapshot();
+            let schema = Schema::new(&snapshot);
+            ensure!(
+                schema.height() == Height::zero(),
+                "State can be imported only into a blockchain with the genesis block"
+            );
+            let genesis_hash = schema
+                .block_hash_by_height(Height::zero())
+                .ok_or_else(|| format_err!("Blockchain is not initialized"))?;
+            // Validator keys are taken from the local blockchain rather than from the snapshot,
+            // which is not trusted until the precommits are verified.
+            let validator_keys = trusted_keys.unwrap_or_else(|| {
+                schema
+                    .actual_configuration()
+                    .validator_keys
+                    .iter()
+                    .map(|keys| keys.consensus_key)
+                    .collect()
+            });
+            (genesis_hash, validator_keys)
+        };
+
+        let mut reader = HashingReader::new(reader);
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        ensure!(&magic[..] == MAGIC, "Not a state snapshot file");
+        let version = reader.read_u32::<LittleEndian>()?;
+        ensure!(
+            version == FORMAT_VERSION,
+            "Unsupported state snapshot version: {}",
+            version
+        );
+        let proof: BlockProof = serde_json::from_slice(&read_bytes(&mut reader)?)?;
+
+        let mut fork = self.fork();
+        // Entries created by the genesis block may be absent in the snapshot.
+        for name in storage::index_names(&fork) {
+            if !is_node_local_index(&name) {
+                fork.remove_by_prefix(&name, None);
+            }
+        }
+        loop {
+            match reader.read_u8()? {
+                ENTRY_TAG => {
+                    let name = String::from_utf8(read_bytes(&mut reader)?)?;
+                    let key = read_bytes(&mut reader)?;
+                    let value = read_bytes(&mut reader)?;
+                    ensure!(
+                        !is_node_local_index(&name),
+                        "State snapshot contains node-local index {}",
+                        name
+                    );
+                    fork.put(&name, key, value);
+                }
+                END_TAG => break,
+                tag => bail!("Unknown entry tag in the state snapshot: {}", tag),
+            }
+        }
+
+        let (mut reader, actual_checksum) = reader.finish();
+        let mut checksum = [0; HASH_SIZE];
+        reader.read_exact(&mut checksum)?;
+        ensure!(
+            Hash::new(checksum) == actual_checksum,
+            "State snapshot checksum mismatch"
+        );
+
+        self.verify_imported_state(&fork, &proof, &genesis_hash, &validator_keys)?;
+        self.merge(fork.into_patch())?;
+        info!("Imported blockchain state at height {}", proof.block.height());
+        Ok(proof)
+    }
+
+    fn verify_imported_state(
+        &self,
+        fork: &Fork,
+        proof: &BlockProof,
+        genesis_hash: &Hash,
+        validator_keys: &[PublicKey],
+    ) -> Result<(), failure::Error> {
+        let schema = Schema::new(fork);
+        let height = proof.block.height();
+        let block_hash = proof.block.hash();
+
+        ensure!(
+            schema.block_hash_by_height(Height::zero()).as_ref() == Some(genesis_hash),
+            "Genesis block of the state snapshot differs from the local one"
+        );
+        ensure!(
+            schema.height() == height && schema.block_hash_by_height(height) == Some(block_hash),
+            "Latest block of the state snapshot does not match the block header"
+        );
+
+        let snapshot_keys: Vec<_> = schema
+            .configuration_by_height(height)
+            .validator_keys
+            .iter()
+            .map(|keys| keys.consensus_key)
+            .collect();
+        ensure!(
+            snapshot_keys == validator_keys,
+            "Validators of the state snapshot at height {} differ from the trusted ones; \
+             if the validator set has changed since the genesis block, the snapshot must be \
+             imported with the keys of the actual validators",
+            height
+        );
+        if height > Height::zero() {
+            verify_block_precommits(proof, &block_hash, validator_keys)?;
+        }
+
+        let aggregator = schema.state_hash_aggregator();
+        for (key, hash) in self.collect_state_hashes(fork, height) {
+            ensure!(
+                aggregator.get(&key) == Some(hash),
+                "State hash of the table {:?} does not match the state snapshot",
+                key
+            );
+        }
+        ensure!(
+            aggregator.merkle_root() == *proof.block.state_hash(),
+            "State hash of the state snapshot does not match the block header"
+        );
+        Ok(())
+    }
+}
+
+/// Checks that the block is approved by the majority of the given validators.
+///
+/// The precommits may come from an untrusted source, so each of them is parsed anew
+/// from its signed raw message, and only the fields of the parsed message are checked.
+pub(crate) fn verify_block_precommits(
+    proof: &BlockProof,
+    block_hash: &Hash,
+    validator_keys: &[PublicKey],
+) -> Result<(), failure::Error> {
+    ensure!(
+        proof.precommits.len() >= State::byzantine_majority_count(validator_keys.len()),
+        "Block has no consensus"
+    );
+
+    let mut validators = HashSet::with_capacity(proof.precommits.len());
+    let mut round = None;
+    for precommit in &proof.precommits {
+        let precommit = Precommit::verify_precommit(precommit.signed_message().raw().to_vec())
+            .map_err(|_| format_err!("Precommit has an invalid signature"))?;
+        ensure!(
+            validators.insert(precommit.validator()),
+            "Several precommits from one validator"
+        );
+        let key = validator_keys
+            .get(precommit.validator().0 as usize)
+            .ok_or_else(|| format_err!("Precommit from unknown validator"))?;
+        ensure!(*key == precommit.author(), "Precommit is signed by a wrong key");
+        let round = *round.get_or_insert(precommit.round());
+        ensure!(
+            precommit.block_hash() == block_hash
+                && precommit.height() == proof.block.height()
+                && precommit.round() == round,
+            "Precommit does not correspond to the block"
+        );
+    }
+    Ok(())
+}
+
+fn write_entry<W: Write>(writer: &mut W, name: &str, key: &[u8], value: &[u8]) -> io::Result<()> {
+    writer.write_u8(ENTRY_TAG)?;
+    write_bytes(writer, name.as_bytes())?;
+    write_bytes(writer, key)?;
+    write_bytes(writer, value)
+}
+
+fn write_bytes<W: Write>(writer: &mut W, bytes: &[u8]) -> io::Result<()> {
+    writer.write_u32::<LittleEndian>(bytes.len() as u32)?;
+    writer.write_all(bytes)
+}
+
+fn read_bytes<R: Read>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let len = reader.read_u32::<LittleEndian>()?;
+    let mut bytes = Vec::new();
+    reader.by_ref().take(u64::from(len)).read_to_end(&mut bytes)?;
+    if bytes.len() != len as usize {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(bytes)
+}
+
+/// Writer calculating the hash of all the written bytes.
+struct HashingWriter<W> {
+    inner: W,
+    stream: HashStream,
+}
+
+impl<W: Write> HashingWriter<W> {
+    fn new(inner: W) -> Self {
+        Self {
+            inner,
+            stream: HashStream::new(),
+        }
+    }
+
+    fn finish(self) -> (W, Hash) {
+        (self.inner, self.stream.hash())
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        let stream = mem::replace(&mut self.stream, HashStream::new());
+        self.stream = stream.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Reader calculating the hash of all the read bytes.
+struct HashingReader<R> {
+    inner: R,
+    stream: HashStream,
+}
+
+impl<R: Read> HashingReader<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            stream: HashStream::new(),
+        }
+    }
+
+    fn finish(self) -> (R, Hash) {
+        (self.inner, self.stream.hash())
+    }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        let stream = mem::replace(&mut self.stream, HashStream::new());
+        self.stream = stream.update(&buf[..read]);
+        Ok(read)
+    }
+}
@@ -0,0 +1,646 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Synchronization of the Merkelized blockchain state over the network.
+//!
+//! The root hashes of the Merkelized tables of the core and the services are aggregated
+//! in the `state_hash_aggregator`, which root hash is the `state_hash` of a block. A node
+//! may download these tables from its peers chunk by chunk with the `StateChunkRequest`
+//! messages. Each `StateChunkResponse` contains the proof of the table root hash in
+//! the `state_hash_aggregator` and the proof of the chunk entries in the table, so every chunk
+//! is verified as soon as it is received.
+//!
+//! After the Merkelized tables, the node downloads the headers of the blocks starting from
+//! the synchronized one down to the genesis block. The headers are verified by their hashes,
+//! since each header contains the hash of the previous one. The contents of the downloaded
+//! blocks (transactions and precommits) are not synchronized, so these blocks are treated
+//! as pruned, see [`Schema::pruning_horizon`]. The node-local and the non-Merkelized service
+//! indexes are not synchronized either.
+//!
+//! [`Schema::pruning_horizon`]: struct.Schema.html#method.pruning_horizon
+
+use byteorder::{BigEndian, ByteOrder};
+use failure;
+use serde_json;
+
+use std::{borrow::Cow, fmt, marker::PhantomData};
+
+use super::{
+    schema::{core_state_indexes, ConfigReference},
+    state_snapshot::verify_block_precommits,
+    Block, BlockProof, Blockchain, Schema, CORE_SERVICE,
+};
+use crypto::{CryptoHash, Hash, PublicKey};
+use helpers::Height;
+use messages::{Precommit, Signed, StateChunkRequest, StateChunkResponse};
+use storage::{
+    hash_storage_value, Fork, ListProof, MapProof, Patch, ProofListIndex, ProofMapIndex, Snapshot,
+    StorageValue, ValueHashFn,
+};
+
+/// Maximal number of entries in a single state chunk.
+pub const STATE_CHUNK_SIZE: u64 = 1_000;
+
+/// Merkelized index, which root hash is a part of the blockchain state.
+///
+/// Services report their Merkelized indexes with [`Service::state_indexes`] so that
+/// their state can be synchronized over the network. Only ordinary indexes (i.e., not members
+/// of index families) are supported.
+///
+/// [`Service::state_indexes`]: trait.Service.html#method.state_indexes
+pub struct StateIndex {
+    name: String,
+    kind: StateIndexKind,
+    apply_chunk: ApplyChunkFn,
+    value_hash: ValueHashFn,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StateIndexKind {
+    ProofMap,
+    ProofList,
+}
+
+/// Verifies a chunk against the table hash, adds its entries to the table and returns
+/// the root hash of the synchronized part of the table.
+type ApplyChunkFn = fn(&mut Fork, &str, StateChunk, Hash) -> Result<Hash, failure::Error>;
+
+impl StateIndex {
+    /// Describes a `ProofMapIndex` with the given name and type of values.
+    pub fn proof_map<S: Into<String>, V: StorageValue>(name: S) -> Self {
+        Self {
+            name: name.into(),
+            kind: StateIndexKind::ProofMap,
+            apply_chunk: apply_map_chunk::<V>,
+            value_hash: hash_storage_value::<V>,
+        }
+    }
+
+    /// Describes a `ProofListIndex` with the given name and type of values.
+    pub fn proof_list<S: Into<String>, V: StorageValue>(name: S) -> Self {
+        Self {
+            name: name.into(),
+            kind: StateIndexKind::ProofList,
+            apply_chunk: apply_list_chunk::<V>,
+            value_hash: hash_storage_value::<V>,
+        }
+    }
+
+    /// Returns the name of the index.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the function computing the hashes of the index values.
+    pub(crate) fn value_hash(&self) -> ValueHashFn {
+        self.value_hash
+    }
+}
+
+impl Clone for StateIndex {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            kind: self.kind,
+            apply_chunk: self.apply_chunk,
+            value_hash: self.value_hash,
+        }
+    }
+}
+
+impl fmt::Debug for StateIndex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("StateIndex")
+            .field("name", &self.name)
+            .field("kind", &self.kind)
+            .finish()
+    }
+}
+
+/// Entries of a state chunk together with the proof of their authenticity.
+///
+/// Keys and values of the entries are transferred in their storage representation.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum StateChunk {
+    /// Entries of a `ProofMapIndex`.
+    Map(MapProof<Hash, Vec<u8>>),
+    /// Entries of a `ProofListIndex` with the given length.
+    List {
+        /// Length of the list.
+        len: u64,
+        /// Proof of the entries, or `None` if the list is empty.
+        proof: Option<ListProof<Vec<u8>>>,
+    },
+    /// Headers of consecutive blocks in the descending order of their heights.
+    Blocks(Vec<Block>),
+}
+
+/// Returns the key used in `StateChunkRequest` to request the block headers.
+///
+/// The key cannot coincide with a `service_table_unique_key` of a Merkelized table.
+fn block_headers_key() -> Hash {
+    Hash::zero()
+}
+
+impl Blockchain {
+    /// Returns the Merkelized indexes of the core and the services keyed by
+    /// `service_table_unique_key`.
+    pub fn state_indexes(&self) -> Vec<(Hash, StateIndex)> {
+        let mut indexes = Vec::new();
+        for (idx, index) in core_state_indexes().into_iter().enumerate() {
+            indexes.push((Self::service_table_unique_key(CORE_SERVICE, idx), index));
+        }
+        for service in self.service_map.values() {
+            let service_id = service.service_id();
+            for (idx, index) in service.state_indexes().into_iter().enumerate() {
+                indexes.push((Self::service_table_unique_key(service_id, idx), index));
+            }
+        }
+        indexes
+    }
+
+    /// Creates a response to the given `StateChunkRequest` with the blockchain state
+    /// in the given snapshot.
+    ///
+    /// Returns `None` if the table is unknown or the requested key is out of the table bounds.
+    pub(crate) fn state_chunk_response(
+        &self,
+        snapshot: &dyn Snapshot,
+        to: &PublicKey,
+        request: &StateChunkRequest,
+    ) -> Option<StateChunkResponse> {
+        let table_key = request.table_key();
+        if *table_key == block_headers_key() {
+            return block_headers_response(snapshot, to, request);
+        }
+        let index = self
+            .state_indexes()
+            .into_iter()
+            .find(|&(ref key, _)| key == table_key)
+            .map(|(_, index)| index)?;
+
+        let name = index.name();
+        let (chunk, next) = match index.kind {
+            StateIndexKind::ProofMap => {
+                let table: ProofMapIndex<_, Hash, Vec<u8>> = ProofMapIndex::new(name, snapshot);
+                let mut keys: Vec<Hash> = if request.from().is_empty() {
+                    table.keys().take(STATE_CHUNK_SIZE as usize + 1).collect()
+                } else {
+                    let from = Hash::from_slice(request.from())?;
+                    table
+                        .keys_from(&from)
+                        .take(STATE_CHUNK_SIZE as usize + 1)
+                        .collect()
+                };
+                let next = if keys.len() > STATE_CHUNK_SIZE as usize {
+                    keys.pop().map(|key| key.as_ref().to_vec())
+                } else {
+                    None
+                };
+                (StateChunk::Map(table.get_multiproof(keys)), next)
+            }
+            StateIndexKind::ProofList => {
+                let table: ProofListIndex<_, Vec<u8>> = ProofListIndex::new(name, snapshot);
+                let len = table.len();
+                let from = decode_list_position(request.from())?;
+                if len == 0 && from == 0 {
+                    (StateChunk::List { len, proof: None }, None)
+                } else if from >= len {
+                    return None;
+                } else {
+                    let to = len.min(from + STATE_CHUNK_SIZE);
+                    let next = if to < len {
+                        Some(encode_list_position(to))
+                    } else {
+                        None
+                    };
+                    let proof = table.get_range_proof(from, to);
+                    (StateChunk::List { len, proof: Some(proof) }, next)
+                }
+            }
+        };
+
+        let schema = Schema::new(snapshot);
+        let table_proof = schema.state_hash_aggregator().get_proof(*table_key);
+        Some(StateChunkResponse::new(
+            to,
+            schema.height(),
+            table_key,
+            serde_json::to_vec(&table_proof).ok()?,
+            serde_json::to_vec(&chunk).ok()?,
+            next.unwrap_or_default(),
+        ))
+    }
+}
+
+/// Process of the synchronization of the Merkelized blockchain state at a certain height.
+///
+/// The synchronization starts from a block authenticated by the precommits of the validators.
+/// The node requests the tables one by one and then the block headers using [`next_request`]
+/// and feeds the received responses to [`handle_response`]. Once all the data is received,
+/// the synchronized state can be obtained with [`into_patch`].
+///
+/// [`next_request`]: #method.next_request
+/// [`handle_response`]: #method.handle_response
+/// [`into_patch`]: #method.into_patch
+pub struct StateSync {
+    block: Block,
+    precommits: Vec<Signed<Precommit>>,
+    genesis_hash: Hash,
+    tables: Vec<(Hash, StateIndex)>,
+    current: usize,
+    from: Vec<u8>,
+    // Hashes of the received block headers in the descending order of heights.
+    block_hashes: Vec<Hash>,
+    // Hash and height of the next expected block header.
+    next_header: (Hash, Height),
+    fork: Fork,
+}
+
+impl StateSync {
+    /// Starts synchronization of the state at the height of the given block.
+    ///
+    /// The block must be approved by the majority of the validators from the actual
+    /// configuration of the local blockchain. Returns an error if some service does not
+    /// declare all its Merkelized indexes with [`Service::state_indexes`], since the state
+    /// of such service cannot be synchronized.
+    ///
+    /// [`Service::state_indexes`]: trait.Service.html#method.state_indexes
+    pub fn new(blockchain: &Blockchain, proof: BlockProof) -> Result<Self, failure::Error> {
+        let snapshot = blockchain.snapshot();
+        let (genesis_hash, validator_keys) = {
+            let schema = Schema::new(&snapshot);
+            let genesis_hash = schema
+                .block_hash_by_height(Height::zero())
+                .ok_or_else(|| format_err!("Blockchain is not initialized"))?;
+            let validator_keys: Vec<_> = schema
+                .actual_configuration()
+                .validator_keys
+                .iter()
+                .map(|keys| keys.consensus_key)
+                .collect();
+            (genesis_hash, validator_keys)
+        };
+        for service in blockchain.service_map.values() {
+            ensure!(
+                service.state_indexes().len() == service.state_hash(&*snapshot).len(),
+                "Service {} does not declare its Merkelized indexes, its state cannot be \
+                 synchronized",
+                service.service_name()
+            );
+        }
+        let block_hash = proof.block.hash();
+        verify_block_precommits(&proof, &block_hash, &validator_keys)?;
+
+        let next_header = (block_hash, proof.block.height());
+        Ok(Self {
+            block: proof.block,
+            precommits: proof.precommits,
+            genesis_hash,
+            tables: blockchain.state_indexes(),
+            current: 0,
+            from: Vec::new(),
+            block_hashes: Vec::new(),
+            next_header,
+            fork: blockchain.fork(),
+        })
+    }
+
+    /// Returns the height of the synchronized state.
+    pub fn height(&self) -> Height {
+        self.block.height()
+    }
+
+    /// Returns `true` if all the tables and the block headers are synchronized.
+    pub fn is_finished(&self) -> bool {
+        self.block_hashes.last() == Some(&self.genesis_hash)
+    }
+
+    /// Returns the request for the next chunk of the state, or `None` if the synchronization
+    /// is finished.
+    pub fn next_request(&self, to: &PublicKey) -> Option<StateChunkRequest> {
+        if self.is_finished() {
+            return None;
+        }
+        let table_key = self
+            .tables
+            .get(self.current)
+            .map_or_else(block_headers_key, |&(table_key, _)| table_key);
+        Some(StateChunkRequest::new(
+            to,
+            self.height(),
+            &table_key,
+            self.from.clone(),
+        ))
+    }
+
+    /// Verifies the received chunk of the state and adds its entries to the synchronized state.
+    pub fn handle_response(&mut self, msg: &StateChunkResponse) -> Result<(), failure::Error> {
+        ensure!(!self.is_finished(), "State synchronization is finished");
+        let (table_key, index) = match self.tables.get(self.current).cloned() {
+            Some(table) => table,
+            None => return self.handle_block_headers(msg),
+        };
+        ensure!(
+            msg.height() == self.height() && *msg.table_key() == table_key,
+            "Received state chunk for an unexpected table"
+        );
+
+        let table_proof: MapProof<Hash, Hash> = serde_json::from_slice(msg.table_proof())?;
+        let table_proof = table_proof
+            .check()
+            .map_err(|e| format_err!("Invalid table proof: {}", e))?;
+        ensure!(
+            table_proof.merkle_root() == *self.block.state_hash(),
+            "Table proof does not match the block state hash"
+        );
+        let table_hash = table_proof
+            .entries()
+            .find(|&(key, _)| *key == table_key)
+            .map(|(_, hash)| *hash);
+
+        if self.from.is_empty() {
+            // Local entries of the table are replaced with the synchronized ones.
+            self.fork.remove_by_prefix(index.name(), None);
+        }
+        let table_hash = match table_hash {
+            Some(hash) => hash,
+            None => {
+                ensure!(
+                    table_proof.missing_keys().any(|key| *key == table_key),
+                    "Table is missing in the table proof"
+                );
+                // The table belongs to a service, which is not active at the synchronized height.
+                self.current += 1;
+                self.from = Vec::new();
+                return Ok(());
+            }
+        };
+
+        let chunk: StateChunk = serde_json::from_slice(msg.chunk())?;
+        let table_root = (index.apply_chunk)(&mut self.fork, index.name(), chunk, table_hash)?;
+
+        if msg.next().is_empty() {
+            ensure!(
+                table_root == table_hash,
+                "Synchronized table does not match the table hash"
+            );
+            self.current += 1;
+            self.from = Vec::new();
+        } else {
+            self.from = msg.next().to_vec();
+        }
+        Ok(())
+    }
+
+    fn handle_block_headers(&mut self, msg: &StateChunkResponse) -> Result<(), failure::Error> {
+        ensure!(
+            msg.height() == self.height() && *msg.table_key() == block_headers_key(),
+            "Received state chunk for an unexpected table"
+        );
+        let blocks = match serde_json::from_slice(msg.chunk())? {
+            StateChunk::Blocks(blocks) => blocks,
+            _ => bail!("Received state chunk of a wrong type"),
+        };
+        ensure!(!blocks.is_empty(), "Received empty chunk of block headers");
+
+        for block in blocks {
+            ensure!(
+                !self.is_finished(),
+                "Received block headers below the genesis block"
+            );
+            let (expected_hash, expected_height) = self.next_header;
+            let block_hash = block.hash();
+            ensure!(
+                block_hash == expected_hash && block.height() == expected_height,
+                "Received block header does not continue the synchronized chain"
+            );
+            if expected_height == Height::zero() {
+                ensure!(
+                    block_hash == self.genesis_hash,
+                    "Genesis block differs from the local one"
+                );
+            } else {
+                self.next_header = (*block.prev_hash(), expected_height.previous());
+            }
+            Schema::new(&mut self.fork)
+                .blocks_mut()
+                .put(&block_hash, block);
+            self.block_hashes.push(block_hash);
+        }
+
+        self.from = encode_list_position(self.next_header.1.into());
+        Ok(())
+    }
+
+    /// Returns the synchronized state.
+    ///
+    /// The contents of the synchronized blocks are not available, except for the precommits
+    /// of the block at the synchronized height.
+    pub fn into_patch(mut self) -> Result<Patch, failure::Error> {
+        ensure!(self.is_finished(), "State synchronization is not finished");
+        {
+            let mut schema = Schema::new(&mut self.fork);
+            {
+                let mut block_hashes = schema.block_hashes_by_height_mut();
+                block_hashes.clear();
+                block_hashes.extend(self.block_hashes.iter().rev().cloned());
+            }
+            let block_hash = self.block.hash();
+            schema.precommits_mut(&block_hash).clear();
+            schema
+                .precommits_mut(&block_hash)
+                .extend(self.precommits.iter().cloned());
+
+            // The order of configurations is restored from the synchronized `configs` table.
+            let mut configs: Vec<_> = schema
+                .configs()
+                .iter()
+                .map(|(hash, config)| ConfigReference::new(config.actual_from, &hash))
+                .collect();
+            configs.sort_by_key(ConfigReference::actual_from);
+            {
+                let mut configs_actual_from = schema.configs_actual_from_mut();
+                configs_actual_from.clear();
+                configs_actual_from.extend(configs);
+            }
+            schema.set_pruning_horizon(self.block.height().next());
+        }
+        Ok(self.fork.into_patch())
+    }
+}
+
+/// Creates a response with the block headers starting from the height specified
+/// in the request in the descending order of heights.
+fn block_headers_response(
+    snapshot: &dyn Snapshot,
+    to: &PublicKey,
+    request: &StateChunkRequest,
+) -> Option<StateChunkResponse> {
+    let schema = Schema::new(snapshot);
+    let height = schema.height();
+    let from = if request.from().is_empty() {
+        height.0
+    } else {
+        decode_list_position(request.from())?
+    };
+    if from > height.0 {
+        return None;
+    }
+
+    let to_height = from.saturating_sub(STATE_CHUNK_SIZE - 1);
+    let mut blocks = Vec::new();
+    for block_height in (to_height..=from).rev() {
+        let block_hash = schema.block_hash_by_height(Height(block_height))?;
+        blocks.push(schema.blocks().get(&block_hash)?);
+    }
+    let next = if to_height > 0 {
+        encode_list_position(to_height - 1)
+    } else {
+        Vec::new()
+    };
+
+    Some(StateChunkResponse::new(
+        to,
+        height,
+        &block_headers_key(),
+        Vec::new(),
+        serde_json::to_vec(&StateChunk::Blocks(blocks)).ok()?,
+        next,
+    ))
+}
+
+impl fmt::Debug for StateSync {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("StateSync")
+            .field("block", &self.block)
+            .field("tables", &self.tables)
+            .field("current", &self.current)
+            .finish()
+    }
+}
+
+fn apply_map_chunk<V: StorageValue>(
+    fork: &mut Fork,
+    name: &str,
+    chunk: StateChunk,
+    table_hash: Hash,
+) -> Result<Hash, failure::Error> {
+    let proof = match chunk {
+        StateChunk::Map(proof) => proof,
+        _ => bail!("Received state chunk of a wrong type"),
+    };
+    let proof = proof
+        .map_values(RawValue::<V>::new)
+        .check()
+        .map_err(|e| format_err!("Invalid chunk proof: {}", e))?;
+    ensure!(
+        proof.merkle_root() == table_hash,
+        "Chunk proof does not match the table hash"
+    );
+
+    let mut table: ProofMapIndex<_, Hash, RawValue<V>> = ProofMapIndex::new(name, fork);
+    for (key, value) in proof.entries() {
+        table.put(key, value.clone());
+    }
+    Ok(table.merkle_root())
+}
+
+fn apply_list_chunk<V: StorageValue>(
+    fork: &mut Fork,
+    name: &str,
+    chunk: StateChunk,
+    table_hash: Hash,
+) -> Result<Hash, failure::Error> {
+    let (len, proof) = match chunk {
+        StateChunk::List { len, proof } => (len, proof),
+        _ => bail!("Received state chunk of a wrong type"),
+    };
+
+    let mut table: ProofListIndex<_, RawValue<V>> = ProofListIndex::new(name, fork);
+    if let Some(proof) = proof {
+        let proof = proof.map_values(&mut RawValue::<V>::new);
+        let entries = proof
+            .validate(table_hash, len)
+            .map_err(|e| format_err!("Invalid chunk proof: {:?}", e))?;
+        for (index, value) in entries {
+            ensure!(
+                index == table.len(),
+                "Chunk entries do not continue the synchronized part of the list"
+            );
+            table.push(value.clone());
+        }
+    } else {
+        ensure!(len == 0, "Chunk proof is missing");
+    }
+    Ok(table.merkle_root())
+}
+
+/// Value in its storage representation, which is hashed as a value of type `V`.
+struct RawValue<V> {
+    bytes: Vec<u8>,
+    hash: Hash,
+    _v: PhantomData<fn() -> V>,
+}
+
+impl<V: StorageValue> RawValue<V> {
+    fn new(bytes: Vec<u8>) -> Self {
+        let hash = V::from_bytes(Cow::Borrowed(&bytes)).hash();
+        Self {
+            bytes,
+            hash,
+            _v: PhantomData,
+        }
+    }
+}
+
+impl<V> Clone for RawValue<V> {
+    fn clone(&self) -> Self {
+        Self {
+            bytes: self.bytes.clone(),
+            hash: self.hash,
+            _v: PhantomData,
+        }
+    }
+}
+
+impl<V> CryptoHash for RawValue<V> {
+    fn hash(&self) -> Hash {
+        self.hash
+    }
+}
+
+impl<V: StorageValue> StorageValue for RawValue<V> {
+    fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    fn from_bytes(value: Cow<[u8]>) -> Self {
+        Self::new(value.into_owned())
+    }
+}
+
+fn encode_list_position(position: u64) -> Vec<u8> {
+    let mut buffer = vec![0; 8];
+    BigEndian::write_u64(&mut buffer, position);
+    buffer
+}
+
+fn decode_list_position(buffer: &[u8]) -> Option<u64> {
+    match buffer.len() {
+        0 => Some(0),
+        8 => Some(BigEndian::read_u64(buffer)),
+        _ => None,
+    }
+}
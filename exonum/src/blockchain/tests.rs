@@ -15,6 +15,7 @@
 #![allow(dead_code, unsafe_code)]
 
 use chrono::{DateTime, TimeZone, Utc};
+use failure;
 use rand::{distributions::Alphanumeric, thread_rng, Rng};
 use serde_json;
 
@@ -24,7 +25,8 @@ use blockchain::{
 use crypto::{gen_keypair, Hash};
 use encoding::Error as MessageError;
 use helpers::{Height, ValidatorId};
-use messages::{Message, RawTransaction};
+use messages::{Message, RawTransaction, Signed, StateChunkRequest};
+use node::NodeConfig;
 use storage::{Database, Error, Fork, ListIndex, Snapshot};
 
 const IDX_NAME: &'static str = "idx_name";
@@ -68,6 +70,14 @@ impl Transaction for Tx {
         index.push(42 / self.value());
         Ok(())
     }
+
+    fn priority(&self) -> u64 {
+        self.value()
+    }
+
+    fn expires_at(&self) -> Option<Height> {
+        Some(Height(self.value() + 100))
+    }
 }
 
 #[test]
@@ -246,6 +256,735 @@ fn handling_tx_panic_storage_error(blockchain: &mut Blockchain) {
     );
 }
 
+fn create_blockchain() -> Blockchain {
+    use futures::sync::mpsc;
+    use node::ApiSender;
+    use storage::MemoryDB;
+
+    let service_keypair = gen_keypair();
+    let api_channel = mpsc::channel(1);
+    Blockchain::new(
+        MemoryDB::new(),
+        vec![Box::new(TestService) as Box<dyn Service>],
+        service_keypair.0,
+        service_keypair.1,
+        ApiSender::new(api_channel.0),
+    )
+}
+
+fn commit_block_with_precommit(
+    blockchain: &mut Blockchain,
+    node_config: &NodeConfig,
+    height: Height,
+    tx: &Signed<RawTransaction>,
+) {
+    use helpers::Round;
+    use messages::Precommit;
+
+    let mut fork = blockchain.fork();
+    Schema::new(&mut fork).add_transaction_into_pool(tx.clone());
+    blockchain.merge(fork.into_patch()).unwrap();
+
+    let (block_hash, patch) = blockchain.create_patch(ValidatorId::zero(), height, &[tx.hash()]);
+    let precommit = Message::concrete(
+        Precommit::new(
+            ValidatorId::zero(),
+            height,
+            Round::first(),
+            &Hash::zero(),
+            &block_hash,
+            Utc::now(),
+        ),
+        node_config.consensus_public_key,
+        &node_config.consensus_secret_key,
+    );
+    blockchain
+        .commit(&patch, block_hash, vec![precommit].into_iter())
+        .unwrap();
+}
+
+#[test]
+fn pruning_removes_old_block_contents() {
+    use explorer::BlockchainExplorer;
+    use helpers::generate_testnet_config;
+    use node::PruningConfig;
+    use std::iter;
+
+    let mut blockchain = create_blockchain();
+    blockchain.set_pruning_config(PruningConfig { keep_heights: 2 });
+    let genesis = generate_testnet_config(1, 16_500)[0].genesis.clone();
+    blockchain.initialize(genesis).unwrap();
+
+    let (pk, sec_key) = gen_keypair();
+    let mut txs = Vec::new();
+    for value in 1..4 {
+        let tx = Message::sign_transaction(Tx::new(value), TEST_SERVICE_ID, pk, &sec_key);
+        let mut fork = blockchain.fork();
+        Schema::new(&mut fork).add_transaction_into_pool(tx.clone());
+        blockchain.merge(fork.into_patch()).unwrap();
+
+        let (block_hash, patch) =
+            blockchain.create_patch(ValidatorId::zero(), Height(value), &[tx.hash()]);
+        blockchain.commit(&patch, block_hash, iter::empty()).unwrap();
+        txs.push(tx);
+    }
+
+    let snapshot = blockchain.snapshot();
+    let schema = Schema::new(&snapshot);
+    assert_eq!(schema.height(), Height(3));
+    assert_eq!(schema.pruning_horizon(), Height(2));
+    assert!(schema.is_pruned(Height(1)));
+    assert!(!schema.is_pruned(Height(2)));
+
+    // Contents of the pruned block are removed.
+    assert!(schema.transactions().get(&txs[0].hash()).is_none());
+    assert!(schema.block_transactions(Height(1)).is_empty());
+    // Headers, locations and results are kept.
+    assert!(schema.block_hash_by_height(Height(1)).is_some());
+    assert!(schema.transactions_locations().contains(&txs[0].hash()));
+    assert!(schema.transaction_results().contains(&txs[0].hash()));
+    // Contents of the latest blocks are kept.
+    assert_eq!(schema.transactions().get(&txs[1].hash()), Some(txs[1].clone()));
+    assert_eq!(schema.transactions().get(&txs[2].hash()), Some(txs[2].clone()));
+    assert_eq!(schema.block_transactions(Height(3)).len(), 1);
+
+    // The explorer marks the pruned blocks.
+    let explorer = BlockchainExplorer::new(&blockchain);
+    assert!(explorer.block(Height(1)).unwrap().is_pruned());
+    assert!(explorer.block_with_txs(Height(1)).unwrap().pruned);
+    assert!(!explorer.block(Height(3)).unwrap().is_pruned());
+    assert!(!explorer.block_with_txs(Height(3)).unwrap().pruned);
+}
+
+#[test]
+fn transaction_pool_limits() {
+    use blockchain::PoolLimitError;
+    use helpers::generate_testnet_config;
+    use node::MemoryPoolConfig;
+    use std::iter;
+
+    let mut blockchain = create_blockchain();
+    blockchain.set_mempool_config(MemoryPoolConfig {
+        max_pool_size: Some(4),
+        max_txs_per_author: Some(3),
+        ..MemoryPoolConfig::default()
+    });
+    let genesis = generate_testnet_config(1, 16_500)[0].genesis.clone();
+    blockchain.initialize(genesis).unwrap();
+
+    let keys = [gen_keypair(), gen_keypair(), gen_keypair()];
+    let sign = |value: u64, author: usize| {
+        let (ref pk, ref sec_key) = keys[author];
+        Message::sign_transaction(Tx::new(value), TEST_SERVICE_ID, *pk, sec_key)
+    };
+    let add_tx = |blockchain: &mut Blockchain,
+                  tx: &Signed<RawTransaction>|
+     -> Result<Option<Hash>, PoolLimitError> {
+        let evicted = blockchain.check_pool_limits(&*blockchain.snapshot(), tx, |_| true)?;
+        let mut fork = blockchain.fork();
+        {
+            let mut schema = Schema::new(&mut fork);
+            if let Some(ref hash) = evicted {
+                schema.evict_transaction(hash);
+            }
+            schema.add_transaction_into_pool(tx.clone());
+        }
+        blockchain.merge(fork.into_patch()).unwrap();
+        Ok(evicted)
+    };
+
+    let first_txs: Vec<_> = (1..4).map(|value| sign(value, 0)).collect();
+    for tx in &first_txs {
+        assert_eq!(add_tx(&mut blockchain, tx), Ok(None));
+    }
+    assert_eq!(
+        add_tx(&mut blockchain, &sign(10, 0)),
+        Err(PoolLimitError::QuotaExceeded)
+    );
+    assert_eq!(add_tx(&mut blockchain, &sign(11, 1)), Ok(None));
+
+    // The pool is full, so the oldest transaction of the heaviest author is evicted.
+    assert_eq!(
+        add_tx(&mut blockchain, &sign(12, 2)),
+        Ok(Some(first_txs[0].hash()))
+    );
+    {
+        let snapshot = blockchain.snapshot();
+        let schema = Schema::new(&snapshot);
+        assert_eq!(schema.transactions_pool_len(), 4);
+        assert!(!schema.transactions().contains(&first_txs[0].hash()));
+        assert_eq!(schema.transactions_pool_len_by_author(&keys[0].0), 2);
+    }
+    // Each author has at most two transactions, so nothing can be evicted anymore.
+    assert_eq!(
+        add_tx(&mut blockchain, &sign(13, 2)),
+        Err(PoolLimitError::PoolIsFull)
+    );
+
+    // Committed transactions free space in the pool.
+    let tx_hashes = [first_txs[1].hash(), first_txs[2].hash()];
+    let (block_hash, patch) = blockchain.create_patch(ValidatorId::zero(), Height(1), &tx_hashes);
+    blockchain.commit(&patch, block_hash, iter::empty()).unwrap();
+    {
+        let snapshot = blockchain.snapshot();
+        let schema = Schema::new(&snapshot);
+        assert_eq!(schema.transactions_pool_len(), 2);
+        assert_eq!(schema.transactions_pool_len_by_author(&keys[0].0), 0);
+    }
+    assert_eq!(add_tx(&mut blockchain, &sign(13, 2)), Ok(None));
+}
+
+#[test]
+fn transaction_pool_ordering() {
+    use helpers::generate_testnet_config;
+    use std::iter;
+
+    let mut blockchain = create_blockchain();
+    let genesis = generate_testnet_config(1, 16_500)[0].genesis.clone();
+    blockchain.initialize(genesis).unwrap();
+
+    let (pk, sec_key) = gen_keypair();
+    let txs: Vec<_> = (1..6)
+        .map(|value| Message::sign_transaction(Tx::new(value), TEST_SERVICE_ID, pk, &sec_key))
+        .collect();
+    let priorities = [1, 5, 0, 5, 2];
+    let mut fork = blockchain.fork();
+    {
+        let mut schema = Schema::new(&mut fork);
+        for (tx, &priority) in txs.iter().zip(&priorities) {
+            schema.add_prioritized_transaction_into_pool(tx.clone(), priority);
+        }
+    }
+    blockchain.merge(fork.into_patch()).unwrap();
+
+    let ordered = Schema::new(&blockchain.snapshot()).transactions_pool_ordered(3);
+    assert_eq!(ordered, vec![txs[1].hash(), txs[3].hash(), txs[4].hash()]);
+
+    let (block_hash, patch) = blockchain.create_patch(ValidatorId::zero(), Height(1), &ordered);
+    blockchain.commit(&patch, block_hash, iter::empty()).unwrap();
+    let ordered = Schema::new(&blockchain.snapshot()).transactions_pool_ordered(10);
+    assert_eq!(ordered, vec![txs[0].hash(), txs[2].hash()]);
+}
+
+#[test]
+fn transaction_pool_expiration() {
+    use explorer::BlockchainExplorer;
+    use helpers::generate_testnet_config;
+    use std::iter;
+
+    let mut blockchain = create_blockchain();
+    let genesis = generate_testnet_config(1, 16_500)[0].genesis.clone();
+    blockchain.initialize(genesis).unwrap();
+
+    let (pk, sec_key) = gen_keypair();
+    let txs: Vec<_> = (1..4)
+        .map(|value| Message::sign_transaction(Tx::new(value), TEST_SERVICE_ID, pk, &sec_key))
+        .collect();
+    let expirations = [Some(Height(2)), Some(Height(3)), None];
+    let mut fork = blockchain.fork();
+    {
+        let mut schema = Schema::new(&mut fork);
+        for (tx, &expires_at) in txs.iter().zip(&expirations) {
+            schema.add_tracked_transaction_into_pool(tx.clone(), 0, expires_at);
+        }
+    }
+    blockchain.merge(fork.into_patch()).unwrap();
+
+    // The first transaction cannot be included into the block at height 2.
+    let (block_hash, patch) = blockchain.create_patch(ValidatorId::zero(), Height(1), &[]);
+    blockchain.commit(&patch, block_hash, iter::empty()).unwrap();
+    {
+        let snapshot = blockchain.snapshot();
+        let schema = Schema::new(&snapshot);
+        assert_eq!(schema.transactions_pool_len(), 2);
+        assert!(!schema.transactions_pool().contains(&txs[0].hash()));
+        assert!(!schema.transactions().contains(&txs[0].hash()));
+        assert_eq!(schema.expired_transactions().get(&txs[0].hash()), Some(2));
+        assert_eq!(
+            schema.transactions_pool_ordered(10),
+            vec![txs[1].hash(), txs[2].hash()]
+        );
+    }
+
+    let explorer = BlockchainExplorer::new(&blockchain);
+    let info = explorer.transaction(&txs[0].hash()).unwrap();
+    assert!(info.is_expired());
+    assert!(info.try_content().is_none());
+    assert!(explorer.transaction(&txs[1].hash()).unwrap().is_in_pool());
+}
+
+#[test]
+fn state_history_snapshots() {
+    use helpers::generate_testnet_config;
+    use node::StateHistoryConfig;
+    use std::iter;
+
+    let mut blockchain = create_blockchain();
+    blockchain.set_state_history_config(StateHistoryConfig {
+        keep_heights: Some(StateHistoryConfig::MIN_KEEP_HEIGHTS + 1),
+        max_snapshot_depth: Some(1),
+    });
+    let genesis = generate_testnet_config(1, 16_500)[0].genesis.clone();
+    blockchain.initialize(genesis).unwrap();
+
+    let (pk, sec_key) = gen_keypair();
+    for value in 1..13 {
+        let tx = Message::sign_transaction(Tx::new(value), TEST_SERVICE_ID, pk, &sec_key);
+        let mut fork = blockchain.fork();
+        Schema::new(&mut fork).add_transaction_into_pool(tx.clone());
+        blockchain.merge(fork.into_patch()).unwrap();
+
+        let (block_hash, patch) =
+            blockchain.create_patch(ValidatorId::zero(), Height(value), &[tx.hash()]);
+        blockchain.commit(&patch, block_hash, iter::empty()).unwrap();
+    }
+
+    assert_eq!(
+        Schema::new(&blockchain.snapshot()).state_history_start(),
+        Some(Height(2))
+    );
+    // The state at height 10 is recorded, but it is too deep to be read.
+    assert!(blockchain.snapshot_at(Height(10)).is_none());
+    // The repeated snapshots at the same height share the changes collected from the history.
+    for &height in &[11, 12, 11, 11] {
+        let snapshot = blockchain.snapshot_at(Height(height)).unwrap();
+        let schema = Schema::new(&snapshot);
+        assert_eq!(schema.height(), Height(height));
+        assert_eq!(
+            schema.state_hash_aggregator().merkle_root(),
+            *schema.last_block().state_hash()
+        );
+        let index: ListIndex<_, u64> = ListIndex::new(IDX_NAME, &snapshot);
+        assert_eq!(index.len(), 2 * height);
+        assert_eq!(index.last(), Some(42 / height));
+    }
+    assert!(blockchain.snapshot_at(Height(1)).is_none());
+    assert!(blockchain.snapshot_at(Height(13)).is_none());
+
+    // Disabling the history keeps only the history needed for the rollback,
+    // which is not available to the clients.
+    blockchain.state_history = None;
+    let (block_hash, patch) = blockchain.create_patch(ValidatorId::zero(), Height(13), &[]);
+    blockchain.commit(&patch, block_hash, iter::empty()).unwrap();
+    let snapshot = blockchain.snapshot();
+    let schema = Schema::new(&snapshot);
+    assert_eq!(schema.state_history_start(), Some(Height(4)));
+    assert!(schema.state_history(Height(4)).iter().next().is_none());
+    assert!(schema.state_history(Height(5)).iter().next().is_some());
+    assert!(blockchain.snapshot_at(Height(12)).is_none());
+}
+
+#[test]
+fn rollback_to_past_height() {
+    use helpers::generate_testnet_config;
+
+    let node_config = generate_testnet_config(1, 16_900).remove(0);
+    // The history for the latest blocks is recorded even if the state history is disabled.
+    let mut blockchain = create_blockchain();
+    blockchain.initialize(node_config.genesis.clone()).unwrap();
+
+    let (pk, sec_key) = gen_keypair();
+    let mut txs = Vec::new();
+    for value in 1..4 {
+        let tx = Message::sign_transaction(Tx::new(value), TEST_SERVICE_ID, pk, &sec_key);
+        commit_block_with_precommit(&mut blockchain, &node_config, Height(value), &tx);
+        txs.push(tx);
+    }
+    let block_hash = Schema::new(&blockchain.snapshot())
+        .block_hash_by_height(Height(2))
+        .unwrap();
+
+    assert!(blockchain.rollback_blocks(Height(3)).is_err());
+    assert!(blockchain.rollback_blocks(Height(5)).is_err());
+    blockchain.rollback_blocks(Height(1)).unwrap();
+
+    let snapshot = blockchain.snapshot();
+    let schema = Schema::new(&snapshot);
+    assert_eq!(schema.height(), Height(1));
+    assert!(schema.block_hash_by_height(Height(2)).is_none());
+    assert!(schema.blocks().get(&block_hash).is_none());
+    assert!(schema.precommits(&block_hash).is_empty());
+    assert!(schema.block_transactions(Height(2)).is_empty());
+    assert!(schema.state_history(Height(2)).iter().next().is_none());
+    assert_eq!(
+        schema.state_hash_aggregator().merkle_root(),
+        *schema.last_block().state_hash()
+    );
+    assert!(schema.transactions_locations().contains(&txs[0].hash()));
+    assert!(!schema.transactions_locations().contains(&txs[1].hash()));
+    assert!(!schema.transaction_results().contains(&txs[2].hash()));
+    // The transactions of the removed blocks are returned to the pool.
+    assert_eq!(schema.transactions_pool_len(), 2);
+    assert!(schema.transactions_pool().contains(&txs[1].hash()));
+    assert!(schema.transactions_pool().contains(&txs[2].hash()));
+    assert_eq!(schema.transactions_pool_priorities().get(&txs[1].hash()), Some(2));
+    assert_eq!(
+        schema.transactions_pool_expirations().get(&txs[2].hash()),
+        Some(103)
+    );
+    assert_eq!(blockchain.verify_integrity(), vec![]);
+}
+
+#[test]
+fn state_snapshot_roundtrip() {
+    use blockchain::{export_state, state_snapshot::verify_block_precommits, BlockProof};
+    use helpers::generate_testnet_config;
+    use node::StateHistoryConfig;
+
+    let node_config = generate_testnet_config(1, 16_600).remove(0);
+    let mut blockchain = create_blockchain();
+    blockchain.set_state_history_config(StateHistoryConfig::default());
+    blockchain.initialize(node_config.genesis.clone()).unwrap();
+
+    let (pk, sec_key) = gen_keypair();
+    let mut txs = Vec::new();
+    for value in 1..3 {
+        let tx = Message::sign_transaction(Tx::new(value), TEST_SERVICE_ID, pk, &sec_key);
+        commit_block_with_precommit(&mut blockchain, &node_config, Height(value), &tx);
+        txs.push(tx);
+    }
+
+    // Unconfirmed transactions are not exported.
+    let unconfirmed = Message::sign_transaction(Tx::new(100), TEST_SERVICE_ID, pk, &sec_key);
+    let mut fork = blockchain.fork();
+    Schema::new(&mut fork).add_transaction_into_pool(unconfirmed.clone());
+    blockchain.merge(fork.into_patch()).unwrap();
+
+    let mut buffer = Vec::new();
+    let proof = export_state(&*blockchain.db, Height(2), &mut buffer).unwrap();
+    assert_eq!(proof.block, blockchain.last_block());
+    assert!(export_state(&*blockchain.db, Height(3), &mut Vec::new()).is_err());
+
+    let mut other = create_blockchain();
+    other.initialize(node_config.genesis.clone()).unwrap();
+    assert_eq!(other.import_state(&buffer[..]).unwrap(), proof);
+
+    let snapshot = other.snapshot();
+    let schema = Schema::new(&snapshot);
+    assert_eq!(schema.height(), Height(2));
+    assert_eq!(schema.last_block(), proof.block);
+    assert_eq!(schema.transactions().get(&txs[0].hash()), Some(txs[0].clone()));
+    assert!(!schema.transactions().contains(&unconfirmed.hash()));
+    assert_eq!(schema.transactions_pool_len(), 0);
+
+    // The state at a past height is exported using the state history.
+    let mut past_buffer = Vec::new();
+    let past_proof = export_state(&*blockchain.db, Height(1), &mut past_buffer).unwrap();
+    let mut other = create_blockchain();
+    other.initialize(node_config.genesis.clone()).unwrap();
+    assert_eq!(other.import_state(&past_buffer[..]).unwrap(), past_proof);
+    let snapshot = other.snapshot();
+    let schema = Schema::new(&snapshot);
+    assert_eq!(schema.height(), Height(1));
+    assert!(schema.transactions().contains(&txs[0].hash()));
+    assert!(!schema.transactions().contains(&txs[1].hash()));
+
+    // Precommits are checked against their signed messages rather than the payloads.
+    let validator_keys = [node_config.genesis.validator_keys[0].consensus_key];
+    let past_hash = past_proof.block.hash();
+    let mut forged = serde_json::to_value(&proof).unwrap();
+    forged["precommits"][0]["payload"]["block_hash"] = serde_json::to_value(&past_hash).unwrap();
+    forged["precommits"][0]["payload"]["height"] = serde_json::to_value(Height(1)).unwrap();
+    let mut forged: BlockProof = serde_json::from_value(forged).unwrap();
+    forged.block = past_proof.block.clone();
+    assert!(verify_block_precommits(&past_proof, &past_hash, &validator_keys).is_ok());
+    assert!(verify_block_precommits(&forged, &past_hash, &validator_keys).is_err());
+
+    // The snapshot is rejected if its validators differ from the trusted ones.
+    let mut other = create_blockchain();
+    other.initialize(node_config.genesis.clone()).unwrap();
+    let other_keys = [gen_keypair().0];
+    assert!(other
+        .import_state_with_validators(&buffer[..], &other_keys)
+        .is_err());
+    assert_eq!(
+        other
+            .import_state_with_validators(&buffer[..], &validator_keys)
+            .unwrap(),
+        proof
+    );
+
+    // A corrupted snapshot is rejected.
+    let mut other = create_blockchain();
+    other.initialize(node_config.genesis.clone()).unwrap();
+    let last = buffer.len() - 1;
+    buffer[last] ^= 1;
+    assert!(other.import_state(&buffer[..]).is_err());
+    assert_eq!(Schema::new(&other.snapshot()).height(), Height(0));
+}
+
+#[test]
+fn state_sync_over_chunks() {
+    use blockchain::StateSync;
+    use futures::sync::mpsc;
+    use helpers::generate_testnet_config;
+    use node::ApiSender;
+    use std::sync::Arc;
+
+    let node_config = generate_testnet_config(1, 16_700).remove(0);
+    let mut blockchain = create_blockchain();
+    blockchain.initialize(node_config.genesis.clone()).unwrap();
+
+    let (pk, sec_key) = gen_keypair();
+    for value in 1..4 {
+        let tx = Message::sign_transaction(Tx::new(value), TEST_SERVICE_ID, pk, &sec_key);
+        commit_block_with_precommit(&mut blockchain, &node_config, Height(value), &tx);
+    }
+    let snapshot = blockchain.snapshot();
+    let proof = Schema::new(&snapshot)
+        .block_and_precommits(Height(3))
+        .unwrap();
+
+    let mut other = create_blockchain();
+    other.initialize(node_config.genesis.clone()).unwrap();
+    let peer = node_config.consensus_public_key;
+
+    // A chunk of another table is rejected.
+    let mut sync = StateSync::new(&other, proof.clone()).unwrap();
+    let tables = other.state_indexes();
+    let request = StateChunkRequest::new(&peer, Height(3), &tables[1].0, Vec::new());
+    let response = blockchain
+        .state_chunk_response(snapshot.as_ref(), &peer, &request)
+        .unwrap();
+    assert!(sync.handle_response(&response).is_err());
+
+    while let Some(request) = sync.next_request(&peer) {
+        let response = blockchain
+            .state_chunk_response(snapshot.as_ref(), &peer, &request)
+            .unwrap();
+        sync.handle_response(&response).unwrap();
+    }
+    other.merge(sync.into_patch().unwrap()).unwrap();
+
+    let other_snapshot = other.snapshot();
+    let (schema, other_schema) = (Schema::new(&snapshot), Schema::new(&other_snapshot));
+    assert_eq!(
+        other_schema.transaction_results().merkle_root(),
+        schema.transaction_results().merkle_root()
+    );
+    assert_eq!(
+        other_schema.configs().merkle_root(),
+        schema.configs().merkle_root()
+    );
+    // Block headers are synchronized, while the block contents are treated as pruned.
+    assert_eq!(other_schema.height(), Height(3));
+    assert_eq!(other_schema.last_block(), proof.block);
+    assert_eq!(
+        other_schema.block_hashes_by_height().iter().collect::<Vec<_>>(),
+        schema.block_hashes_by_height().iter().collect::<Vec<_>>()
+    );
+    assert_eq!(other_schema.block_and_precommits(Height(3)), Some(proof.clone()));
+    assert_eq!(other_schema.pruning_horizon(), Height(4));
+    assert_eq!(
+        other_schema.actual_configuration(),
+        schema.actual_configuration()
+    );
+
+    // The state of a service without declared Merkelized indexes cannot be synchronized.
+    let service_keypair = gen_keypair();
+    let other = Blockchain::new(
+        Arc::clone(&other.db),
+        vec![Box::new(ServiceWithoutStateIndexes) as Box<dyn Service>],
+        service_keypair.0,
+        service_keypair.1,
+        ApiSender::new(mpsc::channel(1).0),
+    );
+    assert!(StateSync::new(&other, proof).is_err());
+}
+
+#[test]
+fn integrity_verification() {
+    use helpers::generate_testnet_config;
+
+    let node_config = generate_testnet_config(1, 16_800).remove(0);
+    let mut blockchain = create_blockchain();
+    blockchain.initialize(node_config.genesis.clone()).unwrap();
+
+    let (pk, sec_key) = gen_keypair();
+    for value in 1..4 {
+        let tx = Message::sign_transaction(Tx::new(value), TEST_SERVICE_ID, pk, &sec_key);
+        commit_block_with_precommit(&mut blockchain, &node_config, Height(value), &tx);
+    }
+    assert_eq!(blockchain.verify_integrity(), vec![]);
+
+    // Corrupt the hash of a child in the root node of the transaction results
+    // and the hash of the transaction in the block at height 1.
+    let (root_key, mut root) = {
+        let snapshot = blockchain.snapshot();
+        let mut iter = snapshot.iter("core.transaction_results", &[]);
+        let (key, value) = iter.next().unwrap();
+        (key.to_vec(), value.to_vec())
+    };
+    root[0] ^= 1;
+    let mut fork = blockchain.fork();
+    fork.put("core.transaction_results", root_key, root);
+    let mut tx_hash_key = vec![0, 0, 0, 0, 0, 0, 0, 1];
+    tx_hash_key.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0]);
+    fork.put(
+        "core.block_transactions",
+        tx_hash_key.clone(),
+        Hash::zero().as_ref().to_vec(),
+    );
+    blockchain.merge(fork.into_patch()).unwrap();
+
+    let errors = blockchain.verify_integrity();
+    assert!(
+        errors
+            .iter()
+            .any(|e| e.index_name == "core.transaction_results" && !e.key.is_empty())
+    );
+    assert!(
+        errors
+            .iter()
+            .any(|e| e.index_name == "core.block_transactions" && e.key == tx_hash_key)
+    );
+    assert!(
+        errors
+            .iter()
+            .any(|e| e.index_name == "core.state_hash_aggregator")
+    );
+}
+
+#[test]
+fn deferred_service_deployment() {
+    use crypto::CryptoHash;
+    use helpers::generate_testnet_config;
+    use std::iter;
+
+    let mut blockchain = create_blockchain();
+    let mut genesis = generate_testnet_config(1, 16_500)[0].genesis.clone();
+    genesis
+        .deferred_services
+        .insert(TestService.service_name().to_owned());
+    blockchain.initialize(genesis).unwrap();
+
+    let snapshot = blockchain.snapshot();
+    assert!(blockchain.active_services(&*snapshot, Height(0)).is_empty());
+    let mut config = Schema::new(&snapshot).actual_configuration();
+    assert!(!config.services.contains_key(TestService.service_name()));
+
+    // Deploy the service at height 2.
+    config.previous_cfg_hash = config.hash();
+    config.actual_from = Height(2);
+    config
+        .services
+        .insert(TestService.service_name().to_owned(), serde_json::Value::Null);
+    let mut fork = blockchain.fork();
+    Schema::new(&mut fork).commit_configuration(config);
+    blockchain.merge(fork.into_patch()).unwrap();
+
+    let (block_hash, patch) = blockchain.create_patch(ValidatorId::zero(), Height(1), &[]);
+    blockchain.commit(&patch, block_hash, iter::empty()).unwrap();
+    let snapshot = blockchain.snapshot();
+    assert!(blockchain.active_services(&*snapshot, Height(1)).is_empty());
+    let active = blockchain.active_services(&*snapshot, Height(2));
+    assert_eq!(active.len(), 1);
+    assert_eq!(active[0].service_id(), TEST_SERVICE_ID);
+
+    let (pk, sec_key) = gen_keypair();
+    let tx = Message::sign_transaction(Tx::new(3), TEST_SERVICE_ID, pk, &sec_key);
+    let mut fork = blockchain.fork();
+    Schema::new(&mut fork).add_transaction_into_pool(tx.clone());
+    blockchain.merge(fork.into_patch()).unwrap();
+    let (block_hash, patch) =
+        blockchain.create_patch(ValidatorId::zero(), Height(2), &[tx.hash()]);
+    blockchain.commit(&patch, block_hash, iter::empty()).unwrap();
+
+    let snapshot = blockchain.snapshot();
+    let schema = Schema::new(&snapshot);
+    assert!(schema.transaction_results().get(&tx.hash()).unwrap().0.is_ok());
+    let index: ListIndex<_, u64> = ListIndex::new(IDX_NAME, &snapshot);
+    assert_eq!(index.get(0), Some(3));
+}
+
+const MIGRATIONS_IDX_NAME: &str = "migrations";
+
+// The next version of `TestService`, which data layout has been changed twice.
+struct MigratedTestService;
+
+impl Service for MigratedTestService {
+    fn service_id(&self) -> u16 {
+        TEST_SERVICE_ID
+    }
+
+    fn service_name(&self) -> &'static str {
+        "test service"
+    }
+
+    fn state_hash(&self, _: &dyn Snapshot) -> Vec<Hash> {
+        vec![]
+    }
+
+    fn tx_from_raw(&self, raw: RawTransaction) -> Result<Box<dyn Transaction>, MessageError> {
+        Ok(TestServiceTxs::tx_from_raw(raw)?.into())
+    }
+
+    fn schema_version(&self) -> u32 {
+        2
+    }
+
+    fn migrate(&self, fork: &mut Fork, version: u32) -> Result<(), failure::Error> {
+        ListIndex::new(MIGRATIONS_IDX_NAME, fork).push(version);
+        Ok(())
+    }
+}
+
+/// Creates a blockchain with `TestService` and replaces the service with its next version.
+fn create_blockchain_with_migrated_service() -> Blockchain {
+    use futures::sync::mpsc;
+    use helpers::generate_testnet_config;
+    use node::ApiSender;
+    use std::sync::Arc;
+
+    let mut blockchain = create_blockchain();
+    let genesis = generate_testnet_config(1, 16_500)[0].genesis.clone();
+    blockchain.initialize(genesis).unwrap();
+
+    let service_keypair = gen_keypair();
+    let api_channel = mpsc::channel(1);
+    Blockchain::new(
+        Arc::clone(&blockchain.db),
+        vec![Box::new(MigratedTestService) as Box<dyn Service>],
+        service_keypair.0,
+        service_keypair.1,
+        ApiSender::new(api_channel.0),
+    )
+}
+
+#[test]
+fn service_migrations() {
+    use helpers::generate_testnet_config;
+
+    let mut blockchain = create_blockchain_with_migrated_service();
+    {
+        let pending = blockchain.pending_migrations();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].0.service_id(), TEST_SERVICE_ID);
+        assert_eq!(pending[0].1, 0);
+    }
+
+    blockchain.migrate().unwrap();
+    assert!(blockchain.pending_migrations().is_empty());
+    let snapshot = blockchain.snapshot();
+    let versions = Schema::new(&snapshot).service_schema_versions();
+    assert_eq!(versions.get(&"test service".to_owned()), Some(2));
+    let migrations: ListIndex<_, u32> = ListIndex::new(MIGRATIONS_IDX_NAME, &snapshot);
+    assert_eq!(migrations.iter().collect::<Vec<_>>(), vec![0, 1]);
+
+    let genesis = generate_testnet_config(1, 16_500)[0].genesis.clone();
+    blockchain.initialize(genesis).unwrap();
+}
+
+#[test]
+#[should_panic(expected = "Service data needs to be migrated")]
+fn pending_migrations_prevent_initialization() {
+    use helpers::generate_testnet_config;
+
+    let mut blockchain = create_blockchain_with_migrated_service();
+    let genesis = generate_testnet_config(1, 16_500)[0].genesis.clone();
+    blockchain.initialize(genesis).unwrap();
+}
+
 mod transactions_tests {
     use super::TEST_SERVICE_ID;
     use blockchain::{ExecutionResult, Transaction, TransactionContext};
@@ -356,6 +1095,26 @@ impl Service for ServiceGood {
     }
 }
 
+struct ServiceWithoutStateIndexes;
+
+impl Service for ServiceWithoutStateIndexes {
+    fn service_id(&self) -> u16 {
+        1
+    }
+
+    fn service_name(&self) -> &'static str {
+        "some_service"
+    }
+
+    fn state_hash(&self, _snapshot: &dyn Snapshot) -> Vec<Hash> {
+        vec![Hash::zero()]
+    }
+
+    fn tx_from_raw(&self, _raw: RawTransaction) -> Result<Box<dyn Transaction>, MessageError> {
+        unimplemented!()
+    }
+}
+
 struct ServicePanic;
 
 impl Service for ServicePanic {
@@ -494,6 +1253,7 @@ mod memorydb_tests {
     }
 }
 
+#[cfg(feature = "rocksdb-storage")]
 mod rocksdb_tests {
     use blockchain::{Blockchain, Service};
     use crypto::gen_keypair;
@@ -540,6 +1300,70 @@ mod rocksdb_tests {
         TempDir::new(super::gen_tempdir_name().as_str()).unwrap()
     }
 
+    #[test]
+    fn checkpoint_roundtrip() {
+        use blockchain::{restore_checkpoint, verify_checkpoint, CheckpointInfo, Schema};
+        use helpers::{generate_testnet_config, Height};
+        use messages::Message;
+        use serde_json;
+        use std::fs;
+
+        let node_config = generate_testnet_config(1, 16_700).remove(0);
+        let dir = create_temp_dir();
+        let mut blockchain = create_blockchain(&dir.path().join("db"));
+        blockchain.initialize(node_config.genesis.clone()).unwrap();
+        let (pk, sec_key) = gen_keypair();
+        let tx = Message::sign_transaction(super::Tx::new(1), super::TEST_SERVICE_ID, pk, &sec_key);
+        super::commit_block_with_precommit(&mut blockchain, &node_config, Height(1), &tx);
+
+        let checkpoint_path = dir.path().join("checkpoint");
+        let info = blockchain.create_checkpoint(&checkpoint_path).unwrap();
+        assert_eq!(info.height, Height(1));
+        assert_eq!(info.block_hash, blockchain.last_hash());
+        assert!(blockchain.create_checkpoint(&checkpoint_path).is_err());
+
+        let options = DbOptions::default();
+        let validator_keys: Vec<_> = node_config
+            .genesis
+            .validator_keys
+            .iter()
+            .map(|keys| keys.consensus_key)
+            .collect();
+        // The latest block is not approved by the untrusted validators.
+        let untrusted_keys = vec![gen_keypair().0];
+        assert!(verify_checkpoint(&checkpoint_path, &options, &untrusted_keys).is_err());
+
+        let restored_path = dir.path().join("restored");
+        assert_eq!(
+            restore_checkpoint(&checkpoint_path, &restored_path, &options, &validator_keys)
+                .unwrap(),
+            info
+        );
+        let restored = create_database(&restored_path);
+        let snapshot = restored.snapshot();
+        assert_eq!(Schema::new(&snapshot).last_block(), blockchain.last_block());
+
+        // A checkpoint with a wrong recorded height is rejected.
+        let wrong_info = CheckpointInfo {
+            height: Height(0),
+            ..info
+        };
+        let info_path = checkpoint_path.join("checkpoint.json");
+        fs::write(&info_path, serde_json::to_vec(&wrong_info).unwrap()).unwrap();
+        assert!(verify_checkpoint(&checkpoint_path, &options, &validator_keys).is_err());
+
+        // Named checkpoints are created only inside the checkpoints directory.
+        let checkpoints_dir = dir.path().join("checkpoints");
+        blockchain.set_checkpoints_dir(&checkpoints_dir);
+        assert_eq!(
+            blockchain.checkpoint_path("first").unwrap(),
+            checkpoints_dir.join("first")
+        );
+        for name in &["", ".", "..", "../escaped", "nested/name", "/absolute"] {
+            assert!(blockchain.checkpoint_path(name).is_err());
+        }
+    }
+
     #[test]
     fn handling_tx_panic() {
         let dir = create_temp_dir();
@@ -19,6 +19,7 @@ use std::{any::Any, borrow::Cow, convert::Into, error::Error, fmt, u8};
 use crypto::{CryptoHash, Hash, PublicKey};
 use encoding;
 use hex::ToHex;
+use helpers::Height;
 use messages::{HexStringRepresentation, RawTransaction, Signed, SignedMessage};
 use storage::{Fork, StorageValue};
 
@@ -205,6 +206,60 @@ pub trait Transaction: ::std::fmt::Debug + Send + 'static + ::erased_serde::Seri
     /// }
     /// # fn main() {}
     fn execute<'a>(&self, context: TransactionContext<'a>) -> ExecutionResult;
+
+    /// Returns the priority of the transaction, e.g., the fee paid by its author.
+    /// The leader includes transactions with a higher priority into block proposals first,
+    /// and validators do not vote for proposals in which a transaction follows a transaction
+    /// with a lower priority. The default priority is zero.
+    ///
+    /// *This method should not use external data, that is, it must be a pure function.*
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate exonum;
+    /// # #[macro_use] extern crate serde_derive;
+    /// #
+    /// use exonum::blockchain::{ExecutionResult, Transaction, TransactionContext};
+    ///
+    /// transactions! {
+    ///     MyTransactions {
+    ///
+    ///         struct MyTransaction {
+    ///             // Transaction definition...
+    ///             fee: u64,
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// impl Transaction for MyTransaction {
+    ///     fn priority(&self) -> u64 {
+    ///         self.fee()
+    ///     }
+    ///
+    ///     // Other methods...
+    ///     // ...
+    /// #   fn execute(&self, _: TransactionContext) -> ExecutionResult { Ok(()) }
+    /// }
+    /// # fn main() {}
+    /// ```
+    fn priority(&self) -> u64 {
+        0
+    }
+
+    /// Returns the height since which the transaction cannot be included into blocks,
+    /// or `None` if the transaction does not expire. The default implementation returns `None`.
+    ///
+    /// The leader does not include expired transactions into block proposals, and validators
+    /// do not vote for proposals containing them. Expired transactions are removed from
+    /// the pool after each commit and are reported by the explorer as expired.
+    ///
+    /// *This method should not use external data, that is, it must be a pure function.*
+    /// In particular, the expiration is specified as a height rather than a timestamp,
+    /// since the validators must agree whether the transaction is expired.
+    fn expires_at(&self) -> Option<Height> {
+        None
+    }
 }
 
 //TODO: Add doc/examples.
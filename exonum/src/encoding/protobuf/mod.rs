@@ -21,8 +21,9 @@
 pub use self::blockchain::{Block, ConfigReference, TxLocation};
 pub use self::helpers::{BitVec, Hash, PublicKey};
 pub use self::protocol::{
-    BlockRequest, BlockResponse, Connect, PeersRequest, Precommit, Prevote, PrevotesRequest,
-    Propose, ProposeRequest, Status, TransactionsRequest, TransactionsResponse,
+    BlockRequest, BlockResponse, BlockUnavailable, Connect, PeersRequest, Precommit, Prevote,
+    PrevotesRequest, Propose, ProposeRequest, StateChunkRequest, StateChunkResponse, Status,
+    TransactionsRequest, TransactionsResponse,
 };
 
 use bit_vec;
@@ -39,6 +39,7 @@ impl InternalPart {
         sender: mpsc::Sender<InternalEvent>,
     ) -> impl Future<Item = (), Error = ()> {
         event.and_then(|evt| {
+            metric!(gauge_delta: "events.internal_events_queue", 1);
             sender
                 .send(evt)
                 .map(drop)
@@ -196,12 +196,14 @@ where
                     return Ok(Async::Ready(None));
                 }
                 Async::Ready(Some(item)) => {
+                    metric!(gauge_delta: "events.internal_events_queue", -1);
                     return Ok(Async::Ready(Some(Event::Internal(item))));
                 }
                 Async::NotReady => {}
             };
             match self.network.poll()? {
                 Async::Ready(Some(item)) => {
+                    metric!(gauge_delta: "events.network_events_queue", -1);
                     return Ok(Async::Ready(Some(Event::Network(item))));
                 }
                 Async::Ready(None) => {
@@ -216,6 +218,7 @@ where
                     return Ok(Async::Ready(None));
                 }
                 Async::Ready(Some(item)) => {
+                    metric!(gauge_delta: "events.api_requests_queue", -1);
                     return Ok(Async::Ready(Some(Event::Api(item))));
                 }
                 Async::NotReady => {}
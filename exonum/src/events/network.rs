@@ -198,6 +198,7 @@ impl ConnectionPool {
         network_tx: &mpsc::Sender<NetworkEvent>,
     ) -> impl Future<Item = (), Error = failure::Error> {
         if self.remove(key).is_some() {
+            metric!(gauge_delta: "events.network_events_queue", 1);
             let send_disconnected = network_tx
                 .clone()
                 .send(NetworkEvent::PeerDisconnected(*key))
@@ -476,7 +477,10 @@ impl NetworkHandler {
         network_tx
             .clone()
             .sink_map_err(into_failure)
-            .send_all(stream.map(NetworkEvent::MessageReceived))
+            .send_all(stream.map(|message| {
+                metric!(gauge_delta: "events.network_events_queue", 1);
+                NetworkEvent::MessageReceived(message)
+            }))
             .then(move |_| pool.disconnect_with_peer(&key, &network_tx))
             .map_err(|e| {
                 error!("Connection terminated: {}: {}", e, e.find_root_cause());
@@ -527,6 +531,7 @@ impl NetworkHandler {
         let handle = self.handle.clone();
 
         let handler = receiver.for_each(move |request| {
+            metric!(gauge_delta: "events.network_requests_queue", -1);
             let fut = match request {
                 NetworkRequest::SendMessage(key, message) => {
                     to_box(self.handle_send_message(&key, message))
@@ -588,6 +593,7 @@ impl NetworkHandler {
         network_tx: &mpsc::Sender<NetworkEvent>,
     ) -> impl Future<Item = mpsc::Sender<NetworkEvent>, Error = failure::Error> {
         let peer_connected = NetworkEvent::PeerConnected(address.clone(), message);
+        metric!(gauge_delta: "events.network_events_queue", 1);
         network_tx
             .clone()
             .send(peer_connected)
@@ -603,6 +609,7 @@ impl NetworkHandler {
         peer: &PublicKey,
     ) -> impl Future<Item = (), Error = failure::Error> {
         let event = NetworkEvent::UnableConnectToPeer(*peer);
+        metric!(gauge_delta: "events.network_events_queue", 1);
         self.network_tx
             .clone()
             .send(event)
@@ -112,6 +112,7 @@ pub struct BlockInfo<'a> {
     explorer: &'a BlockchainExplorer<'a>,
     precommits: RefCell<Option<Vec<Signed<Precommit>>>>,
     txs: RefCell<Option<Vec<Hash>>>,
+    pruned: bool,
 }
 
 impl<'a> BlockInfo<'a> {
@@ -134,6 +135,7 @@ impl<'a> BlockInfo<'a> {
             header,
             precommits: RefCell::new(None),
             txs: RefCell::new(None),
+            pruned: schema.is_pruned(height),
         }
     }
 
@@ -164,6 +166,12 @@ impl<'a> BlockInfo<'a> {
         self.len() == 0
     }
 
+    /// Returns `true` if the contents of this block have been pruned, in which case
+    /// its precommits and transactions are empty.
+    pub fn is_pruned(&self) -> bool {
+        self.pruned
+    }
+
     /// Returns a list of precommits for this block.
     pub fn precommits(&self) -> Ref<[Signed<Precommit>]> {
         if self.precommits.borrow().is_none() {
@@ -204,8 +212,13 @@ impl<'a> BlockInfo<'a> {
 
     /// Loads transactions and precommits for the block.
     pub fn with_transactions(self) -> BlockWithTransactions {
-        let (explorer, header, precommits, transactions) =
-            (self.explorer, self.header, self.precommits, self.txs);
+        let (explorer, header, precommits, transactions, pruned) = (
+            self.explorer,
+            self.header,
+            self.precommits,
+            self.txs,
+            self.pruned,
+        );
 
         let precommits = precommits
             .into_inner()
@@ -221,6 +234,7 @@ impl<'a> BlockInfo<'a> {
             header,
             precommits,
             transactions,
+            pruned,
         }
     }
 }
@@ -229,10 +243,11 @@ impl<'a> Serialize for BlockInfo<'a> {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
         use serde::ser::SerializeStruct;
 
-        let mut s = serializer.serialize_struct("BlockInfo", 3)?;
+        let mut s = serializer.serialize_struct("BlockInfo", 4)?;
         s.serialize_field("block", &self.header)?;
         s.serialize_field("precommits", &*self.precommits())?;
         s.serialize_field("txs", &*self.transaction_hashes())?;
+        s.serialize_field("pruned", &self.pruned)?;
         s.end()
     }
 }
@@ -279,6 +294,10 @@ pub struct BlockWithTransactions {
     pub precommits: Vec<Signed<Precommit>>,
     /// Transactions in the order they appear in the block.
     pub transactions: Vec<CommittedTransaction>,
+    /// Whether the contents of the block have been pruned, in which case the precommits
+    /// and transactions are empty.
+    #[serde(default)]
+    pub pruned: bool,
 }
 
 impl BlockWithTransactions {
@@ -548,6 +567,16 @@ impl CommittedTransaction {
 /// - `type` field contains transaction type (`"in-pool"`).
 /// - `content` is JSON serialization of the transaction.
 ///
+/// ## Expired transaction
+///
+/// Transactions removed from the pool due to their expiration are represented
+/// with a 3-field object. The bodies of the expired transactions are not stored,
+/// so the object contains only the transaction hash:
+///
+/// - `type` field contains transaction type (`"expired"`).
+/// - `tx_hash` is the hash of the transaction.
+/// - `expired_at` is the height since which the transaction cannot be committed.
+///
 /// # Examples
 ///
 /// Use of the custom type parameter for deserialization:
@@ -604,14 +633,42 @@ pub enum TransactionInfo {
 
     /// Transaction is already committed to the blockchain.
     Committed(CommittedTransaction),
+
+    /// Transaction has been removed from the memory pool, because it has not been committed
+    /// before its expiration height.
+    Expired {
+        /// Transaction hash.
+        tx_hash: Hash,
+        /// Height since which the transaction cannot be committed.
+        expired_at: Height,
+    },
 }
 
 impl TransactionInfo {
     /// Returns the content of this transaction.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the transaction is expired, since the bodies of expired transactions
+    /// are not stored. Use [`try_content`] if the transaction may be expired.
+    ///
+    /// [`try_content`]: #method.try_content
     pub fn content(&self) -> &TransactionMessage {
         match *self {
-            TransactionInfo::InPool { ref content } => content,
-            TransactionInfo::Committed(ref tx) => tx.content(),
+            TransactionInfo::Expired { ref tx_hash, .. } => panic!(
+                "Content of the expired transaction {} is not available",
+                tx_hash.to_hex()
+            ),
+            _ => self.try_content().unwrap(),
+        }
+    }
+
+    /// Returns the content of this transaction, or `None` if the transaction is expired.
+    pub fn try_content(&self) -> Option<&TransactionMessage> {
+        match *self {
+            TransactionInfo::InPool { ref content } => Some(content),
+            TransactionInfo::Committed(ref tx) => Some(tx.content()),
+            TransactionInfo::Expired { .. } => None,
         }
     }
 
@@ -623,6 +680,14 @@ impl TransactionInfo {
         }
     }
 
+    /// Is this an expired transaction?
+    pub fn is_expired(&self) -> bool {
+        match *self {
+            TransactionInfo::Expired { .. } => true,
+            _ => false,
+        }
+    }
+
     /// Is this a committed transaction?
     pub fn is_committed(&self) -> bool {
         match *self {
@@ -675,7 +740,16 @@ impl<'a> BlockchainExplorer<'a> {
     /// Returns information about the transaction identified by the hash.
     pub fn transaction(&self, tx_hash: &Hash) -> Option<TransactionInfo> {
         let schema = Schema::new(&self.snapshot);
-        let content = self.transaction_without_proof(tx_hash)?;
+        let content = match self.transaction_without_proof(tx_hash) {
+            Some(content) => content,
+            None => {
+                let expired_at = schema.expired_transactions().get(tx_hash)?;
+                return Some(TransactionInfo::Expired {
+                    tx_hash: *tx_hash,
+                    expired_at: Height(expired_at),
+                });
+            }
+        };
         if schema.transactions_pool().contains(tx_hash) {
             return Some(TransactionInfo::InPool { content });
         }
@@ -684,6 +758,19 @@ impl<'a> BlockchainExplorer<'a> {
         Some(TransactionInfo::Committed(tx))
     }
 
+    /// Returns the location of a committed transaction if its body has been pruned,
+    /// or `None` if the transaction is unknown or its body is available.
+    pub fn pruned_transaction_location(&self, tx_hash: &Hash) -> Option<TxLocation> {
+        let schema = Schema::new(&self.snapshot);
+        if schema.transactions().contains(tx_hash) {
+            return None;
+        }
+        schema
+            .transactions_locations()
+            .get(tx_hash)
+            .filter(|location| schema.is_pruned(location.block_height()))
+    }
+
     /// Returns transaction message without proof.
     pub fn transaction_without_proof(&self, tx_hash: &Hash) -> Option<TransactionMessage> {
         let schema = Schema::new(&self.snapshot);
@@ -752,6 +839,15 @@ impl<'a> BlockchainExplorer<'a> {
         schema.height()
     }
 
+    /// Returns `true` if the contents of the block at the specified height have been pruned.
+    ///
+    /// Only the header is available for a pruned block; its precommits and transactions
+    /// are empty.
+    pub fn is_pruned(&self, height: Height) -> bool {
+        let schema = Schema::new(&self.snapshot);
+        schema.is_pruned(height)
+    }
+
     /// Returns block information for the specified height or `None` if there is no such block.
     pub fn block(&self, height: Height) -> Option<BlockInfo> {
         if self.height() >= height {
@@ -775,6 +871,7 @@ impl<'a> BlockchainExplorer<'a> {
                 .iter()
                 .map(|tx_hash| self.committed_transaction(&tx_hash, None))
                 .collect(),
+            pruned: schema.is_pruned(height),
         })
     }
 
@@ -17,6 +17,7 @@ use std::{
     ffi::OsString,
     fmt,
     panic::{self, PanicInfo},
+    sync::Arc,
 };
 
 use super::{
@@ -30,6 +31,7 @@ use super::{
 };
 use blockchain::Service;
 use node::Node;
+use storage::Database;
 
 /// `NodeBuilder` is a high level object,
 /// usable for fast prototyping and creating app from services list.
@@ -77,15 +79,49 @@ impl NodeBuilder {
                 let config = ctx
                     .get(keys::NODE_CONFIG)
                     .expect("could not find node_config");
-                let db = Run::db_helper(ctx, &config.database);
-                let services: Vec<Box<dyn Service>> = self
-                    .service_factories
+                let db: Arc<dyn Database> = Run::db_helper(ctx, &config.database).into();
+                let mut service_factories = self.service_factories;
+                if let Some(snapshot_path) = Run::import_snapshot_path(ctx) {
+                    let services = service_factories
+                        .iter_mut()
+                        .map(|factory| factory.make_service(ctx))
+                        .collect();
+                    Run::import_snapshot(Arc::clone(&db), services, &config, &snapshot_path);
+                }
+                let services: Vec<Box<dyn Service>> = service_factories
                     .into_iter()
                     .map(|mut factory| factory.make_service(ctx))
                     .collect();
                 let node = Node::new(db, services, config, config_file_path);
                 Some(node)
             }
+            Feedback::Migrate(ref ctx) => {
+                let services = self
+                    .service_factories
+                    .into_iter()
+                    .map(|mut factory| factory.make_service(ctx))
+                    .collect();
+                Maintenance::migrate(ctx, services);
+                None
+            }
+            Feedback::VerifyDb(ref ctx) => {
+                let services = self
+                    .service_factories
+                    .into_iter()
+                    .map(|mut factory| factory.make_service(ctx))
+                    .collect();
+                Maintenance::verify_db(ctx, services);
+                None
+            }
+            Feedback::Rollback(ref ctx) => {
+                let services = self
+                    .service_factories
+                    .into_iter()
+                    .map(|mut factory| factory.make_service(ctx))
+                    .collect();
+                Maintenance::rollback(ctx, services);
+                None
+            }
             _ => None,
         }
     }
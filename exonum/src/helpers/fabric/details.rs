@@ -18,13 +18,16 @@
 // spell-checker:ignore exts, rsplitn
 
 use crypto;
+use futures::sync::mpsc;
 use toml;
 
 use std::{
     collections::{BTreeMap, HashMap},
-    fs,
+    fs::{self, File},
+    io::BufReader,
     net::{IpAddr, SocketAddr},
     path::{Path, PathBuf},
+    sync::Arc,
 };
 
 use super::{
@@ -36,12 +39,14 @@ use super::{
     Argument, CommandName, Context, DEFAULT_EXONUM_LISTEN_PORT,
 };
 use api::backends::actix::AllowOrigin;
-use blockchain::{config::ValidatorKeys, GenesisConfig};
+use blockchain::{config::ValidatorKeys, Blockchain, GenesisConfig, Service};
 use helpers::{config::ConfigFile, generate_testnet_config};
-use node::{ConnectListConfig, NodeApiConfig, NodeConfig};
-use storage::{Database, DbOptions, RocksDB};
+use node::{ApiSender, ConnectListConfig, NodeApiConfig, NodeConfig};
+use storage::{open_database, Database, DbBackend, DbOptions, EncryptionKey};
 
 const DATABASE_PATH: &str = "DATABASE_PATH";
+const DB_BACKEND: &str = "DB_BACKEND";
+const DB_ENCRYPTION_KEY_PATH: &str = "DB_ENCRYPTION_KEY_PATH";
 const OUTPUT_DIR: &str = "OUTPUT_DIR";
 const PEER_ADDRESS: &str = "PEER_ADDRESS";
 const LISTEN_ADDRESS: &str = "LISTEN_ADDRESS";
@@ -50,17 +55,36 @@ const PUBLIC_API_ADDRESS: &str = "PUBLIC_API_ADDRESS";
 const PRIVATE_API_ADDRESS: &str = "PRIVATE_API_ADDRESS";
 const PUBLIC_ALLOW_ORIGIN: &str = "PUBLIC_ALLOW_ORIGIN";
 const PRIVATE_ALLOW_ORIGIN: &str = "PRIVATE_ALLOW_ORIGIN";
+const IMPORT_SNAPSHOT_PATH: &str = "IMPORT_SNAPSHOT_PATH";
 
 /// Run command.
 pub struct Run;
 
 impl Run {
     /// Returns created database instance.
+    ///
+    /// If the database is encrypted, the encryption key is loaded from the file specified
+    /// by `--db-encryption-key` or from the `EXONUM_DB_ENCRYPTION_KEY` environment variable.
     pub fn db_helper(ctx: &Context, options: &DbOptions) -> Box<dyn Database> {
         let path = ctx
             .arg::<String>(DATABASE_PATH)
             .unwrap_or_else(|_| panic!("{} not found.", DATABASE_PATH));
-        Box::new(RocksDB::open(Path::new(&path), options).expect("Can't load database file"))
+        let options = Self::db_options(ctx, options);
+        open_database(Path::new(&path), &options).expect("Can't load database file")
+    }
+
+    /// Returns the database options with the encryption key loaded, if the encryption
+    /// is enabled. The key is loaded from the file specified by `--db-encryption-key`
+    /// or from the `EXONUM_DB_ENCRYPTION_KEY` environment variable.
+    pub(crate) fn db_options(ctx: &Context, options: &DbOptions) -> DbOptions {
+        let mut options = *options;
+        if let Some(ref mut encryption) = options.encryption {
+            let key_path = ctx.arg::<String>(DB_ENCRYPTION_KEY_PATH).ok();
+            let key = EncryptionKey::load(key_path)
+                .unwrap_or_else(|e| panic!("Can't load database encryption key: {}", e));
+            encryption.key = Some(key);
+        }
+        options
     }
 
     fn node_config_path(ctx: &Context) -> String {
@@ -72,6 +96,12 @@ impl Run {
         ConfigFile::load(path).expect("Can't load node config file")
     }
 
+    fn db_backend(ctx: &Context) -> Option<DbBackend> {
+        ctx.arg::<String>(DB_BACKEND)
+            .ok()
+            .map(|backend| backend.parse().unwrap_or_else(|e| panic!("{}", e)))
+    }
+
     fn public_api_address(ctx: &Context) -> Option<SocketAddr> {
         ctx.arg(PUBLIC_API_ADDRESS).ok()
     }
@@ -79,6 +109,43 @@ impl Run {
     fn private_api_address(ctx: &Context) -> Option<SocketAddr> {
         ctx.arg(PRIVATE_API_ADDRESS).ok()
     }
+
+    /// Returns the path to the state snapshot to bootstrap the node from, if any.
+    pub(crate) fn import_snapshot_path(ctx: &Context) -> Option<String> {
+        ctx.arg(IMPORT_SNAPSHOT_PATH).ok()
+    }
+
+    /// Initializes the blockchain in the given database and imports the state snapshot into it.
+    pub(crate) fn import_snapshot(
+        db: Arc<dyn Database>,
+        services: Vec<Box<dyn Service>>,
+        config: &NodeConfig,
+        path: &str,
+    ) {
+        info!("Importing blockchain state from {}", path);
+
+        // The blockchain is not connected to the node, so API requests are discarded.
+        let (api_sender, _) = mpsc::channel(0);
+        let mut blockchain = Blockchain::new(
+            db,
+            services,
+            config.service_public_key,
+            config.service_secret_key.clone(),
+            ApiSender::new(api_sender),
+        );
+        blockchain
+            .initialize(config.genesis.clone())
+            .expect("Can't initialize blockchain");
+        let file = File::open(path).expect("Can't open state snapshot file");
+        let proof = blockchain
+            .import_state(BufReader::new(file))
+            .expect("Can't import blockchain state");
+
+        info!(
+            "Blockchain state at height {} imported successfully",
+            proof.block.height()
+        );
+    }
 }
 
 impl Command for Run {
@@ -100,6 +167,23 @@ impl Command for Run {
                 "db-path",
                 false,
             ),
+            Argument::new_named(
+                DB_BACKEND,
+                false,
+                "Database backend (rocksdb or lmdb), overrides the node configuration.",
+                None,
+                "db-backend",
+                false,
+            ),
+            Argument::new_named(
+                DB_ENCRYPTION_KEY_PATH,
+                false,
+                "Path to the database encryption key file, which is used if the encryption \
+                 is enabled. Defaults to the EXONUM_DB_ENCRYPTION_KEY environment variable.",
+                None,
+                "db-encryption-key",
+                false,
+            ),
             Argument::new_named(
                 PUBLIC_API_ADDRESS,
                 false,
@@ -116,6 +200,14 @@ impl Command for Run {
                 "private-api-address",
                 false,
             ),
+            Argument::new_named(
+                IMPORT_SNAPSHOT_PATH,
+                false,
+                "Bootstrap the node from the given state snapshot.",
+                None,
+                "import-snapshot",
+                false,
+            ),
         ]
     }
 
@@ -136,6 +228,7 @@ impl Command for Run {
         let config_path = Self::node_config_path(&context);
 
         let config = Self::node_config(config_path.clone());
+        let db_backend = Self::db_backend(&context);
         let public_addr = Self::public_api_address(&context);
         let private_addr = Self::private_api_address(&context);
 
@@ -145,6 +238,10 @@ impl Command for Run {
         let mut config = new_context
             .get(keys::NODE_CONFIG)
             .expect("cant load node_config");
+        if let Some(db_backend) = db_backend {
+            config.database.backend = db_backend;
+        }
+
         // Override api options
         if let Some(public_addr) = public_addr {
             config.api.public_api_address = Some(public_addr);
@@ -679,6 +776,9 @@ impl Command for Finalize {
                 mempool: Default::default(),
                 services_configs: Default::default(),
                 database: Default::default(),
+                pruning: Default::default(),
+                state_history: Default::default(),
+                state_sync: Default::default(),
                 connect_list,
                 thread_pool_size: Default::default(),
             }
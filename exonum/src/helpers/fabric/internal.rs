@@ -23,6 +23,12 @@ use super::{Argument, CommandExtension, CommandName, Context};
 pub enum Feedback {
     /// Run node with current context.
     RunNode(Context),
+    /// Migrate the service data in the database of the node with current context.
+    Migrate(Context),
+    /// Verify the integrity of the database of the node with current context.
+    VerifyDb(Context),
+    /// Roll back the blocks in the database of the node with current context.
+    Rollback(Context),
     /// Do nothing
     None,
 }
@@ -14,27 +14,80 @@
 
 //! This module implements node maintenance actions.
 
-use std::{collections::HashMap, path::Path};
+use actix::System;
+use actix_web::{client, HttpMessage};
+use futures::{sync::mpsc, Future};
+use serde_json;
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::BufWriter,
+    net::{IpAddr, Ipv4Addr},
+    path::Path,
+};
 
 use super::{
+    details::Run,
     internal::{CollectedCommand, Command, Feedback},
-    Argument, CommandName, Context,
+    keys, Argument, CommandName, Context,
+};
+use blockchain::{export_state, restore_checkpoint, Blockchain, CheckpointInfo, Schema, Service};
+use crypto::PublicKey;
+use helpers::{config::ConfigFile, Height};
+use node::{ApiSender, NodeConfig};
+use storage::{
+    index_catalogue, open_database, rotate_encryption_key, Database, DbBackend, DbOptions,
+    EncryptionKey,
 };
-use blockchain::Schema;
-use helpers::config::ConfigFile;
-use node::NodeConfig;
-use storage::{Database, DbOptions, RocksDB};
 
 // Context entry for the path to the node config.
 const NODE_CONFIG_PATH: &str = "NODE_CONFIG_PATH";
 // Context entry for the path to the database.
 const DATABASE_PATH: &str = "DATABASE_PATH";
+// Context entry for the database backend overriding the node config.
+const DB_BACKEND: &str = "DB_BACKEND";
+// Context entry for the path to the database encryption key.
+const DB_ENCRYPTION_KEY_PATH: &str = "DB_ENCRYPTION_KEY_PATH";
+// Context entry for the path to the new database encryption key.
+const NEW_DB_ENCRYPTION_KEY_PATH: &str = "NEW_DB_ENCRYPTION_KEY_PATH";
 // Context entry for the type of action to be performed.
 const MAINTENANCE_ACTION_PATH: &str = "MAINTENANCE_ACTION_PATH";
+// Context entry for the path to the state snapshot file.
+const SNAPSHOT_PATH: &str = "SNAPSHOT_PATH";
+// Context entry for the height to export the state snapshot at.
+const SNAPSHOT_HEIGHT: &str = "SNAPSHOT_HEIGHT";
+// Context entry for the path to the database checkpoint.
+const CHECKPOINT_PATH: &str = "CHECKPOINT_PATH";
+// Context entry for the name of the database checkpoint created by the node.
+const CHECKPOINT_NAME: &str = "CHECKPOINT_NAME";
+// Context entry for the height to roll the database back to.
+const ROLLBACK_HEIGHT: &str = "ROLLBACK_HEIGHT";
 
 /// Maintenance command. Supported actions:
 ///
 /// - `clear-cache` - clear message cache.
+/// - `export-snapshot` - export the blockchain state into the file specified
+///   by `--snapshot-path`. The state is exported at the height specified by `--snapshot-height`,
+///   which requires the state history for past heights, or at the latest height by default.
+/// - `migrate` - migrate the data of the services to their current schema versions.
+/// - `verify-db` - recompute the Merkle roots of the stored indexes and check them,
+///   as well as the blocks and their precommits, reporting each found inconsistency.
+/// - `create-checkpoint` - request the running node to create a checkpoint of its database
+///   with the name specified by `--checkpoint-name` via the private API. The checkpoint
+///   is created in the `checkpoints_dir` directory of the node API configuration.
+/// - `restore-checkpoint` - replace the database with the checkpoint in the directory
+///   specified by `--checkpoint-path`, after verifying the checkpoint against the actual
+///   validators of the replaced database, or the genesis ones if there is no database.
+/// - `rollback` - revert the blocks committed after the height specified by `--height`,
+///   using the state history recorded by the node.
+/// - `list-indexes` - print the indexes registered in the database with their types,
+///   the number of entries and the approximate size in bytes.
+/// - `rotate-encryption-key` - re-encrypt the database with the key from the file specified
+///   by `--new-db-encryption-key`, which must be used to run the node afterwards.
+///
+/// The key of an encrypted database is loaded from the file specified by `--db-encryption-key`
+/// or from the `EXONUM_DB_ENCRYPTION_KEY` environment variable.
 #[derive(Debug)]
 pub struct Maintenance;
 
@@ -46,11 +99,30 @@ impl Maintenance {
         ConfigFile::load(path).expect("Can't load node config file")
     }
 
+    fn database_path(ctx: &Context) -> String {
+        ctx.arg::<String>(DATABASE_PATH)
+            .unwrap_or_else(|_| panic!("{} not found.", DATABASE_PATH))
+    }
+
+    fn database_options(ctx: &Context, options: &DbOptions) -> DbOptions {
+        let mut options = *options;
+        if let Ok(backend) = ctx.arg::<String>(DB_BACKEND) {
+            options.backend = backend
+                .parse::<DbBackend>()
+                .unwrap_or_else(|e| panic!("{}", e));
+        }
+        Run::db_options(ctx, &options)
+    }
+
     fn database(ctx: &Context, options: &DbOptions) -> Box<dyn Database> {
-        let path = ctx
-            .arg::<String>(DATABASE_PATH)
-            .unwrap_or_else(|_| panic!("{} not found.", DATABASE_PATH));
-        Box::new(RocksDB::open(Path::new(&path), options).expect("Can't load database file"))
+        let path = Self::database_path(ctx);
+        let options = Self::database_options(ctx, options);
+        open_database(Path::new(&path), &options).expect("Can't load database file")
+    }
+
+    fn checkpoint_path(ctx: &Context) -> String {
+        ctx.arg::<String>(CHECKPOINT_PATH)
+            .unwrap_or_else(|_| panic!("{} not found.", CHECKPOINT_PATH))
     }
 
     fn clear_cache(context: &Context) {
@@ -67,6 +139,224 @@ impl Maintenance {
 
         info!("Cache cleared successfully");
     }
+
+    fn export_snapshot(context: &Context) {
+        let path = context
+            .arg::<String>(SNAPSHOT_PATH)
+            .unwrap_or_else(|_| panic!("{} not found.", SNAPSHOT_PATH));
+        info!("Exporting blockchain state into {}", path);
+
+        let config = Self::node_config(context);
+        let db = Self::database(context, &config.database);
+        let height = match context.arg::<u64>(SNAPSHOT_HEIGHT) {
+            Ok(height) => Height(height),
+            Err(_) => Schema::new(db.snapshot()).height(),
+        };
+        let file = File::create(&path).expect("Can't create state snapshot file");
+        let proof = export_state(&*db, height, BufWriter::new(file))
+            .expect("Can't export blockchain state");
+
+        info!(
+            "Blockchain state at height {} exported successfully",
+            proof.block.height()
+        );
+    }
+
+    /// Returns the consensus keys of the validators, against which a checkpoint is verified.
+    fn trusted_validator_keys(ctx: &Context, config: &NodeConfig) -> Vec<PublicKey> {
+        let path = Self::database_path(ctx);
+        if Path::new(&path).exists() {
+            let db = Self::database(ctx, &config.database);
+            let schema = Schema::new(db.snapshot());
+            if !schema.block_hashes_by_height().is_empty() {
+                return schema
+                    .actual_configuration()
+                    .validator_keys
+                    .iter()
+                    .map(|keys| keys.consensus_key)
+                    .collect();
+            }
+        }
+        config
+            .genesis
+            .validator_keys
+            .iter()
+            .map(|keys| keys.consensus_key)
+            .collect()
+    }
+
+    fn create_checkpoint(context: &Context) {
+        let name = context
+            .arg::<String>(CHECKPOINT_NAME)
+            .unwrap_or_else(|_| panic!("{} not found.", CHECKPOINT_NAME));
+        let config = Self::node_config(context);
+        let mut address = config
+            .api
+            .private_api_address
+            .expect("Private API address is not specified in the node config");
+        // The node listening on all the interfaces is reachable via the loopback one.
+        if address.ip().is_unspecified() {
+            address.set_ip(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)));
+        }
+        info!(
+            "Requesting the node at {} to create checkpoint {}",
+            address, name
+        );
+
+        let request = client::post(format!("http://{}/api/system/v1/checkpoint", address))
+            .json(json!({ "name": name }))
+            .expect("Can't create checkpoint request");
+        let response = request
+            .send()
+            .map_err(|e| format_err!("{}", e))
+            .and_then(|response| {
+                let status = response.status();
+                response
+                    .body()
+                    .map_err(|e| format_err!("{}", e))
+                    .map(move |body| (status, body))
+            });
+        let (status, body) = System::new("create-checkpoint")
+            .block_on(response)
+            .unwrap_or_else(|e| panic!("Can't send request to the node: {}", e));
+        if !status.is_success() {
+            panic!(
+                "Can't create checkpoint: {} {}",
+                status,
+                String::from_utf8_lossy(&body)
+            );
+        }
+        let info: CheckpointInfo =
+            serde_json::from_slice(&body).expect("Can't parse the node response");
+
+        info!(
+            "Checkpoint at height {} (block {}) created successfully",
+            info.height, info.block_hash
+        );
+    }
+
+    fn restore_checkpoint(context: &Context) {
+        let path = Self::checkpoint_path(context);
+        info!("Restoring the database from the checkpoint in {}", path);
+
+        let config = Self::node_config(context);
+        let validator_keys = Self::trusted_validator_keys(context, &config);
+        let db_path = Self::database_path(context);
+        let options = Self::database_options(context, &config.database);
+        let info = restore_checkpoint(
+            Path::new(&path),
+            Path::new(&db_path),
+            &options,
+            &validator_keys,
+        ).expect("Can't restore checkpoint");
+
+        info!(
+            "Checkpoint at height {} (block {}) restored successfully",
+            info.height, info.block_hash
+        );
+    }
+
+    fn list_indexes(context: &Context) {
+        let config = Self::node_config(context);
+        let db = Self::database(context, &config.database);
+        let catalogue = index_catalogue(&*db.snapshot(), true);
+
+        println!(
+            "{:<48} {:<12} {:>12} {:>16}",
+            "NAME", "TYPE", "ENTRIES", "SIZE"
+        );
+        for info in &catalogue {
+            let index_type = if info.is_family {
+                format!("{:?}*", info.index_type)
+            } else {
+                format!("{:?}", info.index_type)
+            };
+            let counts = info.counts.unwrap_or_default();
+            println!(
+                "{:<48} {:<12} {:>12} {:>16}",
+                info.name, index_type, counts.entries, counts.size
+            );
+        }
+        println!("(* index family)");
+    }
+
+    fn rotate_encryption_key(context: &Context) {
+        let key_path = context
+            .arg::<String>(NEW_DB_ENCRYPTION_KEY_PATH)
+            .unwrap_or_else(|_| panic!("{} not found.", NEW_DB_ENCRYPTION_KEY_PATH));
+        info!("Re-encrypting the database with the key from {}", key_path);
+
+        let config = Self::node_config(context);
+        let db_path = Self::database_path(context);
+        let options = Self::database_options(context, &config.database);
+        let new_key = EncryptionKey::load(Some(&key_path))
+            .unwrap_or_else(|e| panic!("Can't load new database encryption key: {}", e));
+        let count = rotate_encryption_key(Path::new(&db_path), &options, &new_key)
+            .expect("Can't re-encrypt the database");
+
+        info!("{} database entries re-encrypted successfully", count);
+    }
+
+    /// Creates the blockchain with the given services on top of the database of the node.
+    ///
+    /// The context must contain the node configuration, which is put there by the actions
+    /// requiring services, since the services are created from the same context.
+    fn blockchain(context: &Context, services: Vec<Box<dyn Service>>) -> Blockchain {
+        let config = context
+            .get(keys::NODE_CONFIG)
+            .expect("could not find node_config");
+        let db = Self::database(context, &config.database);
+        // The blockchain is not connected to the node, so API requests are discarded.
+        let (api_sender, _) = mpsc::channel(0);
+        Blockchain::new(
+            db,
+            services,
+            config.service_public_key,
+            config.service_secret_key.clone(),
+            ApiSender::new(api_sender),
+        )
+    }
+
+    /// Applies pending migrations of the service data in a single patch.
+    pub(crate) fn migrate(context: &Context, services: Vec<Box<dyn Service>>) {
+        info!("Migrating service data");
+
+        let mut blockchain = Self::blockchain(context, services);
+        blockchain.migrate().expect("Can't migrate service data");
+    }
+
+    /// Checks the integrity of the database and reports the found inconsistencies.
+    pub(crate) fn verify_db(context: &Context, services: Vec<Box<dyn Service>>) {
+        info!("Verifying the database integrity");
+
+        let blockchain = Self::blockchain(context, services);
+        let errors = blockchain.verify_integrity();
+        if !errors.is_empty() {
+            for error in &errors {
+                error!("{}", error);
+            }
+            panic!("Database integrity check failed with {} errors", errors.len());
+        }
+
+        info!("Database integrity verified successfully");
+    }
+
+    /// Rolls the database back to the height specified in the context.
+    pub(crate) fn rollback(context: &Context, services: Vec<Box<dyn Service>>) {
+        let height = context
+            .arg::<u64>(ROLLBACK_HEIGHT)
+            .unwrap_or_else(|_| panic!("{} not found.", ROLLBACK_HEIGHT));
+        info!("Rolling the database back to height {}", height);
+
+        // Services determine the priority and expiration of the transactions returned
+        // to the pool.
+        let blockchain = Self::blockchain(context, services);
+        blockchain
+            .rollback_blocks(Height(height))
+            .expect("Can't roll back the database");
+
+        info!("Database rolled back successfully");
+    }
 }
 
 impl Command for Maintenance {
@@ -88,6 +378,23 @@ impl Command for Maintenance {
                 "db-path",
                 false,
             ),
+            Argument::new_named(
+                DB_BACKEND,
+                false,
+                "Database backend (rocksdb or lmdb), overrides the node configuration.",
+                None,
+                "db-backend",
+                false,
+            ),
+            Argument::new_named(
+                DB_ENCRYPTION_KEY_PATH,
+                false,
+                "Path to the database encryption key file, which is used if the encryption \
+                 is enabled. Defaults to the EXONUM_DB_ENCRYPTION_KEY environment variable.",
+                None,
+                "db-encryption-key",
+                false,
+            ),
             Argument::new_named(
                 MAINTENANCE_ACTION_PATH,
                 true,
@@ -96,6 +403,54 @@ impl Command for Maintenance {
                 "action",
                 false,
             ),
+            Argument::new_named(
+                SNAPSHOT_PATH,
+                false,
+                "Path to the state snapshot file.",
+                "s",
+                "snapshot-path",
+                false,
+            ),
+            Argument::new_named(
+                SNAPSHOT_HEIGHT,
+                false,
+                "Height to export the state snapshot at.",
+                None,
+                "snapshot-height",
+                false,
+            ),
+            Argument::new_named(
+                CHECKPOINT_PATH,
+                false,
+                "Path to the database checkpoint directory.",
+                None,
+                "checkpoint-path",
+                false,
+            ),
+            Argument::new_named(
+                CHECKPOINT_NAME,
+                false,
+                "Name of the database checkpoint created by the node.",
+                None,
+                "checkpoint-name",
+                false,
+            ),
+            Argument::new_named(
+                ROLLBACK_HEIGHT,
+                false,
+                "Height to roll the database back to.",
+                None,
+                "height",
+                false,
+            ),
+            Argument::new_named(
+                NEW_DB_ENCRYPTION_KEY_PATH,
+                false,
+                "Path to the new database encryption key file.",
+                None,
+                "new-db-encryption-key",
+                false,
+            ),
         ]
     }
 
@@ -104,13 +459,15 @@ impl Command for Maintenance {
     }
 
     fn about(&self) -> &str {
-        "Maintenance module. Available actions: clear-cache."
+        "Maintenance module. Available actions: clear-cache, export-snapshot, migrate, \
+         verify-db, create-checkpoint, restore-checkpoint, rollback, list-indexes, \
+         rotate-encryption-key."
     }
 
     fn execute(
         &self,
         _commands: &HashMap<CommandName, CollectedCommand>,
-        context: Context,
+        mut context: Context,
         _: &dyn Fn(Context) -> Context,
     ) -> Feedback {
         let action = context
@@ -119,6 +476,31 @@ impl Command for Maintenance {
 
         if action == "clear-cache" {
             Self::clear_cache(&context);
+        } else if action == "export-snapshot" {
+            Self::export_snapshot(&context);
+        } else if action == "create-checkpoint" {
+            Self::create_checkpoint(&context);
+        } else if action == "restore-checkpoint" {
+            Self::restore_checkpoint(&context);
+        } else if action == "list-indexes" {
+            Self::list_indexes(&context);
+        } else if action == "rotate-encryption-key" {
+            Self::rotate_encryption_key(&context);
+        } else if action == "migrate" {
+            // Services are created by the node builder, which performs the migration.
+            let config = Self::node_config(&context);
+            context.set(keys::NODE_CONFIG, config);
+            return Feedback::Migrate(context);
+        } else if action == "verify-db" {
+            // Services are created by the node builder, since their hashes are verified too.
+            let config = Self::node_config(&context);
+            context.set(keys::NODE_CONFIG, config);
+            return Feedback::VerifyDb(context);
+        } else if action == "rollback" {
+            // Services are created by the node builder to return transactions to the pool.
+            let config = Self::node_config(&context);
+            context.set(keys::NODE_CONFIG, config);
+            return Feedback::Rollback(context);
         } else {
             println!("Unsupported maintenance action: {}", action);
         }
@@ -13,18 +13,59 @@
 // limitations under the License.
 
 //! Utilities for collecting metrics.
+//!
+//! Metrics are collected in a process-wide registry with the [`metric!`] macro and can be
+//! exported in the Prometheus text format with [`export_prometheus`]. The node serves
+//! the exported metrics at the `api/system/v1/metrics` endpoint of the private API.
+//!
+//! The core collects the following metrics:
+//!
+//! - `node.height` and `node.rounds_per_height` - the current height and the number
+//!   of consensus rounds spent on the committed heights;
+//! - `node.propose_to_commit_ms` - the time between receiving a propose and committing
+//!   the corresponding block;
+//! - `node.mempool` - the number of transactions in the pool;
+//! - `node.peers` - the number of connected peers;
+//! - `events.*_queue` - the number of queued events in the channels of the node, which
+//!   capacities are specified in `EventsPoolCapacity`;
+//! - `storage.rocksdb_merge_us` and `storage.lmdb_merge_us` - the duration of merging patches
+//!   into `RocksDB` and `LMDB` respectively.
+//!
+//! [`metric!`]: ../../macro.metric.html
+//! [`export_prometheus`]: fn.export_prometheus.html
 
+#[cfg(feature = "metrics-log")]
 use chrono::offset::Utc;
 
+use std::{collections::BTreeMap, fmt::Write, sync::Mutex};
+
+/// Prefix of the names of the exported metrics.
+const PROMETHEUS_PREFIX: &str = "exonum_";
+
+/// Upper bounds of the histogram buckets. The units of the observed values are determined
+/// by the metric, e.g., `node.propose_to_commit_ms` is measured in milliseconds.
+const HISTOGRAM_BUCKETS: &[i64] = &[
+    1, 2, 5, 10, 20, 50, 100, 200, 500, 1_000, 2_000, 5_000, 10_000, 20_000, 50_000, 100_000,
+    200_000, 500_000, 1_000_000,
+];
+
+lazy_static! {
+    static ref REGISTRY: Mutex<BTreeMap<String, Metric>> = Mutex::new(BTreeMap::new());
+}
+
 /// Adds given metric with given value.
 ///
 /// Metric name should be in the following format: `module_name.metric_name`, where `module_name`
 /// is a high level name. For example `storage` or `node` (not `storage_proof_list_index`).
 ///
-/// Value is a string and can be formatted similar to the `println!`. See `std::fmt` and example
-/// for details.
+/// Value is an integer. The kind of the metric is determined by the optional prefix:
+///
+/// - no prefix - a gauge, which is set to the given value;
+/// - `gauge_delta:` - a gauge, which is changed by the given (possibly negative) value;
+/// - `counter:` - a counter, which is increased by the given value;
+/// - `histogram:` - a histogram, to which the given value is added as an observation.
 ///
-/// Metrics output direction is determined by the corresponding `metrics-...` feature. If
+/// Metrics are additionally written to the log if the `metrics-log` feature is enabled.
 ///
 /// # Examples
 ///
@@ -34,23 +75,212 @@ use chrono::offset::Utc;
 /// # fn main() {
 /// let val = 10;
 /// metric!("mod_name.metric_name", val);
+/// metric!(counter: "mod_name.events", 1);
+/// metric!(histogram: "mod_name.latency_ms", 25);
 /// # }
 /// ```
 #[macro_export]
 macro_rules! metric {
+    (counter: $name:expr, $value:expr) => {{
+        $crate::helpers::metrics::increment_counter($name, $value as i64);
+    }};
+    (gauge_delta: $name:expr, $value:expr) => {{
+        $crate::helpers::metrics::add_to_gauge($name, $value as i64);
+    }};
+    (histogram: $name:expr, $value:expr) => {{
+        $crate::helpers::metrics::observe_histogram($name, $value as i64);
+    }};
     ($name:expr, $value:expr) => {{
         $crate::helpers::metrics::add_metric($name, $value as i64);
     }};
 }
 
+#[derive(Debug)]
+enum Metric {
+    Counter(i64),
+    Gauge(i64),
+    Histogram(Histogram),
+}
+
+#[derive(Debug, Default)]
+struct Histogram {
+    // Number of observations in each of `HISTOGRAM_BUCKETS`, not cumulative.
+    buckets: Vec<u64>,
+    count: u64,
+    sum: i64,
+}
+
+impl Histogram {
+    fn observe(&mut self, value: i64) {
+        if self.buckets.is_empty() {
+            self.buckets = vec![0; HISTOGRAM_BUCKETS.len()];
+        }
+        if let Some(idx) = HISTOGRAM_BUCKETS.iter().position(|bound| value <= *bound) {
+            self.buckets[idx] += 1;
+        }
+        self.count += 1;
+        self.sum += value;
+    }
+}
+
+#[allow(unused_variables)]
+fn update_metric<F>(metric_name: &str, kind: &str, value: i64, default: fn() -> Metric, update: F)
+where
+    F: FnOnce(&mut Metric) -> bool,
+{
+    #[cfg(feature = "metrics-log")]
+    {
+        let time = format!("{:?}", Utc::now());
+        trace!("{} {} {} {}", metric_name, kind, value, time);
+    }
+
+    let mut registry = REGISTRY.lock().expect("Metrics registry is poisoned");
+    let metric = registry.entry(metric_name.to_owned()).or_insert_with(default);
+    if !update(metric) {
+        warn!(
+            "Metric {} is updated as a {}, while it has a different kind",
+            metric_name, kind
+        );
+    }
+}
+
 // Do not use directly, use `metric!` macro instead.
 #[doc(hidden)]
-#[allow(unused_variables)]
 pub fn add_metric(metric_name: &str, value: i64) {
-    let time = format!("{:?}", Utc::now());
+    update_metric(
+        metric_name,
+        "gauge",
+        value,
+        || Metric::Gauge(0),
+        |metric| match *metric {
+            Metric::Gauge(ref mut gauge) => {
+                *gauge = value;
+                true
+            }
+            _ => false,
+        },
+    );
+}
 
-    #[cfg(feature = "metrics-log")]
-    {
-        trace!("{} {} {}", metric_name, value, time);
+// Do not use directly, use `metric!` macro instead.
+#[doc(hidden)]
+pub fn add_to_gauge(metric_name: &str, value: i64) {
+    update_metric(
+        metric_name,
+        "gauge",
+        value,
+        || Metric::Gauge(0),
+        |metric| match *metric {
+            Metric::Gauge(ref mut gauge) => {
+                *gauge += value;
+                true
+            }
+            _ => false,
+        },
+    );
+}
+
+// Do not use directly, use `metric!` macro instead.
+#[doc(hidden)]
+pub fn increment_counter(metric_name: &str, value: i64) {
+    update_metric(
+        metric_name,
+        "counter",
+        value,
+        || Metric::Counter(0),
+        |metric| match *metric {
+            Metric::Counter(ref mut counter) => {
+                *counter += value;
+                true
+            }
+            _ => false,
+        },
+    );
+}
+
+// Do not use directly, use `metric!` macro instead.
+#[doc(hidden)]
+pub fn observe_histogram(metric_name: &str, value: i64) {
+    update_metric(
+        metric_name,
+        "histogram",
+        value,
+        || Metric::Histogram(Histogram::default()),
+        |metric| match *metric {
+            Metric::Histogram(ref mut histogram) => {
+                histogram.observe(value);
+                true
+            }
+            _ => false,
+        },
+    );
+}
+
+/// Converts the name of a metric into a valid Prometheus metric name,
+/// e.g., `node.mempool` into `exonum_node_mempool`.
+fn prometheus_name(metric_name: &str) -> String {
+    let name: String = metric_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("{}{}", PROMETHEUS_PREFIX, name)
+}
+
+/// Returns all the collected metrics in the Prometheus text exposition format.
+pub fn export_prometheus() -> String {
+    let registry = REGISTRY.lock().expect("Metrics registry is poisoned");
+    let mut output = String::new();
+    for (metric_name, metric) in registry.iter() {
+        let name = prometheus_name(metric_name);
+        match *metric {
+            Metric::Counter(value) => {
+                writeln!(output, "# TYPE {} counter\n{} {}", name, name, value).unwrap();
+            }
+            Metric::Gauge(value) => {
+                writeln!(output, "# TYPE {} gauge\n{} {}", name, name, value).unwrap();
+            }
+            Metric::Histogram(ref histogram) => {
+                writeln!(output, "# TYPE {} histogram", name).unwrap();
+                let mut cumulative = 0;
+                for (bound, count) in HISTOGRAM_BUCKETS.iter().zip(&histogram.buckets) {
+                    cumulative += count;
+                    writeln!(output, "{}_bucket{{le=\"{}\"}} {}", name, bound, cumulative).unwrap();
+                }
+                writeln!(output, "{}_bucket{{le=\"+Inf\"}} {}", name, histogram.count).unwrap();
+                writeln!(output, "{}_sum {}", name, histogram.sum).unwrap();
+                writeln!(output, "{}_count {}", name, histogram.count).unwrap();
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::export_prometheus;
+
+    #[test]
+    fn prometheus_export() {
+        metric!("test_metrics.gauge", 3);
+        metric!(gauge_delta: "test_metrics.gauge", -1);
+        metric!(counter: "test_metrics.counter", 2);
+        metric!(counter: "test_metrics.counter", 5);
+        metric!(histogram: "test_metrics.histogram", 4);
+        metric!(histogram: "test_metrics.histogram", 2_000_000);
+
+        let output = export_prometheus();
+        assert!(
+            output.contains("# TYPE exonum_test_metrics_gauge gauge\nexonum_test_metrics_gauge 2\n")
+        );
+        assert!(output.contains(
+            "# TYPE exonum_test_metrics_counter counter\nexonum_test_metrics_counter 7\n"
+        ));
+        assert!(output.contains("# TYPE exonum_test_metrics_histogram histogram\n"));
+        assert!(output.contains("exonum_test_metrics_histogram_bucket{le=\"2\"} 0\n"));
+        assert!(output.contains("exonum_test_metrics_histogram_bucket{le=\"5\"} 1\n"));
+        assert!(output.contains("exonum_test_metrics_histogram_bucket{le=\"1000000\"} 1\n"));
+        assert!(output.contains("exonum_test_metrics_histogram_bucket{le=\"+Inf\"} 2\n"));
+        assert!(output.contains("exonum_test_metrics_histogram_sum 2000004\n"));
+        assert!(output.contains("exonum_test_metrics_histogram_count 2\n"));
     }
 }
@@ -74,6 +74,9 @@ pub fn generate_testnet_config(count: u16, start_port: u16) -> Vec<NodeConfig> {
             mempool: Default::default(),
             services_configs: Default::default(),
             database: Default::default(),
+            pruning: Default::default(),
+            state_history: Default::default(),
+            state_sync: Default::default(),
             thread_pool_size: Default::default(),
         }).collect::<Vec<_>>()
 }
@@ -58,6 +58,7 @@ extern crate clap;
 extern crate env_logger;
 extern crate erased_serde;
 pub extern crate exonum_crypto as crypto;
+#[cfg(feature = "rocksdb-storage")]
 extern crate exonum_rocksdb as rocksdb;
 #[cfg(feature = "sodiumoxide-crypto")]
 extern crate exonum_sodiumoxide as sodiumoxide;
@@ -66,6 +67,12 @@ extern crate failure;
 extern crate futures;
 extern crate hex;
 #[macro_use]
+extern crate lazy_static;
+#[cfg(feature = "lmdb-storage")]
+extern crate lmdb;
+#[cfg(feature = "lmdb-storage")]
+extern crate lmdb_sys;
+#[macro_use]
 extern crate log;
 extern crate os_info;
 extern crate rand;
@@ -91,9 +98,6 @@ extern crate uuid;
 
 // Test dependencies.
 #[cfg(test)]
-#[macro_use]
-extern crate lazy_static;
-#[cfg(test)]
 extern crate tempdir;
 #[cfg(all(test, feature = "long_benchmarks"))]
 extern crate test;
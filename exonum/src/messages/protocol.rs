@@ -926,6 +926,236 @@ impl ProtobufConvert for BlockRequest {
     }
 }
 
+/// Notification that the block with the given `height` is not available on the node.
+///
+/// ### Validation
+/// The message is ignored if
+///     * its `to` field corresponds to a different node
+///     * its `height` is not equal to the node's current height
+///
+/// ### Processing
+/// The block is requested from another peer; if the sender is far enough ahead,
+/// the node starts state synchronization with it.
+///
+/// ### Generation
+/// The message is sent as response to `BlockRequest` for a height whose block
+/// has been pruned.
+#[derive(Clone, PartialEq, Eq, Ord, PartialOrd, Debug)]
+pub struct BlockUnavailable {
+    /// Public key of the recipient.
+    to: PublicKey,
+    /// The height of the unavailable block.
+    height: Height,
+}
+
+impl BlockUnavailable {
+    /// Create new `BlockUnavailable` message.
+    pub fn new(to: &PublicKey, height: Height) -> Self {
+        Self { to: *to, height }
+    }
+    /// Public key of the recipient.
+    pub fn to(&self) -> &PublicKey {
+        &self.to
+    }
+    /// The height of the unavailable block.
+    pub fn height(&self) -> Height {
+        self.height
+    }
+}
+
+impl ProtobufConvert for BlockUnavailable {
+    type ProtoStruct = protobuf::BlockUnavailable;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let mut msg = Self::ProtoStruct::new();
+        msg.set_to(self.to.to_pb());
+        msg.set_height(self.height.to_pb());
+        msg
+    }
+
+    fn from_pb(mut pb: Self::ProtoStruct) -> Result<Self, ()> {
+        Ok(Self {
+            to: ProtobufConvert::from_pb(pb.take_to())?,
+            height: ProtobufConvert::from_pb(pb.get_height())?,
+        })
+    }
+}
+
+/// Request for a chunk of a Merkelized table, which root hash is a part of the blockchain
+/// state at the given `height`.
+///
+/// ### Validation
+/// The message is ignored if its `height` is not equal to the node's latest committed height
+/// or if the node does not know the table with the given `table_key`.
+///
+/// ### Processing
+/// `StateChunkResponse` message is sent as the response.
+///
+/// ### Generation
+/// This message is sent by a node synchronizing the blockchain state from its peers.
+#[derive(Clone, PartialEq, Eq, Ord, PartialOrd, Debug)]
+pub struct StateChunkRequest {
+    /// Public key of the recipient.
+    to: PublicKey,
+    /// The height of the block which state is requested.
+    height: Height,
+    /// Key of the table in the `state_hash_aggregator`.
+    table_key: Hash,
+    /// Key of the first requested entry, or an empty vector to request the beginning
+    /// of the table.
+    from: Vec<u8>,
+}
+
+impl StateChunkRequest {
+    /// Create new `StateChunkRequest`.
+    pub fn new(to: &PublicKey, height: Height, table_key: &Hash, from: Vec<u8>) -> Self {
+        Self {
+            to: *to,
+            height,
+            table_key: *table_key,
+            from,
+        }
+    }
+    /// Public key of the recipient.
+    pub fn to(&self) -> &PublicKey {
+        &self.to
+    }
+    /// The height of the block which state is requested.
+    pub fn height(&self) -> Height {
+        self.height
+    }
+    /// Key of the table in the `state_hash_aggregator`.
+    pub fn table_key(&self) -> &Hash {
+        &self.table_key
+    }
+    /// Key of the first requested entry.
+    pub fn from(&self) -> &[u8] {
+        &self.from
+    }
+}
+
+impl ProtobufConvert for StateChunkRequest {
+    type ProtoStruct = protobuf::StateChunkRequest;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let mut msg = Self::ProtoStruct::new();
+        msg.set_to(self.to.to_pb());
+        msg.set_height(self.height.to_pb());
+        msg.set_table_key(self.table_key.to_pb());
+        msg.set_from(self.from.to_pb());
+        msg
+    }
+
+    fn from_pb(mut pb: Self::ProtoStruct) -> Result<Self, ()> {
+        Ok(Self {
+            to: ProtobufConvert::from_pb(pb.take_to())?,
+            height: ProtobufConvert::from_pb(pb.get_height())?,
+            table_key: ProtobufConvert::from_pb(pb.take_table_key())?,
+            from: ProtobufConvert::from_pb(pb.take_from())?,
+        })
+    }
+}
+
+/// A chunk of a Merkelized table together with the proofs of its authenticity.
+///
+/// ### Validation
+/// The message is ignored if
+///     * its `to` field corresponds to a different node
+///     * the node does not synchronize the state of the table at the given height
+///     * the proofs cannot be parsed or do not match the `state_hash` of the block
+///
+/// ### Processing
+/// Entries of the chunk are added to the synchronized state.
+///
+/// ### Generation
+/// The message is sent as response to `StateChunkRequest`.
+#[derive(Clone, PartialEq, Eq, Ord, PartialOrd, Debug)]
+pub struct StateChunkResponse {
+    /// Public key of the recipient.
+    to: PublicKey,
+    /// The height of the block which state the chunk belongs to.
+    height: Height,
+    /// Key of the table in the `state_hash_aggregator`.
+    table_key: Hash,
+    /// Serialized proof of the table root hash in the `state_hash_aggregator`.
+    table_proof: Vec<u8>,
+    /// Serialized entries of the chunk together with their proof.
+    chunk: Vec<u8>,
+    /// Key of the entry following the chunk, or an empty vector for the last chunk.
+    next: Vec<u8>,
+}
+
+impl StateChunkResponse {
+    /// Create new `StateChunkResponse` message.
+    pub fn new(
+        to: &PublicKey,
+        height: Height,
+        table_key: &Hash,
+        table_proof: Vec<u8>,
+        chunk: Vec<u8>,
+        next: Vec<u8>,
+    ) -> Self {
+        Self {
+            to: *to,
+            height,
+            table_key: *table_key,
+            table_proof,
+            chunk,
+            next,
+        }
+    }
+    /// Public key of the recipient.
+    pub fn to(&self) -> &PublicKey {
+        &self.to
+    }
+    /// The height of the block which state the chunk belongs to.
+    pub fn height(&self) -> Height {
+        self.height
+    }
+    /// Key of the table in the `state_hash_aggregator`.
+    pub fn table_key(&self) -> &Hash {
+        &self.table_key
+    }
+    /// Serialized proof of the table root hash in the `state_hash_aggregator`.
+    pub fn table_proof(&self) -> &[u8] {
+        &self.table_proof
+    }
+    /// Serialized entries of the chunk together with their proof.
+    pub fn chunk(&self) -> &[u8] {
+        &self.chunk
+    }
+    /// Key of the entry following the chunk, or an empty vector for the last chunk.
+    pub fn next(&self) -> &[u8] {
+        &self.next
+    }
+}
+
+impl ProtobufConvert for StateChunkResponse {
+    type ProtoStruct = protobuf::StateChunkResponse;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let mut msg = Self::ProtoStruct::new();
+        msg.set_to(self.to.to_pb());
+        msg.set_height(self.height.to_pb());
+        msg.set_table_key(self.table_key.to_pb());
+        msg.set_table_proof(self.table_proof.to_pb());
+        msg.set_chunk(self.chunk.to_pb());
+        msg.set_next(self.next.to_pb());
+        msg
+    }
+
+    fn from_pb(mut pb: Self::ProtoStruct) -> Result<Self, ()> {
+        Ok(Self {
+            to: ProtobufConvert::from_pb(pb.take_to())?,
+            height: ProtobufConvert::from_pb(pb.get_height())?,
+            table_key: ProtobufConvert::from_pb(pb.take_table_key())?,
+            table_proof: ProtobufConvert::from_pb(pb.take_table_proof())?,
+            chunk: ProtobufConvert::from_pb(pb.take_chunk())?,
+            next: ProtobufConvert::from_pb(pb.take_next())?,
+        })
+    }
+}
+
 impl BlockResponse {
     /// Verify Merkle root of transactions in the block.
     pub fn verify_tx_hash(&self) -> bool {
@@ -1108,6 +1338,10 @@ impl_protocol! {
             TransactionsResponse = 0,
             /// Information about block, that sent as response to `BlockRequest`.
             BlockResponse = 1,
+            /// Chunk of the blockchain state, that sent as response to `StateChunkRequest`.
+            StateChunkResponse = 2,
+            /// Notification about pruned block, that sent as response to `BlockRequest`.
+            BlockUnavailable = 3,
         },
         /// Exonum node requests.
         3 => Requests {
@@ -1121,6 +1355,8 @@ impl_protocol! {
             PeersRequest = 3,
             /// Request of some future block.
             BlockRequest = 4,
+            /// Request of a chunk of the blockchain state.
+            StateChunkRequest = 5,
         },
 
     }
@@ -1192,6 +1428,7 @@ impl Requests {
             Requests::PrevotesRequest(ref msg) => msg.to(),
             Requests::PeersRequest(ref msg) => msg.to(),
             Requests::BlockRequest(ref msg) => msg.to(),
+            Requests::StateChunkRequest(ref msg) => msg.to(),
         }
     }
 
@@ -1203,6 +1440,7 @@ impl Requests {
             Requests::PrevotesRequest(ref msg) => msg.author(),
             Requests::PeersRequest(ref msg) => msg.author(),
             Requests::BlockRequest(ref msg) => msg.author(),
+            Requests::StateChunkRequest(ref msg) => msg.author(),
         }
     }
 }
@@ -38,6 +38,12 @@ impl NodeHandler {
             Message::Responses(Responses::TransactionsResponse(msg)) => {
                 self.handle_txs_batch(&msg).log_error()
             }
+            Message::Responses(Responses::StateChunkResponse(msg)) => {
+                self.handle_state_chunk(&msg).log_error()
+            }
+            Message::Responses(Responses::BlockUnavailable(msg)) => {
+                self.handle_block_unavailable(&msg).log_error()
+            }
         }
     }
 
@@ -140,7 +146,9 @@ impl NodeHandler {
     }
 
     /// Handles the `Status` message. Node sends `BlockRequest` as response if height in the
-    /// message is higher than node's height.
+    /// message is higher than node's height. If the node falls far behind and the state
+    /// synchronization is enabled, the requested block is the latest block of the peer,
+    /// which state is synchronized afterwards.
     pub fn handle_status(&mut self, msg: &Signed<Status>) {
         let height = self.state.height();
         trace!(
@@ -167,6 +175,15 @@ impl NodeHandler {
                 self.state.set_node_height(peer, msg.height());
             }
 
+            // Blocks are not requested during the state synchronization.
+            if self.state_sync.is_some() {
+                return;
+            }
+            if self.should_sync_state(msg.height()) {
+                self.request(RequestData::StateSyncBlock(msg.height().previous()), peer);
+                return;
+            }
+
             // Request block
             self.request(RequestData::Block(height), peer);
         }
@@ -20,8 +20,8 @@ use events::InternalRequest;
 use failure;
 use helpers::{Height, Round, ValidatorId};
 use messages::{
-    BlockRequest, BlockResponse, Consensus as ConsensusMessage, Precommit, Prevote,
-    PrevotesRequest, Propose, ProposeRequest, RawTransaction, Signed, SignedMessage,
+    BlockRequest, BlockResponse, BlockUnavailable, Consensus as ConsensusMessage, Precommit,
+    Prevote, PrevotesRequest, Propose, ProposeRequest, RawTransaction, Signed, SignedMessage,
     TransactionsRequest, TransactionsResponse,
 };
 use node::{NodeHandler, RequestData};
@@ -200,6 +200,9 @@ impl NodeHandler {
     /// Handles the `Block` message. For details see the message documentation.
     // TODO: Write helper function which returns Result. (ECR-123)
     pub fn handle_block(&mut self, msg: &Signed<BlockResponse>) -> Result<(), failure::Error> {
+        if msg.block().height() > self.state.height() {
+            return self.handle_state_sync_block(msg);
+        }
         self.validate_block_response(&msg)?;
 
         let block = msg.block();
@@ -237,12 +240,55 @@ impl NodeHandler {
         Ok(())
     }
 
+    /// Handles the `BlockUnavailable` message. For details see the message documentation.
+    pub fn handle_block_unavailable(
+        &mut self,
+        msg: &Signed<BlockUnavailable>,
+    ) -> Result<(), failure::Error> {
+        if msg.to() != self.state.consensus_public_key() {
+            bail!(
+                "Received block unavailability notice intended for another peer, to={}, from={}",
+                msg.to().to_hex(),
+                msg.author().to_hex()
+            );
+        }
+        if msg.height() != self.state.height() {
+            bail!(
+                "Received block unavailability notice for height {}, our height is {}",
+                msg.height(),
+                self.state.height()
+            );
+        }
+
+        info!(
+            "Block at height {} has been pruned on peer {}",
+            msg.height(),
+            msg.author().to_hex()
+        );
+        // Move the request on to the next peer known to have the block.
+        self.handle_request_timeout(&RequestData::Block(msg.height()), Some(msg.author()));
+
+        let peer_height = self.state.node_height(&msg.author());
+        if self.should_sync_state(peer_height) {
+            self.request(
+                RequestData::StateSyncBlock(peer_height.previous()),
+                msg.author(),
+            );
+        }
+        Ok(())
+    }
+
     /// Executes and commits block. This function is called when node has full propose information.
     pub fn handle_full_propose(&mut self, hash: Hash, propose_round: Round) {
         // Send prevote
         if self.state.locked_round() == Round::zero() {
             if self.state.is_validator() && !self.state.have_prevote(propose_round) {
-                self.broadcast_prevote(propose_round, &hash);
+                match self.validate_propose_transactions(&hash) {
+                    Ok(()) => {
+                        self.broadcast_prevote(propose_round, &hash);
+                    }
+                    Err(e) => warn!("{}, propose hash={:?}", e, hash),
+                }
             } else {
                 // TODO: what if we HAVE prevote for the propose round? (ECR-171)
             }
@@ -274,6 +320,37 @@ impl NodeHandler {
         }
     }
 
+    /// Checks that the transactions of the propose are ordered by non-increasing priority
+    /// and are not expired at the height of the propose.
+    fn validate_propose_transactions(&self, propose_hash: &Hash) -> Result<(), failure::Error> {
+        let propose = match self.state.propose(propose_hash) {
+            Some(state) => state.message().clone(),
+            None => bail!("Unknown propose"),
+        };
+        let snapshot = self.blockchain.snapshot();
+        let schema = Schema::new(&snapshot);
+        let transactions = schema.transactions();
+
+        let mut last_priority = u64::max_value();
+        for hash in propose.transactions() {
+            let raw = transactions.get(hash).ok_or_else(|| {
+                format_err!("Received propose with unknown transaction {:?}", hash)
+            })?;
+            let (priority, expires_at) = match self.blockchain.tx_from_raw(raw.payload().clone()) {
+                Ok(tx) => (tx.priority(), tx.expires_at()),
+                Err(_) => (0, None),
+            };
+            if expires_at.map_or(false, |height| height <= propose.height()) {
+                bail!("Received propose with expired transaction {:?}", hash);
+            }
+            if priority > last_priority {
+                bail!("Received propose with transactions not ordered by priority");
+            }
+            last_priority = priority;
+        }
+        Ok(())
+    }
+
     /// Executes and commits block. This function is called when node has full block information.
     ///
     /// # Panics
@@ -477,6 +554,13 @@ impl NodeHandler {
     ) {
         trace!("COMMIT {:?}", block_hash);
 
+        if let Some(received_at) = self.state.propose_received_at(&block_hash) {
+            let elapsed = received_at.elapsed();
+            let millis = elapsed.as_secs() * 1_000 + u64::from(elapsed.subsec_millis());
+            metric!(histogram: "node.propose_to_commit_ms", millis);
+        }
+        metric!(histogram: "node.rounds_per_height", u32::from(self.state.round()));
+
         // Merge changes into storage
         let (committed_txs, proposer) = {
             // FIXME: Avoid of clone here. (ECR-171)
@@ -491,6 +575,7 @@ impl NodeHandler {
             let block_hash = self.blockchain.last_hash();
             self.state
                 .new_height(&block_hash, self.system_state.current_time());
+            self.update_active_services();
             (block_state.txs().len(), block_state.proposer_id())
         };
 
@@ -503,6 +588,7 @@ impl NodeHandler {
         metric!("node.mempool", pool_len);
 
         let height = self.state.height();
+        metric!("node.height", height.0);
         info!(
             "COMMIT ====== height={}, proposer={}, round={}, committed={}, pool={}, hash={}",
             height,
@@ -539,19 +625,55 @@ impl NodeHandler {
             bail!("Received already processed transaction, hash {:?}", hash)
         }
 
-        if let Err(e) = self.blockchain.tx_from_raw(msg.payload().clone()) {
-            error!("Received invalid transaction {:?}, result: {}", msg, e);
-            bail!("Received malicious transaction.")
+        let tx = match self.blockchain.tx_from_raw(msg.payload().clone()) {
+            Ok(tx) => tx,
+            Err(e) => {
+                error!("Received invalid transaction {:?}, result: {}", msg, e);
+                bail!("Received malicious transaction.")
+            }
+        };
+
+        if !self.active_services.contains(&msg.service_id()) {
+            bail!(
+                "Received transaction of an inactive service, service id: {}",
+                msg.service_id()
+            )
+        }
+
+        // Transactions required by the known proposes and blocks bypass the expiration check
+        // and the pool limits, otherwise the node would be unable to process these proposes
+        // and blocks.
+        let is_required = self.state.is_transaction_required(&hash);
+        let is_expired = tx
+            .expires_at()
+            .map_or(false, |height| height <= self.state.height());
+        if is_expired && !is_required {
+            bail!("Received expired transaction, hash {:?}", hash)
         }
 
+        let evicted = if is_required {
+            None
+        } else {
+            let state = &self.state;
+            self.blockchain
+                .check_pool_limits(&*snapshot, &msg, |tx_hash| {
+                    !state.is_transaction_required(tx_hash)
+                }).map_err(|e| format_err!("Transaction {:?} is rejected: {}", hash, e))?
+        };
+
         let mut fork = self.blockchain.fork();
-        {
-            let mut schema = Schema::new(&mut fork);
-            schema.add_transaction_into_pool(msg);
+        if let Some(evicted) = evicted {
+            trace!("Evicting transaction {:?} from the pool", evicted);
+            Schema::new(&mut fork).evict_transaction(&evicted);
         }
+        self.blockchain.add_transaction_into_pool(&mut fork, msg);
         self.blockchain
             .merge(fork.into_patch())
             .expect("Unable to save transaction to persistent pool.");
+        metric!(
+            "node.mempool",
+            Schema::new(&self.blockchain.snapshot()).transactions_pool_len()
+        );
 
         if self.state.is_leader() && self.state.round() != Round::zero() {
             self.maybe_add_propose_timeout();
@@ -689,7 +811,6 @@ impl NodeHandler {
             }
             let snapshot = self.blockchain.snapshot();
             let schema = Schema::new(&snapshot);
-            let pool = schema.transactions_pool();
             let pool_len = schema.transactions_pool_len();
 
             info!("LEADER: pool = {}", pool_len);
@@ -697,7 +818,20 @@ impl NodeHandler {
             let round = self.state.round();
             let max_count = ::std::cmp::min(u64::from(self.txs_block_limit()), pool_len);
 
-            let txs: Vec<Hash> = pool.iter().take(max_count as usize).collect();
+            let height = self.state.height();
+            let transactions = schema.transactions();
+            // Expired transactions are normally removed from the pool after each commit,
+            // but the pool may contain the ones added by older versions of the node.
+            let txs: Vec<Hash> = schema
+                .transactions_pool_ordered(max_count as usize)
+                .into_iter()
+                .filter(|hash| {
+                    transactions.get(hash).map_or(false, |tx| {
+                        self.blockchain
+                            .tx_expiration(&tx)
+                            .map_or(true, |expires_at| expires_at > height)
+                    })
+                }).collect();
             let propose = self.sign_message(Propose::new(
                 validator_id,
                 self.state.height(),
@@ -768,9 +902,19 @@ impl NodeHandler {
                         propose_hash,
                         self.state.known_prevotes(round, propose_hash),
                     )).into(),
-                RequestData::Block(height) => {
+                RequestData::Block(height) | RequestData::StateSyncBlock(height) => {
                     self.sign_message(BlockRequest::new(&peer, height)).into()
                 }
+                RequestData::StateChunk => {
+                    let request = match self.state_sync {
+                        Some(ref sync) => sync.next_request(&peer),
+                        None => None,
+                    };
+                    match request {
+                        Some(request) => self.sign_message(request).into(),
+                        None => return,
+                    }
+                }
             };
             trace!("Send request {:?} to peer {:?}", data, peer);
             self.send_to_peer(peer, message);
@@ -985,6 +1129,17 @@ impl NodeHandler {
         Ok(())
     }
 
+    /// Updates the set of the services active at the current height.
+    pub(crate) fn update_active_services(&mut self) {
+        let snapshot = self.blockchain.snapshot();
+        self.active_services = self
+            .blockchain
+            .active_services(&*snapshot, self.state.height())
+            .iter()
+            .map(|service| service.service_id())
+            .collect();
+    }
+
     /// Checks whether Propose is saved to the consensus cache and saves it otherwise
     fn check_propose_saved(&mut self, round: Round, propose_hash: &Hash) {
         if let Some(propose_state) = self.state.propose_mut(propose_hash) {
@@ -35,6 +35,7 @@ use std::{
     collections::{BTreeMap, HashSet},
     fmt,
     net::SocketAddr,
+    path::PathBuf,
     sync::Arc,
     thread,
     time::{Duration, SystemTime},
@@ -45,7 +46,8 @@ use api::{
     ApiAccess, ApiAggregator,
 };
 use blockchain::{
-    Blockchain, ConsensusConfig, GenesisConfig, Schema, Service, SharedNodeState, ValidatorKeys,
+    Blockchain, ConsensusConfig, GenesisConfig, Schema, Service, SharedNodeState, StateSync,
+    ValidatorKeys,
 };
 use crypto::{self, CryptoHash, Hash, PublicKey, SecretKey};
 use events::{
@@ -68,6 +70,7 @@ mod connect_list;
 mod consensus;
 mod events;
 mod requests;
+mod state_sync;
 
 /// External messages.
 #[derive(Debug)]
@@ -136,6 +139,12 @@ pub struct NodeHandler {
     config_manager: Option<ConfigManager>,
     /// Can we speed up Propose with transaction pressure?
     allow_expedited_propose: bool,
+    /// Identifiers of the services active at the current height.
+    active_services: HashSet<u16>,
+    /// State synchronization configuration.
+    state_sync_config: Option<StateSyncConfig>,
+    /// Ongoing synchronization of the blockchain state.
+    state_sync: Option<StateSync>,
 }
 
 /// Service configuration.
@@ -179,6 +188,10 @@ pub struct NodeApiConfig {
     ///
     /// [cors]: https://developer.mozilla.org/en-US/docs/Web/HTTP/CORS
     pub private_allow_origin: Option<AllowOrigin>,
+    /// Directory, in which the checkpoints of the database are created by the `v1/checkpoint`
+    /// private API endpoint. The endpoint is disabled if not specified.
+    #[serde(default)]
+    pub checkpoints_dir: Option<PathBuf>,
 }
 
 impl Default for NodeApiConfig {
@@ -189,6 +202,7 @@ impl Default for NodeApiConfig {
             private_api_address: None,
             public_allow_origin: None,
             private_allow_origin: None,
+            checkpoints_dir: None,
         }
     }
 }
@@ -223,16 +237,98 @@ pub struct MemoryPoolConfig {
     /// Sets the maximum number of messages that can be buffered on the event loop's
     /// notification channel before a send will fail.
     pub events_pool_capacity: EventsPoolCapacity,
+    /// Maximum number of transactions in the pool, or `None` if the pool is unbounded.
+    ///
+    /// If the pool is full, a new transaction evicts the oldest transaction of the author
+    /// having the most transactions in the pool, provided that this author has at least two
+    /// transactions more than the author of the new transaction. Otherwise, the new
+    /// transaction is rejected.
+    #[serde(default)]
+    pub max_pool_size: Option<u64>,
+    /// Maximum number of transactions of a single author in the pool, or `None` if the number
+    /// is not limited. The author of a transaction is the public key it is signed with.
+    #[serde(default)]
+    pub max_txs_per_author: Option<u64>,
 }
 
 impl Default for MemoryPoolConfig {
     fn default() -> Self {
         Self {
             events_pool_capacity: EventsPoolCapacity::default(),
+            max_pool_size: None,
+            max_txs_per_author: None,
         }
     }
 }
 
+/// Pruning configuration parameters.
+///
+/// If pruning is enabled, the node removes transaction bodies, precommits and lists of
+/// block transactions for old blocks after each commit. Block headers, block hashes and
+/// the Merkelized state (including transaction results) are kept, so the node is still
+/// able to verify the blockchain state and serve proofs for it. Pruned nodes cannot serve
+/// the old blocks to the peers which are catching up with the network.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub struct PruningConfig {
+    /// Number of the latest heights for which the block contents are kept.
+    /// Should be positive.
+    pub keep_heights: u64,
+}
+
+/// State history configuration parameters.
+///
+/// The node records the previous values of the entries changed by each block. If the state
+/// history is enabled, the recorded history allows to read the blockchain state at past heights
+/// with [`Blockchain::snapshot_at`] and to serve proofs against the `state_hash` of
+/// the past blocks. The history also allows to roll the database back to a past height with
+/// [`Blockchain::rollback_blocks`] or the `rollback` maintenance action. The history is
+/// available starting from the height at which it has been enabled. Regardless of
+/// the configuration, the history is kept for at least the latest `MIN_KEEP_HEIGHTS` heights,
+/// so that the latest blocks can always be rolled back.
+///
+/// [`Blockchain::snapshot_at`]: ../blockchain/struct.Blockchain.html#method.snapshot_at
+/// [`Blockchain::rollback_blocks`]: ../blockchain/struct.Blockchain.html#method.rollback_blocks
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct StateHistoryConfig {
+    /// Number of the latest heights for which the state is kept, including the current
+    /// height. Should be positive; values smaller than `MIN_KEEP_HEIGHTS` are treated
+    /// as `MIN_KEEP_HEIGHTS`. The history is not trimmed if not specified.
+    #[serde(default)]
+    pub keep_heights: Option<u64>,
+    /// Maximum number of heights below the current one, the state at which is returned
+    /// by [`Blockchain::snapshot_at`] to the API clients and peers. The state at a past
+    /// height keeps in memory all the entries changed after it, so the limit bounds
+    /// the memory used per request. Defaults to `DEFAULT_MAX_SNAPSHOT_DEPTH` if not specified.
+    ///
+    /// [`Blockchain::snapshot_at`]: ../blockchain/struct.Blockchain.html#method.snapshot_at
+    #[serde(default)]
+    pub max_snapshot_depth: Option<u64>,
+}
+
+impl StateHistoryConfig {
+    /// Default value of `max_snapshot_depth`.
+    pub const DEFAULT_MAX_SNAPSHOT_DEPTH: u64 = 1_000;
+    /// Number of the latest heights, for which the history is recorded even if the state
+    /// history is disabled, so that the latest blocks can be rolled back.
+    pub const MIN_KEEP_HEIGHTS: u64 = 10;
+}
+
+/// State synchronization configuration parameters.
+///
+/// If the state synchronization is enabled, a node with only the genesis block, which falls
+/// behind its peers by more than `min_height_lag` blocks, downloads the Merkelized state
+/// at the latest height of a peer instead of executing all the blocks, see [`StateSync`].
+/// The contents of the blocks below this height are not downloaded, so these blocks
+/// are treated as pruned.
+///
+/// [`StateSync`]: ../blockchain/struct.StateSync.html
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub struct StateSyncConfig {
+    /// Minimal number of blocks by which the node must fall behind its peers to synchronize
+    /// the state instead of executing the blocks.
+    pub min_height_lag: u64,
+}
+
 /// Configuration for the `Node`.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct NodeConfig {
@@ -262,6 +358,16 @@ pub struct NodeConfig {
     /// Optional database configuration.
     #[serde(default)]
     pub database: DbOptions,
+    /// Optional pruning configuration. Pruning is disabled by default.
+    #[serde(default)]
+    pub pruning: Option<PruningConfig>,
+    /// Optional state history configuration. The state history is disabled by default.
+    #[serde(default)]
+    pub state_history: Option<StateHistoryConfig>,
+    /// Optional state synchronization configuration. The state synchronization
+    /// is disabled by default.
+    #[serde(default)]
+    pub state_sync: Option<StateSyncConfig>,
     /// Node's ConnectList.
     pub connect_list: ConnectListConfig,
     /// Transaction Verification Thread Pool size.
@@ -281,6 +387,8 @@ pub struct Configuration {
     pub peer_discovery: Vec<String>,
     /// Memory pool configuration.
     pub mempool: MemoryPoolConfig,
+    /// State synchronization configuration.
+    pub state_sync: Option<StateSyncConfig>,
 }
 
 /// Channel for messages, timeouts and api requests.
@@ -402,6 +510,11 @@ impl NodeHandler {
 
         let stored = Schema::new(&snapshot).actual_configuration();
         info!("Creating a node with config: {:#?}", stored);
+        let active_services = blockchain
+            .active_services(&*snapshot, last_height)
+            .iter()
+            .map(|service| service.service_id())
+            .collect();
 
         let validator_id = stored
             .validator_keys
@@ -455,6 +568,9 @@ impl NodeHandler {
             node_role,
             config_manager,
             allow_expedited_propose: true,
+            active_services,
+            state_sync_config: config.state_sync,
+            state_sync: None,
         }
     }
 
@@ -571,6 +687,7 @@ impl NodeHandler {
     pub fn send_to_peer<T: Into<SignedMessage>>(&mut self, public_key: PublicKey, message: T) {
         let message = message.into();
         let request = NetworkRequest::SendMessage(public_key, message);
+        metric!(gauge_delta: "events.network_requests_queue", 1);
         self.channel.network_requests.send(request).log_error();
     }
 
@@ -735,6 +852,7 @@ impl ApiSender {
 
     /// Sends an external message.
     pub fn send_external_message(&self, message: ExternalMessage) -> Result<(), Error> {
+        metric!(gauge_delta: "events.api_requests_queue", 1);
         self.0
             .clone()
             .send(message)
@@ -856,6 +974,16 @@ impl Node {
             node_cfg.service_secret_key.clone(),
             ApiSender::new(channel.api_requests.0.clone()),
         );
+        if let Some(pruning) = node_cfg.pruning {
+            blockchain.set_pruning_config(pruning);
+        }
+        if let Some(state_history) = node_cfg.state_history {
+            blockchain.set_state_history_config(state_history);
+        }
+        blockchain.set_mempool_config(node_cfg.mempool.clone());
+        if let Some(ref checkpoints_dir) = node_cfg.api.checkpoints_dir {
+            blockchain.set_checkpoints_dir(checkpoints_dir.clone());
+        }
         blockchain.initialize(node_cfg.genesis.clone()).unwrap();
 
         let peers = node_cfg.connect_list.addresses();
@@ -874,6 +1002,7 @@ impl Node {
             mempool: node_cfg.mempool,
             network: node_cfg.network,
             peer_discovery: peers,
+            state_sync: node_cfg.state_sync,
         };
 
         let api_state = SharedNodeState::new(node_cfg.api.state_update_timeout as u64);
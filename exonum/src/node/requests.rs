@@ -15,8 +15,8 @@
 use super::NodeHandler;
 use blockchain::Schema;
 use messages::{
-    BlockRequest, BlockResponse, PrevotesRequest, ProposeRequest, Requests, Signed,
-    TransactionsRequest, TransactionsResponse, RAW_TRANSACTION_HEADER,
+    BlockRequest, BlockResponse, BlockUnavailable, PrevotesRequest, ProposeRequest, Requests,
+    Signed, StateChunkRequest, TransactionsRequest, TransactionsResponse, RAW_TRANSACTION_HEADER,
     TRANSACTION_RESPONSE_EMPTY_SIZE,
 };
 
@@ -46,6 +46,7 @@ impl NodeHandler {
             Requests::PrevotesRequest(ref msg) => self.handle_request_prevotes(msg),
             Requests::PeersRequest(ref msg) => self.handle_request_peers(msg),
             Requests::BlockRequest(ref msg) => self.handle_request_block(msg),
+            Requests::StateChunkRequest(ref msg) => self.handle_request_state_chunk(msg),
         }
     }
 
@@ -141,6 +142,12 @@ impl NodeHandler {
         let schema = Schema::new(&snapshot);
 
         let height = msg.height();
+        if schema.is_pruned(height) {
+            trace!("Requested block at height {} has been pruned", height);
+            let unavailable_msg = self.sign_message(BlockUnavailable::new(&msg.author(), height));
+            self.send_to_peer(msg.author(), unavailable_msg);
+            return;
+        }
         let block_hash = schema.block_hash_by_height(height).unwrap();
 
         let block = schema.blocks().get(&block_hash).unwrap();
@@ -158,4 +165,31 @@ impl NodeHandler {
         ));
         self.send_to_peer(msg.author(), block_msg);
     }
+
+    /// Handles `StateChunkRequest` message. For details see the message documentation.
+    pub fn handle_request_state_chunk(&mut self, msg: &Signed<StateChunkRequest>) {
+        trace!(
+            "Handle state chunk request with height:{}, our height: {}",
+            msg.height(),
+            self.state.height()
+        );
+        if msg.height() >= self.state.height() {
+            return;
+        }
+        // The state at past heights is available only if the state history is recorded.
+        let snapshot = match self.blockchain.snapshot_at(msg.height()) {
+            Some(snapshot) => snapshot,
+            None => {
+                trace!("State at height {} is not available", msg.height());
+                return;
+            }
+        };
+        let response = self
+            .blockchain
+            .state_chunk_response(snapshot.as_ref(), &msg.author(), msg);
+        if let Some(response) = response {
+            let response = self.sign_message(response);
+            self.send_to_peer(msg.author(), response);
+        }
+    }
 }
@@ -22,7 +22,7 @@ use std::{
     collections::{hash_map::Entry, BTreeMap, HashMap, HashSet},
     ops::Deref,
     sync::{Arc, RwLock},
-    time::{Duration, SystemTime},
+    time::{Duration, Instant, SystemTime},
 };
 
 use blockchain::{ConsensusConfig, StoredConfiguration, ValidatorKeys};
@@ -49,6 +49,8 @@ pub const TRANSACTIONS_REQUEST_TIMEOUT: Milliseconds = 100;
 pub const PREVOTES_REQUEST_TIMEOUT: Milliseconds = 100;
 /// Timeout value for the `BlockRequest` message.
 pub const BLOCK_REQUEST_TIMEOUT: Milliseconds = 100;
+/// Timeout value for the `StateChunkRequest` message.
+pub const STATE_CHUNK_REQUEST_TIMEOUT: Milliseconds = 1_000;
 
 /// State of the `NodeHandler`.
 #[derive(Debug)]
@@ -118,6 +120,10 @@ pub enum RequestData {
     Prevotes(Round, Hash),
     /// Represents `BlockRequest` message.
     Block(Height),
+    /// Represents `BlockRequest` message for the block, which state is to be synchronized.
+    StateSyncBlock(Height),
+    /// Represents `StateChunkRequest` message for the next chunk of the synchronized state.
+    StateChunk,
 }
 
 #[derive(Debug)]
@@ -137,6 +143,8 @@ pub struct ProposeState {
     block_hash: Option<Hash>,
     // Whether the message has been saved to the consensus messages' cache or not.
     is_saved: bool,
+    // Time of receiving the message, which is used for the metrics.
+    received_at: Instant,
 }
 
 /// State of a block.
@@ -262,7 +270,8 @@ impl RequestData {
                 TRANSACTIONS_REQUEST_TIMEOUT
             }
             RequestData::Prevotes(..) => PREVOTES_REQUEST_TIMEOUT,
-            RequestData::Block(..) => BLOCK_REQUEST_TIMEOUT,
+            RequestData::Block(..) | RequestData::StateSyncBlock(..) => BLOCK_REQUEST_TIMEOUT,
+            RequestData::StateChunk => STATE_CHUNK_REQUEST_TIMEOUT,
         };
         Duration::from_millis(ms)
     }
@@ -579,7 +588,9 @@ impl State {
 
     /// Adds the public key, address, and `Connect` message of a validator.
     pub fn add_peer(&mut self, pubkey: PublicKey, msg: Signed<Connect>) -> bool {
-        self.peers.insert(pubkey, msg).is_none()
+        let is_new = self.peers.insert(pubkey, msg).is_none();
+        metric!("node.peers", self.peers.len());
+        is_new
     }
 
     /// Add connection to the connection list.
@@ -591,11 +602,9 @@ impl State {
     /// indeed connected or `None` if there was no connection with given socket address.
     pub fn remove_peer_with_pubkey(&mut self, key: &PublicKey) -> Option<Signed<Connect>> {
         self.connections.remove(key);
-        if let Some(c) = self.peers.remove(key) {
-            Some(c)
-        } else {
-            None
-        }
+        let peer = self.peers.remove(key);
+        metric!("node.peers", self.peers.len());
+        peer
     }
 
     /// Checks if this node considers a peer to be a validator.
@@ -778,6 +787,16 @@ impl State {
         self.proposes.get(hash)
     }
 
+    /// Returns the time when the earliest propose of the block with the given hash
+    /// was received.
+    pub(crate) fn propose_received_at(&self, block_hash: &Hash) -> Option<Instant> {
+        self.proposes
+            .values()
+            .filter(|state| state.block_hash == Some(*block_hash))
+            .map(|state| state.received_at)
+            .min()
+    }
+
     /// Returns a block with the specified hash.
     pub fn block(&self, hash: &Hash) -> Option<&BlockState> {
         self.blocks.get(hash)
@@ -820,6 +839,18 @@ impl State {
         self.incomplete_block = None;
     }
 
+    /// Moves the node to the height following the given one, which state has been
+    /// synchronized from the peers.
+    pub fn new_height_after_sync(
+        &mut self,
+        height: Height,
+        block_hash: &Hash,
+        height_start_time: SystemTime,
+    ) {
+        self.height = height;
+        self.new_height(block_hash, height_start_time);
+    }
+
     /// Returns a list of queued consensus messages.
     pub fn queued(&mut self) -> Vec<ConsensusMessage> {
         let mut queued = Vec::new();
@@ -851,6 +882,18 @@ impl State {
         full_proposes
     }
 
+    /// Returns `true` if the transaction is a part of a known propose or of the incomplete
+    /// block, so it must not be removed from the pool.
+    pub fn is_transaction_required(&self, tx_hash: &Hash) -> bool {
+        self.proposes
+            .values()
+            .any(|state| state.message().transactions().contains(tx_hash))
+            || self
+                .incomplete_block
+                .as_ref()
+                .map_or(false, |block| block.message().transactions().contains(tx_hash))
+    }
+
     /// Checks if there is an incomplete block that waits for this transaction.
     /// Returns a block that don't contain unknown transactions.
     ///
@@ -910,6 +953,7 @@ impl State {
                 // saving a propose to the cache. Think about making this approach less error-prone.
                 // (ECR-1635)
                 is_saved: true,
+                received_at: Instant::now(),
             },
         );
 
@@ -953,6 +997,7 @@ impl State {
                     unknown_txs,
                     block_hash: None,
                     is_saved: false,
+                    received_at: Instant::now(),
                 }))
             }
         }
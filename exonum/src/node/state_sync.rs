@@ -0,0 +1,157 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use failure;
+
+use super::{NodeHandler, RequestData};
+use blockchain::{BlockProof, Schema, StateSync};
+use helpers::Height;
+use messages::{BlockResponse, Precommit, Signed, StateChunkResponse};
+
+impl NodeHandler {
+    /// Returns `true` if the node should synchronize the state at the latest height of
+    /// the peer instead of requesting the blocks one by one.
+    ///
+    /// The state is synchronized only into a blockchain with the genesis block.
+    pub(crate) fn should_sync_state(&self, peer_height: Height) -> bool {
+        let config = match self.state_sync_config {
+            Some(config) => config,
+            None => return false,
+        };
+        self.state_sync.is_none()
+            && self.state.height() == Height(1)
+            && peer_height.0 > self.state.height().0 + config.min_height_lag
+    }
+
+    /// Handles the `BlockResponse` with the block, which state is requested to be synchronized,
+    /// and starts the synchronization.
+    pub(crate) fn handle_state_sync_block(
+        &mut self,
+        msg: &Signed<BlockResponse>,
+    ) -> Result<(), failure::Error> {
+        if msg.to() != self.state.consensus_public_key() {
+            bail!(
+                "Received block intended for another peer, to={}, from={}",
+                msg.to().to_hex(),
+                msg.author().to_hex()
+            );
+        }
+        let height = msg.block().height();
+        let known_nodes = self.remove_request(&RequestData::StateSyncBlock(height));
+        if known_nodes.is_empty() || self.state_sync.is_some() {
+            bail!("Received block has another height, msg={:?}", msg);
+        }
+
+        let precommits: Result<Vec<_>, _> = msg
+            .precommits()
+            .into_iter()
+            .map(Precommit::verify_precommit)
+            .collect();
+        let proof = BlockProof {
+            block: msg.block().clone(),
+            precommits: precommits?,
+        };
+        self.state_sync = Some(StateSync::new(&self.blockchain, proof)?);
+        info!("Started state synchronization at height {}", height);
+
+        self.request(RequestData::StateChunk, msg.author());
+        for node in known_nodes {
+            self.request(RequestData::StateChunk, node);
+        }
+        Ok(())
+    }
+
+    /// Handles the `StateChunkResponse` message. For details see the message documentation.
+    pub fn handle_state_chunk(
+        &mut self,
+        msg: &Signed<StateChunkResponse>,
+    ) -> Result<(), failure::Error> {
+        if msg.to() != self.state.consensus_public_key() {
+            bail!(
+                "Received state chunk intended for another peer, to={}, from={}",
+                msg.to().to_hex(),
+                msg.author().to_hex()
+            );
+        }
+        if self.state_sync.is_none() {
+            bail!("Received state chunk while the state is not synchronized");
+        }
+        let mut known_nodes = self.remove_request(&RequestData::StateChunk);
+        if !known_nodes.remove(&msg.author()) {
+            bail!(
+                "Received unexpected state chunk from {}",
+                msg.author().to_hex()
+            );
+        }
+
+        let result = self
+            .state_sync
+            .as_mut()
+            .map_or(Ok(()), |sync| sync.handle_response(msg));
+        if let Err(e) = result {
+            // The chunk is requested from other peers.
+            for node in known_nodes {
+                self.request(RequestData::StateChunk, node);
+            }
+            return Err(e);
+        }
+
+        if self
+            .state_sync
+            .as_ref()
+            .map_or(false, StateSync::is_finished)
+        {
+            return self.finish_state_sync();
+        }
+        self.request(RequestData::StateChunk, msg.author());
+        for node in known_nodes {
+            self.request(RequestData::StateChunk, node);
+        }
+        Ok(())
+    }
+
+    /// Merges the synchronized state into the storage and moves the node to the height
+    /// following the synchronized block.
+    fn finish_state_sync(&mut self) -> Result<(), failure::Error> {
+        let sync = match self.state_sync.take() {
+            Some(sync) => sync,
+            None => bail!("State synchronization is not started"),
+        };
+        if self.state.height() != Height(1) {
+            bail!("Blocks have been committed during the state synchronization");
+        }
+        let height = sync.height();
+        self.blockchain.merge(sync.into_patch()?)?;
+
+        self.state
+            .update_config(Schema::new(&self.blockchain.snapshot()).actual_configuration());
+        let block_hash = self.blockchain.last_hash();
+        self.state
+            .new_height_after_sync(height, &block_hash, self.system_state.current_time());
+        self.update_active_services();
+        info!(
+            "State synchronized at height={}, hash={}",
+            height,
+            block_hash.to_hex()
+        );
+
+        self.broadcast_status();
+        self.add_status_timeout();
+        self.add_round_timeout();
+        if self.state.is_leader() {
+            self.add_propose_timeout();
+        }
+        Ok(())
+    }
+}
@@ -857,6 +857,7 @@ impl Sandbox {
             network: NetworkConfiguration::default(),
             peer_discovery: Vec::new(),
             mempool: Default::default(),
+            state_sync: None,
         };
 
         let system_state = SandboxSystemStateProvider {
@@ -1092,6 +1093,7 @@ fn sandbox_with_services_uninitialized(
         network: NetworkConfiguration::default(),
         peer_discovery: Vec::new(),
         mempool: Default::default(),
+        state_sync: None,
     };
 
     let system_state = SandboxSystemStateProvider {
@@ -20,9 +20,9 @@
 
 // spell-checker:ignore subprefix
 
-use std::{borrow::Cow, marker::PhantomData};
+use std::{any::Any, borrow::Cow, marker::PhantomData, sync::Arc};
 
-use super::{Fork, Iter, Snapshot, StorageKey, StorageValue};
+use super::{DecodedValue, Fork, Iter, Snapshot, StorageKey, StorageValue};
 use storage::indexes_metadata::{self, IndexType, INDEXES_METADATA_TABLE_NAME};
 
 /// Basic struct for all indices that implements common features.
@@ -48,16 +48,25 @@ pub struct BaseIndex<T> {
 
 /// An iterator over the entries of a `BaseIndex`.
 ///
-/// This struct is created by the [`iter`] or
-/// [`iter_from`] method on [`BaseIndex`]. See its documentation for details.
+/// This struct is created by the [`iter`], [`iter_from`], [`iter_rev`], [`iter_rev_from`],
+/// [`iter_range`] or [`iter_range_rev`] method on [`BaseIndex`]. See its documentation
+/// for details.
 ///
 /// [`iter`]: struct.BaseIndex.html#method.iter
 /// [`iter_from`]: struct.BaseIndex.html#method.iter_from
+/// [`iter_rev`]: struct.BaseIndex.html#method.iter_rev
+/// [`iter_rev_from`]: struct.BaseIndex.html#method.iter_rev_from
+/// [`iter_range`]: struct.BaseIndex.html#method.iter_range
+/// [`iter_range_rev`]: struct.BaseIndex.html#method.iter_range_rev
 /// [`BaseIndex`]: struct.BaseIndex.html
 pub struct BaseIndexIter<'a, K, V> {
     base_iter: Iter<'a>,
     base_prefix_len: usize,
     index_id: Vec<u8>,
+    // The upper (exclusive) bound of the keys for the direct order, or the lower (inclusive)
+    // bound for the reverse order.
+    bound: Option<Vec<u8>>,
+    reverse: bool,
     ended: bool,
     _k: PhantomData<K>,
     _v: PhantomData<V>,
@@ -171,6 +180,28 @@ where
             .map(|v| StorageValue::from_bytes(Cow::Owned(v)))
     }
 
+    /// Returns a value corresponding to the key like [`get`](#method.get), but lets
+    /// the database cache the decoded value. See [`Snapshot::get_decoded`] for details.
+    ///
+    /// [`Snapshot::get_decoded`]: ../trait.Snapshot.html#method.get_decoded
+    pub(crate) fn get_decoded<K, V>(&self, key: &K) -> Option<V>
+    where
+        K: StorageKey + ?Sized,
+        V: StorageValue + Clone + Send + Sync + 'static,
+    {
+        let decode = |v: Vec<u8>| -> DecodedValue { Arc::new(V::from_bytes(Cow::Owned(v))) };
+        let decoded =
+            self.view
+                .as_ref()
+                .get_decoded(&self.name, &self.prefixed_key(key), &decode)?;
+        let value: &dyn Any = &*decoded;
+        match value.downcast_ref::<V>() {
+            Some(value) => Some(value.clone()),
+            // The value has been decoded to another type by a different index.
+            None => self.get(key),
+        }
+    }
+
     /// Returns `true` if the index contains a value of *any* type for the specified key of
     /// *any* type.
     pub fn contains<K>(&self, key: &K) -> bool
@@ -192,14 +223,8 @@ where
         V: StorageValue,
     {
         let iter_prefix = self.prefixed_key(subprefix);
-        BaseIndexIter {
-            base_iter: self.view.as_ref().iter(&self.name, &iter_prefix),
-            base_prefix_len: self.index_id.as_ref().map_or(0, |p| p.len()),
-            index_id: iter_prefix,
-            ended: false,
-            _k: PhantomData,
-            _v: PhantomData,
-        }
+        let base_iter = self.view.as_ref().iter(&self.name, &iter_prefix);
+        self.make_iter(base_iter, iter_prefix, None, false)
     }
 
     /// Returns an iterator over the entries of the index in ascending order starting from the
@@ -214,10 +239,93 @@ where
     {
         let iter_prefix = self.prefixed_key(subprefix);
         let iter_from = self.prefixed_key(from);
+        let base_iter = self.view.as_ref().iter(&self.name, &iter_from);
+        self.make_iter(base_iter, iter_prefix, None, false)
+    }
+
+    /// Returns an iterator over the entries of the index in ascending order starting from the
+    /// `from` key (inclusive) up to the `to` key (exclusive). The iterator element type is *any*
+    /// key-value pair. An argument `subprefix` allows specifying a subset of iteration.
+    pub fn iter_range<P, F, K, V>(&self, subprefix: &P, from: &F, to: &F) -> BaseIndexIter<K, V>
+    where
+        P: StorageKey,
+        F: StorageKey + ?Sized,
+        K: StorageKey,
+        V: StorageValue,
+    {
+        let iter_prefix = self.prefixed_key(subprefix);
+        let iter_from = self.prefixed_key(from);
+        let iter_to = self.prefixed_key(to);
+        let base_iter = self.view.as_ref().iter(&self.name, &iter_from);
+        self.make_iter(base_iter, iter_prefix, Some(iter_to), false)
+    }
+
+    /// Returns an iterator over the entries of the index in descending order. The iterator
+    /// element type is *any* key-value pair. An argument `subprefix` allows specifying a subset
+    /// of keys for iteration.
+    pub fn iter_rev<P, K, V>(&self, subprefix: &P) -> BaseIndexIter<K, V>
+    where
+        P: StorageKey,
+        K: StorageKey,
+        V: StorageValue,
+    {
+        let iter_prefix = self.prefixed_key(subprefix);
+        let before = prefix_successor(&iter_prefix);
+        let base_iter = self
+            .view
+            .as_ref()
+            .iter_rev(&self.name, before.as_ref().map(Vec::as_slice));
+        self.make_iter(base_iter, iter_prefix, None, true)
+    }
+
+    /// Returns an iterator over the entries of the index in descending order starting from the
+    /// specified key (inclusive). The iterator element type is *any* key-value pair. An argument
+    /// `subprefix` allows specifying a subset of iteration.
+    pub fn iter_rev_from<P, F, K, V>(&self, subprefix: &P, from: &F) -> BaseIndexIter<K, V>
+    where
+        P: StorageKey,
+        F: StorageKey + ?Sized,
+        K: StorageKey,
+        V: StorageValue,
+    {
+        let iter_prefix = self.prefixed_key(subprefix);
+        // The least key, which is greater than `from`.
+        let mut before = self.prefixed_key(from);
+        before.push(0);
+        let base_iter = self.view.as_ref().iter_rev(&self.name, Some(&before));
+        self.make_iter(base_iter, iter_prefix, None, true)
+    }
+
+    /// Returns an iterator over the entries of the index in descending order starting before
+    /// the `to` key (exclusive) down to the `from` key (inclusive). The iterator element type
+    /// is *any* key-value pair. An argument `subprefix` allows specifying a subset of iteration.
+    pub fn iter_range_rev<P, F, K, V>(&self, subprefix: &P, from: &F, to: &F) -> BaseIndexIter<K, V>
+    where
+        P: StorageKey,
+        F: StorageKey + ?Sized,
+        K: StorageKey,
+        V: StorageValue,
+    {
+        let iter_prefix = self.prefixed_key(subprefix);
+        let iter_from = self.prefixed_key(from);
+        let iter_to = self.prefixed_key(to);
+        let base_iter = self.view.as_ref().iter_rev(&self.name, Some(&iter_to));
+        self.make_iter(base_iter, iter_prefix, Some(iter_from), true)
+    }
+
+    fn make_iter<'b, K, V>(
+        &self,
+        base_iter: Iter<'b>,
+        index_id: Vec<u8>,
+        bound: Option<Vec<u8>>,
+        reverse: bool,
+    ) -> BaseIndexIter<'b, K, V> {
         BaseIndexIter {
-            base_iter: self.view.as_ref().iter(&self.name, &iter_from),
+            base_iter,
             base_prefix_len: self.index_id.as_ref().map_or(0, |p| p.len()),
-            index_id: iter_prefix,
+            index_id,
+            bound,
+            reverse,
             ended: false,
             _k: PhantomData,
             _v: PhantomData,
@@ -286,7 +394,12 @@ where
             return None;
         }
         if let Some((k, v)) = self.base_iter.next() {
-            if k.starts_with(&self.index_id) {
+            let in_bounds = match (self.bound.as_ref(), self.reverse) {
+                (Some(bound), false) => k < &bound[..],
+                (Some(bound), true) => k >= &bound[..],
+                (None, _) => true,
+            };
+            if in_bounds && k.starts_with(&self.index_id) {
                 return Some((
                     K::read(&k[self.base_prefix_len..]),
                     V::from_bytes(Cow::Borrowed(v)),
@@ -304,6 +417,15 @@ impl<'a, K, V> ::std::fmt::Debug for BaseIndexIter<'a, K, V> {
     }
 }
 
+/// Returns the least key, which is greater than all the keys starting with the given prefix,
+/// or `None` if there is no such key.
+fn prefix_successor(prefix: &[u8]) -> Option<Vec<u8>> {
+    let len = prefix.iter().rposition(|&byte| byte != 0xFF)?;
+    let mut successor = prefix[..=len].to_vec();
+    successor[len] += 1;
+    Some(successor)
+}
+
 /// A function that validates an index name. Allowable characters in name: ASCII characters, digits
 /// and underscores.
 fn is_valid_name<S: AsRef<str>>(name: S) -> bool {
@@ -352,4 +474,12 @@ mod tests {
     fn check_invalid_name() {
         assert_valid_name("invalid-name");
     }
+
+    #[test]
+    fn test_prefix_successor() {
+        assert_eq!(prefix_successor(&[1, 2]), Some(vec![1, 3]));
+        assert_eq!(prefix_successor(&[1, 0xFF, 0xFF]), Some(vec![2]));
+        assert_eq!(prefix_successor(&[0xFF]), None);
+        assert_eq!(prefix_successor(&[]), None);
+    }
 }
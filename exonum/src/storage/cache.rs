@@ -0,0 +1,639 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! An implementation of the read cache for databases.
+
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap},
+    fmt,
+    hash::{Hash, Hasher},
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    usize,
+};
+
+use super::{Database, DecodedValue, Iter, Patch, Result, Snapshot};
+
+/// Database wrapper that keeps the recently read values of the underlying database
+/// in memory.
+///
+/// The cache is filled by the snapshots of the latest database state, and the least recently
+/// used values are evicted once the total size of the cached keys and values exceeds
+/// the capacity. Absent values are cached too, so repeated lookups of missing keys do not
+/// reach the underlying database either. Besides the raw bytes, the cache keeps the values
+/// read with [`Snapshot::get_decoded`], such as the branch nodes of `ProofMapIndex`,
+/// in their decoded form, so they are not decoded again on the next reads. The decoded
+/// value is accounted as an additional copy of the raw value against the capacity.
+///
+/// The cache is split into shards by the hashes of the keys, each of which has its own
+/// lock, LRU order and an equal part of the capacity, so concurrent reads of different keys
+/// rarely contend.
+///
+/// Merging a patch removes the changed keys from the cache. The cache is bypassed while
+/// the patch is being merged into the underlying database, and the snapshots created before
+/// the merge bypass the cache afterwards, which preserves their read isolation. Iteration
+/// is not cached and always goes to the underlying database.
+///
+/// The cache is enabled by specifying [`read_cache_size`] in the options of [`open_database`].
+///
+/// # Examples
+///
+/// ```
+/// use exonum::storage::{CachedDB, Database, MapIndex, MemoryDB};
+///
+/// let db = CachedDB::new(Box::new(MemoryDB::new()), 1 << 20);
+/// let mut fork = db.fork();
+/// MapIndex::new("index", &mut fork).put(&1_u64, 2_u64);
+/// db.merge(fork.into_patch()).unwrap();
+///
+/// let snapshot = db.snapshot();
+/// let index: MapIndex<_, u64, u64> = MapIndex::new("index", &snapshot);
+/// // The first read fills the cache, and the second one is served from it.
+/// assert_eq!(index.get(&1), Some(2));
+/// assert_eq!(index.get(&1), Some(2));
+/// ```
+///
+/// [`Snapshot::get_decoded`]: trait.Snapshot.html#method.get_decoded
+/// [`read_cache_size`]: struct.DbOptions.html#structfield.read_cache_size
+/// [`open_database`]: fn.open_database.html
+pub struct CachedDB {
+    inner: Box<dyn Database>,
+    cache: Arc<ShardedCache>,
+    // Serializes the merges, so that the generation is changed by one merge at a time.
+    merge_lock: Mutex<()>,
+}
+
+/// A snapshot reading through the cache while the database state is not changed.
+struct CachedSnapshot {
+    inner: Box<dyn Snapshot>,
+    cache: Arc<ShardedCache>,
+    generation: usize,
+}
+
+/// Number of the cache shards.
+const SHARDS: usize = 16;
+
+/// Generation of the snapshots, which never read through the cache.
+const BYPASS_GENERATION: usize = usize::MAX;
+
+/// Read cache split into shards.
+///
+/// Each merge increases `generation` twice: an odd generation means that a patch is being
+/// merged into the underlying database, and the cache is not used until the merge is over.
+/// The values read from the snapshots of other generations than the current one are neither
+/// taken from the cache nor added to it. The generation is checked under the lock of the shard,
+/// so that no value read before the merge is added to the shard after its invalidation.
+#[derive(Debug)]
+struct ShardedCache {
+    generation: AtomicUsize,
+    shards: Vec<Mutex<ReadCache>>,
+}
+
+struct CacheEntry {
+    value: Option<Vec<u8>>,
+    decoded: Option<DecodedValue>,
+    last_used: u64,
+}
+
+impl CacheEntry {
+    fn size(&self, key: &[u8]) -> usize {
+        let value_size = self.value.as_ref().map_or(0, Vec::len);
+        if self.decoded.is_some() {
+            key.len() + 2 * value_size
+        } else {
+            key.len() + value_size
+        }
+    }
+}
+
+impl fmt::Debug for CacheEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CacheEntry")
+            .field("value", &self.value)
+            .field("is_decoded", &self.decoded.is_some())
+            .field("last_used", &self.last_used)
+            .finish()
+    }
+}
+
+/// LRU cache of the values of the latest database state.
+#[derive(Debug)]
+struct ReadCache {
+    capacity: usize,
+    size: usize,
+    ticks: u64,
+    entries: HashMap<String, HashMap<Vec<u8>, CacheEntry>>,
+    // Keys of the entries ordered by their last use.
+    lru: BTreeMap<u64, (String, Vec<u8>)>,
+}
+
+impl ReadCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            size: 0,
+            ticks: 0,
+            entries: HashMap::new(),
+            lru: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the entry for the key and makes it the most recently used one.
+    fn touch(&mut self, name: &str, key: &[u8]) -> Option<&mut CacheEntry> {
+        let entry = self.entries.get_mut(name)?.get_mut(key)?;
+
+        self.ticks += 1;
+        let lru_key = self.lru.remove(&entry.last_used).unwrap();
+        self.lru.insert(self.ticks, lru_key);
+        entry.last_used = self.ticks;
+        Some(entry)
+    }
+
+    fn get(&mut self, name: &str, key: &[u8]) -> Option<Option<Vec<u8>>> {
+        self.touch(name, key).map(|entry| entry.value.clone())
+    }
+
+    /// Returns the decoded value, decoding the cached raw value if it has not been
+    /// decoded yet.
+    fn get_decoded(
+        &mut self,
+        name: &str,
+        key: &[u8],
+        decode: &dyn Fn(Vec<u8>) -> DecodedValue,
+    ) -> Option<Option<DecodedValue>> {
+        let (decoded, added_size) = {
+            let entry = self.touch(name, key)?;
+            if let Some(ref decoded) = entry.decoded {
+                return Some(Some(Arc::clone(decoded)));
+            }
+            let value = match entry.value {
+                Some(ref value) => value.clone(),
+                None => return Some(None),
+            };
+            let added_size = value.len();
+            let decoded = decode(value);
+            entry.decoded = Some(Arc::clone(&decoded));
+            (decoded, added_size)
+        };
+        self.size += added_size;
+        self.evict();
+        Some(Some(decoded))
+    }
+
+    fn insert(&mut self, name: &str, key: &[u8], value: Option<Vec<u8>>) {
+        self.insert_decoded(name, key, value, None);
+    }
+
+    fn insert_decoded(
+        &mut self,
+        name: &str,
+        key: &[u8],
+        value: Option<Vec<u8>>,
+        decoded: Option<DecodedValue>,
+    ) {
+        self.ticks += 1;
+        let entry = CacheEntry {
+            value,
+            decoded,
+            last_used: self.ticks,
+        };
+        let entry_size = entry.size(key);
+        if entry_size > self.capacity {
+            return;
+        }
+
+        self.remove(name, key);
+        self.size += entry_size;
+        self.lru.insert(self.ticks, (name.to_owned(), key.to_vec()));
+        self.entries
+            .entry(name.to_owned())
+            .or_insert_with(HashMap::new)
+            .insert(key.to_vec(), entry);
+        self.evict();
+    }
+
+    /// Evicts the least recently used entries until the cache fits its capacity.
+    fn evict(&mut self) {
+        while self.size > self.capacity {
+            let oldest = *self.lru.keys().next().unwrap();
+            let (name, key) = self.lru.remove(&oldest).unwrap();
+            self.remove(&name, &key);
+        }
+    }
+
+    fn remove(&mut self, name: &str, key: &[u8]) {
+        let entry = match self.entries.get_mut(name) {
+            Some(table) => table.remove(key),
+            None => return,
+        };
+        if let Some(entry) = entry {
+            self.size -= entry.size(key);
+            self.lru.remove(&entry.last_used);
+        }
+    }
+}
+
+impl ShardedCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            generation: AtomicUsize::new(0),
+            shards: (0..SHARDS)
+                .map(|_| Mutex::new(ReadCache::new(capacity / SHARDS)))
+                .collect(),
+        }
+    }
+
+    fn generation(&self) -> usize {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    fn shard(&self, name: &str, key: &[u8]) -> &Mutex<ReadCache> {
+        let mut hasher = DefaultHasher::new();
+        name.hash(&mut hasher);
+        key.hash(&mut hasher);
+        &self.shards[hasher.finish() as usize % SHARDS]
+    }
+
+    /// Returns the cached value, or `None` if the value is not cached or cannot be read
+    /// from the cache by the snapshot of the given generation.
+    fn get(&self, generation: usize, name: &str, key: &[u8]) -> Option<Option<Vec<u8>>> {
+        let mut shard = self.shard(name, key).lock().unwrap();
+        if generation != self.generation() {
+            return None;
+        }
+        shard.get(name, key)
+    }
+
+    fn insert(&self, generation: usize, name: &str, key: &[u8], value: Option<Vec<u8>>) {
+        let mut shard = self.shard(name, key).lock().unwrap();
+        if generation != self.generation() {
+            return;
+        }
+        shard.insert(name, key, value);
+    }
+
+    /// Returns the cached decoded value like [`get`](#method.get). The value is decoded
+    /// under the lock of the shard if only its raw bytes are cached.
+    fn get_decoded(
+        &self,
+        generation: usize,
+        name: &str,
+        key: &[u8],
+        decode: &dyn Fn(Vec<u8>) -> DecodedValue,
+    ) -> Option<Option<DecodedValue>> {
+        let mut shard = self.shard(name, key).lock().unwrap();
+        if generation != self.generation() {
+            return None;
+        }
+        shard.get_decoded(name, key, decode)
+    }
+
+    fn insert_decoded(
+        &self,
+        generation: usize,
+        name: &str,
+        key: &[u8],
+        value: Option<Vec<u8>>,
+        decoded: Option<DecodedValue>,
+    ) {
+        let mut shard = self.shard(name, key).lock().unwrap();
+        if generation != self.generation() {
+            return;
+        }
+        shard.insert_decoded(name, key, value, decoded);
+    }
+
+    /// Starts the merge of the patch: switches to the odd generation and removes
+    /// the keys changed by the patch.
+    fn start_merge(&self, patch: &Patch) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        for (name, changes) in patch.iter() {
+            for (key, _) in changes.iter() {
+                self.shard(name, key).lock().unwrap().remove(name, key);
+            }
+        }
+    }
+
+    /// Finishes the merge and makes the cache available to the new snapshots.
+    fn finish_merge(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+impl CachedDB {
+    /// Wraps the database with the read cache of the specified capacity in bytes.
+    pub fn new(inner: Box<dyn Database>, capacity: usize) -> Self {
+        Self {
+            inner,
+            cache: Arc::new(ShardedCache::new(capacity)),
+            merge_lock: Mutex::new(()),
+        }
+    }
+
+    fn merge_with<F>(&self, patch: Patch, merge: F) -> Result<()>
+    where
+        F: FnOnce(&dyn Database, Patch) -> Result<()>,
+    {
+        let _guard = self.merge_lock.lock().unwrap();
+        self.cache.start_merge(&patch);
+        let result = merge(&*self.inner, patch);
+        self.cache.finish_merge();
+        result
+    }
+}
+
+impl Database for CachedDB {
+    fn snapshot(&self) -> Box<dyn Snapshot> {
+        // The snapshot reads through the cache only if no merge has been started
+        // or finished while it has been taken.
+        let generation = self.cache.generation();
+        let inner = self.inner.snapshot();
+        let generation = if generation % 2 == 0 && generation == self.cache.generation() {
+            generation
+        } else {
+            BYPASS_GENERATION
+        };
+        Box::new(CachedSnapshot {
+            inner,
+            cache: Arc::clone(&self.cache),
+            generation,
+        })
+    }
+
+    fn merge(&self, patch: Patch) -> Result<()> {
+        self.merge_with(patch, |db, patch| db.merge(patch))
+    }
+
+    fn merge_sync(&self, patch: Patch) -> Result<()> {
+        self.merge_with(patch, |db, patch| db.merge_sync(patch))
+    }
+
+    fn create_checkpoint(&self, path: &Path) -> Result<()> {
+        self.inner.create_checkpoint(path)
+    }
+}
+
+impl fmt::Debug for CachedDB {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (capacity, size) = self
+            .cache
+            .shards
+            .iter()
+            .fold((0, 0), |(capacity, size), shard| {
+                let shard = shard.lock().unwrap();
+                (capacity + shard.capacity, size + shard.size)
+            });
+        f.debug_struct("CachedDB")
+            .field("capacity", &capacity)
+            .field("size", &size)
+            .finish()
+    }
+}
+
+impl Snapshot for CachedSnapshot {
+    fn get(&self, name: &str, key: &[u8]) -> Option<Vec<u8>> {
+        if let Some(value) = self.cache.get(self.generation, name, key) {
+            return value;
+        }
+        let value = self.inner.get(name, key);
+        self.cache.insert(self.generation, name, key, value.clone());
+        value
+    }
+
+    fn contains(&self, name: &str, key: &[u8]) -> bool {
+        // The value is read to populate the cache, since the keys checked for existence
+        // are usually read afterwards.
+        self.get(name, key).is_some()
+    }
+
+    fn get_decoded(
+        &self,
+        name: &str,
+        key: &[u8],
+        decode: &dyn Fn(Vec<u8>) -> DecodedValue,
+    ) -> Option<DecodedValue> {
+        if let Some(decoded) = self.cache.get_decoded(self.generation, name, key, decode) {
+            return decoded;
+        }
+        let value = self.inner.get(name, key);
+        let decoded = value.clone().map(decode);
+        self.cache
+            .insert_decoded(self.generation, name, key, value, decoded.clone());
+        decoded
+    }
+
+    fn iter<'a>(&'a self, name: &str, from: &[u8]) -> Iter<'a> {
+        self.inner.iter(name, from)
+    }
+
+    fn iter_rev<'a>(&'a self, name: &str, before: Option<&[u8]>) -> Iter<'a> {
+        self.inner.iter_rev(name, before)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{any::Any, cell::Cell, sync::Arc};
+
+    use super::{CachedDB, ReadCache, BYPASS_GENERATION};
+    use storage::{Database, DecodedValue, MapIndex, MemoryDB, ProofMapIndex, StorageKey};
+
+    #[test]
+    fn lru_eviction() {
+        let mut cache = ReadCache::new(10);
+        cache.insert("a", &[1], Some(vec![1, 2, 3]));
+        cache.insert("a", &[2], None);
+        cache.insert("b", &[1], Some(vec![4, 5]));
+        assert_eq!(cache.size, 8);
+
+        // Make the first entry the most recently used one.
+        assert_eq!(cache.get("a", &[1]), Some(Some(vec![1, 2, 3])));
+        cache.insert("b", &[2], Some(vec![6, 7]));
+        assert_eq!(cache.get("a", &[2]), None);
+        assert_eq!(cache.get("a", &[1]), Some(Some(vec![1, 2, 3])));
+        assert_eq!(cache.get("b", &[2]), Some(Some(vec![6, 7])));
+        assert_eq!(cache.size, 10);
+        assert_eq!(cache.lru.len(), 3);
+
+        // Too large values are not cached.
+        cache.insert("c", &[1], Some(vec![0; 10]));
+        assert_eq!(cache.get("c", &[1]), None);
+        assert_eq!(cache.size, 10);
+    }
+
+    #[test]
+    fn generations() {
+        let db = CachedDB::new(Box::new(MemoryDB::new()), 1 << 20);
+        let cache = &db.cache;
+        cache.insert(0, "a", &[1], None);
+        cache.insert(0, "b", &[1], None);
+        assert_eq!(cache.get(0, "a", &[1]), Some(None));
+        assert_eq!(cache.get(BYPASS_GENERATION, "a", &[1]), None);
+
+        // The cache is not used while the patch is merged.
+        let mut fork = db.fork();
+        fork.put("b", vec![1], vec![1]);
+        let patch = fork.into_patch();
+        cache.start_merge(&patch);
+        assert_eq!(cache.generation(), 1);
+        assert_eq!(cache.get(0, "a", &[1]), None);
+        cache.finish_merge();
+        // The keys changed by the patch are removed.
+        assert_eq!(cache.get(2, "a", &[1]), Some(None));
+        assert_eq!(cache.get(2, "b", &[1]), None);
+        // Values from previous generations are ignored.
+        assert_eq!(cache.get(0, "a", &[1]), None);
+        cache.insert(0, "a", &[3], None);
+        assert_eq!(cache.get(2, "a", &[3]), None);
+    }
+
+    #[test]
+    fn contains_fills_cache() {
+        let db = CachedDB::new(Box::new(MemoryDB::new()), 1 << 20);
+        let mut fork = db.fork();
+        MapIndex::new("index", &mut fork).put(&1_u64, 1_u64);
+        db.merge(fork.into_patch()).unwrap();
+
+        let snapshot = db.snapshot();
+        let index: MapIndex<_, u64, u64> = MapIndex::new("index", &snapshot);
+        assert!(index.contains(&1));
+        assert!(!index.contains(&2));
+
+        let generation = db.cache.generation();
+        for &(key, is_present) in &[(1_u64, true), (2, false)] {
+            let mut buffer = vec![0; key.size()];
+            key.write(&mut buffer);
+            let cached = db.cache.get(generation, "index", &buffer);
+            assert_eq!(cached.map(|value| value.is_some()), Some(is_present));
+        }
+    }
+
+    #[test]
+    fn decoded_values() {
+        let db = CachedDB::new(Box::new(MemoryDB::new()), 1 << 20);
+        let mut fork = db.fork();
+        fork.put("a", vec![1], vec![1, 2]);
+        fork.put("a", vec![2], vec![1, 2, 3]);
+        db.merge(fork.into_patch()).unwrap();
+
+        let decode_count = Cell::new(0);
+        let decode = |value: Vec<u8>| -> DecodedValue {
+            decode_count.set(decode_count.get() + 1);
+            Arc::new(value.len())
+        };
+        let snapshot = db.snapshot();
+        // The raw value is cached first, then decoded once on the first decoded read.
+        assert_eq!(snapshot.get("a", &[2]), Some(vec![1, 2, 3]));
+        for &(key, len) in &[(1_u8, 2_usize), (2, 3)] {
+            for _ in 0..3 {
+                let decoded = snapshot.get_decoded("a", &[key], &decode).unwrap();
+                let decoded: &dyn Any = &*decoded;
+                assert_eq!(decoded.downcast_ref::<usize>(), Some(&len));
+            }
+        }
+        assert!(snapshot.get_decoded("a", &[3], &decode).is_none());
+        assert_eq!(decode_count.get(), 2);
+        // Decoded values are accounted as the copies of the raw ones.
+        let size: usize = db.cache.shards.iter().map(|s| s.lock().unwrap().size).sum();
+        assert_eq!(size, (1 + 2 * 2) + (1 + 2 * 3) + 1);
+
+        // Decoded values are invalidated along with the raw ones.
+        let mut fork = db.fork();
+        fork.put("a", vec![1], vec![1]);
+        db.merge(fork.into_patch()).unwrap();
+        let snapshot = db.snapshot();
+        let decoded = snapshot.get_decoded("a", &[1], &decode).unwrap();
+        let decoded: &dyn Any = &*decoded;
+        assert_eq!(decoded.downcast_ref::<usize>(), Some(&1));
+        assert_eq!(decode_count.get(), 3);
+    }
+
+    #[test]
+    fn merge_invalidates_cache() {
+        let db = CachedDB::new(Box::new(MemoryDB::new()), 1 << 20);
+        let mut fork = db.fork();
+        {
+            let mut index = MapIndex::new("index", &mut fork);
+            index.put(&1_u64, 1_u64);
+            index.put(&2_u64, 2_u64);
+        }
+        db.merge(fork.into_patch()).unwrap();
+
+        let old_snapshot = db.snapshot();
+        {
+            let index: MapIndex<_, u64, u64> = MapIndex::new("index", &old_snapshot);
+            assert_eq!(index.get(&1), Some(1));
+            assert_eq!(index.get(&3), None);
+            assert!(index.contains(&2));
+        }
+
+        let mut fork = db.fork();
+        {
+            let mut index = MapIndex::new("index", &mut fork);
+            index.put(&1_u64, 10_u64);
+            index.put(&3_u64, 30_u64);
+            index.remove(&2_u64);
+        }
+        db.merge(fork.into_patch()).unwrap();
+
+        let snapshot = db.snapshot();
+        let index: MapIndex<_, u64, u64> = MapIndex::new("index", &snapshot);
+        assert_eq!(index.get(&1), Some(10));
+        assert_eq!(index.get(&3), Some(30));
+        assert!(!index.contains(&2));
+
+        // The snapshot taken before the merge is not affected by the cache.
+        let old_index: MapIndex<_, u64, u64> = MapIndex::new("index", &old_snapshot);
+        assert_eq!(old_index.get(&1), Some(1));
+        assert_eq!(old_index.get(&3), None);
+        assert!(old_index.contains(&2));
+
+        // Neither the new snapshot is affected by the reads of the old one.
+        assert_eq!(index.get(&1), Some(10));
+        assert_eq!(index.get(&3), Some(30));
+    }
+
+    #[test]
+    fn proof_map_with_cache() {
+        let db = CachedDB::new(Box::new(MemoryDB::new()), 1 << 12);
+        let reference = MemoryDB::new();
+
+        for i in 0_u8..20 {
+            for db in &[&db as &dyn Database, &reference] {
+                let mut fork = db.fork();
+                {
+                    let mut index = ProofMapIndex::new("index", &mut fork);
+                    for j in 0..10 {
+                        index.put(&[i.wrapping_mul(j); 32], vec![i; 10]);
+                    }
+                    index.remove(&[i.wrapping_add(3); 32]);
+                }
+                db.merge(fork.into_patch()).unwrap();
+            }
+
+            let snapshot = db.snapshot();
+            let index: ProofMapIndex<_, [u8; 32], Vec<u8>> = ProofMapIndex::new("index", &snapshot);
+            let reference_snapshot = reference.snapshot();
+            let reference_index: ProofMapIndex<_, [u8; 32], Vec<u8>> =
+                ProofMapIndex::new("index", &reference_snapshot);
+            assert_eq!(index.merkle_root(), reference_index.merkle_root());
+            for j in 0..=255 {
+                assert_eq!(index.get(&[j; 32]), reference_index.get(&[j; 32]));
+            }
+        }
+    }
+}
@@ -0,0 +1,212 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Catalogue of the indexes registered in the storage.
+
+use super::{
+    indexes_metadata::{self, IndexType},
+    proof_map_index::{LEAF_KEY_PREFIX, PROOF_PATH_SIZE},
+    Snapshot,
+};
+
+/// Size of the keys of the list elements and the Merkle tree nodes of `ProofListIndex`
+/// (without the index identifier for the members of index families).
+const LIST_KEY_SIZE: usize = 8;
+
+/// Information about an index or an index family registered in the storage.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexInfo {
+    /// Name of the index or the index family.
+    pub name: String,
+    /// Type of the index.
+    pub index_type: IndexType,
+    /// Whether the index is an index family.
+    pub is_family: bool,
+    /// Numbers of entries and records of the index, if they have been requested.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub counts: Option<IndexCounts>,
+}
+
+/// Numbers of entries and records of an index or an index family, and its size.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct IndexCounts {
+    /// Number of entries in the index; for index families, the total number of entries
+    /// in all the members of the family.
+    pub entries: u64,
+    /// Number of the key-value pairs stored in the database, including the service data
+    /// of the index, such as the nodes of Merkle trees and the lengths of lists.
+    pub records: u64,
+    /// Approximate size of the index in bytes, which is the total size of the stored
+    /// keys and values without the overhead of the database.
+    pub size: u64,
+}
+
+/// Returns the information about all the indexes and index families registered
+/// in the storage, sorted by name. The entries and records of the indexes are counted
+/// only if `with_counts` is `true`.
+///
+/// Counting reads all the records of the indexes, so the call may take a while
+/// for large databases. Without the counts, only the indexes metadata is read.
+///
+/// # Examples
+///
+/// ```
+/// use exonum::storage::{index_catalogue, Database, IndexType, MemoryDB, ProofMapIndex};
+/// use exonum::crypto::Hash;
+///
+/// let db = MemoryDB::new();
+/// let mut fork = db.fork();
+/// ProofMapIndex::new("index", &mut fork).put(&Hash::zero(), 1_u64);
+/// db.merge(fork.into_patch()).unwrap();
+///
+/// let catalogue = index_catalogue(&*db.snapshot(), true);
+/// assert_eq!(catalogue[0].name, "index");
+/// assert_eq!(catalogue[0].index_type, IndexType::ProofMap);
+/// assert_eq!(catalogue[0].counts.unwrap().entries, 1);
+/// ```
+pub fn index_catalogue(view: &dyn Snapshot, with_counts: bool) -> Vec<IndexInfo> {
+    indexes_metadata::index_metadata(view)
+        .into_iter()
+        .map(|(name, index_type, is_family)| {
+            let counts = if with_counts {
+                Some(count_records(view, &name, index_type))
+            } else {
+                None
+            };
+            IndexInfo {
+                name,
+                index_type,
+                is_family,
+                counts,
+            }
+        }).collect()
+}
+
+fn count_records(view: &dyn Snapshot, name: &str, index_type: IndexType) -> IndexCounts {
+    let mut counts = IndexCounts::default();
+    // The lists store the length with the key equal to the index identifier,
+    // which precedes the keys of the list elements.
+    let mut list_id: Option<Vec<u8>> = None;
+    let mut iter = view.iter(name, &[]);
+    while let Some((key, value)) = iter.next() {
+        counts.records += 1;
+        counts.size += (key.len() + value.len()) as u64;
+        let is_entry = match index_type {
+            IndexType::ProofMap => {
+                key.len() >= PROOF_PATH_SIZE && key[key.len() - PROOF_PATH_SIZE] == LEAF_KEY_PREFIX
+            }
+            IndexType::List | IndexType::SparseList | IndexType::ProofList | IndexType::Mmr => {
+                let id_len = match list_id {
+                    Some(ref id)
+                        if key.len() == id.len() + LIST_KEY_SIZE && key.starts_with(id) =>
+                    {
+                        Some(id.len())
+                    }
+                    _ => None,
+                };
+                match id_len {
+                    // The leaves of the Merkle trees have zero height, which is stored
+                    // in the most significant byte of the key.
+                    Some(id_len) => match index_type {
+                        IndexType::ProofList | IndexType::Mmr => key[id_len] == 0,
+                        _ => true,
+                    },
+                    None => {
+                        list_id = Some(key.to_vec());
+                        false
+                    }
+                }
+            }
+            _ => true,
+        };
+        if is_entry {
+            counts.entries += 1;
+        }
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{index_catalogue, IndexType};
+    use crypto::Hash;
+    use storage::{
+        Database, Entry, ListIndex, MapIndex, MemoryDB, ProofListIndex, ProofMapIndex,
+        SparseListIndex,
+    };
+
+    #[test]
+    fn catalogue_counts_entries() {
+        let db = MemoryDB::new();
+        let mut fork = db.fork();
+        {
+            Entry::new("entry", &mut fork).set(1_u64);
+            let mut list = ListIndex::new("list", &mut fork);
+            list.extend(vec![1_u64, 2, 3]);
+            let mut sparse_list = SparseListIndex::new("sparse_list", &mut fork);
+            sparse_list.extend(vec![1_u64, 2, 3]);
+            sparse_list.remove(1);
+            for id in 0_u8..3 {
+                let mut proof_list = ProofListIndex::new_in_family("proof_lists", &id, &mut fork);
+                proof_list.extend(vec![1_u64, 2, 3, 4, 5]);
+            }
+            let mut proof_map = ProofMapIndex::new("proof_map", &mut fork);
+            for i in 0_u8..10 {
+                proof_map.put(&Hash::new([i; 32]), u64::from(i));
+            }
+            let mut map = MapIndex::new_in_family("maps", &1_u8, &mut fork);
+            map.put(&1_u8, 1_u64);
+            let mut map = MapIndex::new_in_family("maps", &2_u8, &mut fork);
+            map.put(&1_u8, 1_u64);
+        }
+        db.merge(fork.into_patch()).unwrap();
+
+        let catalogue = index_catalogue(&*db.snapshot(), false);
+        assert!(catalogue.iter().all(|info| info.counts.is_none()));
+        let catalogue = index_catalogue(&*db.snapshot(), true);
+        let entries: Vec<_> = catalogue
+            .iter()
+            .map(|info| {
+                (
+                    info.name.as_str(),
+                    info.index_type,
+                    info.is_family,
+                    info.counts.unwrap().entries,
+                )
+            }).collect();
+        assert_eq!(
+            entries,
+            vec![
+                ("entry", IndexType::Entry, false, 1),
+                ("list", IndexType::List, false, 3),
+                ("maps", IndexType::Map, true, 2),
+                ("proof_lists", IndexType::ProofList, true, 15),
+                ("proof_map", IndexType::ProofMap, false, 10),
+                ("sparse_list", IndexType::SparseList, false, 2),
+            ]
+        );
+
+        let list = catalogue.iter().find(|info| info.name == "list").unwrap();
+        let list = list.counts.unwrap();
+        // The length of the list and three elements.
+        assert_eq!(list.records, 4);
+        assert_eq!(list.size, 8 + 3 * (8 + 8));
+        let proof_map = catalogue
+            .iter()
+            .find(|info| info.name == "proof_map")
+            .unwrap();
+        let proof_map = proof_map.counts.unwrap();
+        assert!(proof_map.records > proof_map.entries);
+    }
+}
@@ -13,17 +13,25 @@
 // limitations under the License.
 
 use std::{
+    any::Any,
     cmp::Ordering::{Equal, Greater, Less},
     collections::{
-        btree_map::{BTreeMap, IntoIter as BtmIntoIter, Iter as BtmIter, Range},
+        btree_map::{BTreeMap, IntoIter as BtmIntoIter, Iter as BtmIter},
         hash_map::{Entry as HmEntry, IntoIter as HmIntoIter, Iter as HmIter},
-        Bound::{Included, Unbounded},
+        Bound::{Excluded, Included, Unbounded},
         HashMap,
     },
     iter::{Iterator as StdIterator, Peekable},
+    path::Path,
+    sync::Arc,
 };
 
-use super::Result;
+use super::{Error, Result};
+
+/// A value decoded by an index, which can be kept by the databases caching the read values.
+///
+/// See [`Snapshot::get_decoded`](trait.Snapshot.html#method.get_decoded) for details.
+pub type DecodedValue = Arc<dyn Any + Send + Sync>;
 
 /// Map containing changes with a corresponding key.
 #[derive(Debug, Clone)]
@@ -33,7 +41,7 @@ pub struct Changes {
 
 impl Changes {
     /// Creates a new empty `Changes` instance.
-    fn new() -> Self {
+    pub(crate) fn new() -> Self {
         Self {
             data: BTreeMap::new(),
         }
@@ -43,6 +51,11 @@ impl Changes {
     pub fn iter(&self) -> BtmIter<Vec<u8>, Change> {
         self.data.iter()
     }
+
+    /// Inserts the change of the given key, replacing the previous one.
+    pub(crate) fn insert(&mut self, key: Vec<u8>, change: Change) {
+        self.data.insert(key, change);
+    }
 }
 
 /// Iterator over the `Changes` data.
@@ -82,7 +95,7 @@ pub struct Patch {
 
 impl Patch {
     /// Creates a new empty `Patch` instance.
-    fn new() -> Self {
+    pub(crate) fn new() -> Self {
         Self {
             changes: HashMap::new(),
         }
@@ -93,6 +106,51 @@ impl Patch {
         self.changes.get(name)
     }
 
+    /// Returns the change of the given key, if any.
+    fn change(&self, name: &str, key: &[u8]) -> Option<&Change> {
+        self.changes(name).and_then(|changes| changes.data.get(key))
+    }
+
+    /// Returns an iterator over the entries of the `snapshot` with the changes of the patch
+    /// applied in ascending order starting from the specified key.
+    fn iter_over<'a>(&'a self, snapshot: &'a dyn Snapshot, name: &str, from: &[u8]) -> Iter<'a> {
+        let range = (Included(from), Unbounded);
+        let changes = match self.changes(name) {
+            Some(changes) => Some(changes.data.range::<[u8], _>(range).peekable()),
+            None => None,
+        };
+
+        Box::new(ForkIter {
+            snapshot: snapshot.iter(name, from),
+            changes,
+            reverse: false,
+        })
+    }
+
+    /// Returns an iterator over the entries of the `snapshot` with the changes of the patch
+    /// applied in descending order, which starts before the specified key if any.
+    fn iter_rev_over<'a>(
+        &'a self,
+        snapshot: &'a dyn Snapshot,
+        name: &str,
+        before: Option<&[u8]>,
+    ) -> Iter<'a> {
+        let range = match before {
+            Some(before) => (Unbounded, Excluded(before)),
+            None => (Unbounded, Unbounded),
+        };
+        let changes = match self.changes(name) {
+            Some(changes) => Some(changes.data.range::<[u8], _>(range).rev().peekable()),
+            None => None,
+        };
+
+        Box::new(ForkIter {
+            snapshot: snapshot.iter_rev(name, before),
+            changes,
+            reverse: true,
+        })
+    }
+
     /// Returns a mutable reference to the changes corresponding to the `name`.
     fn changes_mut(&mut self, name: &str) -> Option<&mut Changes> {
         self.changes.get_mut(name)
@@ -104,7 +162,7 @@ impl Patch {
     }
 
     /// Inserts changes with the given name.
-    fn insert_changes(&mut self, name: String, changes: Changes) {
+    pub(crate) fn insert_changes(&mut self, name: String, changes: Changes) {
         self.changes.insert(name, changes);
     }
 
@@ -178,7 +236,9 @@ pub enum Change {
 ///
 /// `Fork` also supports checkpoints ([`checkpoint`], [`commit`] and
 /// [`rollback`] methods), which allows rolling back some of the latest changes (e.g., after
-/// a runtime error).
+/// a runtime error). Within and around a checkpoint, a stack of named savepoints
+/// ([`savepoint`], [`release_savepoint`] and [`rollback_to_savepoint`] methods) allows rolling
+/// back nested groups of changes, e.g., a failed sub-operation of a transaction.
 ///
 /// `Fork` implements the [`Snapshot`] trait and provides methods for both reading and
 /// writing data. Thus, `&mut Fork` is used as a storage view for creating
@@ -197,18 +257,39 @@ pub enum Change {
 /// [`checkpoint`]: #method.checkpoint
 /// [`commit`]: #method.commit
 /// [`rollback`]: #method.rollback
+/// [`savepoint`]: #method.savepoint
+/// [`release_savepoint`]: #method.release_savepoint
+/// [`rollback_to_savepoint`]: #method.rollback_to_savepoint
 
 // FIXME: make &mut Fork "unwind safe". (ECR-176)
 pub struct Fork {
     snapshot: Box<dyn Snapshot>,
     patch: Patch,
     changelog: Vec<(String, Vec<u8>, Option<Change>)>,
-    logged: bool,
+    savepoints: Vec<Savepoint>,
 }
 
-struct ForkIter<'a> {
+/// Position in the changelog of a fork, to which the changes can be rolled back.
+/// The checkpoint has no name.
+struct Savepoint {
+    name: Option<String>,
+    changelog_len: usize,
+}
+
+/// An iterator over the entries of a fork, which merges the entries of the snapshot
+/// with the changes in the patch. The changes must be ordered in the same direction
+/// as the snapshot entries.
+struct ForkIter<'a, T: StdIterator> {
     snapshot: Iter<'a>,
-    changes: Option<Peekable<Range<'a, Vec<u8>, Change>>>,
+    changes: Option<Peekable<T>>,
+    reverse: bool,
+}
+
+/// An iterator over the entries collected in memory, which is used by the default
+/// implementation of `Snapshot::iter_rev`.
+struct CollectedIter {
+    entries: Vec<(Vec<u8>, Vec<u8>)>,
+    index: usize,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -258,7 +339,7 @@ pub trait Database: Send + Sync + 'static {
             snapshot: self.snapshot(),
             patch: Patch::new(),
             changelog: Vec::new(),
-            logged: false,
+            savepoints: Vec::new(),
         }
     }
 
@@ -285,6 +366,24 @@ pub trait Database: Send + Sync + 'static {
     /// will be returned. In case of an error, the method guarantees no changes are applied to
     /// the database.
     fn merge_sync(&self, patch: Patch) -> Result<()>;
+
+    /// Creates a consistent on-disk copy of the database at the specified path, which
+    /// must not exist. The copy can be opened as a database of the same backend.
+    ///
+    /// The database remains available for reads and merges while the checkpoint is
+    /// being created.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the checkpoint cannot be created. The default implementation
+    /// always fails, since checkpoints are not supported by in-memory databases.
+    fn create_checkpoint(&self, path: &Path) -> Result<()> {
+        Err(Error::new(format!(
+            "Unable to create a checkpoint at {}: checkpoints are not supported \
+             by the database",
+            path.display()
+        )))
+    }
 }
 
 /// A read-only snapshot of a storage backend.
@@ -307,9 +406,46 @@ pub trait Snapshot: 'static {
         self.get(name, key).is_some()
     }
 
+    /// Returns a value corresponding to the specified key decoded with `decode`,
+    /// or `None` if it does not exist.
+    ///
+    /// The databases caching the read values may keep the decoded values as well, so that
+    /// the values read often, such as the branch nodes of Merkelized indexes, are not decoded
+    /// on each read. The value should always be decoded to the same type for the given key.
+    ///
+    /// Default implementation decodes the value returned by [`get`](#tymethod.get).
+    fn get_decoded(
+        &self,
+        name: &str,
+        key: &[u8],
+        decode: &dyn Fn(Vec<u8>) -> DecodedValue,
+    ) -> Option<DecodedValue> {
+        self.get(name, key).map(decode)
+    }
+
     /// Returns an iterator over the entries of the snapshot in ascending order starting from
     /// the specified key. The iterator element type is `(&[u8], &[u8])`.
     fn iter<'a>(&'a self, name: &str, from: &[u8]) -> Iter<'a>;
+
+    /// Returns an iterator over the entries of the snapshot in descending order. If `before`
+    /// is specified, the iteration starts from the greatest key, which is less than `before`;
+    /// otherwise, it starts from the greatest key. The iterator element type
+    /// is `(&[u8], &[u8])`.
+    ///
+    /// Default implementation reads the entries in ascending order using [`iter`](#tymethod.iter)
+    /// and keeps them in memory, so the databases should provide a more efficient one.
+    fn iter_rev<'a>(&'a self, name: &str, before: Option<&[u8]>) -> Iter<'a> {
+        let mut entries = Vec::new();
+        let mut iter = self.iter(name, &[]);
+        while let Some((key, value)) = iter.next() {
+            if before.map_or(false, |before| key >= before) {
+                break;
+            }
+            entries.push((key.to_vec(), value.to_vec()));
+        }
+        entries.reverse();
+        Box::new(CollectedIter { entries, index: 0 })
+    }
 }
 
 /// A trait that defines a streaming iterator over storage view entries. Unlike
@@ -348,17 +484,83 @@ impl Snapshot for Fork {
         self.snapshot.contains(name, key)
     }
 
+    fn get_decoded(
+        &self,
+        name: &str,
+        key: &[u8],
+        decode: &dyn Fn(Vec<u8>) -> DecodedValue,
+    ) -> Option<DecodedValue> {
+        if let Some(changes) = self.patch.changes(name) {
+            if let Some(change) = changes.data.get(key) {
+                match *change {
+                    Change::Put(ref v) => return Some(decode(v.clone())),
+                    Change::Delete => return None,
+                }
+            }
+        }
+        self.snapshot.get_decoded(name, key, decode)
+    }
+
     fn iter<'a>(&'a self, name: &str, from: &[u8]) -> Iter<'a> {
-        let range = (Included(from), Unbounded);
-        let changes = match self.patch.changes(name) {
-            Some(changes) => Some(changes.data.range::<[u8], _>(range).peekable()),
-            None => None,
-        };
+        self.patch.iter_over(&*self.snapshot, name, from)
+    }
 
-        Box::new(ForkIter {
-            snapshot: self.snapshot.iter(name, from),
-            changes,
-        })
+    fn iter_rev<'a>(&'a self, name: &str, before: Option<&[u8]>) -> Iter<'a> {
+        self.patch.iter_rev_over(&*self.snapshot, name, before)
+    }
+}
+
+/// A read-only snapshot with the changes of a patch applied on top of it. Unlike `Fork`,
+/// the patch is shared, so the snapshots with the same changes are created without
+/// copying them.
+pub(crate) struct PatchedSnapshot {
+    snapshot: Box<dyn Snapshot>,
+    patch: Arc<Patch>,
+}
+
+impl PatchedSnapshot {
+    /// Applies the changes of the `patch` on top of the `snapshot`.
+    pub(crate) fn new(snapshot: Box<dyn Snapshot>, patch: Arc<Patch>) -> Self {
+        Self { snapshot, patch }
+    }
+}
+
+impl Snapshot for PatchedSnapshot {
+    fn get(&self, name: &str, key: &[u8]) -> Option<Vec<u8>> {
+        match self.patch.change(name, key) {
+            Some(&Change::Put(ref v)) => Some(v.clone()),
+            Some(&Change::Delete) => None,
+            None => self.snapshot.get(name, key),
+        }
+    }
+
+    fn contains(&self, name: &str, key: &[u8]) -> bool {
+        match self.patch.change(name, key) {
+            Some(&Change::Put(..)) => true,
+            Some(&Change::Delete) => false,
+            None => self.snapshot.contains(name, key),
+        }
+    }
+
+    fn get_decoded(
+        &self,
+        name: &str,
+        key: &[u8],
+        decode: &dyn Fn(Vec<u8>) -> DecodedValue,
+    ) -> Option<DecodedValue> {
+        match self.patch.change(name, key) {
+            Some(&Change::Put(ref v)) => Some(decode(v.clone())),
+            Some(&Change::Delete) => None,
+            None => self.snapshot.get_decoded(name, key, decode),
+        }
+    }
+
+    fn iter<'a>(&'a self, name: &str, from: &[u8]) -> Iter<'a> {
+        self.patch.iter_over(&*self.snapshot, name, from)
+    }
+
+    fn iter_rev<'a>(&'a self, name: &str, before: Option<&[u8]>) -> Iter<'a> {
+        self.patch.iter_rev_over(&*self.snapshot, name, before)
     }
 }
 
@@ -372,37 +574,129 @@ impl Fork {
     ///
     /// Panics if another checkpoint was created before and has not been committed or rolled back.
     pub fn checkpoint(&mut self) {
-        if self.logged {
+        if self.checkpoint_position().is_some() {
             panic!("call checkpoint before rollback or commit");
         }
-        self.logged = true;
+        self.push_savepoint(None);
     }
 
-    /// Finalizes all changes after the latest checkpoint.
+    /// Finalizes all changes after the latest checkpoint. The savepoints created after
+    /// the checkpoint are released.
     ///
     /// # Panics
     ///
     /// Panics if there is no active checkpoint, or the latest checkpoint
     /// is already committed or rolled back.
     pub fn commit(&mut self) {
-        if !self.logged {
-            panic!("call commit before checkpoint");
-        }
-        self.changelog.clear();
-        self.logged = false;
+        let position = self
+            .checkpoint_position()
+            .expect("call commit before checkpoint");
+        self.release(position);
     }
 
-    /// Rolls back all changes after the latest checkpoint.
+    /// Rolls back all changes after the latest checkpoint. The savepoints created after
+    /// the checkpoint are removed.
     ///
     /// # Panics
     ///
     /// Panics if there is no active checkpoint, or the latest checkpoint
     /// is already committed or rolled back.
     pub fn rollback(&mut self) {
-        if !self.logged {
-            panic!("call rollback before checkpoint");
+        let position = self
+            .checkpoint_position()
+            .expect("call rollback before checkpoint");
+        self.undo(position);
+        self.savepoints.truncate(position);
+        self.release(position);
+    }
+
+    /// Creates a new savepoint with the given name. Savepoints may be nested and
+    /// may have the same names; the operations with a name refer to the latest savepoint
+    /// with this name.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use exonum::storage::{Database, MemoryDB, Snapshot};
+    ///
+    /// let db = MemoryDB::new();
+    /// let mut fork = db.fork();
+    /// fork.put("index", vec![1], vec![1]);
+    ///
+    /// fork.savepoint("outer");
+    /// fork.put("index", vec![2], vec![2]);
+    /// fork.savepoint("inner");
+    /// fork.put("index", vec![1], vec![10]);
+    /// fork.rollback_to_savepoint("inner");
+    /// fork.release_savepoint("outer");
+    ///
+    /// assert_eq!(fork.get("index", &[1]), Some(vec![1]));
+    /// assert_eq!(fork.get("index", &[2]), Some(vec![2]));
+    /// ```
+    pub fn savepoint(&mut self, name: &str) {
+        self.push_savepoint(Some(name.to_owned()));
+    }
+
+    /// Removes the latest savepoint with the given name and all the savepoints created after
+    /// it, keeping the changes made after them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no savepoint with the given name, or if the checkpoint
+    /// has been created after the savepoint and has not been committed or rolled back.
+    pub fn release_savepoint(&mut self, name: &str) {
+        let position = self.savepoint_position(name);
+        self.release(position);
+    }
+
+    /// Rolls back all changes after the latest savepoint with the given name. The savepoint
+    /// is kept, so that the changes can be rolled back to it again; the savepoints created
+    /// after it are removed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no savepoint with the given name, or if the checkpoint
+    /// has been created after the savepoint and has not been committed or rolled back.
+    pub fn rollback_to_savepoint(&mut self, name: &str) {
+        let position = self.savepoint_position(name);
+        self.undo(position);
+        self.savepoints.truncate(position + 1);
+    }
+
+    fn is_logged(&self) -> bool {
+        !self.savepoints.is_empty()
+    }
+
+    fn push_savepoint(&mut self, name: Option<String>) {
+        self.savepoints.push(Savepoint {
+            name,
+            changelog_len: self.changelog.len(),
+        });
+    }
+
+    fn checkpoint_position(&self) -> Option<usize> {
+        self.savepoints.iter().rposition(|s| s.name.is_none())
+    }
+
+    fn savepoint_position(&self, name: &str) -> usize {
+        let position = self
+            .savepoints
+            .iter()
+            .rposition(|s| s.name.as_ref().map_or(false, |n| n == name))
+            .unwrap_or_else(|| panic!("savepoint `{}` does not exist", name));
+        if self.checkpoint_position().map_or(false, |c| c > position) {
+            panic!(
+                "call commit or rollback before savepoint `{}` is finished",
+                name
+            );
         }
-        for (name, k, c) in self.changelog.drain(..).rev() {
+        position
+    }
+
+    /// Reverts the changes made after the savepoint at the given position in the stack.
+    fn undo(&mut self, position: usize) {
+        let changelog_len = self.savepoints[position].changelog_len;
+        for (name, k, c) in self.changelog.drain(changelog_len..).rev() {
             if let Some(changes) = self.patch.changes_mut(&name) {
                 match c {
                     Some(change) => changes.data.insert(k, change),
@@ -410,7 +704,15 @@ impl Fork {
                 };
             }
         }
-        self.logged = false;
+    }
+
+    /// Removes the savepoints starting from the given position in the stack. The changelog
+    /// is kept while there are outer savepoints, since the changes may be rolled back to them.
+    fn release(&mut self, position: usize) {
+        self.savepoints.truncate(position);
+        if self.savepoints.is_empty() {
+            self.changelog.clear();
+        }
     }
 
     /// Inserts a key-value pair into the fork.
@@ -419,7 +721,7 @@ impl Fork {
             .patch
             .changes_entry(name.to_string())
             .or_insert_with(Changes::new);
-        if self.logged {
+        if self.is_logged() {
             self.changelog.push((
                 name.to_string(),
                 key.clone(),
@@ -436,7 +738,7 @@ impl Fork {
             .patch
             .changes_entry(name.to_string())
             .or_insert_with(Changes::new);
-        if self.logged {
+        if self.is_logged() {
             self.changelog.push((
                 name.to_string(),
                 key.clone(),
@@ -474,7 +776,7 @@ impl Fork {
             .iter(name, prefix.map_or(&[], |k| k.as_slice()));
         while let Some((k, ..)) = iter.next() {
             let change = changes.data.insert(k.to_vec(), Change::Delete);
-            if self.logged {
+            if self.is_logged() {
                 self.changelog.push((name.to_string(), k.to_vec(), change));
             }
         }
@@ -497,10 +799,10 @@ impl Fork {
     ///
     /// # Panics
     ///
-    /// Panics if a checkpoint has been created before and has not been committed
-    /// or rolled back yet.
+    /// Panics if a checkpoint or a savepoint has been created before and has not been
+    /// finished yet.
     pub fn merge(&mut self, patch: Patch) {
-        if self.logged {
+        if self.is_logged() {
             panic!("call merge before commit or rollback");
         }
 
@@ -534,23 +836,34 @@ impl ::std::fmt::Debug for Fork {
     }
 }
 
-impl<'a> ForkIter<'a> {
+impl<'a, T> ForkIter<'a, T>
+where
+    T: StdIterator<Item = (&'a Vec<u8>, &'a Change)>,
+{
     fn step(&mut self) -> NextIterValue {
         if let Some(ref mut changes) = self.changes {
             match changes.peek() {
                 Some(&(k, change)) => match self.snapshot.peek() {
-                    Some((key, ..)) => match *change {
-                        Change::Put(..) => match k[..].cmp(key) {
-                            Equal => NextIterValue::Replaced,
-                            Less => NextIterValue::Inserted,
-                            Greater => NextIterValue::Stored,
-                        },
-                        Change::Delete => match k[..].cmp(key) {
-                            Equal => NextIterValue::Deleted,
-                            Less => NextIterValue::MissDeleted,
-                            Greater => NextIterValue::Stored,
-                        },
-                    },
+                    Some((key, ..)) => {
+                        // In the reverse order, the greater key goes first.
+                        let ordering = if self.reverse {
+                            k[..].cmp(key).reverse()
+                        } else {
+                            k[..].cmp(key)
+                        };
+                        match *change {
+                            Change::Put(..) => match ordering {
+                                Equal => NextIterValue::Replaced,
+                                Less => NextIterValue::Inserted,
+                                Greater => NextIterValue::Stored,
+                            },
+                            Change::Delete => match ordering {
+                                Equal => NextIterValue::Deleted,
+                                Less => NextIterValue::MissDeleted,
+                                Greater => NextIterValue::Stored,
+                            },
+                        }
+                    }
                     None => match *change {
                         Change::Put(..) => NextIterValue::Inserted,
                         Change::Delete => NextIterValue::MissDeleted,
@@ -570,7 +883,10 @@ impl<'a> ForkIter<'a> {
     }
 }
 
-impl<'a> Iterator for ForkIter<'a> {
+impl<'a, T> Iterator for ForkIter<'a, T>
+where
+    T: StdIterator<Item = (&'a Vec<u8>, &'a Change)>,
+{
     fn next(&mut self) -> Option<(&[u8], &[u8])> {
         loop {
             match self.step() {
@@ -638,6 +954,30 @@ impl<'a> Iterator for ForkIter<'a> {
     }
 }
 
+impl CollectedIter {
+    fn peek_at(&self, index: usize) -> Option<(&[u8], &[u8])> {
+        self.entries
+            .get(index)
+            .map(|&(ref key, ref value)| (key.as_slice(), value.as_slice()))
+    }
+}
+
+impl Iterator for CollectedIter {
+    fn next(&mut self) -> Option<(&[u8], &[u8])> {
+        if self.index < self.entries.len() {
+            self.index += 1;
+            self.peek_at(self.index - 1)
+        } else {
+            None
+        }
+    }
+
+    fn peek(&mut self) -> Option<(&[u8], &[u8])> {
+        let index = self.index;
+        self.peek_at(index)
+    }
+}
+
 impl<T: Database> From<T> for Box<dyn Database> {
     fn from(db: T) -> Self {
         Box::new(db) as Self
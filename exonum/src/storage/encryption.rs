@@ -0,0 +1,628 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! An implementation of the encryption at rest for databases.
+
+// spell-checker:ignore chacha, xchacha, ciphertext
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use rand::{thread_rng, RngCore};
+use sodiumoxide::crypto::{aead::xchacha20poly1305_ietf as aead, auth::hmacsha256};
+
+use std::{fmt, mem, path::Path, sync::Arc};
+
+use super::{
+    db::Change, index_names, Changes, Database, EncryptionKey, Error, Iter, Iterator, Patch,
+    Result, Snapshot, INDEXES_METADATA_TABLE_NAME,
+};
+
+// Contexts of the keys derived from the encryption key.
+const VALUES_KEY_CONTEXT: &[u8] = b"exonum.storage.values";
+const KEYS_KEY_CONTEXT: &[u8] = b"exonum.storage.keys";
+// Length of the encrypted key byte.
+const KEY_BYTE_CODE_LENGTH: usize = 2;
+// Number of the entries re-encrypted in a single patch on the key rotation.
+const ROTATION_BATCH_SIZE: usize = 10_000;
+
+/// Database wrapper that encrypts the data before passing it to the underlying database.
+///
+/// The values are encrypted with `XChaCha20-Poly1305` and a random 192-bit nonce, which
+/// makes nonce collisions negligible for any number of writes, and are bound
+/// to the index name and the key, so a value moved to another key fails to decrypt.
+/// The index names are stored unencrypted. The keys are encrypted only if `encrypt_keys`
+/// is set, since any key encryption usable for the index iteration is deterministic and
+/// reveals the order of the keys and their common prefixes. Each key byte is replaced
+/// with a two-byte code chosen by a keyed order-preserving function of the byte, which
+/// depends on the preceding bytes of the key; thus, the encrypted keys are ordered
+/// in the same way as the original ones and the keys sharing a prefix share the prefix
+/// of their encryptions. Encrypting the keys considerably slows down the reads.
+///
+/// The data encrypted with another key is detected on opening the database, as well as
+/// unencrypted data, and reading it later results in a panic. The key can be changed
+/// with [`rotate_key`], which re-encrypts the whole database into another one.
+///
+/// The encryption is enabled by specifying [`encryption`] in the options of
+/// [`open_database`]; the key is loaded when the node is started, see [`EncryptionKey`].
+///
+/// # Examples
+///
+/// ```
+/// use exonum::storage::{Database, EncryptedDB, EncryptionKey, MapIndex, MemoryDB};
+///
+/// let key = EncryptionKey::generate();
+/// let db = EncryptedDB::new(Box::new(MemoryDB::new()), &key, true).unwrap();
+/// let mut fork = db.fork();
+/// MapIndex::new("index", &mut fork).put(&1_u64, 2_u64);
+/// db.merge(fork.into_patch()).unwrap();
+///
+/// let snapshot = db.snapshot();
+/// let index: MapIndex<_, u64, u64> = MapIndex::new("index", &snapshot);
+/// assert_eq!(index.get(&1), Some(2));
+/// ```
+///
+/// [`rotate_key`]: #method.rotate_key
+/// [`encryption`]: struct.DbOptions.html#structfield.encryption
+/// [`open_database`]: fn.open_database.html
+/// [`EncryptionKey`]: struct.EncryptionKey.html
+pub struct EncryptedDB {
+    inner: Box<dyn Database>,
+    cipher: Arc<Cipher>,
+    encrypt_keys: bool,
+}
+
+/// A snapshot decrypting the data of the underlying snapshot.
+struct EncryptedSnapshot {
+    inner: Box<dyn Snapshot>,
+    cipher: Arc<Cipher>,
+}
+
+/// An iterator decrypting the entries of the underlying iterator.
+struct EncryptedIter<'a> {
+    inner: Iter<'a>,
+    cipher: &'a Cipher,
+    name: String,
+    peeked: Option<(Vec<u8>, Vec<u8>)>,
+    current: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+/// Encryption of the keys and values with the keys derived from the encryption key.
+struct Cipher {
+    values_key: aead::Key,
+    keys_key: Option<hmacsha256::Key>,
+}
+
+impl Cipher {
+    fn new(key: &EncryptionKey, encrypt_keys: bool) -> Self {
+        let key = hmacsha256::Key(*key.as_bytes());
+        let values_key = aead::Key(hmacsha256::authenticate(VALUES_KEY_CONTEXT, &key).0);
+        let keys_key = if encrypt_keys {
+            Some(hmacsha256::Key(
+                hmacsha256::authenticate(KEYS_KEY_CONTEXT, &key).0,
+            ))
+        } else {
+            None
+        };
+        Self {
+            values_key,
+            keys_key,
+        }
+    }
+
+    fn encrypt_key(&self, key: &[u8]) -> Vec<u8> {
+        let keys_key = match self.keys_key {
+            Some(ref keys_key) => keys_key,
+            None => return key.to_vec(),
+        };
+        let mut encrypted = vec![0; key.len() * KEY_BYTE_CODE_LENGTH];
+        let mut state = hmacsha256::authenticate(&[], keys_key);
+        for (&byte, code) in key.iter().zip(encrypted.chunks_mut(KEY_BYTE_CODE_LENGTH)) {
+            let position_key = hmacsha256::Key(state.0);
+            BigEndian::write_u16(code, encode_key_byte(&position_key, byte));
+            state = next_key_state(keys_key, &state, byte);
+        }
+        encrypted
+    }
+
+    fn decrypt_key(&self, encrypted: &[u8]) -> Option<Vec<u8>> {
+        let keys_key = match self.keys_key {
+            Some(ref keys_key) => keys_key,
+            None => return Some(encrypted.to_vec()),
+        };
+        if encrypted.len() % KEY_BYTE_CODE_LENGTH != 0 {
+            return None;
+        }
+        let mut key = Vec::with_capacity(encrypted.len() / KEY_BYTE_CODE_LENGTH);
+        let mut state = hmacsha256::authenticate(&[], keys_key);
+        for code in encrypted.chunks(KEY_BYTE_CODE_LENGTH) {
+            let position_key = hmacsha256::Key(state.0);
+            let byte = decode_key_byte(&position_key, BigEndian::read_u16(code))?;
+            key.push(byte);
+            state = next_key_state(keys_key, &state, byte);
+        }
+        Some(key)
+    }
+
+    fn encrypt_value(&self, name: &str, key: &[u8], value: &[u8]) -> Vec<u8> {
+        let mut nonce = aead::Nonce([0; aead::NONCEBYTES]);
+        thread_rng().fill_bytes(&mut nonce.0);
+        let context = value_context(name, key);
+        let sealed = aead::seal(value, Some(&context[..]), &nonce, &self.values_key);
+
+        let mut encrypted = Vec::with_capacity(aead::NONCEBYTES + sealed.len());
+        encrypted.extend_from_slice(&nonce.0);
+        encrypted.extend_from_slice(&sealed);
+        encrypted
+    }
+
+    fn decrypt_value(&self, name: &str, key: &[u8], encrypted: &[u8]) -> Option<Vec<u8>> {
+        if encrypted.len() < aead::NONCEBYTES {
+            return None;
+        }
+        let (nonce, sealed) = encrypted.split_at(aead::NONCEBYTES);
+        let nonce = aead::Nonce::from_slice(nonce)?;
+        let context = value_context(name, key);
+        aead::open(sealed, Some(&context[..]), &nonce, &self.values_key).ok()
+    }
+
+    fn encrypt_patch(&self, patch: Patch) -> Patch {
+        let mut encrypted = Patch::new();
+        for (name, changes) in patch {
+            let mut encrypted_changes = Changes::new();
+            for (key, change) in changes {
+                let change = match change {
+                    Change::Put(value) => Change::Put(self.encrypt_value(&name, &key, &value)),
+                    Change::Delete => Change::Delete,
+                };
+                encrypted_changes.insert(self.encrypt_key(&key), change);
+            }
+            encrypted.insert_changes(name, encrypted_changes);
+        }
+        encrypted
+    }
+
+    /// Decrypts an entry of the underlying database, panicking if it cannot be decrypted.
+    fn decrypt_entry(&self, name: &str, key: &[u8], value: &[u8]) -> (Vec<u8>, Vec<u8>) {
+        let key = self
+            .decrypt_key(key)
+            .unwrap_or_else(|| decryption_failed(name));
+        let value = self
+            .decrypt_value(name, &key, value)
+            .unwrap_or_else(|| decryption_failed(name));
+        (key, value)
+    }
+}
+
+/// Returns the additional authenticated data of a value.
+fn value_context(name: &str, key: &[u8]) -> Vec<u8> {
+    let mut context = Vec::with_capacity(name.len() + 1 + key.len());
+    context.extend_from_slice(name.as_bytes());
+    // Index names cannot contain zero bytes.
+    context.push(0);
+    context.extend_from_slice(key);
+    context
+}
+
+fn decryption_failed(name: &str) -> ! {
+    panic!(
+        "Can't decrypt the data of `{}`: the encryption key is invalid or the data is corrupted",
+        name
+    )
+}
+
+/// Returns the state of the key encryption after the given key byte.
+fn next_key_state(
+    keys_key: &hmacsha256::Key,
+    state: &hmacsha256::Tag,
+    byte: u8,
+) -> hmacsha256::Tag {
+    let mut data = [0; hmacsha256::TAGBYTES + 1];
+    data[..hmacsha256::TAGBYTES].copy_from_slice(&state.0);
+    data[hmacsha256::TAGBYTES] = byte;
+    hmacsha256::authenticate(&data, keys_key)
+}
+
+/// Splits the codes `[code_from, code_to)` of the key bytes `[from, to)` between the lower
+/// and the upper halves of the bytes, leaving enough codes for each of them.
+fn split_codes(
+    position_key: &hmacsha256::Key,
+    (from, to): (u32, u32),
+    (code_from, code_to): (u32, u32),
+) -> u32 {
+    let middle = from + (to - from) / 2;
+    let spare_codes = (code_to - code_from) - (to - from);
+    let tag = hmacsha256::authenticate(&[from as u8, (to - 1) as u8], position_key);
+    code_from + (middle - from) + LittleEndian::read_u32(&tag.0) % (spare_codes + 1)
+}
+
+/// Encodes the key byte preserving the order of the bytes.
+fn encode_key_byte(position_key: &hmacsha256::Key, byte: u8) -> u16 {
+    let (mut from, mut to) = (0, 1 << 8);
+    let (mut code_from, mut code_to) = (0, 1 << 16);
+    while to - from > 1 {
+        let middle = from + (to - from) / 2;
+        let split = split_codes(position_key, (from, to), (code_from, code_to));
+        if u32::from(byte) < middle {
+            to = middle;
+            code_to = split;
+        } else {
+            from = middle;
+            code_from = split;
+        }
+    }
+    code_from as u16
+}
+
+/// Decodes the key byte, returning `None` if the code does not correspond to any byte.
+fn decode_key_byte(position_key: &hmacsha256::Key, code: u16) -> Option<u8> {
+    let (mut from, mut to) = (0, 1 << 8);
+    let (mut code_from, mut code_to) = (0, 1 << 16);
+    while to - from > 1 {
+        let middle = from + (to - from) / 2;
+        let split = split_codes(position_key, (from, to), (code_from, code_to));
+        if u32::from(code) < split {
+            to = middle;
+            code_to = split;
+        } else {
+            from = middle;
+            code_from = split;
+        }
+    }
+    if u32::from(code) == code_from {
+        Some(from as u8)
+    } else {
+        None
+    }
+}
+
+impl EncryptedDB {
+    /// Wraps the database with the encryption using the given key.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database contains data which cannot be decrypted with the key
+    /// and `encrypt_keys` setting, e.g., the data encrypted with another key or unencrypted.
+    pub fn new(inner: Box<dyn Database>, key: &EncryptionKey, encrypt_keys: bool) -> Result<Self> {
+        let db = Self {
+            inner,
+            cipher: Arc::new(Cipher::new(key, encrypt_keys)),
+            encrypt_keys,
+        };
+        db.check_key()?;
+        Ok(db)
+    }
+
+    /// Checks that the first entry of the indexes metadata, if any, can be decrypted.
+    fn check_key(&self) -> Result<()> {
+        let name = INDEXES_METADATA_TABLE_NAME;
+        let snapshot = self.inner.snapshot();
+        let mut iter = snapshot.iter(name, &[]);
+        if let Some((key, value)) = iter.next() {
+            let decrypted = self
+                .cipher
+                .decrypt_key(key)
+                .and_then(|key| self.cipher.decrypt_value(name, &key, value));
+            if decrypted.is_none() {
+                return Err(Error::new(
+                    "Can't decrypt the database: the encryption key is invalid",
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Re-encrypts the data of all the indexes registered in the database with the new key
+    /// and writes it into the `target` database, which must be empty. The data is written
+    /// in patches of a bounded size, and the last patch is merged with fsync, so the rotation
+    /// does not need to hold the whole database in memory. Returns the `target` database
+    /// wrapped with the new key and the number of the re-encrypted entries.
+    ///
+    /// The data of this database is left intact; [`rotate_encryption_key`] replaces
+    /// the database directory with the re-encrypted one after the rotation.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `target` database is not empty or if a patch cannot be merged
+    /// into it. The target database should be discarded in the latter case.
+    ///
+    /// [`rotate_encryption_key`]: fn.rotate_encryption_key.html
+    pub fn rotate_key(
+        &self,
+        new_key: &EncryptionKey,
+        target: Box<dyn Database>,
+    ) -> Result<(Self, usize)> {
+        let mut is_empty = true;
+        {
+            let snapshot = target.snapshot();
+            let mut iter = snapshot.iter(INDEXES_METADATA_TABLE_NAME, &[]);
+            if iter.next().is_some() {
+                is_empty = false;
+            }
+        }
+        if !is_empty {
+            return Err(Error::new(
+                "Can't re-encrypt the database: the target database is not empty",
+            ));
+        }
+
+        let new_cipher = Cipher::new(new_key, self.encrypt_keys);
+        let mut names = index_names(&*self.snapshot());
+        names.push(INDEXES_METADATA_TABLE_NAME.to_owned());
+
+        let snapshot = self.inner.snapshot();
+        let mut patch = Patch::new();
+        let mut batch_len = 0;
+        let mut count = 0;
+        for name in names {
+            let mut changes = Changes::new();
+            {
+                let mut iter = snapshot.iter(&name, &[]);
+                while let Some((key, value)) = iter.next() {
+                    let (key, value) = self.cipher.decrypt_entry(&name, key, value);
+                    let value = new_cipher.encrypt_value(&name, &key, &value);
+                    changes.insert(new_cipher.encrypt_key(&key), Change::Put(value));
+                    batch_len += 1;
+                    if batch_len == ROTATION_BATCH_SIZE {
+                        patch.insert_changes(
+                            name.clone(),
+                            mem::replace(&mut changes, Changes::new()),
+                        );
+                        target.merge(mem::replace(&mut patch, Patch::new()))?;
+                        count += batch_len;
+                        batch_len = 0;
+                    }
+                }
+            }
+            patch.insert_changes(name, changes);
+        }
+        target.merge_sync(patch)?;
+        count += batch_len;
+
+        let db = Self {
+            inner: target,
+            cipher: Arc::new(new_cipher),
+            encrypt_keys: self.encrypt_keys,
+        };
+        Ok((db, count))
+    }
+}
+
+impl Database for EncryptedDB {
+    fn snapshot(&self) -> Box<dyn Snapshot> {
+        Box::new(EncryptedSnapshot {
+            inner: self.inner.snapshot(),
+            cipher: Arc::clone(&self.cipher),
+        })
+    }
+
+    fn merge(&self, patch: Patch) -> Result<()> {
+        self.inner.merge(self.cipher.encrypt_patch(patch))
+    }
+
+    fn merge_sync(&self, patch: Patch) -> Result<()> {
+        self.inner.merge_sync(self.cipher.encrypt_patch(patch))
+    }
+
+    fn create_checkpoint(&self, path: &Path) -> Result<()> {
+        self.inner.create_checkpoint(path)
+    }
+}
+
+impl fmt::Debug for EncryptedDB {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EncryptedDB")
+            .field("encrypt_keys", &self.encrypt_keys)
+            .finish()
+    }
+}
+
+impl Snapshot for EncryptedSnapshot {
+    fn get(&self, name: &str, key: &[u8]) -> Option<Vec<u8>> {
+        let value = self.inner.get(name, &self.cipher.encrypt_key(key))?;
+        let value = self
+            .cipher
+            .decrypt_value(name, key, &value)
+            .unwrap_or_else(|| decryption_failed(name));
+        Some(value)
+    }
+
+    fn contains(&self, name: &str, key: &[u8]) -> bool {
+        self.inner.contains(name, &self.cipher.encrypt_key(key))
+    }
+
+    fn iter<'a>(&'a self, name: &str, from: &[u8]) -> Iter<'a> {
+        let from = self.cipher.encrypt_key(from);
+        let inner = self.inner.iter(name, &from);
+        Box::new(EncryptedIter::new(inner, &self.cipher, name))
+    }
+
+    fn iter_rev<'a>(&'a self, name: &str, before: Option<&[u8]>) -> Iter<'a> {
+        let before = before.map(|key| self.cipher.encrypt_key(key));
+        let inner = self
+            .inner
+            .iter_rev(name, before.as_ref().map(Vec::as_slice));
+        Box::new(EncryptedIter::new(inner, &self.cipher, name))
+    }
+}
+
+impl<'a> EncryptedIter<'a> {
+    fn new(inner: Iter<'a>, cipher: &'a Cipher, name: &str) -> Self {
+        Self {
+            inner,
+            cipher,
+            name: name.to_owned(),
+            peeked: None,
+            current: None,
+        }
+    }
+
+    fn next_entry(&mut self) -> Option<(Vec<u8>, Vec<u8>)> {
+        let cipher = self.cipher;
+        let name = &self.name;
+        self.inner
+            .next()
+            .map(|(key, value)| cipher.decrypt_entry(name, key, value))
+    }
+}
+
+impl<'a> Iterator for EncryptedIter<'a> {
+    fn next(&mut self) -> Option<(&[u8], &[u8])> {
+        self.current = match self.peeked.take() {
+            Some(entry) => Some(entry),
+            None => self.next_entry(),
+        };
+        self.current
+            .as_ref()
+            .map(|&(ref key, ref value)| (key.as_slice(), value.as_slice()))
+    }
+
+    fn peek(&mut self) -> Option<(&[u8], &[u8])> {
+        if self.peeked.is_none() {
+            self.peeked = self.next_entry();
+        }
+        self.peeked
+            .as_ref()
+            .map(|&(ref key, ref value)| (key.as_slice(), value.as_slice()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cipher, EncryptedDB};
+    use storage::{Database, EncryptionKey, MapIndex, MemoryDB, ProofMapIndex, Snapshot};
+
+    fn encrypted_db(key: &EncryptionKey, encrypt_keys: bool) -> EncryptedDB {
+        EncryptedDB::new(Box::new(MemoryDB::new()), key, encrypt_keys).unwrap()
+    }
+
+    fn entries(snapshot: &dyn Snapshot, name: &str) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let mut entries = Vec::new();
+        let mut iter = snapshot.iter(name, &[]);
+        while let Some((key, value)) = iter.next() {
+            entries.push((key.to_vec(), value.to_vec()));
+        }
+        entries
+    }
+
+    #[test]
+    fn key_encryption_preserves_order_and_prefixes() {
+        let cipher = Cipher::new(&EncryptionKey::generate(), true);
+        let mut keys = vec![
+            vec![],
+            vec![0],
+            vec![0, 0],
+            vec![0, 255],
+            vec![1],
+            vec![1, 2, 3],
+            vec![1, 2, 4],
+            vec![127, 128],
+            vec![128],
+            vec![254, 0, 1],
+            vec![255],
+            vec![255, 255, 255],
+        ];
+        keys.extend((0..=255_u8).map(|byte| vec![7, byte]));
+        keys.sort();
+
+        let encrypted = keys
+            .iter()
+            .map(|key| cipher.encrypt_key(key))
+            .collect::<Vec<_>>();
+        for i in 1..keys.len() {
+            assert!(encrypted[i - 1] < encrypted[i]);
+            assert_eq!(
+                encrypted[i].starts_with(&encrypted[i - 1]),
+                keys[i].starts_with(&keys[i - 1])
+            );
+        }
+        for (key, encrypted) in keys.iter().zip(&encrypted) {
+            assert_eq!(cipher.decrypt_key(encrypted).as_ref(), Some(key));
+        }
+        assert_eq!(cipher.decrypt_key(&[1, 2, 3]), None);
+    }
+
+    #[test]
+    fn data_is_encrypted() {
+        let key = EncryptionKey::generate();
+        for &encrypt_keys in &[false, true] {
+            let db = encrypted_db(&key, encrypt_keys);
+            let mut fork = db.fork();
+            fork.put("index", vec![1, 2, 3], vec![4, 5, 6]);
+            db.merge(fork.into_patch()).unwrap();
+            assert_eq!(db.snapshot().get("index", &[1, 2, 3]), Some(vec![4, 5, 6]));
+
+            let (key, value) = entries(&*db.inner.snapshot(), "index").remove(0);
+            assert_eq!(key == vec![1_u8, 2, 3], !encrypt_keys);
+            assert!(!value.windows(3).any(|window| window == [4_u8, 5, 6]));
+            // The value is bound to the key.
+            assert_eq!(db.cipher.decrypt_value("index", &[1, 2, 4], &value), None);
+            assert_eq!(db.cipher.decrypt_value("other", &[1, 2, 3], &value), None);
+        }
+    }
+
+    #[test]
+    fn invalid_key_is_detected() {
+        let key = EncryptionKey::generate();
+        let db = encrypted_db(&key, true);
+        let mut fork = db.fork();
+        MapIndex::new("index", &mut fork).put(&1_u64, 2_u64);
+        db.merge(fork.into_patch()).unwrap();
+
+        let inner = db.inner;
+        assert!(EncryptedDB::new(inner, &EncryptionKey::generate(), true).is_err());
+    }
+
+    #[test]
+    fn rotate_key() {
+        let key = EncryptionKey::generate();
+        for &encrypt_keys in &[false, true] {
+            let db = encrypted_db(&key, encrypt_keys);
+            let mut fork = db.fork();
+            {
+                let mut index = ProofMapIndex::new("index", &mut fork);
+                for i in 0_u8..50 {
+                    index.put(&[i; 32], u64::from(i));
+                }
+            }
+            db.merge(fork.into_patch()).unwrap();
+            let root_hash =
+                ProofMapIndex::<_, [u8; 32], u64>::new("index", &db.snapshot()).merkle_root();
+
+            let new_key = EncryptionKey::generate();
+            let (new_db, count) = db.rotate_key(&new_key, Box::new(MemoryDB::new())).unwrap();
+            assert!(count > 50);
+            {
+                let snapshot = new_db.snapshot();
+                let index: ProofMapIndex<_, [u8; 32], u64> = ProofMapIndex::new("index", &snapshot);
+                assert_eq!(index.merkle_root(), root_hash);
+                assert_eq!(index.iter().count(), 50);
+                assert_eq!(index.get(&[7; 32]), Some(7));
+            }
+            let raw_entries = entries(&*new_db.inner.snapshot(), "index");
+            assert_eq!(
+                raw_entries.len(),
+                entries(&*db.inner.snapshot(), "index").len()
+            );
+            // The rotated data can't be decrypted with the previous key.
+            let (ref raw_key, ref raw_value) = raw_entries[0];
+            let decrypted_key = new_db.cipher.decrypt_key(raw_key).unwrap();
+            assert_eq!(
+                db.cipher.decrypt_value("index", &decrypted_key, raw_value),
+                None
+            );
+
+            // The target database must be empty.
+            assert!(db.rotate_key(&new_key, new_db.inner).is_err());
+        }
+    }
+}
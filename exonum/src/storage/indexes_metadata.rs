@@ -40,17 +40,28 @@ encoding_struct! {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+/// Type of an index, which is recorded in the storage when the index is created.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 #[repr(u8)]
 pub enum IndexType {
+    /// [`Entry`](struct.Entry.html).
     Entry,
+    /// [`KeySetIndex`](key_set_index/struct.KeySetIndex.html).
     KeySet,
+    /// [`ListIndex`](list_index/struct.ListIndex.html).
     List,
+    /// [`SparseListIndex`](sparse_list_index/struct.SparseListIndex.html).
     SparseList,
+    /// [`MapIndex`](map_index/struct.MapIndex.html).
     Map,
+    /// [`ProofListIndex`](proof_list_index/struct.ProofListIndex.html).
     ProofList,
+    /// [`ProofMapIndex`](proof_map_index/struct.ProofMapIndex.html).
     ProofMap,
+    /// [`ValueSetIndex`](value_set_index/struct.ValueSetIndex.html).
     ValueSet,
+    /// [`MmrIndex`](mmr_index/struct.MmrIndex.html).
+    Mmr,
 }
 
 impl From<u8> for IndexType {
@@ -65,6 +76,7 @@ impl From<u8> for IndexType {
             5 => ProofList,
             6 => ProofMap,
             7 => ValueSet,
+            8 => Mmr,
             invalid => panic!(
                 "Unreachable pattern ({:?}) while constructing table type. \
                  Storage data is probably corrupted",
@@ -134,33 +146,48 @@ impl json::ExonumJson for IndexType {
 }
 
 pub fn assert_index_type(name: &str, index_type: IndexType, is_family: bool, view: &dyn Snapshot) {
+    if let Err(e) = check_index_type(view, name, index_type, is_family) {
+        panic!("{}", e);
+    }
+}
+
+/// Checks that the index with the given name was created with the given type, or is not
+/// created yet.
+pub fn check_index_type(
+    view: &dyn Snapshot,
+    name: &str,
+    index_type: IndexType,
+    is_family: bool,
+) -> Result<(), super::Error> {
     let metadata = BaseIndex::indexes_metadata(view);
     if let Some(value) = metadata.get::<_, IndexMetadata>(name) {
         let stored_type = value.index_type();
         let stored_is_family = value.is_family();
-        assert_eq!(
-            stored_type, index_type,
-            "Attempt to access index '{}' of type {:?}, \
-             while said index was initially created with type {:?}",
-            name, index_type, stored_type
-        );
-        assert_eq!(
-            stored_is_family,
-            is_family,
-            "Attempt to access {} '{}' while it's {}",
-            if is_family {
-                "index family"
-            } else {
-                "an ordinary index"
-            },
-            name,
-            if stored_is_family {
-                "index family "
-            } else {
-                "an ordinary index"
-            }
-        );
+        if stored_type != index_type {
+            return Err(super::Error::new(format!(
+                "Attempt to access index '{}' of type {:?}, \
+                 while said index was initially created with type {:?}",
+                name, index_type, stored_type
+            )));
+        }
+        if stored_is_family != is_family {
+            return Err(super::Error::new(format!(
+                "Attempt to access {} '{}' while it's {}",
+                if is_family {
+                    "index family"
+                } else {
+                    "an ordinary index"
+                },
+                name,
+                if stored_is_family {
+                    "index family "
+                } else {
+                    "an ordinary index"
+                }
+            )));
+        }
     }
+    Ok(())
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -226,6 +253,43 @@ impl fmt::Display for StorageMetadata {
     }
 }
 
+/// Returns names of all the indexes and index families registered in the storage.
+pub fn index_names(view: &dyn Snapshot) -> Vec<String> {
+    BaseIndex::indexes_metadata(view)
+        .iter::<_, String, ()>(&())
+        .map(|(name, _)| name)
+        .filter(|name| name != CORE_STORAGE_METADATA_KEY)
+        .collect()
+}
+
+/// Returns the names, types and family flags of all the indexes and index families
+/// registered in the storage.
+pub fn index_metadata(view: &dyn Snapshot) -> Vec<(String, IndexType, bool)> {
+    BaseIndex::indexes_metadata(view)
+        .iter::<_, String, Vec<u8>>(&())
+        .filter(|(name, _)| name != CORE_STORAGE_METADATA_KEY)
+        .map(|(name, value)| {
+            let metadata = IndexMetadata::from_bytes(Cow::Owned(value));
+            (name, metadata.index_type(), metadata.is_family())
+        }).collect()
+}
+
+/// Returns the types of all the indexes and index families registered in the storage.
+#[cfg(feature = "rocksdb-storage")]
+pub fn index_types(view: &dyn Snapshot) -> ::std::collections::HashMap<String, IndexType> {
+    BaseIndex::indexes_metadata(view)
+        .iter::<_, String, Vec<u8>>(&())
+        .filter(|(name, _)| name != CORE_STORAGE_METADATA_KEY)
+        .map(|(name, value)| (name, decode_index_type(&value)))
+        .collect()
+}
+
+/// Returns the index type from the serialized metadata of an index.
+#[cfg(feature = "rocksdb-storage")]
+pub fn decode_index_type(value: &[u8]) -> IndexType {
+    IndexMetadata::from_bytes(Cow::Borrowed(value)).index_type()
+}
+
 pub fn set_index_type(name: &str, index_type: IndexType, is_family: bool, view: &mut Fork) {
     if name == INDEXES_METADATA_TABLE_NAME || name == CORE_STORAGE_METADATA_KEY {
         panic!("Attempt to access an internal storage infrastructure");
@@ -0,0 +1,268 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Integrity checks of the Merkelized indexes.
+
+use std::{
+    borrow::Cow,
+    collections::{BTreeSet, HashMap},
+    fmt, mem,
+};
+
+use super::{
+    indexes_metadata::{self, IndexType},
+    proof_list_index::ProofListKey,
+    proof_map_index::PROOF_MAP_KEY_SIZE,
+    ProofListIndex, ProofMapIndex, Snapshot, StorageKey, StorageValue,
+};
+use crypto::{CryptoHash, Hash};
+use encoding::serialize::encode_hex;
+
+/// Computes the hash of a value from its storage representation.
+pub type ValueHashFn = fn(&[u8]) -> Hash;
+
+/// Computes the hash of a value of the given type from its storage representation.
+pub fn hash_storage_value<V: StorageValue>(value: &[u8]) -> Hash {
+    V::from_bytes(Cow::Borrowed(value)).hash()
+}
+
+/// Inconsistency found in the stored data.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IntegrityError {
+    /// Name of the index or the index family.
+    pub index_name: String,
+    /// Key of the inconsistent entry within the index; for the members of index families,
+    /// the key is prefixed with the index identifier. Empty if the error relates
+    /// to the whole index.
+    pub key: Vec<u8>,
+    /// Description of the inconsistency.
+    pub message: String,
+}
+
+impl IntegrityError {
+    /// Creates a new error for the entry with the given key.
+    pub fn new<S: Into<String>, M: Into<String>>(index_name: S, key: Vec<u8>, message: M) -> Self {
+        Self {
+            index_name: index_name.into(),
+            key,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for IntegrityError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.key.is_empty() {
+            write!(f, "index `{}`: {}", self.index_name, self.message)
+        } else {
+            write!(
+                f,
+                "index `{}`, key {}: {}",
+                self.index_name,
+                encode_hex(&self.key),
+                self.message
+            )
+        }
+    }
+}
+
+/// Checks the internal consistency of all the `ProofMapIndex` and `ProofListIndex` indexes
+/// (including the members of index families) in the given view.
+///
+/// The hashes of the values can be computed only if the type of the values is known,
+/// so `value_hashes` specifies the hash functions for the values of indexes and index families
+/// with the given names. For other indexes, only the hashes of the intermediate tree nodes
+/// are checked.
+///
+/// Returns the recomputed root hashes of the ordinary indexes and the found inconsistencies.
+pub fn check_merkelized_indexes(
+    view: &dyn Snapshot,
+    value_hashes: &HashMap<String, ValueHashFn>,
+) -> (HashMap<String, Hash>, Vec<IntegrityError>) {
+    let mut roots = HashMap::new();
+    let mut errors = Vec::new();
+    for (name, index_type, is_family) in indexes_metadata::index_metadata(view) {
+        let value_hash = value_hashes.get(&name).cloned();
+        let mut index_errors = Vec::new();
+        match (index_type, is_family) {
+            (IndexType::ProofMap, false) => {
+                let index: ProofMapIndex<_, Hash, Vec<u8>> = ProofMapIndex::new(&name, view);
+                if let Some(root) = index.check_integrity(value_hash, &mut index_errors) {
+                    roots.insert(name.clone(), root);
+                }
+            }
+            (IndexType::ProofList, false) => {
+                let index: ProofListIndex<_, Vec<u8>> = ProofListIndex::new(&name, view);
+                if let Some(root) = index.check_integrity(value_hash, &mut index_errors) {
+                    roots.insert(name.clone(), root);
+                }
+            }
+            (IndexType::ProofMap, true) => {
+                for index_id in map_family_ids(view, &name, &mut index_errors) {
+                    let index: ProofMapIndex<_, Hash, Vec<u8>> =
+                        ProofMapIndex::new_in_family(&name, &index_id, view);
+                    let mut member_errors = Vec::new();
+                    index.check_integrity(value_hash, &mut member_errors);
+                    index_errors.extend(prefix_keys(&index_id, member_errors));
+                }
+            }
+            (IndexType::ProofList, true) => {
+                for index_id in list_family_ids(view, &name, &mut index_errors) {
+                    let index: ProofListIndex<_, Vec<u8>> =
+                        ProofListIndex::new_in_family(&name, &index_id, view);
+                    let mut member_errors = Vec::new();
+                    index.check_integrity(value_hash, &mut member_errors);
+                    index_errors.extend(prefix_keys(&index_id, member_errors));
+                }
+            }
+            _ => {}
+        }
+        errors.extend(
+            index_errors
+                .into_iter()
+                .map(|(key, message)| IntegrityError::new(name.as_str(), key, message)),
+        );
+    }
+    (roots, errors)
+}
+
+pub(crate) fn raw_key<K: StorageKey + ?Sized>(key: &K) -> Vec<u8> {
+    let mut buffer = vec![0; key.size()];
+    key.write(&mut buffer);
+    buffer
+}
+
+fn prefix_keys(
+    index_id: &[u8],
+    errors: Vec<(Vec<u8>, String)>,
+) -> impl Iterator<Item = (Vec<u8>, String)> {
+    let index_id = index_id.to_vec();
+    errors.into_iter().map(move |(key, message)| {
+        let mut prefixed = index_id.clone();
+        prefixed.extend_from_slice(&key);
+        (prefixed, message)
+    })
+}
+
+/// Returns the identifiers of the members of a `ProofMapIndex` family. The keys of the tree
+/// nodes have a fixed length, so the identifier is the rest of the key.
+fn map_family_ids(
+    view: &dyn Snapshot,
+    name: &str,
+    errors: &mut Vec<(Vec<u8>, String)>,
+) -> Vec<Vec<u8>> {
+    let node_key_size = PROOF_MAP_KEY_SIZE + 2;
+    let mut ids = BTreeSet::new();
+    let mut iter = view.iter(name, &[]);
+    while let Some((key, _)) = iter.next() {
+        if key.len() < node_key_size {
+            errors.push((key.to_vec(), "Invalid length of the node key".to_owned()));
+        } else {
+            ids.insert(key[..key.len() - node_key_size].to_vec());
+        }
+    }
+    ids.into_iter().collect()
+}
+
+/// Returns the identifiers of the members of a `ProofListIndex` family. The length of a list
+/// is stored with the key equal to the identifier, which precedes the keys of the tree nodes,
+/// consisting of the identifier and the node key. Since the identifiers may have different
+/// lengths, a key is treated as a node key only if it extends a known identifier with a node key
+/// which is valid for the length of the list.
+fn list_family_ids(
+    view: &dyn Snapshot,
+    name: &str,
+    errors: &mut Vec<(Vec<u8>, String)>,
+) -> Vec<Vec<u8>> {
+    let node_key_size = mem::size_of::<u64>();
+    let mut lengths: HashMap<Vec<u8>, u64> = HashMap::new();
+    let mut ids = Vec::new();
+    let mut iter = view.iter(name, &[]);
+    while let Some((key, value)) = iter.next() {
+        if key.len() >= node_key_size {
+            let (id, node_key) = key.split_at(key.len() - node_key_size);
+            let node_key = ProofListKey::read(node_key);
+            if lengths
+                .get(id)
+                .map_or(false, |&len| is_list_node(len, &node_key))
+            {
+                continue;
+            }
+        }
+        if value.len() == mem::size_of::<u64>() {
+            lengths.insert(key.to_vec(), u64::from_bytes(Cow::Borrowed(value)));
+            ids.push(key.to_vec());
+        } else {
+            errors.push((key.to_vec(), "Invalid length of the list".to_owned()));
+        }
+    }
+    ids
+}
+
+/// Returns `true` if the node with the given key exists in a `ProofListIndex` of the given length.
+fn is_list_node(len: u64, key: &ProofListKey) -> bool {
+    if len == 0 {
+        return false;
+    }
+    let last_index = len - 1;
+    let height = u32::from(key.height());
+    if height == 0 {
+        return key.index() <= last_index;
+    }
+    let max_height = 64 - last_index.leading_zeros() + 1;
+    height <= max_height && key.index() <= last_index.checked_shr(height - 1).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{list_family_ids, map_family_ids};
+    use crypto::Hash;
+    use storage::{Database, MemoryDB, ProofListIndex, ProofMapIndex};
+
+    #[test]
+    fn family_ids_of_different_lengths() {
+        let db = MemoryDB::new();
+        let mut fork = db.fork();
+        // The keys of the first list include the keys with the identifiers of the others.
+        let list_ids: Vec<&[u8]> = vec![&[1], &[1, 0], &[1, 0, 0, 0, 0, 0, 0, 0, 9], &[2]];
+        for (i, &index_id) in list_ids.iter().enumerate() {
+            let mut index = ProofListIndex::new_in_family("lists", index_id, &mut fork);
+            index.extend((0..i as u64 + 3).map(|value| value * 10));
+        }
+        let map_ids: Vec<&[u8]> = vec![&[1], &[2, 3, 4]];
+        for &index_id in &map_ids {
+            let mut index = ProofMapIndex::new_in_family("maps", index_id, &mut fork);
+            index.put(&Hash::zero(), 1_u64);
+            index.put(&Hash::new([1; 32]), 2_u64);
+        }
+        db.merge(fork.into_patch()).unwrap();
+
+        let snapshot = db.snapshot();
+        let mut errors = Vec::new();
+        assert_eq!(list_family_ids(&*snapshot, "lists", &mut errors), list_ids);
+        assert_eq!(map_family_ids(&*snapshot, "maps", &mut errors), map_ids);
+        assert!(errors.is_empty());
+
+        // Keys too short for a map node are reported.
+        let mut fork = db.fork();
+        fork.put("maps", vec![3], vec![0]);
+        db.merge(fork.into_patch()).unwrap();
+        assert_eq!(
+            map_family_ids(&*db.snapshot(), "maps", &mut errors),
+            map_ids
+        );
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, vec![3]);
+    }
+}
@@ -18,7 +18,7 @@
 //! The given section contains information on the methods related to `KeySetIndex`
 //! and the iterator over the items of this set.
 
-use std::{borrow::Borrow, marker::PhantomData};
+use std::{borrow::Borrow, marker::PhantomData, ops::Range};
 
 use super::{
     base_index::{BaseIndex, BaseIndexIter},
@@ -40,11 +40,14 @@ pub struct KeySetIndex<T, K> {
 
 /// Returns an iterator over the items of a `KeySetIndex`.
 ///
-/// This struct is created by the [`iter`] or
-/// [`iter_from`] method on [`KeySetIndex`]. See its documentation for details.
+/// This struct is created by the [`iter`], [`iter_from`], [`iter_rev`], [`iter_rev_from`]
+/// or [`range`] method on [`KeySetIndex`]. See its documentation for details.
 ///
 /// [`iter`]: struct.KeySetIndex.html#method.iter
 /// [`iter_from`]: struct.KeySetIndex.html#method.iter_from
+/// [`iter_rev`]: struct.KeySetIndex.html#method.iter_rev
+/// [`iter_rev_from`]: struct.KeySetIndex.html#method.iter_rev_from
+/// [`range`]: struct.KeySetIndex.html#method.range
 /// [`KeySetIndex`]: struct.KeySetIndex.html
 #[derive(Debug)]
 pub struct KeySetIndexIter<'a, K> {
@@ -183,6 +186,78 @@ where
             base_iter: self.base.iter_from(&(), from),
         }
     }
+
+    /// Returns an iterator visiting all elements in descending order. The iterator element type
+    /// is K.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use exonum::storage::{MemoryDB, Database, KeySetIndex};
+    ///
+    /// let db = MemoryDB::new();
+    /// let name = "name";
+    /// let mut fork = db.fork();
+    /// let mut index = KeySetIndex::new(name, &mut fork);
+    /// index.insert(1_u8);
+    /// index.insert(2_u8);
+    ///
+    /// let items: Vec<_> = index.iter_rev().collect();
+    /// assert_eq!(items, vec![2, 1]);
+    /// ```
+    pub fn iter_rev(&self) -> KeySetIndexIter<K> {
+        KeySetIndexIter {
+            base_iter: self.base.iter_rev(&()),
+        }
+    }
+
+    /// Returns an iterator visiting elements in descending order starting from the specified
+    /// value (inclusive). The iterator element type is K.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use exonum::storage::{MemoryDB, Database, KeySetIndex};
+    ///
+    /// let db = MemoryDB::new();
+    /// let name = "name";
+    /// let snapshot = db.snapshot();
+    /// let index: KeySetIndex<_, u8> = KeySetIndex::new(name, &snapshot);
+    ///
+    /// for val in index.iter_rev_from(&2) {
+    ///     println!("{}", val);
+    /// }
+    /// ```
+    pub fn iter_rev_from(&self, from: &K) -> KeySetIndexIter<K> {
+        KeySetIndexIter {
+            base_iter: self.base.iter_rev_from(&(), from),
+        }
+    }
+
+    /// Returns an iterator visiting elements in the specified range in ascending order.
+    /// The iterator element type is K.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use exonum::storage::{MemoryDB, Database, KeySetIndex};
+    ///
+    /// let db = MemoryDB::new();
+    /// let name = "name";
+    /// let mut fork = db.fork();
+    /// let mut index = KeySetIndex::new(name, &mut fork);
+    /// for i in 0_u8..5 {
+    ///     index.insert(i);
+    /// }
+    ///
+    /// let items: Vec<_> = index.range(&1..&4).collect();
+    /// assert_eq!(items, vec![1, 2, 3]);
+    /// ```
+    pub fn range(&self, range: Range<&K>) -> KeySetIndexIter<K> {
+        KeySetIndexIter {
+            base_iter: self.base.iter_range(&(), range.start, range.end),
+        }
+    }
 }
 
 impl<'a, K> KeySetIndex<&'a mut Fork, K>
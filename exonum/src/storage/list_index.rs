@@ -17,7 +17,7 @@
 //! The given section contains methods related to `ListIndex` and the iterator
 //! over the items of this list.
 
-use std::{cell::Cell, marker::PhantomData};
+use std::{cell::Cell, marker::PhantomData, ops::Range};
 
 use super::{
     base_index::{BaseIndex, BaseIndexIter},
@@ -43,11 +43,13 @@ pub struct ListIndex<T, V> {
 
 /// Returns an iterator over the items of a `ListIndex`.
 ///
-/// This struct is created by the [`iter`] or
-/// [`iter_from`] method on [`ListIndex`]. See its documentation for details.
+/// This struct is created by the [`iter`], [`iter_from`], [`iter_rev`] or [`range`]
+/// method on [`ListIndex`]. See its documentation for details.
 ///
 /// [`iter`]: struct.ListIndex.html#method.iter
 /// [`iter_from`]: struct.ListIndex.html#method.iter_from
+/// [`iter_rev`]: struct.ListIndex.html#method.iter_rev
+/// [`range`]: struct.ListIndex.html#method.range
 /// [`ListIndex`]: struct.ListIndex.html
 #[derive(Debug)]
 pub struct ListIndexIter<'a, V> {
@@ -260,6 +262,55 @@ where
             base_iter: self.base.iter_from(&(), &from),
         }
     }
+
+    /// Returns an iterator over the list in reverse order. The iterator element type is V.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use exonum::storage::{MemoryDB, Database, ListIndex};
+    ///
+    /// let db = MemoryDB::new();
+    /// let name = "name";
+    /// let mut fork = db.fork();
+    /// let mut index = ListIndex::new(name, &mut fork);
+    ///
+    /// index.extend([1, 2, 3, 4, 5].iter().cloned());
+    ///
+    /// let last: Vec<_> = index.iter_rev().take(2).collect();
+    /// assert_eq!(last, vec![5, 4]);
+    /// ```
+    pub fn iter_rev(&self) -> ListIndexIter<V> {
+        // The length of the list is stored before the elements, so the iteration
+        // is bounded by the first element.
+        ListIndexIter {
+            base_iter: self.base.iter_range_rev(&(), &0_u64, &self.len()),
+        }
+    }
+
+    /// Returns an iterator over the elements of the list with the positions in the specified
+    /// range. The iterator element type is V.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use exonum::storage::{MemoryDB, Database, ListIndex};
+    ///
+    /// let db = MemoryDB::new();
+    /// let name = "name";
+    /// let mut fork = db.fork();
+    /// let mut index = ListIndex::new(name, &mut fork);
+    ///
+    /// index.extend([1, 2, 3, 4, 5].iter().cloned());
+    ///
+    /// let items: Vec<_> = index.range(1..3).collect();
+    /// assert_eq!(items, vec![2, 3]);
+    /// ```
+    pub fn range(&self, range: Range<u64>) -> ListIndexIter<V> {
+        ListIndexIter {
+            base_iter: self.base.iter_range(&(), &range.start, &range.end),
+        }
+    }
 }
 
 impl<'a, V> ListIndex<&'a mut Fork, V>
@@ -583,6 +634,7 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "rocksdb-storage")]
     mod rocksdb_tests {
         use std::path::Path;
         use storage::{Database, DbOptions, ListIndex, RocksDB};
@@ -635,4 +687,58 @@ mod tests {
             super::list_index_iter(&mut list_index);
         }
     }
+
+    #[cfg(feature = "lmdb-storage")]
+    mod lmdb_tests {
+        use std::path::Path;
+        use storage::{Database, DbOptions, ListIndex, LMDB};
+        use tempdir::TempDir;
+
+        const IDX_NAME: &'static str = "idx_name";
+
+        fn create_database(path: &Path) -> Box<dyn Database> {
+            let opts = DbOptions::default();
+            Box::new(LMDB::open(path, &opts).unwrap())
+        }
+
+        #[test]
+        fn test_list_index_methods() {
+            let dir = TempDir::new(super::gen_tempdir_name().as_str()).unwrap();
+            let path = dir.path();
+            let db = create_database(path);
+            let mut fork = db.fork();
+            let mut list_index = ListIndex::new(IDX_NAME, &mut fork);
+            super::list_index_methods(&mut list_index);
+        }
+
+        #[test]
+        fn test_list_index_in_family_methods() {
+            let dir = TempDir::new(super::gen_tempdir_name().as_str()).unwrap();
+            let path = dir.path();
+            let db = create_database(path);
+            let mut fork = db.fork();
+            let mut list_index = ListIndex::new_in_family(IDX_NAME, &vec![01], &mut fork);
+            super::list_index_methods(&mut list_index);
+        }
+
+        #[test]
+        fn test_list_index_iter() {
+            let dir = TempDir::new(super::gen_tempdir_name().as_str()).unwrap();
+            let path = dir.path();
+            let db = create_database(path);
+            let mut fork = db.fork();
+            let mut list_index = ListIndex::new(IDX_NAME, &mut fork);
+            super::list_index_iter(&mut list_index);
+        }
+
+        #[test]
+        fn test_list_index_in_family_iter() {
+            let dir = TempDir::new(super::gen_tempdir_name().as_str()).unwrap();
+            let path = dir.path();
+            let db = create_database(path);
+            let mut fork = db.fork();
+            let mut list_index = ListIndex::new_in_family(IDX_NAME, &vec![01], &mut fork);
+            super::list_index_iter(&mut list_index);
+        }
+    }
 }
@@ -0,0 +1,388 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![allow(unsafe_code)]
+
+//! An implementation of `LMDB` database.
+
+use lmdb::{
+    self, Cursor, Environment, EnvironmentFlags, RoCursor, RoTransaction, Transaction, WriteFlags,
+};
+use lmdb_sys::{mdb_env_copy2, MDB_CP_COMPACT, MDB_LAST, MDB_NEXT, MDB_PREV, MDB_SET_RANGE};
+
+use std::{
+    ffi::CString,
+    fmt, fs, mem,
+    path::Path,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use storage::{self, db::Change, Database, DbOptions, Iter, Iterator, Patch, Snapshot};
+
+/// Default maximum size of the database.
+#[cfg(target_pointer_width = "64")]
+const DEFAULT_MAP_SIZE: usize = 16 << 30;
+#[cfg(not(target_pointer_width = "64"))]
+const DEFAULT_MAP_SIZE: usize = 1 << 30;
+
+/// Default maximum number of simultaneously open snapshots.
+const DEFAULT_MAX_READERS: u32 = 1024;
+
+/// Maximum length of a key in `LMDB`, including the index name and the separator.
+const MAX_KEY_SIZE: usize = 511;
+
+/// Delay before the next attempt to create a snapshot if all the reader slots are taken.
+const READERS_FULL_RETRY_DELAY_MS: u64 = 1;
+
+/// Maximum time to wait for a free reader slot before giving up on creating a snapshot.
+const READERS_FULL_TIMEOUT_MS: u64 = 30_000;
+
+impl From<lmdb::Error> for storage::Error {
+    fn from(err: lmdb::Error) -> Self {
+        Self::new(err.to_string())
+    }
+}
+
+/// Database implementation on top of [`LMDB`](https://symas.com/lmdb/) backend.
+///
+/// `LMDB` is a compact embedded key-value store with multi-version concurrency control,
+/// so snapshots are cheap and never block merges. Unlike `RocksDB`, it is built from
+/// a single C source file and does not require a C++ toolchain, which makes it easier
+/// to build and to cross-compile. The backend is available with the `lmdb-storage` feature.
+///
+/// All the indices are stored in a single `LMDB` database, the key of each entry is prefixed
+/// with the index name followed by a zero byte. The total length of such a key is limited
+/// by `LMDB` to 511 bytes; merges of the patches with longer keys fail.
+///
+/// The database file is memory-mapped, its maximum size is specified by
+/// the [`lmdb_map_size`] option. On Windows, the file occupies the whole maximum size
+/// on disk, so the option should be adjusted accordingly.
+///
+/// The number of simultaneously open snapshots is limited by the [`lmdb_max_readers`]
+/// option. Creating another snapshot blocks until one of them is dropped, and panics
+/// if no snapshot is dropped within 30 seconds.
+///
+/// [`lmdb_map_size`]: struct.DbOptions.html#structfield.lmdb_map_size
+/// [`lmdb_max_readers`]: struct.DbOptions.html#structfield.lmdb_max_readers
+pub struct LMDB {
+    env: Arc<Environment>,
+    db: lmdb::Database,
+    max_readers: u32,
+}
+
+/// A snapshot of a `LMDB`.
+pub struct LMDBSnapshot {
+    // The transaction should be dropped before the environment.
+    txn: RoTransaction<'static>,
+    db: lmdb::Database,
+    _env: Arc<Environment>,
+}
+
+/// An iterator over the entries of an index in a `LMDB`.
+struct LMDBIterator<'a> {
+    cursor: RoCursor<'a>,
+    prefix: Vec<u8>,
+    // The key to position the cursor at, if the iteration has not started yet.
+    // For the reverse iteration, the cursor is positioned before this key.
+    from: Option<Vec<u8>>,
+    reverse: bool,
+    finished: bool,
+    peeked: Option<Option<(&'a [u8], &'a [u8])>>,
+}
+
+fn encode_key(name: &str, key: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(name.len() + 1 + key.len());
+    encoded.extend_from_slice(name.as_bytes());
+    encoded.push(0);
+    encoded.extend_from_slice(key);
+    encoded
+}
+
+impl LMDB {
+    /// Opens a database stored at the specified path with the specified options.
+    ///
+    /// If the database does not exist at the indicated path and the option
+    /// `create_if_missing` is switched on in `DbOptions`, a new database will
+    /// be created at the indicated path.
+    pub fn open<P: AsRef<Path>>(path: P, options: &DbOptions) -> storage::Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            if !options.create_if_missing {
+                return Err(storage::Error::new(format!(
+                    "Database not found at {}",
+                    path.display()
+                )));
+            }
+            fs::create_dir_all(path).map_err(|e| storage::Error::new(e.to_string()))?;
+        }
+
+        // Snapshots may be created and dropped in different threads, and a single thread
+        // may hold several snapshots at once, so transactions are not bound to threads.
+        // Merges do not sync the metadata, which keeps the database consistent after
+        // a system crash, but may lose the last merged patches.
+        let max_readers = options.lmdb_max_readers.unwrap_or(DEFAULT_MAX_READERS);
+        if max_readers == 0 {
+            return Err(storage::Error::new(
+                "The `lmdb_max_readers` option should be positive",
+            ));
+        }
+        let env = Environment::new()
+            .set_flags(EnvironmentFlags::NO_TLS | EnvironmentFlags::NO_META_SYNC)
+            .set_map_size(options.lmdb_map_size.unwrap_or(DEFAULT_MAP_SIZE))
+            .set_max_readers(max_readers)
+            .open(path)?;
+        let db = env.open_db(None)?;
+        Ok(Self {
+            env: Arc::new(env),
+            db,
+            max_readers,
+        })
+    }
+
+    fn do_merge(&self, patch: Patch, sync: bool) -> storage::Result<()> {
+        // The keys are checked before the transaction is started, so that a patch is either
+        // applied as a whole or rejected with a clear error.
+        for (name, changes) in patch.iter() {
+            for (key, _) in changes.iter() {
+                let len = name.len() + 1 + key.len();
+                if len > MAX_KEY_SIZE {
+                    return Err(storage::Error::new(format!(
+                        "Key of the index {} is too long: {} bytes including the index name, \
+                         while LMDB supports at most {} bytes",
+                        name, len, MAX_KEY_SIZE
+                    )));
+                }
+            }
+        }
+
+        let start = Instant::now();
+        let mut txn = self.env.begin_rw_txn()?;
+        for (name, changes) in patch {
+            for (key, change) in changes {
+                let key = encode_key(&name, &key);
+                match change {
+                    Change::Put(ref value) => txn
+                        .put(self.db, &key, value, WriteFlags::empty())
+                        .map_err(map_full_error)?,
+                    Change::Delete => match txn.del(self.db, &key, None) {
+                        Ok(()) | Err(lmdb::Error::NotFound) => {}
+                        Err(e) => return Err(e.into()),
+                    },
+                }
+            }
+        }
+        txn.commit().map_err(map_full_error)?;
+        if sync {
+            self.env.sync(true)?;
+        }
+
+        let elapsed = start.elapsed();
+        let micros = elapsed.as_secs() * 1_000_000 + u64::from(elapsed.subsec_micros());
+        metric!(histogram: "storage.lmdb_merge_us", micros);
+        Ok(())
+    }
+}
+
+/// Converts the error returned when the database reaches its maximum size.
+fn map_full_error(err: lmdb::Error) -> storage::Error {
+    match err {
+        lmdb::Error::MapFull => storage::Error::new(
+            "LMDB database has reached its maximum size, increase the `lmdb_map_size` option",
+        ),
+        err => err.into(),
+    }
+}
+
+impl Database for LMDB {
+    fn snapshot(&self) -> Box<dyn Snapshot> {
+        let mut wait_start = None;
+        let txn = loop {
+            match self.env.begin_ro_txn() {
+                Ok(txn) => break txn,
+                // The reader slots are released as soon as other snapshots are dropped.
+                Err(lmdb::Error::ReadersFull) => {
+                    let wait_start = *wait_start.get_or_insert_with(|| {
+                        warn!(
+                            "All {} LMDB reader slots are taken, waiting",
+                            self.max_readers
+                        );
+                        Instant::now()
+                    });
+                    if wait_start.elapsed() > Duration::from_millis(READERS_FULL_TIMEOUT_MS) {
+                        panic!(
+                            "All {} LMDB reader slots have been taken for {} ms, \
+                             increase the `lmdb_max_readers` option",
+                            self.max_readers, READERS_FULL_TIMEOUT_MS
+                        );
+                    }
+                    thread::sleep(Duration::from_millis(READERS_FULL_RETRY_DELAY_MS));
+                }
+                Err(e) => panic!("Unable to begin a read transaction: {}", e),
+            }
+        };
+        Box::new(LMDBSnapshot {
+            txn: unsafe { mem::transmute::<RoTransaction, RoTransaction<'static>>(txn) },
+            db: self.db,
+            _env: Arc::clone(&self.env),
+        })
+    }
+
+    fn merge(&self, patch: Patch) -> storage::Result<()> {
+        self.do_merge(patch, false)
+    }
+
+    fn merge_sync(&self, patch: Patch) -> storage::Result<()> {
+        self.do_merge(patch, true)
+    }
+
+    /// Creates a compacted copy of the database file in the specified directory.
+    fn create_checkpoint(&self, path: &Path) -> storage::Result<()> {
+        if path.exists() {
+            return Err(storage::Error::new(format!(
+                "Checkpoint path {} already exists",
+                path.display()
+            )));
+        }
+        fs::create_dir_all(path).map_err(|e| storage::Error::new(e.to_string()))?;
+        let c_path = path
+            .to_str()
+            .and_then(|path| CString::new(path).ok())
+            .ok_or_else(|| storage::Error::new("Invalid checkpoint path"))?;
+        let code = unsafe { mdb_env_copy2(self.env.env(), c_path.as_ptr(), MDB_CP_COMPACT) };
+        if code != 0 {
+            return Err(lmdb::Error::from_err_code(code).into());
+        }
+        Ok(())
+    }
+}
+
+impl Snapshot for LMDBSnapshot {
+    fn get(&self, name: &str, key: &[u8]) -> Option<Vec<u8>> {
+        // Longer keys cannot be stored in the database.
+        if name.len() + 1 + key.len() > MAX_KEY_SIZE {
+            return None;
+        }
+        match self.txn.get(self.db, &encode_key(name, key)) {
+            Ok(value) => Some(value.to_vec()),
+            Err(lmdb::Error::NotFound) => None,
+            Err(e) => panic!("Unable to read a value from the index {}: {}", name, e),
+        }
+    }
+
+    fn iter<'a>(&'a self, name: &str, from: &[u8]) -> Iter<'a> {
+        let cursor = self
+            .txn
+            .open_ro_cursor(self.db)
+            .expect("Unable to open a cursor");
+        Box::new(LMDBIterator {
+            cursor,
+            prefix: encode_key(name, &[]),
+            from: Some(encode_key(name, from)),
+            reverse: false,
+            finished: false,
+            peeked: None,
+        })
+    }
+
+    fn iter_rev<'a>(&'a self, name: &str, before: Option<&[u8]>) -> Iter<'a> {
+        let cursor = self
+            .txn
+            .open_ro_cursor(self.db)
+            .expect("Unable to open a cursor");
+        let from = match before {
+            Some(before) => encode_key(name, before),
+            // The name followed by `1` is greater than all the keys of the index.
+            None => {
+                let mut from = name.as_bytes().to_vec();
+                from.push(1);
+                from
+            }
+        };
+        Box::new(LMDBIterator {
+            cursor,
+            prefix: encode_key(name, &[]),
+            from: Some(from),
+            reverse: true,
+            finished: false,
+            peeked: None,
+        })
+    }
+}
+
+impl<'a> LMDBIterator<'a> {
+    fn advance(&mut self) -> Option<(&'a [u8], &'a [u8])> {
+        // A cursor which has not been positioned moves to the first entry of the database
+        // on `MDB_NEXT`, so the iteration should not continue after its end.
+        if self.finished {
+            return None;
+        }
+        let result = match (self.from.take(), self.reverse) {
+            (Some(from), false) => self.cursor.get(Some(&from[..]), None, MDB_SET_RANGE),
+            (Some(from), true) => match self.cursor.get(Some(&from[..]), None, MDB_SET_RANGE) {
+                Ok(..) => self.cursor.get(None, None, MDB_PREV),
+                // All the keys in the database are less than `from`.
+                Err(lmdb::Error::NotFound) => self.cursor.get(None, None, MDB_LAST),
+                Err(e) => Err(e),
+            },
+            (None, false) => self.cursor.get(None, None, MDB_NEXT),
+            (None, true) => self.cursor.get(None, None, MDB_PREV),
+        };
+        match result {
+            Ok((Some(key), value)) if key.starts_with(&self.prefix) => {
+                Some((&key[self.prefix.len()..], value))
+            }
+            Ok(_) | Err(lmdb::Error::NotFound) => {
+                self.finished = true;
+                None
+            }
+            Err(e) => panic!(e),
+        }
+    }
+}
+
+impl<'a> Iterator for LMDBIterator<'a> {
+    fn next(&mut self) -> Option<(&[u8], &[u8])> {
+        match self.peeked.take() {
+            Some(peeked) => peeked,
+            None => self.advance(),
+        }
+    }
+
+    fn peek(&mut self) -> Option<(&[u8], &[u8])> {
+        if self.peeked.is_none() {
+            self.peeked = Some(self.advance());
+        }
+        self.peeked.unwrap()
+    }
+}
+
+impl From<LMDB> for Arc<dyn Database> {
+    fn from(db: LMDB) -> Self {
+        Self::from(Box::new(db) as Box<dyn Database>)
+    }
+}
+
+impl fmt::Debug for LMDB {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "LMDB(..)")
+    }
+}
+
+impl fmt::Debug for LMDBSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "LMDBSnapshot(..)")
+    }
+}
@@ -18,7 +18,7 @@
 //! the [`StorageValue`] trait. The given section contains methods related to
 //! `MapIndex` and iterators over the items of this map.
 
-use std::{borrow::Borrow, marker::PhantomData};
+use std::{borrow::Borrow, marker::PhantomData, ops::Range};
 
 use super::{
     base_index::{BaseIndex, BaseIndexIter},
@@ -42,11 +42,14 @@ pub struct MapIndex<T, K, V> {
 
 /// Returns an iterator over the entries of a `MapIndex`.
 ///
-/// This struct is created by the [`iter`] or
-/// [`iter_from`] method on [`MapIndex`]. See its documentation for additional details.
+/// This struct is created by the [`iter`], [`iter_from`], [`iter_rev`], [`iter_rev_from`]
+/// or [`range`] method on [`MapIndex`]. See its documentation for additional details.
 ///
 /// [`iter`]: struct.MapIndex.html#method.iter
 /// [`iter_from`]: struct.MapIndex.html#method.iter_from
+/// [`iter_rev`]: struct.MapIndex.html#method.iter_rev
+/// [`iter_rev_from`]: struct.MapIndex.html#method.iter_rev_from
+/// [`range`]: struct.MapIndex.html#method.range
 /// [`MapIndex`]: struct.MapIndex.html
 #[derive(Debug)]
 pub struct MapIndexIter<'a, K, V> {
@@ -342,6 +345,86 @@ where
             base_iter: self.base.iter_from(&(), from),
         }
     }
+
+    /// Returns an iterator over the entries of the map in descending order. The iterator element
+    /// type is (K, V).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use exonum::storage::{MemoryDB, Database, MapIndex};
+    ///
+    /// let db = MemoryDB::new();
+    /// let name = "name";
+    /// let mut fork = db.fork();
+    /// let mut index = MapIndex::new(name, &mut fork);
+    /// index.put(&1_u8, 10_u8);
+    /// index.put(&2_u8, 20_u8);
+    ///
+    /// let entries: Vec<_> = index.iter_rev().collect();
+    /// assert_eq!(entries, vec![(2, 20), (1, 10)]);
+    /// ```
+    pub fn iter_rev(&self) -> MapIndexIter<K, V> {
+        MapIndexIter {
+            base_iter: self.base.iter_rev(&()),
+        }
+    }
+
+    /// Returns an iterator over the entries of the map in descending order starting from the
+    /// specified key (inclusive). The iterator element type is (K, V).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use exonum::storage::{MemoryDB, Database, MapIndex};
+    ///
+    /// let db = MemoryDB::new();
+    /// let name = "name";
+    /// let snapshot = db.snapshot();
+    /// let index: MapIndex<_, u8, u8> = MapIndex::new(name, &snapshot);
+    ///
+    /// for v in index.iter_rev_from(&2) {
+    ///     println!("{:?}", v);
+    /// }
+    /// ```
+    pub fn iter_rev_from<Q>(&self, from: &Q) -> MapIndexIter<K, V>
+    where
+        K: Borrow<Q>,
+        Q: StorageKey + ?Sized,
+    {
+        MapIndexIter {
+            base_iter: self.base.iter_rev_from(&(), from),
+        }
+    }
+
+    /// Returns an iterator over the entries of the map in ascending order with the keys
+    /// in the specified range. The iterator element type is (K, V).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use exonum::storage::{MemoryDB, Database, MapIndex};
+    ///
+    /// let db = MemoryDB::new();
+    /// let name = "name";
+    /// let mut fork = db.fork();
+    /// let mut index = MapIndex::new(name, &mut fork);
+    /// for i in 0_u8..5 {
+    ///     index.put(&i, i);
+    /// }
+    ///
+    /// let keys: Vec<_> = index.range(&1..&4).map(|(k, _)| k).collect();
+    /// assert_eq!(keys, vec![1, 2, 3]);
+    /// ```
+    pub fn range<Q>(&self, range: Range<&Q>) -> MapIndexIter<K, V>
+    where
+        K: Borrow<Q>,
+        Q: StorageKey + ?Sized,
+    {
+        MapIndexIter {
+            base_iter: self.base.iter_range(&(), range.start, range.end),
+        }
+    }
 }
 
 impl<'a, K, V> MapIndex<&'a mut Fork, K, V>
@@ -631,6 +714,7 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "rocksdb-storage")]
     mod rocksdb_tests {
         use std::path::Path;
         use storage::Database;
@@ -658,4 +742,33 @@ mod tests {
             super::iter(db);
         }
     }
+
+    #[cfg(feature = "lmdb-storage")]
+    mod lmdb_tests {
+        use std::path::Path;
+        use storage::Database;
+        use tempdir::TempDir;
+
+        fn create_database(path: &Path) -> Box<dyn Database> {
+            use storage::{DbOptions, LMDB};
+            let opts = DbOptions::default();
+            Box::new(LMDB::open(path, &opts).unwrap())
+        }
+
+        #[test]
+        fn test_methods() {
+            let dir = TempDir::new(super::gen_tempdir_name().as_str()).unwrap();
+            let path = dir.path();
+            let db = create_database(path);
+            super::methods(db);
+        }
+
+        #[test]
+        fn test_iter() {
+            let dir = TempDir::new(super::gen_tempdir_name().as_str()).unwrap();
+            let path = dir.path();
+            let db = create_database(path);
+            super::iter(db);
+        }
+    }
 }
@@ -111,6 +111,21 @@ impl Snapshot for MemoryDB {
 
         Box::new(MemoryDBIter { data, index: 0 })
     }
+
+    fn iter_rev(&self, name: &str, before: Option<&[u8]>) -> Iter {
+        let map_guard = self.map.read().unwrap();
+        let data = match map_guard.get(name) {
+            Some(table) => table
+                .iter()
+                .rev()
+                .skip_while(|&(k, _)| before.map_or(false, |before| k.as_slice() >= before))
+                .map(|(k, v)| (k.to_vec(), v.to_vec()))
+                .collect(),
+            None => Vec::new(),
+        };
+
+        Box::new(MemoryDBIter { data, index: 0 })
+    }
 }
 
 impl Iterator for MemoryDBIter {
@@ -0,0 +1,600 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! An implementation of an append-only Merkelized list (Merkle mountain range).
+
+pub use self::proof::{ConsistencyProof, MmrProof, MmrProofError};
+
+use byteorder::{BigEndian, ByteOrder};
+
+use std::{cell::Cell, marker::PhantomData};
+
+use super::{
+    base_index::{BaseIndex, BaseIndexIter},
+    indexes_metadata::IndexType,
+    proof_list_index::ProofListKey,
+    Fork, Snapshot, StorageKey, StorageValue,
+};
+use crypto::{Hash, HashStream};
+
+mod proof;
+#[cfg(test)]
+mod tests;
+
+/// An append-only Merkelized list that provides proofs of existence for the list items
+/// and proofs of consistency between the states of the list.
+///
+/// `MmrIndex` implements a Merkle mountain range: the list is split into perfect binary
+/// Merkle trees (*peaks*) of decreasing sizes, which correspond to the binary representation
+/// of the list length. Appending an element only adds new nodes, so the root hash of a shorter
+/// list remains verifiable against a longer one. The root hash commits both to the length
+/// of the list and to the hashes of the peaks.
+///
+/// A client holding the root hash of an older state of the list may request
+/// a [`ConsistencyProof`] to make sure that the list has only been appended to since.
+/// `MmrIndex` requires that elements implement the [`StorageValue`] trait.
+///
+/// [`ConsistencyProof`]: struct.ConsistencyProof.html
+/// [`StorageValue`]: ../trait.StorageValue.html
+#[derive(Debug)]
+pub struct MmrIndex<T, V> {
+    base: BaseIndex<T>,
+    length: Cell<Option<u64>>,
+    _v: PhantomData<V>,
+}
+
+/// An iterator over the items of an `MmrIndex`.
+///
+/// This struct is created by the [`iter`] or
+/// [`iter_from`] method on [`MmrIndex`]. See its documentation for details.
+///
+/// [`iter`]: struct.MmrIndex.html#method.iter
+/// [`iter_from`]: struct.MmrIndex.html#method.iter_from
+/// [`MmrIndex`]: struct.MmrIndex.html
+#[derive(Debug)]
+pub struct MmrIndexIter<'a, V> {
+    base_iter: BaseIndexIter<'a, ProofListKey, V>,
+}
+
+fn hash_pair(left: &Hash, right: &Hash) -> Hash {
+    HashStream::new()
+        .update(left.as_ref())
+        .update(right.as_ref())
+        .hash()
+}
+
+/// Returns the keys of the peaks of the list with the given length from left to right.
+///
+/// Like in `ProofListIndex`, the node with the height `h` covers `2^(h - 1)` elements;
+/// the elements themselves are stored with the zero height.
+fn peaks(len: u64) -> Vec<ProofListKey> {
+    let mut peaks = Vec::new();
+    let mut start = 0;
+    for height in (0..64).rev() {
+        if len & (1 << height) != 0 {
+            peaks.push(ProofListKey::new(height + 1, start >> height));
+            start += 1 << height;
+        }
+    }
+    peaks
+}
+
+/// Returns the range of the elements covered by the node.
+fn leaves_range(key: ProofListKey) -> (u64, u64) {
+    let start = key.first_left_leaf_index();
+    (start, start + (1 << (key.height() - 1)))
+}
+
+/// Computes the root hash of the list with the given length from the hashes of its peaks.
+fn root_hash(len: u64, peaks: &[Hash]) -> Hash {
+    let bagged = match peaks.split_last() {
+        Some((last, rest)) => rest
+            .iter()
+            .rev()
+            .fold(*last, |acc, peak| hash_pair(peak, &acc)),
+        None => return Hash::zero(),
+    };
+    let mut len_bytes = [0; 8];
+    BigEndian::write_u64(&mut len_bytes, len);
+    HashStream::new()
+        .update(&len_bytes)
+        .update(bagged.as_ref())
+        .hash()
+}
+
+impl<T, V> MmrIndex<T, V>
+where
+    T: AsRef<dyn Snapshot>,
+    V: StorageValue,
+{
+    /// Creates a new index representation based on the name and storage view.
+    ///
+    /// Storage view can be specified as [`&Snapshot`] or [`&mut Fork`]. In the first case, only
+    /// immutable methods are available. In the second case, both immutable and mutable methods are
+    /// available.
+    ///
+    /// [`&Snapshot`]: ../trait.Snapshot.html
+    /// [`&mut Fork`]: ../struct.Fork.html
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use exonum::storage::{MemoryDB, Database, MmrIndex};
+    ///
+    /// let db = MemoryDB::new();
+    /// let name = "name";
+    ///
+    /// let snapshot = db.snapshot();
+    /// let index: MmrIndex<_, u8> = MmrIndex::new(name, &snapshot);
+    ///
+    /// let mut fork = db.fork();
+    /// let mut mut_index: MmrIndex<_, u8> = MmrIndex::new(name, &mut fork);
+    /// ```
+    pub fn new<S: AsRef<str>>(index_name: S, view: T) -> Self {
+        Self {
+            base: BaseIndex::new(index_name, IndexType::Mmr, view),
+            length: Cell::new(None),
+            _v: PhantomData,
+        }
+    }
+
+    /// Creates a new index representation based on the name, common prefix of its keys
+    /// and storage view.
+    ///
+    /// Storage view can be specified as [`&Snapshot`] or [`&mut Fork`]. In the first case, only
+    /// immutable methods are available. In the second case, both immutable and mutable methods are
+    /// available.
+    ///
+    /// [`&Snapshot`]: ../trait.Snapshot.html
+    /// [`&mut Fork`]: ../struct.Fork.html
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use exonum::storage::{MemoryDB, Database, MmrIndex};
+    ///
+    /// let db = MemoryDB::new();
+    /// let name = "name";
+    /// let index_id = vec![01];
+    ///
+    /// let snapshot = db.snapshot();
+    /// let index: MmrIndex<_, u8> = MmrIndex::new_in_family(name, &index_id, &snapshot);
+    ///
+    /// let mut fork = db.fork();
+    /// let mut mut_index: MmrIndex<_, u8> = MmrIndex::new_in_family(name, &index_id, &mut fork);
+    /// ```
+    pub fn new_in_family<S, I>(family_name: S, index_id: &I, view: T) -> Self
+    where
+        I: StorageKey,
+        I: ?Sized,
+        S: AsRef<str>,
+    {
+        Self {
+            base: BaseIndex::new_in_family(family_name, index_id, IndexType::Mmr, view),
+            length: Cell::new(None),
+            _v: PhantomData,
+        }
+    }
+
+    fn get_node(&self, key: ProofListKey) -> Hash {
+        debug_assert!(key.height() > 0);
+
+        self.base.get(&key).unwrap()
+    }
+
+    fn peak_hashes(&self, len: u64) -> Vec<Hash> {
+        peaks(len)
+            .into_iter()
+            .map(|key| self.get_node(key))
+            .collect()
+    }
+
+    /// Collects the hashes of the nodes, which are needed to compute the hash of the node
+    /// with the given key from the peaks of the list with the length `old_len`.
+    fn collect_consistency_nodes(&self, key: ProofListKey, old_len: u64, nodes: &mut Vec<Hash>) {
+        let (start, end) = leaves_range(key);
+        if end <= old_len {
+            // The node is a peak of the old list.
+            return;
+        }
+        if start >= old_len {
+            nodes.push(self.get_node(key));
+            return;
+        }
+        self.collect_consistency_nodes(key.left(), old_len, nodes);
+        self.collect_consistency_nodes(key.right(), old_len, nodes);
+    }
+
+    /// Returns the element at the indicated position or `None` if the indicated position
+    /// is out of bounds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use exonum::storage::{MemoryDB, Database, MmrIndex};
+    ///
+    /// let db = MemoryDB::new();
+    /// let name = "name";
+    /// let mut fork = db.fork();
+    /// let mut index = MmrIndex::new(name, &mut fork);
+    /// assert_eq!(None, index.get(0));
+    ///
+    /// index.push(10);
+    /// assert_eq!(Some(10), index.get(0));
+    /// ```
+    pub fn get(&self, index: u64) -> Option<V> {
+        self.base.get(&ProofListKey::leaf(index))
+    }
+
+    /// Returns the last element of the list or `None` if it is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use exonum::storage::{MemoryDB, Database, MmrIndex};
+    ///
+    /// let db = MemoryDB::new();
+    /// let name = "name";
+    /// let mut fork = db.fork();
+    /// let mut index = MmrIndex::new(name, &mut fork);
+    /// assert_eq!(None, index.last());
+    ///
+    /// index.push(1);
+    /// assert_eq!(Some(1), index.last());
+    /// ```
+    pub fn last(&self) -> Option<V> {
+        match self.len() {
+            0 => None,
+            l => self.get(l - 1),
+        }
+    }
+
+    /// Returns `true` if the list contains no elements.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use exonum::storage::{MemoryDB, Database, MmrIndex};
+    ///
+    /// let db = MemoryDB::new();
+    /// let name = "name";
+    /// let mut fork = db.fork();
+    /// let mut index = MmrIndex::new(name, &mut fork);
+    /// assert!(index.is_empty());
+    ///
+    /// index.push(10);
+    /// assert!(!index.is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of elements in the list.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use exonum::storage::{MemoryDB, Database, MmrIndex};
+    ///
+    /// let db = MemoryDB::new();
+    /// let name = "name";
+    /// let mut fork = db.fork();
+    /// let mut index = MmrIndex::new(name, &mut fork);
+    /// assert_eq!(0, index.len());
+    ///
+    /// index.push(1);
+    /// assert_eq!(1, index.len());
+    /// ```
+    pub fn len(&self) -> u64 {
+        if let Some(len) = self.length.get() {
+            return len;
+        }
+        let len = self.base.get(&()).unwrap_or(0);
+        self.length.set(Some(len));
+        len
+    }
+
+    /// Returns the root hash of the list or the default hash value if it is empty.
+    /// The default hash consists solely of zeroes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use exonum::storage::{MemoryDB, Database, MmrIndex};
+    /// use exonum::crypto::Hash;
+    ///
+    /// let db = MemoryDB::new();
+    /// let name = "name";
+    /// let mut fork = db.fork();
+    /// let mut index = MmrIndex::new(name, &mut fork);
+    ///
+    /// let default_hash = index.merkle_root();
+    /// assert_eq!(Hash::default(), default_hash);
+    ///
+    /// index.push(1);
+    /// let hash = index.merkle_root();
+    /// assert_ne!(hash, default_hash);
+    /// ```
+    pub fn merkle_root(&self) -> Hash {
+        let len = self.len();
+        root_hash(len, &self.peak_hashes(len))
+    }
+
+    /// Returns the proof of existence for the list element at the specified position.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use exonum::storage::{MemoryDB, Database, MmrIndex};
+    ///
+    /// let db = MemoryDB::new();
+    /// let name = "name";
+    /// let mut fork = db.fork();
+    /// let mut index = MmrIndex::new(name, &mut fork);
+    ///
+    /// index.extend([1, 2, 3].iter().cloned());
+    ///
+    /// let proof = index.get_proof(1);
+    /// assert_eq!(proof.check(index.merkle_root()).unwrap(), &2);
+    /// ```
+    pub fn get_proof(&self, index: u64) -> MmrProof<V> {
+        let len = self.len();
+        if index >= len {
+            panic!(
+                "Index out of bounds: the len is {} but the index is {}",
+                len, index
+            );
+        }
+
+        let mut path = Vec::new();
+        let mut other_peaks = Vec::new();
+        for peak in peaks(len) {
+            let (start, end) = leaves_range(peak);
+            if start <= index && index < end {
+                let mut key = ProofListKey::new(1, index);
+                while key.height() < peak.height() {
+                    let sibling = if key.is_left() {
+                        key.as_right()
+                    } else {
+                        key.as_left()
+                    };
+                    path.push(self.get_node(sibling));
+                    key = key.parent();
+                }
+            } else {
+                other_peaks.push(self.get_node(peak));
+            }
+        }
+
+        MmrProof {
+            index,
+            length: len,
+            value: self.get(index).unwrap(),
+            path,
+            peaks: other_peaks,
+        }
+    }
+
+    /// Returns the proof that the list with the length `old_len` is a prefix of the current
+    /// list, that is, the current list has been obtained from the old one only by appending
+    /// elements.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `old_len` is greater than the length of the list.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use exonum::storage::{MemoryDB, Database, MmrIndex};
+    ///
+    /// let db = MemoryDB::new();
+    /// let name = "name";
+    /// let mut fork = db.fork();
+    /// let mut index = MmrIndex::new(name, &mut fork);
+    ///
+    /// index.extend([1, 2, 3].iter().cloned());
+    /// let old_root = index.merkle_root();
+    /// index.extend([4, 5].iter().cloned());
+    ///
+    /// let proof = index.get_consistency_proof(3);
+    /// assert!(proof.check(old_root, index.merkle_root()).is_ok());
+    /// ```
+    pub fn get_consistency_proof(&self, old_len: u64) -> ConsistencyProof {
+        let len = self.len();
+        if old_len > len {
+            panic!(
+                "Illegal old length: the len is {} but the old length is {}",
+                len, old_len
+            );
+        }
+
+        let mut nodes = Vec::new();
+        for peak in peaks(len) {
+            self.collect_consistency_nodes(peak, old_len, &mut nodes);
+        }
+        ConsistencyProof {
+            old_length: old_len,
+            length: len,
+            old_peaks: self.peak_hashes(old_len),
+            nodes,
+        }
+    }
+
+    /// Returns an iterator over the list. The iterator element type is V.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use exonum::storage::{MemoryDB, Database, MmrIndex};
+    ///
+    /// let db = MemoryDB::new();
+    /// let name = "name";
+    /// let snapshot = db.snapshot();
+    /// let index: MmrIndex<_, u8> = MmrIndex::new(name, &snapshot);
+    ///
+    /// for val in index.iter() {
+    ///     println!("{}", val);
+    /// }
+    /// ```
+    pub fn iter(&self) -> MmrIndexIter<V> {
+        MmrIndexIter {
+            base_iter: self.base.iter(&0_u8),
+        }
+    }
+
+    /// Returns an iterator over the list starting from the specified position. The iterator
+    /// element type is V.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use exonum::storage::{MemoryDB, Database, MmrIndex};
+    ///
+    /// let db = MemoryDB::new();
+    /// let name = "name";
+    /// let snapshot = db.snapshot();
+    /// let index: MmrIndex<_, u8> = MmrIndex::new(name, &snapshot);
+    ///
+    /// for val in index.iter_from(1) {
+    ///     println!("{}", val);
+    /// }
+    /// ```
+    pub fn iter_from(&self, from: u64) -> MmrIndexIter<V> {
+        MmrIndexIter {
+            base_iter: self.base.iter_from(&0_u8, &ProofListKey::leaf(from)),
+        }
+    }
+}
+
+impl<'a, V> MmrIndex<&'a mut Fork, V>
+where
+    V: StorageValue,
+{
+    fn set_len(&mut self, len: u64) {
+        self.base.put(&(), len);
+        self.length.set(Some(len));
+    }
+
+    /// Appends an element to the back of the list.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use exonum::storage::{MemoryDB, Database, MmrIndex};
+    ///
+    /// let db = MemoryDB::new();
+    /// let name = "name";
+    /// let mut fork = db.fork();
+    /// let mut index = MmrIndex::new(name, &mut fork);
+    ///
+    /// index.push(1);
+    /// assert!(!index.is_empty());
+    /// ```
+    pub fn push(&mut self, value: V) {
+        let len = self.len();
+        let mut key = ProofListKey::new(1, len);
+        let mut hash = value.hash();
+        self.base.put(&key, hash);
+        self.base.put(&ProofListKey::leaf(len), value);
+        // The new element completes the trees, in which it is the rightmost leaf.
+        while !key.is_left() {
+            hash = hash_pair(&self.get_node(key.as_left()), &hash);
+            key = key.parent();
+            self.base.put(&key, hash);
+        }
+        self.set_len(len + 1);
+    }
+
+    /// Extends the list with the contents of an iterator.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use exonum::storage::{MemoryDB, Database, MmrIndex};
+    ///
+    /// let db = MemoryDB::new();
+    /// let name = "name";
+    /// let mut fork = db.fork();
+    /// let mut index = MmrIndex::new(name, &mut fork);
+    ///
+    /// index.extend([1, 2, 3].iter().cloned());
+    /// assert_eq!(3, index.len());
+    /// ```
+    pub fn extend<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = V>,
+    {
+        for value in iter {
+            self.push(value)
+        }
+    }
+
+    /// Clears the list, removing all values.
+    ///
+    /// # Notes
+    ///
+    /// Currently, this method is not optimized to delete a large set of data. During the execution of
+    /// this method, the amount of allocated memory is linearly dependent on the number of elements
+    /// in the index.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use exonum::storage::{MemoryDB, Database, MmrIndex};
+    ///
+    /// let db = MemoryDB::new();
+    /// let name = "name";
+    /// let mut fork = db.fork();
+    /// let mut index = MmrIndex::new(name, &mut fork);
+    ///
+    /// index.push(1);
+    /// assert!(!index.is_empty());
+    ///
+    /// index.clear();
+    /// assert!(index.is_empty());
+    /// ```
+    pub fn clear(&mut self) {
+        self.length.set(Some(0));
+        self.base.clear()
+    }
+}
+
+impl<'a, T, V> ::std::iter::IntoIterator for &'a MmrIndex<T, V>
+where
+    T: AsRef<dyn Snapshot>,
+    V: StorageValue,
+{
+    type Item = V;
+    type IntoIter = MmrIndexIter<'a, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, V> Iterator for MmrIndexIter<'a, V>
+where
+    V: StorageValue,
+{
+    type Item = V;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.base_iter.next().map(|(_, v)| v)
+    }
+}
@@ -0,0 +1,178 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::slice;
+
+use super::{super::StorageValue, hash_pair, leaves_range, peaks, root_hash, ProofListKey};
+use crypto::Hash;
+
+/// A proof of existence for an element of `MmrIndex`.
+///
+/// The proof consists of the hashes of the siblings on the path from the element
+/// to the peak containing it and the hashes of the other peaks.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MmrProof<V> {
+    pub(super) index: u64,
+    pub(super) length: u64,
+    pub(super) value: V,
+    pub(super) path: Vec<Hash>,
+    pub(super) peaks: Vec<Hash>,
+}
+
+/// A proof that a list is a prefix of another list, which has been obtained from it
+/// by appending elements.
+///
+/// The proof consists of the hashes of the peaks of the old list and the hashes of the nodes
+/// of the new list covering only the appended elements, which are needed to compute
+/// the peaks of the new list.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConsistencyProof {
+    pub(super) old_length: u64,
+    pub(super) length: u64,
+    pub(super) old_peaks: Vec<Hash>,
+    pub(super) nodes: Vec<Hash>,
+}
+
+/// An error returned when a proof of `MmrIndex` is invalid.
+#[derive(Debug, Fail)]
+pub enum MmrProofError {
+    /// The index of the element is not less than the length of the list.
+    #[fail(display = "index of the element is out of bounds")]
+    IndexOutOfBounds,
+
+    /// The old length of the list is greater than the new one.
+    #[fail(display = "old length of the list is greater than the new one")]
+    InvalidLengths,
+
+    /// The number of hashes in the proof does not correspond to the length of the list.
+    #[fail(display = "number of hashes does not correspond to the length of the list")]
+    InvalidHashesCount,
+
+    /// The hash of the proof is not equal to the trusted root hash.
+    #[fail(display = "root hash does not match the trusted one")]
+    UnmatchedRootHash,
+}
+
+impl<V: StorageValue> MmrProof<V> {
+    /// Returns the index of the element.
+    pub fn index(&self) -> u64 {
+        self.index
+    }
+
+    /// Returns the length of the list.
+    pub fn length(&self) -> u64 {
+        self.length
+    }
+
+    /// Verifies the correctness of the proof by the trusted root hash of the list.
+    ///
+    /// If the proof is valid, a reference to the element is returned.
+    /// Otherwise, `Err` is returned.
+    pub fn check(&self, merkle_root: Hash) -> Result<&V, MmrProofError> {
+        if self.index >= self.length {
+            return Err(MmrProofError::IndexOutOfBounds);
+        }
+        let peak_keys = peaks(self.length);
+        if self.peaks.len() + 1 != peak_keys.len() {
+            return Err(MmrProofError::InvalidHashesCount);
+        }
+        let position = peak_keys
+            .iter()
+            .position(|&key| leaves_range(key).1 > self.index)
+            .unwrap();
+        if self.path.len() + 1 != peak_keys[position].height() as usize {
+            return Err(MmrProofError::InvalidHashesCount);
+        }
+
+        let mut index = self.index;
+        let mut hash = self.value.hash();
+        for sibling in &self.path {
+            hash = if index & 1 == 0 {
+                hash_pair(&hash, sibling)
+            } else {
+                hash_pair(sibling, &hash)
+            };
+            index >>= 1;
+        }
+        let mut peaks = self.peaks.clone();
+        peaks.insert(position, hash);
+        if root_hash(self.length, &peaks) != merkle_root {
+            return Err(MmrProofError::UnmatchedRootHash);
+        }
+        Ok(&self.value)
+    }
+}
+
+impl ConsistencyProof {
+    /// Returns the length of the old list.
+    pub fn old_length(&self) -> u64 {
+        self.old_length
+    }
+
+    /// Returns the length of the new list.
+    pub fn length(&self) -> u64 {
+        self.length
+    }
+
+    /// Verifies that the list with the root hash `old_root` is a prefix of the list
+    /// with the root hash `new_root`.
+    pub fn check(&self, old_root: Hash, new_root: Hash) -> Result<(), MmrProofError> {
+        if self.old_length > self.length {
+            return Err(MmrProofError::InvalidLengths);
+        }
+        if self.old_peaks.len() != peaks(self.old_length).len() {
+            return Err(MmrProofError::InvalidHashesCount);
+        }
+        if root_hash(self.old_length, &self.old_peaks) != old_root {
+            return Err(MmrProofError::UnmatchedRootHash);
+        }
+
+        let mut old_peaks = self.old_peaks.iter();
+        let mut nodes = self.nodes.iter();
+        let mut new_peaks = Vec::new();
+        for key in peaks(self.length) {
+            new_peaks.push(self.compute_node(key, &mut old_peaks, &mut nodes)?);
+        }
+        if nodes.next().is_some() {
+            return Err(MmrProofError::InvalidHashesCount);
+        }
+        if root_hash(self.length, &new_peaks) != new_root {
+            return Err(MmrProofError::UnmatchedRootHash);
+        }
+        Ok(())
+    }
+
+    /// Computes the hash of the node of the new list in the same order, in which
+    /// the nodes are collected by `MmrIndex`. The nodes covering only the old elements
+    /// are exactly the peaks of the old list.
+    fn compute_node(
+        &self,
+        key: ProofListKey,
+        old_peaks: &mut slice::Iter<Hash>,
+        nodes: &mut slice::Iter<Hash>,
+    ) -> Result<Hash, MmrProofError> {
+        let (start, end) = leaves_range(key);
+        let hash = if end <= self.old_length {
+            *old_peaks.next().ok_or(MmrProofError::InvalidHashesCount)?
+        } else if start >= self.old_length {
+            *nodes.next().ok_or(MmrProofError::InvalidHashesCount)?
+        } else {
+            hash_pair(
+                &self.compute_node(key.left(), old_peaks, nodes)?,
+                &self.compute_node(key.right(), old_peaks, nodes)?,
+            )
+        };
+        Ok(hash)
+    }
+}
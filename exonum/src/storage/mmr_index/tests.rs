@@ -0,0 +1,198 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::{hash_pair, root_hash, ConsistencyProof, MmrIndex, MmrProof, MmrProofError};
+use crypto::{CryptoHash, Hash};
+use encoding::serialize::json::reexport::{from_str, to_string};
+use storage::{Database, Fork, MemoryDB};
+
+const IDX_NAME: &str = "idx_name";
+const MAX_LEN: u64 = 33;
+
+/// Computes the root hash of the list directly from its elements.
+fn naive_root(values: &[u64]) -> Hash {
+    fn tree_hash(hashes: &[Hash]) -> Hash {
+        if hashes.len() == 1 {
+            return hashes[0];
+        }
+        let (left, right) = hashes.split_at(hashes.len() / 2);
+        hash_pair(&tree_hash(left), &tree_hash(right))
+    }
+
+    let hashes: Vec<_> = values.iter().map(CryptoHash::hash).collect();
+    let mut peaks = Vec::new();
+    let mut start = 0;
+    while start < hashes.len() {
+        let size = (hashes.len() - start + 1).next_power_of_two() / 2;
+        peaks.push(tree_hash(&hashes[start..start + size]));
+        start += size;
+    }
+    root_hash(values.len() as u64, &peaks)
+}
+
+fn mmr_roots(fork: &mut Fork) -> Vec<Hash> {
+    let mut index = MmrIndex::new(IDX_NAME, fork);
+    let mut roots = vec![index.merkle_root()];
+    for i in 0..MAX_LEN {
+        index.push(i);
+        roots.push(index.merkle_root());
+    }
+    roots
+}
+
+#[test]
+fn merkle_root() {
+    let db = MemoryDB::new();
+    let mut fork = db.fork();
+    let roots = mmr_roots(&mut fork);
+
+    assert_eq!(roots[0], Hash::zero());
+    for len in 0..=MAX_LEN {
+        let values: Vec<_> = (0..len).collect();
+        assert_eq!(roots[len as usize], naive_root(&values), "len = {}", len);
+    }
+
+    let index: MmrIndex<_, u64> = MmrIndex::new(IDX_NAME, &fork);
+    assert_eq!(index.len(), MAX_LEN);
+    assert_eq!(index.last(), Some(MAX_LEN - 1));
+    assert_eq!(
+        index.iter().collect::<Vec<_>>(),
+        (0..MAX_LEN).collect::<Vec<_>>()
+    );
+    assert_eq!(index.iter_from(30).collect::<Vec<_>>(), vec![30, 31, 32]);
+}
+
+#[test]
+fn inclusion_proofs() {
+    let db = MemoryDB::new();
+    let mut fork = db.fork();
+    let mut index = MmrIndex::new(IDX_NAME, &mut fork);
+    for len in 1..=MAX_LEN {
+        index.push(len - 1);
+        let root = index.merkle_root();
+        for i in 0..len {
+            let proof = index.get_proof(i);
+            assert_eq!(proof.index(), i);
+            assert_eq!(proof.length(), len);
+            assert_eq!(*proof.check(root).unwrap(), i);
+        }
+    }
+
+    let root = index.merkle_root();
+    let proof = index.get_proof(5);
+    assert!(proof.check(Hash::zero()).is_err());
+
+    let mut tampered = proof.clone();
+    tampered.value = 6;
+    match tampered.check(root).unwrap_err() {
+        MmrProofError::UnmatchedRootHash => {}
+        e => panic!("Unexpected error: {:?}", e),
+    }
+    let mut tampered = proof.clone();
+    tampered.length = MAX_LEN + 1;
+    assert!(tampered.check(root).is_err());
+    let mut tampered = proof.clone();
+    tampered.index = MAX_LEN;
+    match tampered.check(root).unwrap_err() {
+        MmrProofError::IndexOutOfBounds => {}
+        e => panic!("Unexpected error: {:?}", e),
+    }
+    let mut tampered = proof.clone();
+    tampered.path.pop();
+    match tampered.check(root).unwrap_err() {
+        MmrProofError::InvalidHashesCount => {}
+        e => panic!("Unexpected error: {:?}", e),
+    }
+}
+
+#[test]
+fn consistency_proofs() {
+    let db = MemoryDB::new();
+    let mut fork = db.fork();
+    let roots = mmr_roots(&mut fork);
+    let index: MmrIndex<_, u64> = MmrIndex::new(IDX_NAME, &fork);
+
+    for old_len in 0..=MAX_LEN {
+        let proof = index.get_consistency_proof(old_len);
+        assert_eq!(proof.old_length(), old_len);
+        assert_eq!(proof.length(), MAX_LEN);
+        proof
+            .check(roots[old_len as usize], roots[MAX_LEN as usize])
+            .unwrap();
+        if old_len < MAX_LEN {
+            assert!(proof
+                .check(roots[old_len as usize], roots[MAX_LEN as usize - 1])
+                .is_err());
+        }
+    }
+
+    // The old list is not a prefix of the new one.
+    let mut other_fork = db.fork();
+    {
+        let mut other = MmrIndex::new(IDX_NAME, &mut other_fork);
+        other.extend(vec![0_u64, 1, 2, 100, 4]);
+    }
+    let other_root = MmrIndex::<_, u64>::new(IDX_NAME, &other_fork).merkle_root();
+    let proof = index.get_consistency_proof(5);
+    assert!(proof.check(other_root, roots[MAX_LEN as usize]).is_err());
+
+    let mut tampered = proof.clone();
+    tampered.old_peaks = MmrIndex::<_, u64>::new(IDX_NAME, &other_fork)
+        .get_consistency_proof(5)
+        .old_peaks;
+    match tampered
+        .check(other_root, roots[MAX_LEN as usize])
+        .unwrap_err()
+    {
+        MmrProofError::UnmatchedRootHash => {}
+        e => panic!("Unexpected error: {:?}", e),
+    }
+    let mut tampered = proof.clone();
+    tampered.nodes.push(Hash::zero());
+    match tampered
+        .check(roots[5], roots[MAX_LEN as usize])
+        .unwrap_err()
+    {
+        MmrProofError::InvalidHashesCount => {}
+        e => panic!("Unexpected error: {:?}", e),
+    }
+}
+
+#[test]
+fn proofs_serialization() {
+    let db = MemoryDB::new();
+    let mut fork = db.fork();
+    let mut index = MmrIndex::new(IDX_NAME, &mut fork);
+    index.extend(vec![1_u64, 2, 3, 4, 5]);
+
+    let proof = index.get_proof(2);
+    let json = to_string(&proof).unwrap();
+    let deserialized: MmrProof<u64> = from_str(&json).unwrap();
+    assert_eq!(deserialized, proof);
+
+    let proof = index.get_consistency_proof(3);
+    let json = to_string(&proof).unwrap();
+    let deserialized: ConsistencyProof = from_str(&json).unwrap();
+    assert_eq!(deserialized, proof);
+}
+
+#[test]
+#[should_panic(expected = "Index out of bounds")]
+fn proof_out_of_bounds() {
+    let db = MemoryDB::new();
+    let mut fork = db.fork();
+    let mut index = MmrIndex::new(IDX_NAME, &mut fork);
+    index.push(1_u64);
+    index.get_proof(1);
+}
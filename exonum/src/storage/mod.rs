@@ -24,7 +24,13 @@
 //! that is, the Exonum process has exclusive access to the DB during blockchain operation.
 //! You can interact with the `Database` from multiple threads by cloning its instance.
 //!
-//! Exonum provides two database types: [`RocksDB`] and [`MemoryDB`].
+//! Exonum provides the following database types: [`RocksDB`], [`LMDB`] and [`MemoryDB`].
+//! The persistent databases are available with the `rocksdb-storage` (enabled by default)
+//! and `lmdb-storage` features; [`open_database`] opens the database with the backend
+//! specified in [`DbOptions`]. Reads of a database can be sped up with an in-memory
+//! [`CachedDB`] wrapper, which `open_database` adds if the cache size is specified
+//! in the options. Similarly, the data can be encrypted at rest with the [`EncryptedDB`]
+//! wrapper.
 //!
 //! # Snapshot and Fork
 //!
@@ -76,11 +82,22 @@
 //!   proofs of existence and is implemented as a Merkle tree.
 //! - [`ProofMapIndex`] is a Merkelized version of `MapIndex` that supports cryptographic
 //!   proofs of existence and is implemented as a binary Merkle Patricia tree.
+//! - [`MmrIndex`] is an append-only Merkelized list implemented as a Merkle mountain range.
+//!   Besides proofs of existence, it supports proofs that the list has only been appended to
+//!   since a certain length.
 //! - [`KeySetIndex`] and [`ValueSetIndex`] is a set of items, similar to [`BTreeSet`] and
 //!   [`HashSet`].
 //!
+//! The indexes registered in the storage, along with their types and sizes, can be listed
+//! with [`index_catalogue`].
+//!
 //! [`Database`]: trait.Database.html
 //! [`RocksDB`]: struct.RocksDB.html
+//! [`LMDB`]: struct.LMDB.html
+//! [`open_database`]: fn.open_database.html
+//! [`DbOptions`]: struct.DbOptions.html
+//! [`CachedDB`]: struct.CachedDB.html
+//! [`EncryptedDB`]: struct.EncryptedDB.html
 //! [`MemoryDB`]: struct.MemoryDB.html
 //! [`Snapshot`]: trait.Snapshot.html
 //! [`Fork`]: struct.Fork.html
@@ -96,8 +113,10 @@
 //! [`MapIndex`]: map_index/struct.MapIndex.html
 //! [`ProofListIndex`]: proof_list_index/struct.ProofListIndex.html
 //! [`ProofMapIndex`]: proof_map_index/struct.ProofMapIndex.html
+//! [`MmrIndex`]: mmr_index/struct.MmrIndex.html
 //! [`KeySetIndex`]: key_set_index/struct.KeySetIndex.html
 //! [`ValueSetIndex`]: value_set_index/struct.ValueSetIndex.html
+//! [`index_catalogue`]: fn.index_catalogue.html
 //! [doc:storage]: https://exonum.com/doc/architecture/storage
 //! [`Option`]: https://doc.rust-lang.org/std/option/enum.Option.html
 //! [`Box`]: https://doc.rust-lang.org/std/boxed/struct.Box.html
@@ -106,49 +125,77 @@
 //! [`BTreeSet`]: https://doc.rust-lang.org/std/collections/struct.BTreeSet.html
 //! [`HashSet`]: https://doc.rust-lang.org/std/collections/struct.HashSet.html
 
-pub(crate) use self::indexes_metadata::StorageMetadata;
+pub(crate) use self::db::PatchedSnapshot;
+pub(crate) use self::indexes_metadata::{
+    index_names, StorageMetadata, INDEXES_METADATA_TABLE_NAME,
+};
 
 #[doc(no_inline)]
-pub use self::proof_map_index::{HashedKey, MapProof, ProofMapIndex};
+pub use self::proof_map_index::{HashedKey, MapProof, MapRangeProof, ProofMapIndex};
 pub use self::{
+    cache::CachedDB,
+    catalogue::{index_catalogue, IndexCounts, IndexInfo},
     db::{
-        Change, Changes, ChangesIterator, Database, Fork, Iter, Iterator, Patch, PatchIterator,
-        Snapshot,
+        Change, Changes, ChangesIterator, Database, DecodedValue, Fork, Iter, Iterator, Patch,
+        PatchIterator, Snapshot,
     },
     entry::Entry,
     error::Error,
     hash::UniqueHash,
+    indexes_metadata::{check_index_type, IndexType},
+    integrity::{check_merkelized_indexes, hash_storage_value, IntegrityError, ValueHashFn},
     key_set_index::KeySetIndex,
     keys::StorageKey,
     list_index::ListIndex,
     map_index::MapIndex,
     memorydb::MemoryDB,
-    options::DbOptions,
+    mmr_index::{ConsistencyProof, MmrIndex, MmrProof},
+    options::{
+        open_database, rotate_encryption_key, DbBackend, DbEncryption, DbOptions, EncryptionKey,
+        RocksDbCompression, RocksDbProfile, RocksDbTuning, DB_ENCRYPTION_KEY_ENV,
+        ENCRYPTION_KEY_LENGTH,
+    },
     proof_list_index::{ListProof, ProofListIndex},
-    rocksdb::RocksDB,
     sparse_list_index::SparseListIndex,
     value_set_index::ValueSetIndex,
     values::StorageValue,
 };
 
+#[cfg(feature = "sodiumoxide-crypto")]
+pub use self::encryption::EncryptedDB;
+#[cfg(feature = "lmdb-storage")]
+pub use self::lmdb::LMDB;
+#[cfg(feature = "rocksdb-storage")]
+pub use self::rocksdb::RocksDB;
+
 /// A specialized `Result` type for I/O operations with storage.
 pub type Result<T> = ::std::result::Result<T, Error>;
 
 mod base_index;
+mod cache;
+mod catalogue;
 mod db;
 mod entry;
 mod error;
 mod hash;
 mod indexes_metadata;
+mod integrity;
 mod keys;
 mod memorydb;
 mod options;
-mod rocksdb;
 mod values;
 
+#[cfg(feature = "sodiumoxide-crypto")]
+mod encryption;
+#[cfg(feature = "lmdb-storage")]
+mod lmdb;
+#[cfg(feature = "rocksdb-storage")]
+mod rocksdb;
+
 pub mod key_set_index;
 pub mod list_index;
 pub mod map_index;
+pub mod mmr_index;
 pub mod proof_list_index;
 pub mod proof_map_index;
 pub mod sparse_list_index;
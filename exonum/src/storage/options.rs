@@ -14,11 +14,301 @@
 
 //! Abstract settings for databases.
 
+use failure;
+use hex;
+use rand::{thread_rng, RngCore};
+
+use std::{
+    env, fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+#[cfg(feature = "sodiumoxide-crypto")]
+use super::EncryptedDB;
+#[cfg(feature = "lmdb-storage")]
+use super::LMDB;
+#[cfg(feature = "rocksdb-storage")]
+use super::RocksDB;
+use super::{CachedDB, Database, Error, Result};
+
+/// Length of the database encryption key in bytes.
+pub const ENCRYPTION_KEY_LENGTH: usize = 32;
+
+/// Environment variable with the hex-encoded database encryption key, which is used
+/// if the key file is not specified.
+pub const DB_ENCRYPTION_KEY_ENV: &str = "EXONUM_DB_ENCRYPTION_KEY";
+
+/// Persistent database backends.
+///
+/// A backend is available only if the crate is compiled with the corresponding feature:
+/// `rocksdb-storage` (enabled by default) for `RocksDB` and `lmdb-storage` for `LMDB`.
+/// Databases created by different backends are not compatible with each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DbBackend {
+    /// [`RocksDB`](struct.RocksDB.html) backend, which is used by default.
+    #[serde(rename = "rocksdb")]
+    RocksDB,
+    /// [`LMDB`](struct.LMDB.html) backend.
+    #[serde(rename = "lmdb")]
+    LMDB,
+}
+
+impl Default for DbBackend {
+    fn default() -> Self {
+        DbBackend::RocksDB
+    }
+}
+
+impl fmt::Display for DbBackend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DbBackend::RocksDB => write!(f, "rocksdb"),
+            DbBackend::LMDB => write!(f, "lmdb"),
+        }
+    }
+}
+
+impl FromStr for DbBackend {
+    type Err = failure::Error;
+
+    fn from_str(s: &str) -> ::std::result::Result<Self, Self::Err> {
+        match s {
+            "rocksdb" => Ok(DbBackend::RocksDB),
+            "lmdb" => Ok(DbBackend::LMDB),
+            _ => bail!("Unknown database backend: {}", s),
+        }
+    }
+}
+
+/// Compression algorithms supported by `RocksDB`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RocksDbCompression {
+    /// No compression.
+    None,
+    /// `Snappy` compression, which is used by `RocksDB` by default.
+    Snappy,
+    /// `zlib` compression.
+    Zlib,
+    /// `bzip2` compression.
+    Bz2,
+    /// `LZ4` compression.
+    Lz4,
+    /// `LZ4HC`, the high compression mode of `LZ4`.
+    Lz4hc,
+}
+
+/// Tuning options of a `RocksDB` column family.
+///
+/// `RocksDB` stores each index (or index family) in a separate column family. The options
+/// which are not specified keep the `RocksDB` defaults.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct RocksDbProfile {
+    /// Compression of the data blocks.
+    #[serde(default)]
+    pub compression: Option<RocksDbCompression>,
+    /// Size of a memtable in bytes. Bigger memtables speed up writes at the cost
+    /// of the memory usage and the recovery time.
+    #[serde(default)]
+    pub write_buffer_size: Option<usize>,
+    /// Maximum number of memtables, including the ones being flushed.
+    #[serde(default)]
+    pub max_write_buffer_number: Option<i32>,
+    /// Size of a data block in bytes.
+    #[serde(default)]
+    pub block_size: Option<usize>,
+    /// Size of the LRU cache of data blocks in bytes. Note that the cache is allocated
+    /// for each column family separately.
+    #[serde(default)]
+    pub block_cache_size: Option<usize>,
+    /// Number of bits per key in the bloom filters, which allow to skip the data blocks
+    /// not containing a key on reads. `10` bits give about 1% of false positives.
+    #[serde(default)]
+    pub bloom_filter_bits: Option<i32>,
+}
+
+impl RocksDbProfile {
+    /// Returns the profile with the options of `self` replaced by the ones specified
+    /// in `other`.
+    pub(crate) fn overridden_by(self, other: Option<Self>) -> Self {
+        let other = match other {
+            Some(other) => other,
+            None => return self,
+        };
+        Self {
+            compression: other.compression.or(self.compression),
+            write_buffer_size: other.write_buffer_size.or(self.write_buffer_size),
+            max_write_buffer_number: other
+                .max_write_buffer_number
+                .or(self.max_write_buffer_number),
+            block_size: other.block_size.or(self.block_size),
+            block_cache_size: other.block_cache_size.or(self.block_cache_size),
+            bloom_filter_bits: other.bloom_filter_bits.or(self.bloom_filter_bits),
+        }
+    }
+}
+
+/// Tuning options of `RocksDB`.
+///
+/// The `common` profile applies to all the column families, and can be overridden
+/// for lists (`ListIndex`, `ProofListIndex` and `SparseListIndex`) and maps
+/// (`MapIndex`, `ProofMapIndex`, `KeySetIndex` and `ValueSetIndex`). For example, lists
+/// which are only appended to may be compressed harder, while maps read often may
+/// benefit from a larger cache and bloom filters:
+///
+/// ```toml
+/// [database.rocksdb.common]
+/// compression = "lz4"
+///
+/// [database.rocksdb.lists]
+/// compression = "zlib"
+///
+/// [database.rocksdb.maps]
+/// block_cache_size = 67108864
+/// bloom_filter_bits = 10
+/// ```
+///
+/// The index types are taken from the storage metadata, so if the list or map profiles
+/// are specified, the database is opened twice: first to read the metadata and then
+/// with the options for each column family.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct RocksDbTuning {
+    /// Options of all the column families.
+    #[serde(default)]
+    pub common: RocksDbProfile,
+    /// Options of the lists, overriding the common ones.
+    #[serde(default)]
+    pub lists: Option<RocksDbProfile>,
+    /// Options of the maps and sets, overriding the common ones.
+    #[serde(default)]
+    pub maps: Option<RocksDbProfile>,
+    /// Whether to sync the write-ahead log on each merge, rather than only on
+    /// `merge_sync`. Synced merges survive a system crash, but are considerably slower.
+    ///
+    /// Defaults to `false`.
+    #[serde(default)]
+    pub sync_wal: bool,
+}
+
+/// Secret key of the database encryption at rest.
+///
+/// The key is never stored in the node configuration. Instead, it is loaded from a file
+/// or from the [`EXONUM_DB_ENCRYPTION_KEY`] environment variable when the node is started,
+/// see [`load`]. Both contain the key as a hex string, which can be generated, for example,
+/// with `openssl rand -hex 32`. The `Debug` output of the key does not reveal it.
+///
+/// [`EXONUM_DB_ENCRYPTION_KEY`]: constant.DB_ENCRYPTION_KEY_ENV.html
+/// [`load`]: #method.load
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct EncryptionKey([u8; ENCRYPTION_KEY_LENGTH]);
+
+impl EncryptionKey {
+    /// Creates the key from raw bytes.
+    pub fn new(bytes: [u8; ENCRYPTION_KEY_LENGTH]) -> Self {
+        EncryptionKey(bytes)
+    }
+
+    /// Generates a random key.
+    pub fn generate() -> Self {
+        let mut bytes = [0; ENCRYPTION_KEY_LENGTH];
+        thread_rng().fill_bytes(&mut bytes);
+        EncryptionKey(bytes)
+    }
+
+    /// Loads the hex-encoded key from the file at the given path, or from
+    /// the [`EXONUM_DB_ENCRYPTION_KEY`] environment variable if the path is not specified.
+    ///
+    /// [`EXONUM_DB_ENCRYPTION_KEY`]: constant.DB_ENCRYPTION_KEY_ENV.html
+    pub fn load<P: AsRef<Path>>(path: Option<P>) -> ::std::result::Result<Self, failure::Error> {
+        let encoded = match path {
+            Some(path) => fs::read_to_string(path)?,
+            None => env::var(DB_ENCRYPTION_KEY_ENV).map_err(|_| {
+                format_err!(
+                    "Database encryption key file is not specified, and `{}` is not set",
+                    DB_ENCRYPTION_KEY_ENV
+                )
+            })?,
+        };
+        encoded.trim().parse()
+    }
+
+    /// Returns the hex representation of the key.
+    pub fn to_hex(&self) -> String {
+        hex::encode(&self.0[..])
+    }
+
+    /// Returns the raw bytes of the key.
+    pub(crate) fn as_bytes(&self) -> &[u8; ENCRYPTION_KEY_LENGTH] {
+        &self.0
+    }
+}
+
+impl FromStr for EncryptionKey {
+    type Err = failure::Error;
+
+    fn from_str(s: &str) -> ::std::result::Result<Self, Self::Err> {
+        let decoded = hex::decode(s)?;
+        if decoded.len() != ENCRYPTION_KEY_LENGTH {
+            bail!(
+                "Invalid length of the database encryption key: expected {} bytes, got {}",
+                ENCRYPTION_KEY_LENGTH,
+                decoded.len()
+            );
+        }
+        let mut bytes = [0; ENCRYPTION_KEY_LENGTH];
+        bytes.copy_from_slice(&decoded);
+        Ok(EncryptionKey(bytes))
+    }
+}
+
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("EncryptionKey(..)")
+    }
+}
+
+/// Options of the database encryption at rest, see [`EncryptedDB`] for details.
+///
+/// The `lists` and `maps` profiles of [`RocksDbTuning`] cannot be used with the encryption,
+/// since the types of the indexes are read from the encrypted data.
+///
+/// ```toml
+/// [database.encryption]
+/// encrypt_keys = true
+/// ```
+///
+/// [`EncryptedDB`]: struct.EncryptedDB.html
+/// [`RocksDbTuning`]: struct.RocksDbTuning.html
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct DbEncryption {
+    /// Whether to encrypt the keys in addition to the values. The keys are encrypted
+    /// deterministically, preserving their order and common prefixes, and take twice
+    /// as much space. The option cannot be changed for an existing database.
+    ///
+    /// Defaults to `false`.
+    #[serde(default)]
+    pub encrypt_keys: bool,
+    /// Encryption key, which is not a part of the configuration and must be loaded
+    /// before the database is opened.
+    #[serde(skip)]
+    pub key: Option<EncryptionKey>,
+}
+
 /// Options for the database.
 ///
-/// These parameters apply to the underlying database of Exonum, currently `RocksDB`.
+/// These parameters apply to the underlying database of Exonum, see [`DbBackend`]
+/// for the available backends. The options specific to a backend are ignored
+/// by the other ones.
+///
+/// [`DbBackend`]: enum.DbBackend.html
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct DbOptions {
+    /// Backend of the database.
+    ///
+    /// Defaults to `RocksDB`.
+    #[serde(default)]
+    pub backend: DbBackend,
     /// Number of open files that can be used by the database.
     ///
     /// The underlying database opens multiple files during operation. If your system has a
@@ -38,13 +328,314 @@ pub struct DbOptions {
     ///
     /// Defaults to `true`.
     pub create_if_missing: bool,
+    /// Maximum size of the `LMDB` database in bytes. Merges fail once the database
+    /// reaches this size, so it should be set with a margin.
+    ///
+    /// Defaults to `None`, meaning 16 GiB on 64-bit platforms and 1 GiB on 32-bit ones.
+    #[serde(default)]
+    pub lmdb_map_size: Option<usize>,
+    /// Maximum number of simultaneously open snapshots of the `LMDB` database. Creating
+    /// another snapshot waits until one of them is dropped, and panics if none is dropped
+    /// within 30 seconds.
+    ///
+    /// Defaults to `None`, meaning 1024 snapshots.
+    #[serde(default)]
+    pub lmdb_max_readers: Option<u32>,
+    /// Tuning options of `RocksDB`.
+    ///
+    /// Defaults to `None`, meaning that the `RocksDB` defaults are used.
+    #[serde(default)]
+    pub rocksdb: Option<RocksDbTuning>,
+    /// Size of the in-memory read cache in bytes. The cache keeps the recently read values
+    /// of the database, including the decoded branch nodes of `ProofMapIndex`, and is
+    /// invalidated on merges. See [`CachedDB`] for details.
+    ///
+    /// Defaults to `None`, meaning that the cache is disabled.
+    ///
+    /// [`CachedDB`]: struct.CachedDB.html
+    #[serde(default)]
+    pub read_cache_size: Option<usize>,
+    /// Encryption of the data at rest. The encryption cannot be enabled or disabled
+    /// for an existing database. See [`DbEncryption`] for details.
+    ///
+    /// Defaults to `None`, meaning that the data is stored unencrypted.
+    ///
+    /// [`DbEncryption`]: struct.DbEncryption.html
+    #[serde(default)]
+    pub encryption: Option<DbEncryption>,
 }
 
 impl Default for DbOptions {
     fn default() -> Self {
         Self {
+            backend: DbBackend::default(),
             max_open_files: None,
             create_if_missing: true,
+            lmdb_map_size: None,
+            lmdb_max_readers: None,
+            rocksdb: None,
+            read_cache_size: None,
+            encryption: None,
+        }
+    }
+}
+
+impl DbOptions {
+    /// Returns `true` if the RocksDB options of lists or maps are specified.
+    pub(crate) fn has_index_profiles(&self) -> bool {
+        self.rocksdb.map_or(false, |tuning| {
+            tuning.lists.is_some() || tuning.maps.is_some()
+        })
+    }
+}
+
+/// Opens a database stored at the specified path with the backend and other options
+/// specified in `options`. If `encryption` is specified, the data is encrypted with the key
+/// loaded into the options. If `read_cache_size` is specified, the database is wrapped
+/// with the read cache.
+///
+/// # Errors
+///
+/// Returns an error if the database cannot be opened, if the encryption key is not loaded
+/// or does not match the database, or if the backend is not available because the crate
+/// is compiled without the corresponding feature.
+// Depending on the enabled features, some arms of the match may be unreachable.
+#[allow(unreachable_patterns, unused_variables)]
+pub fn open_database<P: AsRef<Path>>(path: P, options: &DbOptions) -> Result<Box<dyn Database>> {
+    let db: Box<dyn Database> = match options.backend {
+        #[cfg(feature = "rocksdb-storage")]
+        DbBackend::RocksDB => Box::new(RocksDB::open(path, options)?),
+        #[cfg(feature = "lmdb-storage")]
+        DbBackend::LMDB => Box::new(LMDB::open(path, options)?),
+        backend => {
+            return Err(Error::new(format!(
+                "Database backend `{}` is not enabled, recompile with the `{}-storage` feature",
+                backend, backend
+            )))
+        }
+    };
+    let db = match options.encryption {
+        Some(ref encryption) => encrypted_database(db, encryption, options)?,
+        None => db,
+    };
+    Ok(match options.read_cache_size {
+        Some(size) => Box::new(CachedDB::new(db, size)),
+        None => db,
+    })
+}
+
+/// Re-encrypts the database stored at the specified path with the new key. The current key
+/// must be loaded into the `encryption` options. Returns the number of the re-encrypted
+/// entries. See [`EncryptedDB::rotate_key`] for details.
+///
+/// The data is re-encrypted into a fresh database in a directory with the `.rotated` suffix,
+/// so the database remains encrypted with the current key until the re-encryption
+/// is finished. Then the directories are swapped: the database is moved to a directory
+/// with the `.old` suffix, the re-encrypted one takes its place, and the old one is removed.
+/// If the rotation is interrupted between the renames, the next call restores the database
+/// from the `.old` directory and starts the rotation over. The node must be stopped during
+/// the rotation.
+///
+/// # Errors
+///
+/// Returns an error if the encryption is not enabled, if the current key does not match
+/// the database, or if the databases cannot be opened, written or moved.
+///
+/// [`EncryptedDB::rotate_key`]: struct.EncryptedDB.html#method.rotate_key
+pub fn rotate_encryption_key<P: AsRef<Path>>(
+    path: P,
+    options: &DbOptions,
+    new_key: &EncryptionKey,
+) -> Result<usize> {
+    let encryption = options
+        .encryption
+        .ok_or_else(|| Error::new("Database encryption is not enabled"))?;
+    check_encryption_options(options)?;
+    let path = path.as_ref();
+    let rotated_path = suffixed_path(path, ".rotated");
+    let old_path = suffixed_path(path, ".old");
+
+    if !path.exists() && old_path.exists() {
+        rename_dir(&old_path, path)?;
+    }
+    if rotated_path.exists() {
+        // The leftover of an interrupted rotation.
+        remove_dir(&rotated_path)?;
+    }
+
+    let options = DbOptions {
+        encryption: None,
+        read_cache_size: None,
+        ..*options
+    };
+    let count = {
+        let db = open_database(path, &options)?;
+        let target_options = DbOptions {
+            create_if_missing: true,
+            ..options
+        };
+        let target = open_database(&rotated_path, &target_options)?;
+        reencrypt_database(db, target, &encryption, new_key)?
+    };
+
+    rename_dir(path, &old_path)?;
+    rename_dir(&rotated_path, path)?;
+    remove_dir(&old_path)?;
+    Ok(count)
+}
+
+/// Returns the path with the given suffix appended to the last component.
+fn suffixed_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+fn rename_dir(from: &Path, to: &Path) -> Result<()> {
+    fs::rename(from, to).map_err(|e| {
+        Error::new(format!(
+            "Can't move {} to {}: {}",
+            from.display(),
+            to.display(),
+            e
+        ))
+    })
+}
+
+fn remove_dir(path: &Path) -> Result<()> {
+    fs::remove_dir_all(path)
+        .map_err(|e| Error::new(format!("Can't remove {}: {}", path.display(), e)))
+}
+
+/// Checks that the options are compatible with the encryption.
+fn check_encryption_options(options: &DbOptions) -> Result<()> {
+    // The index profiles are chosen by the types of the indexes read from the underlying
+    // database, which are encrypted.
+    if options.has_index_profiles() {
+        return Err(Error::new(
+            "RocksDB profiles of lists and maps are not supported for encrypted databases",
+        ));
+    }
+    Ok(())
+}
+
+/// Wraps the database with the encryption at rest.
+#[cfg(feature = "sodiumoxide-crypto")]
+fn encrypted_database(
+    db: Box<dyn Database>,
+    encryption: &DbEncryption,
+    options: &DbOptions,
+) -> Result<Box<dyn Database>> {
+    check_encryption_options(options)?;
+    let key = encryption
+        .key
+        .ok_or_else(|| Error::new("Database encryption key is not loaded"))?;
+    let db = EncryptedDB::new(db, &key, encryption.encrypt_keys)?;
+    Ok(Box::new(db))
+}
+
+#[cfg(not(feature = "sodiumoxide-crypto"))]
+fn encrypted_database(
+    _db: Box<dyn Database>,
+    _encryption: &DbEncryption,
+    _options: &DbOptions,
+) -> Result<Box<dyn Database>> {
+    Err(Error::new(
+        "Database encryption is not enabled, recompile with the `sodiumoxide-crypto` feature",
+    ))
+}
+
+#[cfg(feature = "sodiumoxide-crypto")]
+fn reencrypt_database(
+    db: Box<dyn Database>,
+    target: Box<dyn Database>,
+    encryption: &DbEncryption,
+    new_key: &EncryptionKey,
+) -> Result<usize> {
+    let key = encryption
+        .key
+        .ok_or_else(|| Error::new("Database encryption key is not loaded"))?;
+    let db = EncryptedDB::new(db, &key, encryption.encrypt_keys)?;
+    let (_, count) = db.rotate_key(new_key, target)?;
+    Ok(count)
+}
+
+#[cfg(not(feature = "sodiumoxide-crypto"))]
+fn reencrypt_database(
+    _db: Box<dyn Database>,
+    _target: Box<dyn Database>,
+    _encryption: &DbEncryption,
+    _new_key: &EncryptionKey,
+) -> Result<usize> {
+    Err(Error::new(
+        "Database encryption is not enabled, recompile with the `sodiumoxide-crypto` feature",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+    use tempdir::TempDir;
+
+    use super::{EncryptionKey, DB_ENCRYPTION_KEY_ENV};
+
+    #[test]
+    fn load_encryption_key() {
+        let key = EncryptionKey::generate();
+        let dir = TempDir::new("exonum_encryption_key").unwrap();
+        let path = dir.path().join("key");
+        fs::write(&path, format!("{}\n", key.to_hex())).unwrap();
+        assert_eq!(EncryptionKey::load(Some(&path)).unwrap(), key);
+
+        fs::write(&path, &key.to_hex()[2..]).unwrap();
+        assert!(EncryptionKey::load(Some(&path)).is_err());
+        assert!(EncryptionKey::load(Some(dir.path().join("missing"))).is_err());
+
+        let env_key = EncryptionKey::generate();
+        env::set_var(DB_ENCRYPTION_KEY_ENV, env_key.to_hex());
+        assert_eq!(EncryptionKey::load(None::<&str>).unwrap(), env_key);
+        // The file takes precedence over the environment variable.
+        fs::write(&path, key.to_hex()).unwrap();
+        assert_eq!(EncryptionKey::load(Some(&path)).unwrap(), key);
+
+        env::remove_var(DB_ENCRYPTION_KEY_ENV);
+        assert!(EncryptionKey::load(None::<&str>).is_err());
+    }
+
+    #[cfg(all(feature = "rocksdb-storage", feature = "sodiumoxide-crypto"))]
+    #[test]
+    fn rotate_encryption_key() {
+        use super::{open_database, rotate_encryption_key, suffixed_path, DbEncryption, DbOptions};
+        use storage::MapIndex;
+
+        let dir = TempDir::new("exonum_rotate_encryption_key").unwrap();
+        let path = dir.path().join("db");
+        let key = EncryptionKey::generate();
+        let mut options = DbOptions::default();
+        options.encryption = Some(DbEncryption {
+            encrypt_keys: true,
+            key: Some(key),
+        });
+        {
+            let db = open_database(&path, &options).unwrap();
+            let mut fork = db.fork();
+            MapIndex::new("index", &mut fork).put(&1_u64, 2_u64);
+            db.merge(fork.into_patch()).unwrap();
         }
+
+        let new_key = EncryptionKey::generate();
+        assert!(rotate_encryption_key(&path, &options, &new_key).unwrap() > 1);
+        assert!(!suffixed_path(&path, ".rotated").exists());
+        assert!(!suffixed_path(&path, ".old").exists());
+        assert!(open_database(&path, &options).is_err());
+
+        options.encryption = Some(DbEncryption {
+            encrypt_keys: true,
+            key: Some(new_key),
+        });
+        let db = open_database(&path, &options).unwrap();
+        let snapshot = db.snapshot();
+        let index: MapIndex<_, u64, u64> = MapIndex::new("index", &snapshot);
+        assert_eq!(index.get(&1), Some(2));
     }
 }
@@ -16,12 +16,14 @@
 
 pub use self::proof::{ListProof, ListProofError};
 
-use std::{cell::Cell, marker::PhantomData};
+pub(crate) use self::key::ProofListKey;
+
+use std::{cell::Cell, marker::PhantomData, ops::Range};
 
-use self::key::ProofListKey;
 use super::{
     base_index::{BaseIndex, BaseIndexIter},
     indexes_metadata::IndexType,
+    integrity::{raw_key, ValueHashFn},
     Fork, Snapshot, StorageKey, StorageValue,
 };
 use crypto::{hash, Hash, HashStream};
@@ -48,11 +50,13 @@ pub struct ProofListIndex<T, V> {
 
 /// An iterator over the items of a `ProofListIndex`.
 ///
-/// This struct is created by the [`iter`] or
-/// [`iter_from`] method on [`ProofListIndex`]. See its documentation for details.
+/// This struct is created by the [`iter`], [`iter_from`], [`iter_rev`] or [`range`]
+/// method on [`ProofListIndex`]. See its documentation for details.
 ///
 /// [`iter`]: struct.ProofListIndex.html#method.iter
 /// [`iter_from`]: struct.ProofListIndex.html#method.iter_from
+/// [`iter_rev`]: struct.ProofListIndex.html#method.iter_rev
+/// [`range`]: struct.ProofListIndex.html#method.range
 /// [`ProofListIndex`]: struct.ProofListIndex.html
 #[derive(Debug)]
 pub struct ProofListIndexIter<'a, V> {
@@ -443,6 +447,156 @@ where
             base_iter: self.base.iter_from(&0_u8, &ProofListKey::leaf(from)),
         }
     }
+
+    /// Returns an iterator over the list in reverse order. The iterator element type is V.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use exonum::storage::{MemoryDB, Database, ProofListIndex};
+    ///
+    /// let db = MemoryDB::new();
+    /// let name = "name";
+    /// let snapshot = db.snapshot();
+    /// let index: ProofListIndex<_, u8> = ProofListIndex::new(name, &snapshot);
+    ///
+    /// for val in index.iter_rev() {
+    ///     println!("{}", val);
+    /// }
+    /// ```
+    pub fn iter_rev(&self) -> ProofListIndexIter<V> {
+        ProofListIndexIter {
+            base_iter: self.base.iter_rev(&0_u8),
+        }
+    }
+
+    /// Returns an iterator over the elements of the list with the positions in the specified
+    /// range. The iterator element type is V.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use exonum::storage::{MemoryDB, Database, ProofListIndex};
+    ///
+    /// let db = MemoryDB::new();
+    /// let name = "name";
+    /// let snapshot = db.snapshot();
+    /// let index: ProofListIndex<_, u8> = ProofListIndex::new(name, &snapshot);
+    ///
+    /// for val in index.range(1..3) {
+    ///     println!("{}", val);
+    /// }
+    /// ```
+    pub fn range(&self, range: Range<u64>) -> ProofListIndexIter<V> {
+        ProofListIndexIter {
+            base_iter: self.base.iter_range(
+                &0_u8,
+                &ProofListKey::leaf(range.start),
+                &ProofListKey::leaf(range.end),
+            ),
+        }
+    }
+}
+
+impl<T> ProofListIndex<T, Vec<u8>>
+where
+    T: AsRef<dyn Snapshot>,
+{
+    /// Recomputes the hashes of the tree nodes level by level and compares them with
+    /// the stored ones. The found inconsistencies are added to `errors` together with
+    /// the keys of the corresponding nodes.
+    ///
+    /// Returns the recomputed root hash, or `None` if it cannot be computed, e.g., because
+    /// some nodes are missing.
+    pub(crate) fn check_integrity(
+        &self,
+        value_hash: Option<ValueHashFn>,
+        errors: &mut Vec<(Vec<u8>, String)>,
+    ) -> Option<Hash> {
+        let len = self.len();
+        let height = self.height();
+
+        let mut entries = 0;
+        for (key, _) in self.base.iter::<_, Vec<u8>, ()>(&()) {
+            match key.len() {
+                0 => {}
+                8 => entries += 1,
+                _ => errors.push((key, "Invalid length of the node key".to_owned())),
+            }
+        }
+        let mut expected_entries = len;
+        let mut level_len = len;
+        for _ in 0..height {
+            expected_entries += level_len;
+            level_len = (level_len + 1) / 2;
+        }
+        if len == 0 {
+            expected_entries = 0;
+        }
+        if entries > expected_entries {
+            errors.push((
+                Vec::new(),
+                format!(
+                    "{} nodes are outside of the tree of the list with length {}",
+                    entries - expected_entries,
+                    len
+                ),
+            ));
+        }
+
+        let mut hashes = Vec::with_capacity(len as usize);
+        for index in 0..len {
+            let leaf = ProofListKey::leaf(index);
+            let computed = match self.base.get::<_, Vec<u8>>(&leaf) {
+                Some(value) => value_hash.map(|value_hash| value_hash(&value)),
+                None => {
+                    errors.push((raw_key(&leaf), "Value is missing".to_owned()));
+                    None
+                }
+            };
+            hashes.push(self.check_branch(ProofListKey::new(1, index), computed, errors));
+        }
+        for level in 2..=height {
+            hashes = hashes
+                .chunks(2)
+                .enumerate()
+                .map(|(index, children)| {
+                    let computed = match (children[0], children.get(1)) {
+                        (Some(left), Some(&Some(right))) => Some(hash_pair(&left, &right)),
+                        (Some(left), None) => Some(hash_one(&left)),
+                        _ => None,
+                    };
+                    self.check_branch(ProofListKey::new(level, index as u64), computed, errors)
+                }).collect();
+        }
+        hashes.first().cloned().unwrap_or_else(|| Some(Hash::zero()))
+    }
+
+    /// Compares the stored hash of the node with the computed one and returns the hash,
+    /// which should be used for the parent node.
+    fn check_branch(
+        &self,
+        key: ProofListKey,
+        computed: Option<Hash>,
+        errors: &mut Vec<(Vec<u8>, String)>,
+    ) -> Option<Hash> {
+        match (self.base.get::<_, Hash>(&key), computed) {
+            (Some(stored), Some(computed)) => {
+                if stored != computed {
+                    errors.push((
+                        raw_key(&key),
+                        "Hash of the node does not match the recomputed one".to_owned(),
+                    ));
+                }
+                Some(stored)
+            }
+            (None, computed) => {
+                errors.push((raw_key(&key), "Node is missing".to_owned()));
+                computed
+            }
+            (stored, None) => stored,
+        }
+    }
 }
 
 impl<'a, V> ProofListIndex<&'a mut Fork, V>
@@ -89,6 +89,23 @@ impl<V: StorageValue> ListProof<V> {
     }
 }
 
+impl<V> ListProof<V> {
+    /// Converts the values of the proof with the given function.
+    pub(crate) fn map_values<W, F>(self, f: &mut F) -> ListProof<W>
+    where
+        F: FnMut(V) -> W,
+    {
+        match self {
+            ListProof::Full(left, right) => {
+                ListProof::Full(Box::new(left.map_values(f)), Box::new(right.map_values(f)))
+            }
+            ListProof::Left(left, right) => ListProof::Left(Box::new(left.map_values(f)), right),
+            ListProof::Right(left, right) => ListProof::Right(left, Box::new(right.map_values(f))),
+            ListProof::Leaf(value) => ListProof::Leaf(f(value)),
+        }
+    }
+}
+
 impl<V: Serialize> Serialize for ListProof<V> {
     fn serialize<S>(&self, ser: S) -> Result<S::Ok, S::Error>
     where
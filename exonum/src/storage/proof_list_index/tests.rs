@@ -666,6 +666,7 @@ mod memorydb_tests {
     }
 }
 
+#[cfg(feature = "rocksdb-storage")]
 mod rocksdb_tests {
     use std::path::Path;
     use storage::{Database, DbOptions, RocksDB};
@@ -779,6 +780,120 @@ mod rocksdb_tests {
     }
 }
 
+#[cfg(feature = "lmdb-storage")]
+mod lmdb_tests {
+    use std::path::Path;
+    use storage::{Database, DbOptions, LMDB};
+    use tempdir::TempDir;
+
+    fn create_database(path: &Path) -> Box<dyn Database> {
+        let opts = DbOptions::default();
+        Box::new(LMDB::open(path, &opts).unwrap())
+    }
+
+    #[test]
+    fn test_list_methods() {
+        let dir = TempDir::new(super::gen_tempdir_name().as_str()).unwrap();
+        let path = dir.path();
+        let db = create_database(path);
+        super::list_methods(db);
+    }
+
+    #[test]
+    fn test_height() {
+        let dir = TempDir::new(super::gen_tempdir_name().as_str()).unwrap();
+        let path = dir.path();
+        let db = create_database(path);
+        super::height(db);
+    }
+
+    #[test]
+    fn test_iter() {
+        let dir = TempDir::new(super::gen_tempdir_name().as_str()).unwrap();
+        let path = dir.path();
+        let db = create_database(path);
+        super::iter(db);
+    }
+
+    #[test]
+    fn test_list_index_proof() {
+        let dir = TempDir::new(super::gen_tempdir_name().as_str()).unwrap();
+        let path = dir.path();
+        let db = create_database(path);
+        super::list_index_proof(db);
+    }
+
+    #[test]
+    fn test_randomly_generate_proofs() {
+        let dir = TempDir::new(super::gen_tempdir_name().as_str()).unwrap();
+        let path = dir.path();
+        let db = create_database(path);
+        super::randomly_generate_proofs(db);
+    }
+
+    #[test]
+    fn test_index_and_proof_roots() {
+        let dir = TempDir::new(super::gen_tempdir_name().as_str()).unwrap();
+        let path = dir.path();
+        let db = create_database(path);
+        super::index_and_proof_roots(db);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_proof_illegal_lower_bound() {
+        let dir = TempDir::new(super::gen_tempdir_name().as_str()).unwrap();
+        let path = dir.path();
+        let db = create_database(path);
+        super::proof_illegal_lower_bound(db);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_proof_illegal_bound_empty() {
+        let dir = TempDir::new(super::gen_tempdir_name().as_str()).unwrap();
+        let path = dir.path();
+        let db = create_database(path);
+        super::proof_illegal_bound_empty(db);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_proof_illegal_range() {
+        let dir = TempDir::new(super::gen_tempdir_name().as_str()).unwrap();
+        let path = dir.path();
+        let db = create_database(path);
+        super::proof_illegal_range(db);
+    }
+
+    #[test]
+    fn test_proof_structure() {
+        let dir = TempDir::new(super::gen_tempdir_name().as_str()).unwrap();
+        let path = dir.path();
+        let db = create_database(path);
+        super::proof_structure(db);
+    }
+
+    #[test]
+    fn test_simple_merkle_root() {
+        let dir = TempDir::new(super::gen_tempdir_name().as_str()).unwrap();
+        let path = dir.path();
+        let db = create_database(path);
+        super::simple_merkle_root(db);
+    }
+
+    #[test]
+    fn test_same_merkle_root() {
+        let dir1 = TempDir::new(super::gen_tempdir_name().as_str()).unwrap();
+        let path1 = dir1.path();
+        let db1 = create_database(path1);
+        let dir2 = TempDir::new(super::gen_tempdir_name().as_str()).unwrap();
+        let path2 = dir2.path();
+        let db2 = create_database(path2);
+        super::same_merkle_root(db1, db2);
+    }
+}
+
 mod root_hash_tests {
     use crypto::{self, Hash};
     use storage::{Database, MemoryDB, ProofListIndex};
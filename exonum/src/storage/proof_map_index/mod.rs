@@ -16,19 +16,22 @@
 
 pub use self::{
     key::{HashedKey, ProofMapKey, ProofPath, KEY_SIZE as PROOF_MAP_KEY_SIZE},
-    proof::{CheckedMapProof, MapProof, MapProofError},
+    proof::{CheckedMapProof, CheckedMapRangeProof, MapProof, MapProofError, MapRangeProof},
 };
 
-use std::{fmt, marker::PhantomData};
+use std::{borrow::Cow, cmp::min, fmt, marker::PhantomData, ops::Range};
+
+pub(crate) use self::key::{LEAF_KEY_PREFIX, PROOF_PATH_SIZE};
 
 use self::{
-    key::{BitsRange, ChildKind, LEAF_KEY_PREFIX},
-    node::{BranchNode, Node},
-    proof::{create_multiproof, create_proof},
+    key::{BitsRange, ChildKind, BRANCH_KEY_PREFIX, PROOF_PATH_LEN_POS},
+    node::{BranchNode, Node, BRANCH_NODE_SIZE},
+    proof::{create_multiproof, create_proof, create_range_proof},
 };
 use super::{
     base_index::{BaseIndex, BaseIndexIter},
     indexes_metadata::IndexType,
+    integrity::ValueHashFn,
     Fork, Snapshot, StorageKey, StorageValue,
 };
 use crypto::{CryptoHash, Hash, HashStream};
@@ -61,11 +64,14 @@ pub struct ProofMapIndex<T, K, V> {
 
 /// An iterator over the entries of a `ProofMapIndex`.
 ///
-/// This struct is created by the [`iter`] or
-/// [`iter_from`] method on [`ProofMapIndex`]. See its documentation for details.
+/// This struct is created by the [`iter`], [`iter_from`], [`iter_rev`], [`iter_rev_from`]
+/// or [`range`] method on [`ProofMapIndex`]. See its documentation for details.
 ///
 /// [`iter`]: struct.ProofMapIndex.html#method.iter
 /// [`iter_from`]: struct.ProofMapIndex.html#method.iter_from
+/// [`iter_rev`]: struct.ProofMapIndex.html#method.iter_rev
+/// [`iter_rev_from`]: struct.ProofMapIndex.html#method.iter_rev_from
+/// [`range`]: struct.ProofMapIndex.html#method.range
 /// [`ProofMapIndex`]: struct.ProofMapIndex.html
 #[derive(Debug)]
 pub struct ProofMapIndexIter<'a, K, V> {
@@ -210,7 +216,7 @@ where
         if key.is_leaf() {
             Node::Leaf(self.base.get(key).unwrap())
         } else {
-            Node::Branch(self.base.get(key).unwrap())
+            Node::Branch(self.base.get_decoded(key).unwrap())
         }
     }
 
@@ -334,6 +340,42 @@ where
         })
     }
 
+    /// Returns the proof of existence for all entries with keys in the inclusive interval
+    /// `[from, to]`, which also shows that the map has no other entries within the interval.
+    ///
+    /// Keys are compared by their [`ProofPath`]s; see [`MapRangeProof`] for details.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `from` is greater than `to`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use exonum::storage::{MemoryDB, Database, ProofMapIndex};
+    ///
+    /// let db = MemoryDB::new();
+    /// let snapshot = db.snapshot();
+    /// let index: ProofMapIndex<_, [u8; 32], u8> = ProofMapIndex::new("index", &snapshot);
+    ///
+    /// let proof = index.get_range_proof([0; 32], [0xff; 32]);
+    /// ```
+    ///
+    /// [`ProofPath`]: struct.ProofPath.html
+    /// [`MapRangeProof`]: struct.MapRangeProof.html
+    pub fn get_range_proof(&self, from: K, to: K) -> MapRangeProof<K, V>
+    where
+        K: ProofMapKey<Output = K>,
+    {
+        assert!(
+            ProofPath::new(&from) <= ProofPath::new(&to),
+            "Illegal range bounds: `from` is greater than `to`"
+        );
+        create_range_proof(from, to, self.get_root_node(), |path| {
+            self.get_node_unchecked(path)
+        })
+    }
+
     /// Returns an iterator over the entries of the map in ascending order. The iterator element
     /// type is `(K::Output, V)`.
     ///
@@ -484,6 +526,196 @@ where
             base_iter: self.base.iter_from(&LEAF_KEY_PREFIX, &ProofPath::new(from)),
         }
     }
+
+    /// Returns an iterator over the entries of the map in descending order. The iterator element
+    /// type is `(K::Output, V)`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use exonum::storage::{MemoryDB, Database, ProofMapIndex};
+    /// use exonum::crypto::Hash;
+    ///
+    /// let db = MemoryDB::new();
+    /// let name = "name";
+    /// let snapshot = db.snapshot();
+    /// let index: ProofMapIndex<_, Hash, u8> = ProofMapIndex::new(name, &snapshot);
+    ///
+    /// for val in index.iter_rev() {
+    ///     println!("{:?}", val);
+    /// }
+    /// ```
+    pub fn iter_rev(&self) -> ProofMapIndexIter<K, V> {
+        ProofMapIndexIter {
+            base_iter: self.base.iter_rev(&LEAF_KEY_PREFIX),
+            _k: PhantomData,
+        }
+    }
+
+    /// Returns an iterator over the entries of the map in descending order starting from the
+    /// specified key (inclusive). The iterator element type is `(K::Output, V)`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use exonum::storage::{MemoryDB, Database, ProofMapIndex};
+    /// use exonum::crypto::Hash;
+    ///
+    /// let db = MemoryDB::new();
+    /// let name = "name";
+    /// let snapshot = db.snapshot();
+    /// let index: ProofMapIndex<_, Hash, u8> = ProofMapIndex::new(name, &snapshot);
+    ///
+    /// let hash = Hash::default();
+    /// for val in index.iter_rev_from(&hash) {
+    ///     println!("{:?}", val);
+    /// }
+    /// ```
+    pub fn iter_rev_from(&self, from: &K) -> ProofMapIndexIter<K, V> {
+        ProofMapIndexIter {
+            base_iter: self
+                .base
+                .iter_rev_from(&LEAF_KEY_PREFIX, &ProofPath::new(from)),
+            _k: PhantomData,
+        }
+    }
+
+    /// Returns an iterator over the entries of the map in ascending order with the keys
+    /// in the specified range. The iterator element type is `(K::Output, V)`.
+    ///
+    /// Both the order and the range bounds refer to the `ProofPath`s of the keys, that is,
+    /// to the bytes written by [`ProofMapKey::write_key`]. For [`HashedKey`]s, these are
+    /// the hashes of the keys, so the range does not correspond to the order of the keys
+    /// themselves.
+    ///
+    /// [`ProofMapKey::write_key`]: trait.ProofMapKey.html#tymethod.write_key
+    /// [`HashedKey`]: trait.HashedKey.html
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use exonum::storage::{MemoryDB, Database, ProofMapIndex};
+    /// use exonum::crypto::Hash;
+    ///
+    /// let db = MemoryDB::new();
+    /// let name = "name";
+    /// let snapshot = db.snapshot();
+    /// let index: ProofMapIndex<_, Hash, u8> = ProofMapIndex::new(name, &snapshot);
+    ///
+    /// let from = Hash::new([1; 32]);
+    /// let to = Hash::new([2; 32]);
+    /// for val in index.range(&from..&to) {
+    ///     println!("{:?}", val);
+    /// }
+    /// ```
+    pub fn range(&self, range: Range<&K>) -> ProofMapIndexIter<K, V> {
+        ProofMapIndexIter {
+            base_iter: self.base.iter_range(
+                &LEAF_KEY_PREFIX,
+                &ProofPath::new(range.start),
+                &ProofPath::new(range.end),
+            ),
+            _k: PhantomData,
+        }
+    }
+}
+
+impl<T> ProofMapIndex<T, Hash, Vec<u8>>
+where
+    T: AsRef<dyn Snapshot>,
+{
+    /// Recomputes the hashes of the tree nodes and compares them with the hashes stored
+    /// in the parent nodes. The found inconsistencies are added to `errors` together with
+    /// the keys of the corresponding nodes.
+    ///
+    /// Returns the recomputed root hash, or `None` if it cannot be computed, e.g., because
+    /// the hashes of the values are unknown.
+    pub(crate) fn check_integrity(
+        &self,
+        value_hash: Option<ValueHashFn>,
+        errors: &mut Vec<(Vec<u8>, String)>,
+    ) -> Option<Hash> {
+        let mut root = None;
+        let mut entries = 0;
+        for (key, _) in self.base.iter::<_, Vec<u8>, ()>(&()) {
+            if key.len() != PROOF_PATH_SIZE {
+                errors.push((key.clone(), "Invalid length of the node key".to_owned()));
+            }
+            root.get_or_insert(key);
+            entries += 1;
+        }
+        let root = match root {
+            Some(ref root) if root.len() == PROOF_PATH_SIZE => root.clone(),
+            Some(_) => return None,
+            None => return Some(Hash::zero()),
+        };
+
+        let mut visited = 0;
+        let root_hash = if root[0] == LEAF_KEY_PREFIX {
+            self.check_node(&root, value_hash, &mut visited, errors)
+                .map(|hash| HashStream::new().update(&root).update(hash.as_ref()).hash())
+        } else {
+            self.check_node(&root, value_hash, &mut visited, errors)
+        };
+        if visited < entries {
+            errors.push((
+                Vec::new(),
+                format!(
+                    "{} nodes are not reachable from the root node",
+                    entries - visited
+                ),
+            ));
+        }
+        root_hash
+    }
+
+    /// Checks the subtree with the root at the given key and returns the hash of its root
+    /// as it should be stored in the parent node.
+    fn check_node(
+        &self,
+        key: &[u8],
+        value_hash: Option<ValueHashFn>,
+        visited: &mut usize,
+        errors: &mut Vec<(Vec<u8>, String)>,
+    ) -> Option<Hash> {
+        let value: Vec<u8> = match self.base.get(key) {
+            Some(value) => value,
+            None => {
+                errors.push((key.to_vec(), "Node is missing".to_owned()));
+                return None;
+            }
+        };
+        *visited += 1;
+        if key[0] == LEAF_KEY_PREFIX {
+            return value_hash.map(|value_hash| value_hash(&value));
+        }
+        if value.len() != BRANCH_NODE_SIZE {
+            errors.push((key.to_vec(), "Invalid length of the branch node".to_owned()));
+            return None;
+        }
+
+        let branch = BranchNode::from_bytes(Cow::Owned(value));
+        for &kind in &[ChildKind::Left, ChildKind::Right] {
+            let child = branch.child_path_bytes(kind);
+            // Paths to the child branches are strictly longer, which rules out cycles.
+            let is_valid_path = child[0] == LEAF_KEY_PREFIX
+                || (child[0] == BRANCH_KEY_PREFIX
+                    && child[PROOF_PATH_LEN_POS] > key[PROOF_PATH_LEN_POS]);
+            if !is_valid_path {
+                errors.push((key.to_vec(), "Invalid path to a child node".to_owned()));
+                continue;
+            }
+            if let Some(hash) = self.check_node(child, value_hash, visited, errors) {
+                if hash != *branch.child_hash(kind) {
+                    errors.push((
+                        child.to_vec(),
+                        "Hash of the node does not match the one in its parent node".to_owned(),
+                    ));
+                }
+            }
+        }
+        Some(branch.hash())
+    }
 }
 
 impl<'a, K, V> ProofMapIndex<&'a mut Fork, K, V>
@@ -732,6 +964,218 @@ where
         }
     }
 
+    /// Applies the changes sorted by path to the subtree with the specified root.
+    /// `None` values denote removals. Each affected branch node is rehashed and saved once.
+    ///
+    /// Returns the path and the hash of the updated subtree, or `None` if the subtree
+    /// has become empty.
+    fn update_subtree(
+        &mut self,
+        root: Option<(ProofPath, Hash)>,
+        mut changes: Vec<(ProofPath, Option<V>)>,
+    ) -> Option<(ProofPath, Hash)> {
+        let (root_path, root_hash) = match root {
+            Some(root) => root,
+            None => return self.build_subtree(changes),
+        };
+        if changes.is_empty() {
+            return Some((root_path, root_hash));
+        }
+
+        let prefix_len = {
+            let (first, last) = (&changes[0].0, &changes[changes.len() - 1].0);
+            min(
+                first.common_prefix_len(last),
+                root_path.common_prefix_len(first),
+            )
+        };
+
+        if prefix_len < root_path.len() {
+            // The changes diverge from the root path, so a new branch is required.
+            let other_changes = split_changes(&mut changes, prefix_len);
+            let root = Some((root_path, root_hash));
+            let (left, right) = match root_path.bit(prefix_len) {
+                ChildKind::Left => (
+                    self.update_subtree(root, changes),
+                    self.build_subtree(other_changes),
+                ),
+                ChildKind::Right => (
+                    self.build_subtree(changes),
+                    self.update_subtree(root, other_changes),
+                ),
+            };
+            self.join_subtrees(root_path.prefix(prefix_len), left, right)
+        } else if root_path.is_leaf() {
+            // Changes are deduplicated, so this is the only change of the leaf.
+            let (path, value) = changes.pop().unwrap();
+            match value {
+                Some(value) => Some((path, self.insert_leaf(&path, value))),
+                None => {
+                    self.base.remove(&path);
+                    None
+                }
+            }
+        } else {
+            let branch = match self.get_node_unchecked(&root_path) {
+                Node::Branch(branch) => branch,
+                Node::Leaf(_) => unreachable!("Something went wrong!"),
+            };
+            let right_changes = split_changes(&mut changes, prefix_len);
+            let left = self.update_subtree(
+                Some((
+                    branch.child_path(ChildKind::Left),
+                    *branch.child_hash(ChildKind::Left),
+                )),
+                changes,
+            );
+            let right = self.update_subtree(
+                Some((
+                    branch.child_path(ChildKind::Right),
+                    *branch.child_hash(ChildKind::Right),
+                )),
+                right_changes,
+            );
+
+            let subtree = self.join_subtrees(root_path, left, right);
+            if subtree.map_or(true, |(path, _)| path != root_path) {
+                self.base.remove(&root_path);
+            }
+            subtree
+        }
+    }
+
+    /// Builds a new subtree from the changes sorted by path, ignoring removals.
+    fn build_subtree(
+        &mut self,
+        mut changes: Vec<(ProofPath, Option<V>)>,
+    ) -> Option<(ProofPath, Hash)> {
+        changes.retain(|&(_, ref value)| value.is_some());
+        match changes.len() {
+            0 => None,
+            1 => {
+                let (path, value) = changes.pop().unwrap();
+                Some((path, self.insert_leaf(&path, value.unwrap())))
+            }
+            _ => {
+                let prefix_len = changes[0]
+                    .0
+                    .common_prefix_len(&changes[changes.len() - 1].0);
+                let path = changes[0].0.prefix(prefix_len);
+                let right_changes = split_changes(&mut changes, prefix_len);
+                let left = self.build_subtree(changes);
+                let right = self.build_subtree(right_changes);
+                self.join_subtrees(path, left, right)
+            }
+        }
+    }
+
+    /// Saves a branch node with the specified children if both of them are present.
+    /// Otherwise, returns the present child (if any) in place of the branch.
+    fn join_subtrees(
+        &mut self,
+        path: ProofPath,
+        left: Option<(ProofPath, Hash)>,
+        right: Option<(ProofPath, Hash)>,
+    ) -> Option<(ProofPath, Hash)> {
+        match (left, right) {
+            (Some((left_path, left_hash)), Some((right_path, right_hash))) => {
+                let mut branch = BranchNode::empty();
+                branch.set_child(ChildKind::Left, &left_path, &left_hash);
+                branch.set_child(ChildKind::Right, &right_path, &right_hash);
+                let hash = branch.hash();
+                self.base.put(&path, branch);
+                Some((path, hash))
+            }
+            (child, None) | (None, child) => child,
+        }
+    }
+
+    /// Applies the changes to the proof map, rehashing each affected branch node once.
+    /// If a path occurs several times among the changes, only the last change takes effect.
+    fn update_many(&mut self, mut changes: Vec<(ProofPath, Option<V>)>) {
+        // The sort is stable, so the last change for each path goes first after the reversal.
+        changes.reverse();
+        changes.sort_by(|x, y| {
+            // `unwrap` is safe here because all paths start from the same position `0`
+            x.0.partial_cmp(&y.0).unwrap()
+        });
+        changes.dedup_by(|x, y| x.0 == y.0);
+
+        let root = self.get_root_node().map(|(path, node)| match node {
+            Node::Leaf(value) => (path, value.hash()),
+            Node::Branch(branch) => (path, branch.hash()),
+        });
+        self.update_subtree(root, changes);
+    }
+
+    /// Inserts the key-value pairs into the proof map.
+    ///
+    /// Unlike the repeated calls of [`put()`], this method recalculates and saves each affected
+    /// branch node of the tree only once, which is much faster for large batches of entries.
+    /// If a key occurs several times, the last value is inserted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use exonum::storage::{MemoryDB, Database, ProofMapIndex};
+    /// use exonum::crypto::hash;
+    ///
+    /// let db = MemoryDB::new();
+    /// let name = "name";
+    /// let mut fork = db.fork();
+    /// let mut index = ProofMapIndex::new(name, &mut fork);
+    ///
+    /// index.put_many(vec![(hash(&[1]), 1), (hash(&[2]), 2)]);
+    /// assert!(index.contains(&hash(&[1])));
+    /// assert_eq!(index.get(&hash(&[2])), Some(2));
+    /// ```
+    ///
+    /// [`put()`]: #method.put
+    pub fn put_many<I>(&mut self, entries: I)
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let changes = entries
+            .into_iter()
+            .map(|(key, value)| (ProofPath::new(&key), Some(value)))
+            .collect();
+        self.update_many(changes);
+    }
+
+    /// Removes the keys from the proof map.
+    ///
+    /// Unlike the repeated calls of [`remove()`], this method recalculates and saves each
+    /// affected branch node of the tree only once.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use exonum::storage::{MemoryDB, Database, ProofMapIndex};
+    /// use exonum::crypto::hash;
+    ///
+    /// let db = MemoryDB::new();
+    /// let name = "name";
+    /// let mut fork = db.fork();
+    /// let mut index = ProofMapIndex::new(name, &mut fork);
+    ///
+    /// index.put_many(vec![(hash(&[1]), 1), (hash(&[2]), 2), (hash(&[3]), 3)]);
+    /// index.remove_many(vec![hash(&[1]), hash(&[3])]);
+    /// assert!(!index.contains(&hash(&[1])));
+    /// assert!(index.contains(&hash(&[2])));
+    /// ```
+    ///
+    /// [`remove()`]: #method.remove
+    pub fn remove_many<KI>(&mut self, keys: KI)
+    where
+        KI: IntoIterator<Item = K>,
+    {
+        let changes = keys
+            .into_iter()
+            .map(|key| (ProofPath::new(&key), None))
+            .collect();
+        self.update_many(changes);
+    }
+
     /// Clears the proof map, removing all entries.
     ///
     /// # Notes
@@ -763,6 +1207,19 @@ where
     }
 }
 
+/// Splits the changes sorted by path into the ones going to the left and to the right child
+/// of a branch with the specified path length. The left changes remain in `changes`.
+fn split_changes<V>(
+    changes: &mut Vec<(ProofPath, Option<V>)>,
+    prefix_len: u16,
+) -> Vec<(ProofPath, Option<V>)> {
+    let index = changes
+        .iter()
+        .position(|&(ref path, _)| path.bit(prefix_len) == ChildKind::Right)
+        .unwrap_or_else(|| changes.len());
+    changes.split_off(index)
+}
+
 impl<'a, T, K, V> ::std::iter::IntoIterator for &'a ProofMapIndex<T, K, V>
 where
     T: AsRef<dyn Snapshot>,
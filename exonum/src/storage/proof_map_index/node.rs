@@ -22,7 +22,7 @@ use super::{
 };
 use crypto::{hash, CryptoHash, Hash, HASH_SIZE};
 
-const BRANCH_NODE_SIZE: usize = 2 * (HASH_SIZE + PROOF_PATH_SIZE);
+pub const BRANCH_NODE_SIZE: usize = 2 * (HASH_SIZE + PROOF_PATH_SIZE);
 
 #[derive(Debug)]
 pub enum Node<T: StorageValue> {
@@ -53,11 +53,16 @@ impl BranchNode {
     }
 
     pub fn child_path(&self, kind: ChildKind) -> ProofPath {
+        ProofPath::read(self.child_path_bytes(kind))
+    }
+
+    /// Returns the serialized path to the child, which is not checked for consistency.
+    pub fn child_path_bytes(&self, kind: ChildKind) -> &[u8] {
         let from = match kind {
             ChildKind::Right => 2 * HASH_SIZE + PROOF_PATH_SIZE,
             ChildKind::Left => 2 * HASH_SIZE,
         };
-        ProofPath::read(&self.raw[from..from + PROOF_PATH_SIZE])
+        &self.raw[from..from + PROOF_PATH_SIZE]
     }
 
     pub fn set_child_path(&mut self, kind: ChildKind, prefix: &ProofPath) {
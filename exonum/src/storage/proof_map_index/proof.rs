@@ -122,6 +122,18 @@ pub enum MapProofError {
     /// Entries in the proof are not ordered by increasing path.
     #[fail(display = "invalid path ordering")]
     InvalidOrdering(ProofPath, ProofPath),
+
+    /// The lower bound of the range proof is greater than its upper bound.
+    #[fail(display = "invalid range bounds")]
+    InvalidRange,
+
+    /// An entry of the range proof lies outside of the proven range.
+    #[fail(display = "entry outside of the proven range")]
+    OutOfRange(ProofPath),
+
+    /// A hashed subtree of the range proof overlaps with the proven range.
+    #[fail(display = "hashed subtree overlapping with the proven range")]
+    OverlappingSubtree(ProofPath),
 }
 
 // Used instead of `(ProofPath, Hash)` only for the purpose of clearer (de)serialization.
@@ -275,6 +287,149 @@ pub struct CheckedMapProof<K, V> {
     hash: Hash,
 }
 
+// Used instead of `(K, V)` only for the purpose of clearer (de)serialization.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+struct MapRangeEntry<K, V> {
+    key: K,
+    value: V,
+}
+
+/// View of a `ProofMapIndex` restricted to an interval of keys, i.e., all the elements
+/// of the index with keys in the interval coupled with a *proof*, which jointly allow
+/// restoring the `merkle_root()` of the index. Unlike [`MapProof`], a verified `MapRangeProof`
+/// also guarantees that the index contains no other elements within the interval.
+///
+/// # Key order
+///
+/// Keys are compared by their [`ProofPath`]s, i.e., bit by bit starting from the least
+/// significant bit of the first byte. In particular, the interval
+/// `[prefix ‖ 00…00, prefix ‖ ff…ff]` contains exactly the keys starting with
+/// the byte sequence `prefix`; however, the order of keys within a byte may differ from
+/// the lexicographic one.
+///
+/// # Workflow
+///
+/// You can create `MapRangeProof`s with the [`get_range_proof()`] method of `ProofMapIndex`.
+/// Proofs can be verified on the client side with the help of [`check()`]. The client should
+/// compare the bounds and the root hash of the checked proof with the expected ones.
+///
+/// ```
+/// # use exonum::storage::{Database, MemoryDB, ProofMapIndex};
+/// let mut fork = { let db = MemoryDB::new(); db.fork() };
+/// let mut map = ProofMapIndex::new("index", &mut fork);
+/// map.put(&[1; 32], 100u32);
+/// map.put(&[2; 32], 200u32);
+/// map.put(&[3; 32], 300u32);
+///
+/// // Get the proof for all keys starting with `2`
+/// let (mut from, mut to) = ([0; 32], [0xff; 32]);
+/// from[0] = 2;
+/// to[0] = 2;
+/// let proof = map.get_range_proof(from, to);
+///
+/// // Check the proof consistency
+/// let checked_proof = proof.check().unwrap();
+/// assert_eq!(checked_proof.bounds(), (&from, &to));
+/// assert_eq!(checked_proof.entries().collect::<Vec<_>>(), vec![(&[2; 32], &200u32)]);
+/// assert_eq!(checked_proof.merkle_root(), map.merkle_root());
+/// ```
+///
+/// # JSON serialization
+///
+/// `MapRangeProof` is serialized to JSON as an object with the following fields:
+///
+/// - `from` and `to` are the inclusive bounds of the range.
+/// - `entries` is an array of `{ "key": K, "value": V }` objects for all key-value pairs
+///   of the underlying index within the range.
+/// - `proof` is an array of `{ "path": ProofPath, "hash": Hash }` objects for subtrees
+///   lying outside of the range, sorted by increasing [`ProofPath`].
+///
+/// [`MapProof`]: struct.MapProof.html
+/// [`ProofPath`]: struct.ProofPath.html
+/// [`get_range_proof()`]: struct.ProofMapIndex.html#method.get_range_proof
+/// [`check()`]: #method.check
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MapRangeProof<K, V> {
+    from: K,
+    to: K,
+    entries: Vec<MapRangeEntry<K, V>>,
+    proof: Vec<MapProofEntry>,
+}
+
+/// Version of `MapRangeProof` obtained after verification.
+///
+/// See [`MapRangeProof`] for an example of usage.
+///
+/// [`MapRangeProof`]: struct.MapRangeProof.html#workflow
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CheckedMapRangeProof<K, V> {
+    from: K,
+    to: K,
+    entries: Vec<(K, V)>,
+    hash: Hash,
+}
+
+/// Checks that the entries in the proof are ordered by increasing path and that
+/// none of the paths is a prefix of another one.
+fn check_proof_ordering(proof: &[MapProofEntry]) -> Result<(), MapProofError> {
+    use self::MapProofError::*;
+    use std::cmp::Ordering;
+
+    for w in proof.windows(2) {
+        let (prev_path, path) = (&w[0].path, &w[1].path);
+        match prev_path.partial_cmp(path) {
+            Some(Ordering::Less) => {
+                if path.starts_with(prev_path) {
+                    return Err(EmbeddedPaths {
+                        prefix: *prev_path,
+                        path: *path,
+                    });
+                }
+            }
+            Some(Ordering::Equal) => {
+                return Err(DuplicatePath(*path));
+            }
+            Some(Ordering::Greater) => {
+                return Err(InvalidOrdering(*prev_path, *path));
+            }
+            None => unreachable!("Incomparable keys in proof"),
+        }
+    }
+    Ok(())
+}
+
+/// Sorts the entries by increasing path and checks that there are no duplicates among them.
+fn sort_entries(entries: &mut [MapProofEntry]) -> Result<(), MapProofError> {
+    // Rust docs state that in the case the entries are mostly sorted
+    // (which is the case for proofs returned by `ProofMapIndex`),
+    // the sort is performed very quickly.
+    entries.sort_unstable_by(|x, y| {
+        x.path.partial_cmp(&y.path).expect(
+            "Incorrectly formed paths supplied to MapProof; \
+             paths should have `start` field set to 0",
+        )
+    });
+
+    // This check is required as duplicate paths can be introduced by entries
+    // (further, it's generally possible that two different entry keys lead to the same
+    // `ProofPath`).
+    for window in entries.windows(2) {
+        if window[0].path == window[1].path {
+            return Err(MapProofError::DuplicatePath(window[0].path));
+        }
+    }
+    Ok(())
+}
+
+/// Checks if the subtree with the specified path contains paths lying in the inclusive
+/// interval `[from, to]`.
+///
+/// If the subtree path is not a prefix of either bound, the whole subtree compares
+/// to each bound in the same way as the subtree path itself.
+fn overlaps_range(path: &ProofPath, from: &ProofPath, to: &ProofPath) -> bool {
+    from.starts_with(path) || to.starts_with(path) || (path > from && path < to)
+}
+
 /// Computes the root hash of the Merkle Patricia tree backing the specified entries
 /// in the map view.
 ///
@@ -447,6 +602,27 @@ impl<K, V> MapProofBuilder<K, V> {
 }
 
 impl<K, V> MapProof<K, V> {
+    /// Converts the values of the proof with the given function.
+    pub(crate) fn map_values<W, F>(self, mut f: F) -> MapProof<K, W>
+    where
+        F: FnMut(V) -> W,
+    {
+        let entries = self
+            .entries
+            .into_iter()
+            .map(|entry| match entry {
+                OptionalEntry::Missing { missing } => OptionalEntry::Missing { missing },
+                OptionalEntry::KV { key, value } => OptionalEntry::KV {
+                    key,
+                    value: f(value),
+                },
+            }).collect();
+        MapProof {
+            entries,
+            proof: self.proof,
+        }
+    }
+
     /// Provides access to the proof part of the view. Useful mainly for debug purposes.
     pub fn proof_unchecked(&self) -> Vec<(ProofPath, Hash)> {
         self.proof
@@ -481,29 +657,9 @@ where
 {
     fn precheck(&self) -> Result<(), MapProofError> {
         use self::MapProofError::*;
-        use std::cmp::Ordering;
 
         // Check that entries in proof are in increasing order
-        for w in self.proof.windows(2) {
-            let (prev_path, path) = (&w[0].path, &w[1].path);
-            match prev_path.partial_cmp(path) {
-                Some(Ordering::Less) => {
-                    if path.starts_with(prev_path) {
-                        return Err(EmbeddedPaths {
-                            prefix: *prev_path,
-                            path: *path,
-                        });
-                    }
-                }
-                Some(Ordering::Equal) => {
-                    return Err(DuplicatePath(*path));
-                }
-                Some(Ordering::Greater) => {
-                    return Err(InvalidOrdering(*prev_path, *path));
-                }
-                None => unreachable!("Incomparable keys in proof"),
-            }
-        }
+        check_proof_ordering(&self.proof)?;
 
         // Check that no entry has a prefix among the paths in the proof entries.
         // In order to do this, it suffices to locate the closest smaller path in the proof entries
@@ -569,24 +725,7 @@ where
                 hash: v.hash(),
             })
         }));
-        // Rust docs state that in the case `self.proof` and `self.entries` are sorted
-        // (which is the case for `MapProof`s returned by `ProofMapIndex.get_proof()`),
-        // the sort is performed very quickly.
-        proof.sort_unstable_by(|x, y| {
-            x.path.partial_cmp(&y.path).expect(
-                "Incorrectly formed paths supplied to MapProof; \
-                 paths should have `start` field set to 0",
-            )
-        });
-
-        // This check is required as duplicate paths can be introduced by entries
-        // (further, it's generally possible that two different entry keys lead to the same
-        // `ProofPath`).
-        for window in proof.windows(2) {
-            if window[0].path == window[1].path {
-                return Err(MapProofError::DuplicatePath(window[0].path));
-            }
-        }
+        sort_entries(&mut proof)?;
 
         collect(&proof).map(|h| CheckedMapProof {
             entries: entries.into_iter().map(OptionalEntry::into).collect(),
@@ -624,6 +763,97 @@ impl<K, V> CheckedMapProof<K, V> {
     }
 }
 
+impl<K, V> MapRangeProof<K, V> {
+    /// Returns the bounds of the range, which the proof is constructed for.
+    /// This method does not perform any integrity checks of the proof.
+    pub fn bounds_unchecked(&self) -> (&K, &K) {
+        (&self.from, &self.to)
+    }
+
+    /// Provides access to the proof part of the view. Useful mainly for debug purposes.
+    pub fn proof_unchecked(&self) -> Vec<(ProofPath, Hash)> {
+        self.proof.iter().map(|e| (e.path, e.hash)).collect()
+    }
+
+    /// Retrieves references to key-value pairs in the proof.
+    /// This method does not perform any integrity checks of the proof.
+    pub fn entries_unchecked(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.entries.iter().map(|e| (&e.key, &e.value))
+    }
+}
+
+impl<K, V> MapRangeProof<K, V>
+where
+    K: ProofMapKey,
+    V: StorageValue,
+{
+    /// Consumes this proof producing a `CheckedMapRangeProof` structure.
+    ///
+    /// Fails if the proof is malformed, in particular, if it contains entries outside
+    /// of the range or hashes of subtrees which may contain such entries.
+    /// Provided that the root hash of the checked proof matches the `merkle_root()`
+    /// of the index, the checked proof contains all the entries of the index within the range.
+    pub fn check(self) -> Result<CheckedMapRangeProof<K, V>, MapProofError> {
+        use self::MapProofError::*;
+
+        let MapRangeProof {
+            from,
+            to,
+            entries,
+            mut proof,
+        } = self;
+        let (from_path, to_path) = (ProofPath::new(&from), ProofPath::new(&to));
+        if from_path > to_path {
+            return Err(InvalidRange);
+        }
+
+        check_proof_ordering(&proof)?;
+        if let Some(entry) = proof
+            .iter()
+            .find(|e| overlaps_range(&e.path, &from_path, &to_path))
+        {
+            return Err(OverlappingSubtree(entry.path));
+        }
+
+        for entry in &entries {
+            let path = ProofPath::new(&entry.key);
+            if path < from_path || path > to_path {
+                return Err(OutOfRange(path));
+            }
+            proof.push(MapProofEntry {
+                path,
+                hash: entry.value.hash(),
+            });
+        }
+        sort_entries(&mut proof)?;
+
+        collect(&proof).map(|hash| CheckedMapRangeProof {
+            from,
+            to,
+            entries: entries.into_iter().map(|e| (e.key, e.value)).collect(),
+            hash,
+        })
+    }
+}
+
+impl<K, V> CheckedMapRangeProof<K, V> {
+    /// Returns the bounds of the range, which the proof is constructed for.
+    pub fn bounds(&self) -> (&K, &K) {
+        (&self.from, &self.to)
+    }
+
+    /// Retrieves references to key-value pairs that the proof shows as present in the map.
+    /// The map has no other entries within the range.
+    pub fn entries(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.entries.iter().map(|&(ref k, ref v)| (k, v))
+    }
+
+    /// Returns a hash of the map that this proof is constructed for.
+    pub fn merkle_root(&self) -> Hash {
+        self.hash
+    }
+}
+
 /// Creates a proof for a single key.
 pub fn create_proof<K, V, F>(
     key: K,
@@ -832,6 +1062,87 @@ where
     builder
 }
 
+/// Creates a proof for the keys with paths in the inclusive interval `[from, to]`.
+///
+/// The tree is traversed in the order of increasing paths; subtrees lying outside
+/// the interval are added to the proof as hashes, and the others are expanded.
+pub fn create_range_proof<K, V, F>(
+    from: K,
+    to: K,
+    root_node: Option<(ProofPath, Node<V>)>,
+    lookup: F,
+) -> MapRangeProof<K, V>
+where
+    K: ProofMapKey<Output = K>,
+    V: StorageValue,
+    F: Fn(&ProofPath) -> Node<V>,
+{
+    fn add_child<K, V, F>(
+        proof: &mut MapRangeProof<K, V>,
+        bounds: &(ProofPath, ProofPath),
+        branch: &BranchNode,
+        kind: ChildKind,
+        lookup: &F,
+    ) where
+        K: ProofMapKey<Output = K>,
+        V: StorageValue,
+        F: Fn(&ProofPath) -> Node<V>,
+    {
+        let path = branch.child_path(kind);
+        if !overlaps_range(&path, &bounds.0, &bounds.1) {
+            proof.proof.push(MapProofEntry {
+                path,
+                hash: *branch.child_hash(kind),
+            });
+            return;
+        }
+
+        match lookup(&path) {
+            Node::Branch(child) => {
+                add_child(proof, bounds, &child, ChildKind::Left, lookup);
+                add_child(proof, bounds, &child, ChildKind::Right, lookup);
+            }
+            Node::Leaf(value) => proof.entries.push(MapRangeEntry {
+                key: K::read_key(path.raw_key()),
+                value,
+            }),
+        }
+    }
+
+    let bounds = (ProofPath::new(&from), ProofPath::new(&to));
+    let mut proof = MapRangeProof {
+        from,
+        to,
+        entries: vec![],
+        proof: vec![],
+    };
+
+    match root_node {
+        Some((_, Node::Branch(root_branch))) => {
+            add_child(&mut proof, &bounds, &root_branch, ChildKind::Left, &lookup);
+            add_child(&mut proof, &bounds, &root_branch, ChildKind::Right, &lookup);
+        }
+
+        Some((root_path, Node::Leaf(root_value))) => {
+            if overlaps_range(&root_path, &bounds.0, &bounds.1) {
+                proof.entries.push(MapRangeEntry {
+                    key: K::read_key(root_path.raw_key()),
+                    value: root_value,
+                });
+            } else {
+                proof.proof.push(MapProofEntry {
+                    path: root_path,
+                    hash: root_value.hash(),
+                });
+            }
+        }
+
+        None => {}
+    }
+
+    proof
+}
+
 pub fn create_multiproof<K, V, KI, F>(
     keys: KI,
     root_node: Option<(ProofPath, Node<V>)>,
@@ -27,11 +27,11 @@ use super::{
     key::{BitsRange, ChildKind, KEY_SIZE, LEAF_KEY_PREFIX},
     node::BranchNode,
     proof::MapProofBuilder,
-    HashedKey, MapProof, MapProofError, ProofMapIndex, ProofMapKey, ProofPath,
+    HashedKey, MapProof, MapProofError, MapRangeProof, ProofMapIndex, ProofMapKey, ProofPath,
 };
 use crypto::{hash, CryptoHash, Hash, HashStream};
 use encoding::serialize::reexport::{DeserializeOwned, Serialize};
-use storage::{Database, Fork, StorageValue};
+use storage::{Database, Fork, MemoryDB, Snapshot, StorageValue};
 
 const IDX_NAME: &'static str = "idx_name";
 
@@ -412,6 +412,42 @@ fn check_map_multiproof<K, V>(
     );
 }
 
+fn check_map_range_proof<K, V>(
+    proof: MapRangeProof<K, V>,
+    mut entries: Vec<(K, V)>,
+    table: &ProofMapIndex<&mut Fork, K, V>,
+) where
+    K: ProofMapKey + PartialEq + Debug + Serialize + DeserializeOwned,
+    V: StorageValue + PartialEq + Debug + Serialize + DeserializeOwned,
+{
+    let serialized_proof = serde_json::to_value(&proof).unwrap();
+    let deserialized_proof: MapRangeProof<K, V> = serde_json::from_value(serialized_proof).unwrap();
+
+    entries.sort_by(|x, y| {
+        ProofPath::new(&x.0)
+            .partial_cmp(&ProofPath::new(&y.0))
+            .unwrap()
+    });
+
+    let proof = proof.check().unwrap();
+    assert_eq!(
+        proof.entries().collect::<Vec<_>>(),
+        entries
+            .iter()
+            .map(|&(ref k, ref v)| (k, v))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(proof.merkle_root(), table.merkle_root());
+
+    let deserialized_proof = deserialized_proof.check().unwrap();
+    assert_eq!(deserialized_proof.bounds(), proof.bounds());
+    assert_eq!(
+        deserialized_proof.entries().collect::<Vec<_>>(),
+        proof.entries().collect::<Vec<_>>()
+    );
+    assert_eq!(deserialized_proof.merkle_root(), proof.merkle_root());
+}
+
 const MAX_CHECKED_ELEMENTS: usize = 1_024;
 
 fn check_proofs_for_data<K, V>(db: &Box<dyn Database>, data: Vec<(K, V)>, nonexisting_keys: Vec<K>)
@@ -564,6 +600,59 @@ fn test_invalid_map_proofs() {
     }
 }
 
+#[test]
+fn test_invalid_map_range_proofs() {
+    use self::MapProofError::*;
+
+    let db = MemoryDB::new();
+    let mut storage = db.fork();
+    let mut table = ProofMapIndex::new(IDX_NAME, &mut storage);
+    for i in 1_u8..5 {
+        table.put(&[i; 32], vec![i]);
+    }
+
+    let proof = table.get_range_proof([2; 32], [3; 32]);
+    let json = serde_json::to_value(&proof).unwrap();
+
+    let mut tampered = json.clone();
+    tampered["from"] = json["to"].clone();
+    tampered["to"] = json["from"].clone();
+    let tampered: MapRangeProof<[u8; 32], Vec<u8>> = serde_json::from_value(tampered).unwrap();
+    match tampered.check().unwrap_err() {
+        InvalidRange => {}
+        e => panic!("expected invalid range error, got {}", e),
+    }
+
+    let mut tampered = json.clone();
+    tampered["to"] = json["from"].clone();
+    let tampered: MapRangeProof<[u8; 32], Vec<u8>> = serde_json::from_value(tampered).unwrap();
+    match tampered.check().unwrap_err() {
+        OutOfRange(..) => {}
+        e => panic!("expected out of range error, got {}", e),
+    }
+
+    // The entry with the key `[3; 32]` is hidden behind the hash of a subtree.
+    let proof = table.get_range_proof([2; 32], [2; 32]);
+    let mut tampered = serde_json::to_value(&proof).unwrap();
+    tampered["to"] = json["to"].clone();
+    let tampered: MapRangeProof<[u8; 32], Vec<u8>> = serde_json::from_value(tampered).unwrap();
+    match tampered.check().unwrap_err() {
+        OverlappingSubtree(..) => {}
+        e => panic!("expected overlapping subtree error, got {}", e),
+    }
+
+    // The entry with the key `[3; 32]` is omitted altogether.
+    let key = serde_json::to_value(&[3_u8; 32]).unwrap();
+    let mut tampered = json.clone();
+    tampered["entries"]
+        .as_array_mut()
+        .unwrap()
+        .retain(|e| e["key"] != key);
+    let tampered: MapRangeProof<[u8; 32], Vec<u8>> = serde_json::from_value(tampered).unwrap();
+    let checked = tampered.check().unwrap();
+    assert_ne!(checked.merkle_root(), table.merkle_root());
+}
+
 fn build_proof_in_empty_tree(db: Box<dyn Database>) {
     let mut storage = db.fork();
     let mut table = ProofMapIndex::new(IDX_NAME, &mut storage);
@@ -999,6 +1088,69 @@ fn build_multiproof_simple(db: Box<dyn Database>) {
     check_map_multiproof(proof, keys, &table);
 }
 
+fn build_range_proofs(db: Box<dyn Database>) {
+    let mut storage = db.fork();
+    let mut table = ProofMapIndex::new(IDX_NAME, &mut storage);
+
+    let proof = table.get_range_proof([0; 32], [255; 32]);
+    assert_eq!(proof.proof_unchecked(), vec![]);
+    check_map_range_proof(proof, vec![], &table);
+
+    table.put(&[230; 32], vec![1]);
+    let proof = table.get_range_proof([0; 32], [255; 32]);
+    assert_eq!(proof.proof_unchecked(), vec![]);
+    check_map_range_proof(proof, vec![([230; 32], vec![1])], &table);
+
+    let proof = table.get_range_proof([0; 32], [1; 32]);
+    assert_eq!(
+        proof.proof_unchecked(),
+        vec![(ProofPath::new(&[230; 32]), hash(&vec![1]))]
+    );
+    check_map_range_proof(proof, vec![], &table);
+
+    let mut rng = XorShiftRng::from_seed(rand::random());
+    let mut data = generate_random_data(100);
+    data.extend(generate_random_data_keys(100, &mut rng));
+    data.push(([230; 32], vec![1]));
+    for &(ref key, ref value) in &data {
+        table.put(key, value.clone());
+    }
+
+    for _ in 0..50 {
+        let mut bounds = [data[rng.gen_range(0, data.len())].0, [0; 32]];
+        if rng.gen() {
+            rng.fill_bytes(&mut bounds[1]);
+        } else {
+            bounds[1] = data[rng.gen_range(0, data.len())].0;
+        }
+        bounds.sort_by(|x, y| ProofPath::new(x).partial_cmp(&ProofPath::new(y)).unwrap());
+        let (from, to) = (ProofPath::new(&bounds[0]), ProofPath::new(&bounds[1]));
+
+        let entries = data
+            .iter()
+            .filter(|&&(ref key, _)| {
+                let path = ProofPath::new(key);
+                path >= from && path <= to
+            }).cloned()
+            .collect();
+        let proof = table.get_range_proof(bounds[0], bounds[1]);
+        check_map_range_proof(proof, entries, &table);
+    }
+
+    // All keys with the first byte equal to the first byte of the first key.
+    let (mut from, mut to) = ([0; 32], [255; 32]);
+    from[0] = data[0].0[0];
+    to[0] = data[0].0[0];
+    let entries: Vec<_> = data
+        .iter()
+        .filter(|&&(ref key, _)| key[0] == from[0])
+        .cloned()
+        .collect();
+    assert!(!entries.is_empty());
+    let proof = table.get_range_proof(from, to);
+    check_map_range_proof(proof, entries, &table);
+}
+
 fn fuzz_insert_build_proofs_in_table_filled_with_hashes(db: Box<dyn Database>) {
     let mut rng = XorShiftRng::from_seed(rand::random());
     let batch_sizes = (7..9).map(|x| 1 << x);
@@ -1092,6 +1244,72 @@ fn fuzz_delete_build_proofs(db: Box<dyn Database>) {
     }
 }
 
+// Returns all the nodes of the tree including the branch ones.
+fn raw_nodes<T: AsRef<dyn Snapshot>>(
+    index: &ProofMapIndex<T, [u8; KEY_SIZE], Vec<u8>>,
+) -> Vec<(Vec<u8>, Vec<u8>)> {
+    index.base.iter::<_, Vec<u8>, Vec<u8>>(&()).collect()
+}
+
+fn fuzz_put_and_remove_many(db1: Box<dyn Database>, db2: Box<dyn Database>) {
+    let mut rng = XorShiftRng::from_seed(rand::random());
+    let mut data = generate_random_data(100);
+    data.extend(generate_random_data_keys(100, &mut rng));
+
+    let mut storage1 = db1.fork();
+    let mut index1 = ProofMapIndex::new(IDX_NAME, &mut storage1);
+    let mut storage2 = db2.fork();
+    let mut index2 = ProofMapIndex::new(IDX_NAME, &mut storage2);
+
+    // Insert entries into an empty map and a non-empty one.
+    let (first, second) = data.split_at(50);
+    for &(ref key, ref value) in first.iter().chain(second) {
+        index1.put(key, value.clone());
+    }
+    index2.put_many(first.iter().cloned());
+    index2.put_many(second.iter().cloned());
+    assert_eq!(raw_nodes(&index2), raw_nodes(&index1));
+    assert_eq!(index2.merkle_root(), index1.merkle_root());
+
+    // Overwrite some values; the last value of a duplicate key wins.
+    let mut updates: Vec<_> = data
+        .choose_multiple(&mut rng, 50)
+        .map(|&(key, _)| (key, vec![rng.gen::<u8>()]))
+        .collect();
+    let duplicate = (updates[0].0, vec![1, 2, 3]);
+    updates.push(duplicate);
+    for &(ref key, ref value) in &updates {
+        index1.put(key, value.clone());
+    }
+    index2.put_many(updates);
+    assert_eq!(raw_nodes(&index2), raw_nodes(&index1));
+
+    // Remove existing and non-existing keys.
+    data.shuffle(&mut rng);
+    let mut keys: Vec<_> = data[..100].iter().map(|&(key, _)| key).collect();
+    keys.extend(
+        generate_random_data_keys(20, &mut rng)
+            .into_iter()
+            .map(|(key, _)| key),
+    );
+    let duplicate = keys[0];
+    keys.push(duplicate);
+    for key in &keys {
+        index1.remove(key);
+    }
+    index2.remove_many(keys);
+    assert_eq!(raw_nodes(&index2), raw_nodes(&index1));
+    assert_eq!(index2.merkle_root(), index1.merkle_root());
+    for &(ref key, _) in &data[100..] {
+        let proof = index2.get_proof(*key);
+        check_map_proof(proof, Some(*key), &index2);
+    }
+
+    index2.remove_many(data.iter().map(|&(key, _)| key));
+    assert_eq!(raw_nodes(&index2), vec![]);
+    assert_eq!(index2.merkle_root(), Hash::zero());
+}
+
 fn fuzz_delete(db1: Box<dyn Database>, db2: Box<dyn Database>) {
     let mut data = generate_random_data(100);
     let mut rng = rand::thread_rng();
@@ -1371,6 +1589,7 @@ macro_rules! common_tests {
         }
         test_on_db!{test_build_proof_in_complex_tree, build_proof_in_complex_tree}
         test_on_db!{test_build_multiproof_simple, build_multiproof_simple}
+        test_on_db!{test_build_range_proofs, build_range_proofs}
         test_on_db!{
             test_fuzz_insert_build_proofs_in_table_filled_with_hashes,
             fuzz_insert_build_proofs_in_table_filled_with_hashes
@@ -1379,6 +1598,7 @@ macro_rules! common_tests {
         test_on_db!{test_fuzz_insert_build_multiproofs, fuzz_insert_build_multiproofs}
         test_on_db!{test_fuzz_delete_build_proofs, fuzz_delete_build_proofs}
         test_on_2dbs!{test_fuzz_delete, fuzz_delete}
+        test_on_2dbs!{test_fuzz_put_and_remove_many, fuzz_put_and_remove_many}
         test_on_db!{test_fuzz_insert_after_delete, fuzz_insert_after_delete}
         test_on_db!{test_iter, iter}
         test_on_db!{test_tree_with_hashed_key, tree_with_hashed_key}
@@ -1397,6 +1617,7 @@ mod memorydb_tests {
     common_tests!{}
 }
 
+#[cfg(feature = "rocksdb-storage")]
 mod rocksdb_tests {
     use std::path::Path;
     use storage::{Database, DbOptions, RocksDB};
@@ -1409,3 +1630,17 @@ mod rocksdb_tests {
 
     common_tests!{}
 }
+
+#[cfg(feature = "lmdb-storage")]
+mod lmdb_tests {
+    use std::path::Path;
+    use storage::{Database, DbOptions, LMDB};
+    use tempdir::TempDir;
+
+    fn create_database(path: &Path) -> Box<dyn Database> {
+        let opts = DbOptions::default();
+        Box::new(LMDB::open(path, &opts).unwrap())
+    }
+
+    common_tests!{}
+}
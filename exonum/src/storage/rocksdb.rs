@@ -18,11 +18,23 @@
 
 pub use rocksdb::{BlockBasedOptions as RocksBlockOptions, WriteOptions as RocksDBWriteOptions};
 
-use rocksdb::{self, utils::get_cf_names, DBIterator, Options as RocksDbOptions, WriteBatch};
+use rocksdb::{
+    self, checkpoint::Checkpoint, utils::get_cf_names, ColumnFamilyDescriptor, DBCompressionType,
+    DBIterator, Options as RocksDbOptions, WriteBatch,
+};
 
-use std::{error::Error, fmt, iter::Peekable, mem, path::Path, sync::Arc};
+use std::{
+    collections::HashMap, error::Error, fmt, iter::Peekable, mem, path::Path, sync::Arc,
+    time::Instant,
+};
 
-use storage::{self, db::Change, Database, DbOptions, Iter, Iterator, Patch, Snapshot};
+use storage::{
+    self,
+    db::Change,
+    indexes_metadata::{self, IndexType},
+    Database, DbOptions, Iter, Iterator, Patch, RocksDbCompression, RocksDbProfile, Snapshot,
+    INDEXES_METADATA_TABLE_NAME,
+};
 
 impl From<rocksdb::Error> for storage::Error {
     fn from(err: rocksdb::Error) -> Self {
@@ -36,17 +48,91 @@ impl From<rocksdb::Error> for storage::Error {
 /// `RocksDB` is an embedded database for key-value data, which is optimized for fast storage.
 /// This structure is required to potentially adapt the interface to
 /// use different databases.
+///
+/// The column families of the database can be tuned with the [`rocksdb`] option.
+///
+/// [`rocksdb`]: struct.DbOptions.html#structfield.rocksdb
 pub struct RocksDB {
     db: Arc<rocksdb::DB>,
+    options: DbOptions,
 }
 
 impl DbOptions {
     fn to_rocksdb(&self) -> RocksDbOptions {
-        let mut defaults = RocksDbOptions::default();
+        let mut defaults = self.column_family_options(None);
         defaults.create_if_missing(self.create_if_missing);
         defaults.set_max_open_files(self.max_open_files.unwrap_or(-1));
         defaults
     }
+
+    /// Returns the options of a column family storing an index of the given type.
+    fn column_family_options(&self, index_type: Option<IndexType>) -> RocksDbOptions {
+        let mut options = RocksDbOptions::default();
+        if let Some(ref tuning) = self.rocksdb {
+            let profile = match index_type {
+                Some(IndexType::List)
+                | Some(IndexType::ProofList)
+                | Some(IndexType::SparseList)
+                | Some(IndexType::Mmr) => tuning.common.overridden_by(tuning.lists),
+                Some(IndexType::Map)
+                | Some(IndexType::ProofMap)
+                | Some(IndexType::KeySet)
+                | Some(IndexType::ValueSet) => tuning.common.overridden_by(tuning.maps),
+                Some(IndexType::Entry) | None => tuning.common,
+            };
+            profile.apply(&mut options);
+        }
+        options
+    }
+
+    fn sync_wal(&self) -> bool {
+        self.rocksdb.map_or(false, |tuning| tuning.sync_wal)
+    }
+}
+
+impl RocksDbProfile {
+    fn apply(&self, options: &mut RocksDbOptions) {
+        if let Some(compression) = self.compression {
+            options.set_compression_type(compression.into());
+        }
+        if let Some(size) = self.write_buffer_size {
+            options.set_write_buffer_size(size);
+        }
+        if let Some(number) = self.max_write_buffer_number {
+            options.set_max_write_buffer_number(number);
+        }
+        if self.block_size.is_none()
+            && self.block_cache_size.is_none()
+            && self.bloom_filter_bits.is_none()
+        {
+            return;
+        }
+
+        let mut block_options = RocksBlockOptions::default();
+        if let Some(size) = self.block_size {
+            block_options.set_block_size(size);
+        }
+        if let Some(size) = self.block_cache_size {
+            block_options.set_lru_cache(size);
+        }
+        if let Some(bits) = self.bloom_filter_bits {
+            block_options.set_bloom_filter(bits, false);
+        }
+        options.set_block_based_table_factory(&block_options);
+    }
+}
+
+impl From<RocksDbCompression> for DBCompressionType {
+    fn from(compression: RocksDbCompression) -> Self {
+        match compression {
+            RocksDbCompression::None => DBCompressionType::None,
+            RocksDbCompression::Snappy => DBCompressionType::Snappy,
+            RocksDbCompression::Zlib => DBCompressionType::Zlib,
+            RocksDbCompression::Bz2 => DBCompressionType::Bz2,
+            RocksDbCompression::Lz4 => DBCompressionType::Lz4,
+            RocksDbCompression::Lz4hc => DBCompressionType::Lz4hc,
+        }
+    }
 }
 
 /// A snapshot of a `RocksDB`.
@@ -71,24 +157,82 @@ impl RocksDB {
     pub fn open<P: AsRef<Path>>(path: P, options: &DbOptions) -> storage::Result<Self> {
         let db = {
             if let Ok(names) = get_cf_names(&path) {
-                let cf_names = names.iter().map(|name| name.as_str()).collect::<Vec<_>>();
-                rocksdb::DB::open_cf(&options.to_rocksdb(), path, cf_names.as_ref())?
+                let index_types = if options.has_index_profiles() {
+                    Self::read_index_types(&path, options, &names)?
+                } else {
+                    HashMap::new()
+                };
+                let descriptors = names
+                    .iter()
+                    .map(|name| {
+                        let index_type = index_types.get(name).cloned();
+                        let cf_options = options.column_family_options(index_type);
+                        ColumnFamilyDescriptor::new(name.as_str(), cf_options)
+                    }).collect::<Vec<_>>();
+                rocksdb::DB::open_cf_descriptors(&options.to_rocksdb(), path, descriptors)?
             } else {
                 rocksdb::DB::open(&options.to_rocksdb(), path)?
             }
         };
-        Ok(Self { db: Arc::new(db) })
+        Ok(Self {
+            db: Arc::new(db),
+            options: *options,
+        })
+    }
+
+    /// Reads the types of the indexes from the storage metadata, opening the database
+    /// with the same options for all the column families.
+    fn read_index_types<P: AsRef<Path>>(
+        path: P,
+        options: &DbOptions,
+        names: &[String],
+    ) -> storage::Result<HashMap<String, IndexType>> {
+        let cf_names = names.iter().map(|name| name.as_str()).collect::<Vec<_>>();
+        let db = rocksdb::DB::open_cf(&options.to_rocksdb(), path, cf_names.as_ref())?;
+        let db = Self {
+            db: Arc::new(db),
+            options: *options,
+        };
+        let index_types = indexes_metadata::index_types(&*db.snapshot());
+        Ok(index_types)
+    }
+
+    /// Returns the type of an index, which column family is created on merging the patch.
+    fn new_index_type(&self, patch: &Patch, name: &str) -> Option<IndexType> {
+        if !self.options.has_index_profiles() {
+            return None;
+        }
+        let metadata = patch
+            .iter()
+            .filter(|&(cf_name, _)| cf_name == INDEXES_METADATA_TABLE_NAME)
+            .flat_map(|(_, changes)| changes.iter())
+            .find(|&(key, _)| key[..] == *name.as_bytes());
+        match metadata {
+            Some((_, &Change::Put(ref value))) => Some(indexes_metadata::decode_index_type(value)),
+            _ => self
+                .snapshot()
+                .get(INDEXES_METADATA_TABLE_NAME, name.as_bytes())
+                .map(|value| indexes_metadata::decode_index_type(&value)),
+        }
     }
 
     fn do_merge(&self, patch: Patch, w_opts: &RocksDBWriteOptions) -> storage::Result<()> {
+        let start = Instant::now();
         let mut batch = WriteBatch::default();
+        let new_index_types = patch
+            .iter()
+            .filter(|&(cf_name, _)| self.db.cf_handle(cf_name).is_none())
+            .map(|(cf_name, _)| (cf_name.clone(), self.new_index_type(&patch, cf_name)))
+            .collect::<HashMap<_, _>>();
         for (cf_name, changes) in patch {
             let cf = match self.db.cf_handle(&cf_name) {
                 Some(cf) => cf,
-                None => self
-                    .db
-                    .create_cf(&cf_name, &DbOptions::default().to_rocksdb())
-                    .unwrap(),
+                None => {
+                    let cf_options = self
+                        .options
+                        .column_family_options(new_index_types[&cf_name]);
+                    self.db.create_cf(&cf_name, &cf_options).unwrap()
+                }
             };
             for (key, change) in changes {
                 match change {
@@ -97,7 +241,12 @@ impl RocksDB {
                 }
             }
         }
-        self.db.write_opt(batch, w_opts).map_err(Into::into)
+        let result = self.db.write_opt(batch, w_opts).map_err(Into::into);
+
+        let elapsed = start.elapsed();
+        let micros = elapsed.as_secs() * 1_000_000 + u64::from(elapsed.subsec_micros());
+        metric!(histogram: "storage.rocksdb_merge_us", micros);
+        result
     }
 }
 
@@ -110,7 +259,8 @@ impl Database for RocksDB {
     }
 
     fn merge(&self, patch: Patch) -> storage::Result<()> {
-        let w_opts = RocksDBWriteOptions::default();
+        let mut w_opts = RocksDBWriteOptions::default();
+        w_opts.set_sync(self.options.sync_wal());
         self.do_merge(patch, &w_opts)
     }
 
@@ -119,6 +269,15 @@ impl Database for RocksDB {
         w_opts.set_sync(true);
         self.do_merge(patch, &w_opts)
     }
+
+    /// Creates a checkpoint with the `RocksDB` checkpoint facility. The files of
+    /// the checkpoint are hard-linked to the ones of the database if they are located
+    /// on the same file system.
+    fn create_checkpoint(&self, path: &Path) -> storage::Result<()> {
+        let checkpoint = Checkpoint::new(&self.db)?;
+        checkpoint.create_checkpoint(path)?;
+        Ok(())
+    }
 }
 
 impl Snapshot for RocksDBSnapshot {
@@ -148,6 +307,30 @@ impl Snapshot for RocksDBSnapshot {
             value: None,
         })
     }
+
+    fn iter_rev<'a>(&'a self, name: &str, before: Option<&[u8]>) -> Iter<'a> {
+        use rocksdb::{Direction, IteratorMode};
+        let mode = match before {
+            Some(before) => IteratorMode::From(before, Direction::Reverse),
+            None => IteratorMode::End,
+        };
+        let iter = match self.db.cf_handle(name) {
+            Some(cf) => self.snapshot.iterator_cf(cf, mode).unwrap(),
+            None => self.snapshot.iterator(IteratorMode::End),
+        };
+        let mut iter = iter.peekable();
+        // The reverse seek may be positioned at the key equal to `before`.
+        if let Some(before) = before {
+            while iter.peek().map_or(false, |&(ref key, _)| &key[..] >= before) {
+                iter.next();
+            }
+        }
+        Box::new(RocksDBIterator {
+            iter,
+            key: None,
+            value: None,
+        })
+    }
 }
 
 impl Iterator for RocksDBIterator {
@@ -780,6 +780,7 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "rocksdb-storage")]
     mod rocksdb_tests {
         use std::path::Path;
         use storage::{Database, DbOptions, RocksDB};
@@ -806,4 +807,32 @@ mod tests {
             super::list_index_iter(db);
         }
     }
+
+    #[cfg(feature = "lmdb-storage")]
+    mod lmdb_tests {
+        use std::path::Path;
+        use storage::{Database, DbOptions, LMDB};
+        use tempdir::TempDir;
+
+        fn create_database(path: &Path) -> Box<dyn Database> {
+            let opts = DbOptions::default();
+            Box::new(LMDB::open(path, &opts).unwrap())
+        }
+
+        #[test]
+        fn test_list_index_methods() {
+            let dir = TempDir::new(super::gen_tempdir_name().as_str()).unwrap();
+            let path = dir.path();
+            let db = create_database(path);
+            super::list_index_methods(db);
+        }
+
+        #[test]
+        fn test_list_index_iter() {
+            let dir = TempDir::new(super::gen_tempdir_name().as_str()).unwrap();
+            let path = dir.path();
+            let db = create_database(path);
+            super::list_index_iter(db);
+        }
+    }
 }
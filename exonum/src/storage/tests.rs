@@ -117,6 +117,143 @@ fn fork_iter<T: Database>(db: T) {
     assert_iter(&fork, 0, &[(10, 10), (20, 20), (30, 30)]);
 }
 
+fn fork_iter_rev<T: Database>(db: T) {
+    let mut fork = db.fork();
+
+    fork.put(IDX_NAME, vec![10], vec![10]);
+    fork.put(IDX_NAME, vec![20], vec![20]);
+    fork.put(IDX_NAME, vec![30], vec![30]);
+    // Neighboring indexes must not be visited.
+    fork.put("idx_nam", vec![40], vec![40]);
+    fork.put("idx_name.a", vec![0], vec![0]);
+
+    db.merge(fork.into_patch()).unwrap();
+
+    fn assert_iter(fork: &Fork, before: Option<u8>, assumed: &[(u8, u8)]) {
+        let mut values = Vec::new();
+
+        let before = before.map(|before| [before]);
+        let mut iter = fork.iter_rev(IDX_NAME, before.as_ref().map(|before| &before[..]));
+        while let Some((k, v)) = iter.next() {
+            values.push((k[0], v[0]));
+        }
+        assert_eq!(values, assumed);
+    }
+
+    // Stored
+    let mut fork = db.fork();
+    assert_iter(&fork, None, &[(30, 30), (20, 20), (10, 10)]);
+    assert_iter(&fork, Some(31), &[(30, 30), (20, 20), (10, 10)]);
+    assert_iter(&fork, Some(30), &[(20, 20), (10, 10)]);
+    assert_iter(&fork, Some(11), &[(10, 10)]);
+    assert_iter(&fork, Some(10), &[]);
+
+    // Inserted and replaced
+    fork.put(IDX_NAME, vec![5], vec![5]);
+    fork.put(IDX_NAME, vec![25], vec![25]);
+    fork.put(IDX_NAME, vec![35], vec![35]);
+    fork.put(IDX_NAME, vec![20], vec![21]);
+    assert_iter(
+        &fork,
+        None,
+        &[(35, 35), (30, 30), (25, 25), (20, 21), (10, 10), (5, 5)],
+    );
+    assert_iter(&fork, Some(30), &[(25, 25), (20, 21), (10, 10), (5, 5)]);
+    assert_iter(&fork, Some(6), &[(5, 5)]);
+
+    // Deleted and miss deleted
+    let mut fork = db.fork();
+    fork.remove(IDX_NAME, vec![30]);
+    fork.remove(IDX_NAME, vec![15]);
+    fork.remove(IDX_NAME, vec![35]);
+    assert_iter(&fork, None, &[(20, 20), (10, 10)]);
+    fork.remove(IDX_NAME, vec![10]);
+    assert_iter(&fork, None, &[(20, 20)]);
+    fork.put(IDX_NAME, vec![10], vec![11]);
+    assert_iter(&fork, Some(20), &[(10, 11)]);
+
+    // Indexes
+    let mut fork = db.fork();
+    {
+        let mut list = ListIndex::new("list", &mut fork);
+        list.extend(0_u64..10);
+        let mut map = MapIndex::new("map", &mut fork);
+        for i in 0_u8..10 {
+            map.put(&i, u64::from(i));
+        }
+    }
+    db.merge(fork.into_patch()).unwrap();
+
+    let mut fork = db.fork();
+    {
+        let mut list = ListIndex::new("list", &mut fork);
+        list.push(10_u64);
+        list.set(9, 90);
+        let mut map = MapIndex::new("map", &mut fork);
+        map.remove(&9_u8);
+        map.put(&5, 50_u64);
+    }
+    let list: ListIndex<_, u64> = ListIndex::new("list", &fork);
+    assert_eq!(list.iter_rev().take(3).collect::<Vec<_>>(), vec![10, 90, 8]);
+    assert_eq!(list.range(8..10).collect::<Vec<_>>(), vec![8, 90]);
+    assert_eq!(list.range(10..20).collect::<Vec<_>>(), vec![10]);
+    let map: MapIndex<_, u8, u64> = MapIndex::new("map", &fork);
+    assert_eq!(
+        map.iter_rev().take(3).collect::<Vec<_>>(),
+        vec![(8, 8), (7, 7), (6, 6)]
+    );
+    assert_eq!(
+        map.iter_rev_from(&5).take(2).collect::<Vec<_>>(),
+        vec![(5, 50), (4, 4)]
+    );
+    assert_eq!(map.range(&4..&6).collect::<Vec<_>>(), vec![(4, 4), (5, 50)]);
+}
+
+#[test]
+fn default_iter_rev() {
+    use super::{Iter, MemoryDB};
+
+    // Snapshot relying on the default implementation of `iter_rev`.
+    struct ForwardSnapshot(Box<dyn Snapshot>);
+
+    impl Snapshot for ForwardSnapshot {
+        fn get(&self, name: &str, key: &[u8]) -> Option<Vec<u8>> {
+            self.0.get(name, key)
+        }
+
+        fn iter<'a>(&'a self, name: &str, from: &[u8]) -> Iter<'a> {
+            self.0.iter(name, from)
+        }
+    }
+
+    let db = MemoryDB::new();
+    let mut fork = db.fork();
+    fork.put(IDX_NAME, vec![10], vec![10]);
+    fork.put(IDX_NAME, vec![20], vec![20]);
+    fork.put(IDX_NAME, vec![30], vec![30]);
+    db.merge(fork.into_patch()).unwrap();
+
+    fn assert_iter(snapshot: &dyn Snapshot, before: Option<u8>, assumed: &[(u8, u8)]) {
+        let mut values = Vec::new();
+
+        let before = before.map(|before| [before]);
+        let mut iter = snapshot.iter_rev(IDX_NAME, before.as_ref().map(|before| &before[..]));
+        assert_eq!(
+            iter.peek().map(|(k, _)| k[0]),
+            assumed.first().map(|&(k, _)| k)
+        );
+        while let Some((k, v)) = iter.next() {
+            values.push((k[0], v[0]));
+        }
+        assert_eq!(values, assumed);
+    }
+
+    let snapshot = ForwardSnapshot(db.snapshot());
+    assert_iter(&snapshot, None, &[(30, 30), (20, 20), (10, 10)]);
+    assert_iter(&snapshot, Some(30), &[(20, 20), (10, 10)]);
+    assert_iter(&snapshot, Some(10), &[]);
+}
+
 fn changelog<T: Database>(db: T) {
     let mut fork = db.fork();
 
@@ -183,6 +320,76 @@ fn changelog<T: Database>(db: T) {
     assert_eq!(fork.get(IDX_NAME, &[4]), None);
 }
 
+fn savepoints<T: Database>(db: T) {
+    let mut fork = db.fork();
+    fork.put(IDX_NAME, vec![1], vec![1]);
+
+    fork.savepoint("outer");
+    fork.put(IDX_NAME, vec![2], vec![2]);
+
+    fork.savepoint("inner");
+    fork.put(IDX_NAME, vec![1], vec![10]);
+    fork.remove(IDX_NAME, vec![2]);
+    assert_eq!(fork.get(IDX_NAME, &[1]), Some(vec![10]));
+    assert_eq!(fork.get(IDX_NAME, &[2]), None);
+
+    fork.rollback_to_savepoint("inner");
+    assert_eq!(fork.get(IDX_NAME, &[1]), Some(vec![1]));
+    assert_eq!(fork.get(IDX_NAME, &[2]), Some(vec![2]));
+
+    // The savepoint is kept after the rollback.
+    fork.put(IDX_NAME, vec![3], vec![3]);
+    fork.rollback_to_savepoint("inner");
+    assert_eq!(fork.get(IDX_NAME, &[3]), None);
+
+    fork.put(IDX_NAME, vec![3], vec![30]);
+    fork.release_savepoint("inner");
+    assert_eq!(fork.get(IDX_NAME, &[3]), Some(vec![30]));
+
+    // Released changes are rolled back together with the outer savepoint.
+    fork.rollback_to_savepoint("outer");
+    assert_eq!(fork.get(IDX_NAME, &[1]), Some(vec![1]));
+    assert_eq!(fork.get(IDX_NAME, &[2]), None);
+    assert_eq!(fork.get(IDX_NAME, &[3]), None);
+    fork.release_savepoint("outer");
+
+    // Savepoints within a checkpoint.
+    fork.checkpoint();
+    fork.put(IDX_NAME, vec![2], vec![2]);
+    fork.savepoint("tx");
+    fork.put(IDX_NAME, vec![3], vec![3]);
+    fork.rollback_to_savepoint("tx");
+    fork.put(IDX_NAME, vec![4], vec![4]);
+    fork.commit();
+    assert_eq!(fork.get(IDX_NAME, &[2]), Some(vec![2]));
+    assert_eq!(fork.get(IDX_NAME, &[3]), None);
+    assert_eq!(fork.get(IDX_NAME, &[4]), Some(vec![4]));
+
+    // A checkpoint within a savepoint.
+    fork.savepoint("bundle");
+    fork.put(IDX_NAME, vec![5], vec![5]);
+    fork.checkpoint();
+    fork.put(IDX_NAME, vec![6], vec![6]);
+    fork.savepoint("inner");
+    fork.rollback();
+    assert_eq!(fork.get(IDX_NAME, &[5]), Some(vec![5]));
+    assert_eq!(fork.get(IDX_NAME, &[6]), None);
+    fork.checkpoint();
+    fork.put(IDX_NAME, vec![7], vec![7]);
+    fork.commit();
+    fork.rollback_to_savepoint("bundle");
+    fork.release_savepoint("bundle");
+    assert_eq!(fork.get(IDX_NAME, &[5]), None);
+    assert_eq!(fork.get(IDX_NAME, &[7]), None);
+
+    let patch = fork.into_patch();
+    db.merge(patch).unwrap();
+    let snapshot = db.snapshot();
+    assert_eq!(snapshot.get(IDX_NAME, &[1]), Some(vec![1]));
+    assert_eq!(snapshot.get(IDX_NAME, &[4]), Some(vec![4]));
+    assert_eq!(snapshot.get(IDX_NAME, &[5]), None);
+}
+
 mod memorydb_tests {
     use super::super::MemoryDB;
 
@@ -195,12 +402,23 @@ mod memorydb_tests {
         super::fork_iter(memorydb_database());
     }
 
+    #[test]
+    fn test_memory_fork_iter_rev() {
+        super::fork_iter_rev(memorydb_database());
+    }
+
     #[test]
     fn test_memory_changelog() {
         super::changelog(memorydb_database());
     }
+
+    #[test]
+    fn test_memory_savepoints() {
+        super::savepoints(memorydb_database());
+    }
 }
 
+#[cfg(feature = "rocksdb-storage")]
 mod rocksdb_tests {
     use super::super::{DbOptions, RocksDB};
     use std::path::Path;
@@ -219,6 +437,13 @@ mod rocksdb_tests {
         super::fork_iter(rocksdb_database(path));
     }
 
+    #[test]
+    fn test_rocksdb_fork_iter_rev() {
+        let dir = TempDir::new("exonum_rocksdb_iter_rev").unwrap();
+        let path = dir.path();
+        super::fork_iter_rev(rocksdb_database(path));
+    }
+
     #[test]
     fn test_rocksdb_changelog() {
         let dir = TempDir::new("exonum_rocksdb2").unwrap();
@@ -226,6 +451,55 @@ mod rocksdb_tests {
         super::changelog(rocksdb_database(path));
     }
 
+    #[test]
+    fn test_rocksdb_savepoints() {
+        let dir = TempDir::new("exonum_rocksdb_savepoints").unwrap();
+        let path = dir.path();
+        super::savepoints(rocksdb_database(path));
+    }
+
+    #[test]
+    fn test_rocksdb_tuning() {
+        use storage::{MapIndex, RocksDbCompression, RocksDbProfile, RocksDbTuning};
+
+        let dir = TempDir::new("exonum_rocksdb_tuning").unwrap();
+        let path = dir.path();
+        let mut options = DbOptions::default();
+        options.rocksdb = Some(RocksDbTuning {
+            common: RocksDbProfile {
+                compression: Some(RocksDbCompression::Lz4),
+                ..RocksDbProfile::default()
+            },
+            lists: Some(RocksDbProfile {
+                compression: Some(RocksDbCompression::Zlib),
+                write_buffer_size: Some(1 << 20),
+                ..RocksDbProfile::default()
+            }),
+            maps: Some(RocksDbProfile {
+                block_cache_size: Some(1 << 20),
+                bloom_filter_bits: Some(10),
+                ..RocksDbProfile::default()
+            }),
+            sync_wal: true,
+        });
+
+        {
+            let db = RocksDB::open(path, &options).unwrap();
+            let mut fork = db.fork();
+            ListIndex::new("list", &mut fork).extend(0_u64..10);
+            MapIndex::new("map", &mut fork).put(&1_u64, 2_u64);
+            db.merge(fork.into_patch()).unwrap();
+        }
+
+        // The column families are reopened with the options for their index types.
+        let db = RocksDB::open(path, &options).unwrap();
+        let snapshot = db.snapshot();
+        let list: ListIndex<_, u64> = ListIndex::new("list", &snapshot);
+        assert_eq!(list.iter().collect::<Vec<_>>(), (0..10).collect::<Vec<_>>());
+        let map: MapIndex<_, u64, u64> = MapIndex::new("map", &snapshot);
+        assert_eq!(map.get(&1), Some(2));
+    }
+
     #[ignore]
     #[test]
     fn test_multiple_patch() {
@@ -264,6 +538,125 @@ mod rocksdb_tests {
     }
 }
 
+#[cfg(feature = "lmdb-storage")]
+mod lmdb_tests {
+    use super::super::{DbOptions, LMDB};
+    use std::path::Path;
+    use storage::{Database, Snapshot};
+    use tempdir::TempDir;
+
+    fn lmdb_database(path: &Path) -> LMDB {
+        let options = DbOptions::default();
+        LMDB::open(path, &options).unwrap()
+    }
+
+    #[test]
+    fn test_lmdb_fork_iter() {
+        let dir = TempDir::new("exonum_lmdb1").unwrap();
+        let path = dir.path();
+        super::fork_iter(lmdb_database(path));
+    }
+
+    #[test]
+    fn test_lmdb_fork_iter_rev() {
+        let dir = TempDir::new("exonum_lmdb_iter_rev").unwrap();
+        let path = dir.path();
+        super::fork_iter_rev(lmdb_database(path));
+    }
+
+    #[test]
+    fn test_lmdb_changelog() {
+        let dir = TempDir::new("exonum_lmdb2").unwrap();
+        let path = dir.path();
+        super::changelog(lmdb_database(path));
+    }
+
+    #[test]
+    fn test_lmdb_savepoints() {
+        let dir = TempDir::new("exonum_lmdb_savepoints").unwrap();
+        let path = dir.path();
+        super::savepoints(lmdb_database(path));
+    }
+
+    #[test]
+    fn test_lmdb_index_bounds_and_isolation() {
+        let dir = TempDir::new("exonum_lmdb3").unwrap();
+        let db = lmdb_database(dir.path());
+        let mut fork = db.fork();
+        fork.put("a", vec![1], vec![1]);
+        fork.put("a.b", vec![0], vec![2]);
+        fork.put("b", vec![0], vec![3]);
+        db.merge(fork.into_patch()).unwrap();
+
+        let snapshot = db.snapshot();
+        let mut iter = snapshot.iter("a", &[0]);
+        assert_eq!(iter.next(), Some((&[1][..], &[1][..])));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next(), None);
+        assert_eq!(snapshot.iter("a", &[2]).next(), None);
+        // There are no keys after the starting one in the whole database.
+        let mut iter = snapshot.iter("b", &[1]);
+        assert_eq!(iter.peek(), None);
+        assert_eq!(iter.next(), None);
+
+        let mut fork = db.fork();
+        fork.remove("a", vec![1]);
+        db.merge_sync(fork.into_patch()).unwrap();
+        assert_eq!(snapshot.get("a", &[1]), Some(vec![1]));
+        assert_eq!(db.snapshot().get("a", &[1]), None);
+    }
+
+    #[test]
+    fn test_lmdb_long_keys() {
+        let dir = TempDir::new("exonum_lmdb_long_keys").unwrap();
+        let db = lmdb_database(dir.path());
+        // The index name, the separator and the key take 511 bytes.
+        let mut fork = db.fork();
+        fork.put("a", vec![0; 509], vec![1]);
+        db.merge(fork.into_patch()).unwrap();
+
+        let mut fork = db.fork();
+        fork.put("a", vec![0; 510], vec![2]);
+        fork.put("b", vec![0], vec![3]);
+        let err = db.merge(fork.into_patch()).unwrap_err();
+        assert!(err.to_string().contains("Key of the index a is too long"));
+
+        let snapshot = db.snapshot();
+        assert_eq!(snapshot.get("a", &[0; 509]), Some(vec![1]));
+        assert_eq!(snapshot.get("a", &[0; 510]), None);
+        // The patch is rejected as a whole.
+        assert_eq!(snapshot.get("b", &[0]), None);
+    }
+
+    #[test]
+    fn test_lmdb_max_readers() {
+        use std::{sync::Arc, thread, time::Duration};
+
+        let dir = TempDir::new("exonum_lmdb_max_readers").unwrap();
+        let mut options = DbOptions::default();
+        options.lmdb_max_readers = Some(0);
+        assert!(LMDB::open(dir.path(), &options).is_err());
+
+        options.lmdb_max_readers = Some(2);
+        let db = Arc::new(LMDB::open(dir.path(), &options).unwrap());
+        let mut fork = db.fork();
+        fork.put("a", vec![1], vec![1]);
+        db.merge(fork.into_patch()).unwrap();
+
+        let first = db.snapshot();
+        let second = db.snapshot();
+        // The third snapshot waits until one of the reader slots is released.
+        let handle = {
+            let db = Arc::clone(&db);
+            thread::spawn(move || db.snapshot().get("a", &[1]))
+        };
+        thread::sleep(Duration::from_millis(100));
+        drop(first);
+        assert_eq!(handle.join().unwrap(), Some(vec![1]));
+        assert_eq!(second.get("a", &[1]), Some(vec![1]));
+    }
+}
+
 // This should compile to ensure ?Sized bound on `new_in_family` (see #1024).
 #[allow(dead_code, unreachable_code, unused_variables)]
 fn should_compile() {
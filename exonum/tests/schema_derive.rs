@@ -0,0 +1,113 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests for the `Schema` derive from the `exonum-derive` crate.
+
+extern crate exonum;
+#[macro_use]
+extern crate exonum_derive;
+
+use exonum::{
+    crypto::{Hash, PublicKey},
+    storage::{Database, Entry, Fork, MapIndex, MemoryDB, ProofListIndex, ProofMapIndex, Snapshot},
+};
+
+use std::marker::PhantomData;
+
+#[derive(Debug, Schema)]
+#[exonum(service = "test")]
+struct TestSchema<T> {
+    view: T,
+    /// Values by their keys.
+    values: PhantomData<ProofMapIndex<T, PublicKey, u64>>,
+    #[exonum(family = "PublicKey")]
+    history: PhantomData<ProofListIndex<T, Hash>>,
+    log: PhantomData<ProofListIndex<T, u64>>,
+    #[exonum(name = "test_counter")]
+    counter: PhantomData<Entry<T, u64>>,
+}
+
+#[test]
+fn derived_accessors() {
+    let db = MemoryDB::new();
+    let mut fork = db.fork();
+    let key = PublicKey::zero();
+    {
+        let mut schema = TestSchema::new(&mut fork);
+        schema.values_mut().put(&key, 1);
+        schema.history_mut(&key).push(Hash::zero());
+        schema.log_mut().push(2);
+        schema.counter_mut().set(3);
+    }
+    db.merge(fork.into_patch()).unwrap();
+
+    let snapshot = db.snapshot();
+    let schema = TestSchema::new(&snapshot);
+    assert_eq!(schema.values().get(&key), Some(1));
+    assert_eq!(schema.history(&key).len(), 1);
+    assert_eq!(schema.counter().get(), Some(3));
+    assert_eq!(
+        ProofMapIndex::<_, PublicKey, u64>::new("test.values", &snapshot).get(&key),
+        Some(1)
+    );
+    assert_eq!(
+        Entry::<_, u64>::new("test_counter", &snapshot).get(),
+        Some(3)
+    );
+    // Index families are not included into the state hash.
+    assert_eq!(
+        schema.state_hash(),
+        vec![schema.values().merkle_root(), schema.log().merkle_root()]
+    );
+    assert!(schema.check_index_types().is_ok());
+}
+
+#[test]
+fn index_type_mismatch() {
+    let db = MemoryDB::new();
+    let mut fork = db.fork();
+    MapIndex::new("test.log", &mut fork).put(&1_u64, 1_u64);
+    db.merge(fork.into_patch()).unwrap();
+
+    let snapshot: Box<dyn Snapshot> = db.snapshot();
+    let error = TestSchema::try_new(&snapshot).unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("index 'test.log' of type ProofList"));
+}
+
+#[test]
+#[should_panic(expected = "Attempt to access index 'test.log' of type ProofList")]
+fn index_type_mismatch_in_new() {
+    let db = MemoryDB::new();
+    let mut fork = db.fork();
+    MapIndex::new("test.log", &mut fork).put(&1_u64, 1_u64);
+    db.merge(fork.into_patch()).unwrap();
+
+    let snapshot: Box<dyn Snapshot> = db.snapshot();
+    TestSchema::new(&snapshot);
+}
+
+#[test]
+fn mutable_accessors() {
+    fn push_twice(schema: &mut TestSchema<&mut Fork>) {
+        schema.log_mut().push(1);
+        schema.log_mut().push(2);
+    }
+
+    let db = MemoryDB::new();
+    let mut fork = db.fork();
+    push_twice(&mut TestSchema::new(&mut fork));
+    assert_eq!(TestSchema::new(&fork).log().len(), 2);
+}
@@ -0,0 +1,325 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Block headers and precommits in the format returned by the explorer API of a full node.
+//!
+//! Only the fields required for verification are decoded. The block hash is calculated
+//! from the Protobuf encoding of the header, exactly like a full node does.
+
+use hex::FromHex;
+use serde::{de, Deserialize, Deserializer};
+
+use crypto::{self, Hash, PublicKey, Signature, HASH_SIZE, PUBLIC_KEY_LENGTH, SIGNATURE_LENGTH};
+use error::Error;
+
+/// Message class of the consensus messages.
+const CONSENSUS_CLASS: u8 = 1;
+/// Message type of `Precommit` within the consensus messages.
+const PRECOMMIT_TYPE: u8 = 0;
+
+/// Protobuf wire types.
+const WIRE_VARINT: u64 = 0;
+const WIRE_FIXED64: u64 = 1;
+const WIRE_LENGTH_DELIMITED: u64 = 2;
+const WIRE_FIXED32: u64 = 5;
+
+/// Exonum block header.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Block {
+    /// Identifier of the validator which has proposed the block.
+    pub proposer_id: u16,
+    /// Height of the block.
+    pub height: u64,
+    /// Number of transactions in the block.
+    pub tx_count: u32,
+    /// Hash of the previous block.
+    pub prev_hash: Hash,
+    /// Root hash of the Merkle tree of transactions in the block.
+    pub tx_hash: Hash,
+    /// Hash of the blockchain state after applying the block.
+    pub state_hash: Hash,
+}
+
+impl Block {
+    /// Calculates the hash of the block header.
+    pub fn hash(&self) -> Hash {
+        let mut buffer = Vec::new();
+        write_varint_field(&mut buffer, 1, u64::from(self.proposer_id));
+        write_varint_field(&mut buffer, 2, self.height);
+        write_varint_field(&mut buffer, 3, u64::from(self.tx_count));
+        write_hash_field(&mut buffer, 4, &self.prev_hash);
+        write_hash_field(&mut buffer, 5, &self.tx_hash);
+        write_hash_field(&mut buffer, 6, &self.state_hash);
+        crypto::hash(&buffer)
+    }
+}
+
+/// Block header together with the precommits of the validators, as returned by the
+/// explorer API of a full node.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BlockProof {
+    /// Block header.
+    pub block: Block,
+    /// Precommits for the block.
+    pub precommits: Vec<Precommit>,
+}
+
+/// Signed `Precommit` message of a validator.
+///
+/// The message is decoded from its raw representation, and its signature is verified
+/// on creation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Precommit {
+    author: PublicKey,
+    validator: u16,
+    height: u64,
+    round: u32,
+    propose_hash: Hash,
+    block_hash: Hash,
+}
+
+impl Precommit {
+    /// Decodes a signed precommit from its raw representation and verifies its signature.
+    pub fn from_raw(raw: &[u8]) -> Result<Self, Error> {
+        let header_len = PUBLIC_KEY_LENGTH + 2;
+        if raw.len() <= header_len + SIGNATURE_LENGTH {
+            return Err(malformed("message is too short"));
+        }
+
+        let sign_idx = raw.len() - SIGNATURE_LENGTH;
+        let author = PublicKey::from_slice(&raw[..PUBLIC_KEY_LENGTH]).unwrap();
+        let signature = Signature::from_slice(&raw[sign_idx..]).unwrap();
+        if !crypto::verify(&signature, &raw[..sign_idx], &author) {
+            return Err(malformed("invalid signature"));
+        }
+        if raw[PUBLIC_KEY_LENGTH] != CONSENSUS_CLASS || raw[PUBLIC_KEY_LENGTH + 1] != PRECOMMIT_TYPE
+        {
+            return Err(malformed("message is not a precommit"));
+        }
+
+        let mut precommit = Self {
+            author,
+            validator: 0,
+            height: 0,
+            round: 0,
+            propose_hash: Hash::zero(),
+            block_hash: Hash::zero(),
+        };
+        let (mut propose_hash, mut block_hash) = (None, None);
+
+        let mut reader = ProtobufReader::new(&raw[header_len..sign_idx]);
+        while let Some((field, value)) = reader.read_field()? {
+            match (field, value) {
+                (1, FieldValue::Varint(value)) => precommit.validator = value as u16,
+                (2, FieldValue::Varint(value)) => precommit.height = value,
+                (3, FieldValue::Varint(value)) => precommit.round = value as u32,
+                (4, FieldValue::Bytes(bytes)) => propose_hash = Some(read_hash(bytes)?),
+                (5, FieldValue::Bytes(bytes)) => block_hash = Some(read_hash(bytes)?),
+                (1..=5, _) => return Err(malformed("unexpected wire type")),
+                // The precommit time is not used for verification.
+                _ => {}
+            }
+        }
+
+        precommit.propose_hash = propose_hash.ok_or_else(|| malformed("missing propose hash"))?;
+        precommit.block_hash = block_hash.ok_or_else(|| malformed("missing block hash"))?;
+        Ok(precommit)
+    }
+
+    /// Returns the consensus key of the precommit author.
+    pub fn author(&self) -> &PublicKey {
+        &self.author
+    }
+
+    /// Returns the identifier of the validator which has signed the precommit.
+    pub fn validator(&self) -> u16 {
+        self.validator
+    }
+
+    /// Returns the height of the precommitted block.
+    pub fn height(&self) -> u64 {
+        self.height
+    }
+
+    /// Returns the consensus round of the precommit.
+    pub fn round(&self) -> u32 {
+        self.round
+    }
+
+    /// Returns the hash of the proposal.
+    pub fn propose_hash(&self) -> &Hash {
+        &self.propose_hash
+    }
+
+    /// Returns the hash of the precommitted block.
+    pub fn block_hash(&self) -> &Hash {
+        &self.block_hash
+    }
+}
+
+/// Precommits are serialized as objects with the decoded `payload` and the hex-encoded
+/// raw `message`. Only the latter is trusted.
+impl<'de> Deserialize<'de> for Precommit {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct SignedRepr {
+            message: String,
+        }
+
+        let repr = SignedRepr::deserialize(deserializer)?;
+        let raw = Vec::<u8>::from_hex(&repr.message).map_err(de::Error::custom)?;
+        Self::from_raw(&raw).map_err(de::Error::custom)
+    }
+}
+
+fn malformed(msg: &str) -> Error {
+    Error::MalformedPrecommit(msg.to_owned())
+}
+
+/// Reads a `Hash` Protobuf message.
+fn read_hash(message: &[u8]) -> Result<Hash, Error> {
+    let mut reader = ProtobufReader::new(message);
+    let mut hash = None;
+    while let Some((field, value)) = reader.read_field()? {
+        if let (1, FieldValue::Bytes(bytes)) = (field, value) {
+            hash = Hash::from_slice(bytes);
+        }
+    }
+    hash.ok_or_else(|| malformed("invalid hash"))
+}
+
+fn write_varint(buffer: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buffer.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
+
+/// Writes a scalar field; default values are omitted according to the `proto3` rules.
+fn write_varint_field(buffer: &mut Vec<u8>, field: u64, value: u64) {
+    if value != 0 {
+        write_varint(buffer, (field << 3) | WIRE_VARINT);
+        write_varint(buffer, value);
+    }
+}
+
+/// Writes a `Hash` message field.
+fn write_hash_field(buffer: &mut Vec<u8>, field: u64, hash: &Hash) {
+    write_varint(buffer, (field << 3) | WIRE_LENGTH_DELIMITED);
+    write_varint(buffer, HASH_SIZE as u64 + 2);
+    write_varint(buffer, (1 << 3) | WIRE_LENGTH_DELIMITED);
+    write_varint(buffer, HASH_SIZE as u64);
+    buffer.extend_from_slice(hash.as_ref());
+}
+
+enum FieldValue<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed,
+}
+
+/// Minimal reader of the Protobuf wire format.
+struct ProtobufReader<'a> {
+    buffer: &'a [u8],
+}
+
+impl<'a> ProtobufReader<'a> {
+    fn new(buffer: &'a [u8]) -> Self {
+        Self { buffer }
+    }
+
+    fn read_varint(&mut self) -> Result<u64, Error> {
+        let mut value = 0_u64;
+        for (i, byte) in self.buffer.iter().enumerate().take(10) {
+            value |= u64::from(byte & 0x7f) << (7 * i);
+            if byte & 0x80 == 0 {
+                self.buffer = &self.buffer[i + 1..];
+                return Ok(value);
+            }
+        }
+        Err(malformed("invalid varint"))
+    }
+
+    fn read_slice(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.buffer.len() < len {
+            return Err(malformed("unexpected end of message"));
+        }
+        let (slice, rest) = self.buffer.split_at(len);
+        self.buffer = rest;
+        Ok(slice)
+    }
+
+    fn read_field(&mut self) -> Result<Option<(u64, FieldValue<'a>)>, Error> {
+        if self.buffer.is_empty() {
+            return Ok(None);
+        }
+
+        let tag = self.read_varint()?;
+        let value = match tag & 0x07 {
+            WIRE_VARINT => FieldValue::Varint(self.read_varint()?),
+            WIRE_FIXED64 => {
+                self.read_slice(8)?;
+                FieldValue::Fixed
+            }
+            WIRE_LENGTH_DELIMITED => {
+                let len = self.read_varint()? as usize;
+                FieldValue::Bytes(self.read_slice(len)?)
+            }
+            WIRE_FIXED32 => {
+                self.read_slice(4)?;
+                FieldValue::Fixed
+            }
+            _ => return Err(malformed("unsupported wire type")),
+        };
+        Ok(Some((tag >> 3, value)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn varint_roundtrip() {
+        for &value in &[0, 1, 127, 128, 300, u64::from(u32::max_value()), u64::max_value()] {
+            let mut buffer = Vec::new();
+            write_varint(&mut buffer, value);
+            let mut reader = ProtobufReader::new(&buffer);
+            assert_eq!(reader.read_varint().unwrap(), value);
+            assert!(reader.buffer.is_empty());
+        }
+    }
+
+    #[test]
+    fn precommit_with_wrong_signature() {
+        let (public_key, secret_key) = crypto::gen_keypair();
+        let mut raw = public_key.as_ref().to_vec();
+        raw.extend_from_slice(&[CONSENSUS_CLASS, PRECOMMIT_TYPE, 8, 1]);
+        let signature = crypto::sign(&raw, &secret_key);
+        raw.extend_from_slice(signature.as_ref());
+
+        // The hashes are missing from the payload.
+        assert_eq!(
+            Precommit::from_raw(&raw),
+            Err(malformed("missing propose hash"))
+        );
+
+        raw[PUBLIC_KEY_LENGTH + 3] = 2;
+        assert_eq!(Precommit::from_raw(&raw), Err(malformed("invalid signature")));
+    }
+}
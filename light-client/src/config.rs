@@ -0,0 +1,84 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Global configuration of the blockchain.
+//!
+//! The structures mirror the ones of the `exonum` crate field by field, since the hash of
+//! the configuration is calculated from its JSON representation.
+
+use serde_json::{self, Value};
+
+use std::collections::BTreeMap;
+
+use crypto::{self, CryptoHash, Hash, PublicKey};
+
+/// Public keys of a validator.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ValidatorKeys {
+    /// Key used to sign consensus messages.
+    pub consensus_key: PublicKey,
+    /// Key used to sign service transactions.
+    pub service_key: PublicKey,
+}
+
+/// Consensus algorithm parameters.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConsensusConfig {
+    /// Interval between first two rounds.
+    pub first_round_timeout: u64,
+    /// Period of sending a `Status` message.
+    pub status_timeout: u64,
+    /// Peer exchange timeout.
+    pub peers_timeout: u64,
+    /// Maximum number of transactions per block.
+    pub txs_block_limit: u32,
+    /// Maximum message length (in bytes).
+    pub max_message_len: u32,
+    /// Minimal propose timeout.
+    pub min_propose_timeout: u64,
+    /// Maximal propose timeout.
+    pub max_propose_timeout: u64,
+    /// Amount of transactions in pool to start use `min_propose_timeout`.
+    pub propose_timeout_threshold: u32,
+}
+
+/// Global configuration of the blockchain, which is stored in the core `configs` table.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StoredConfiguration {
+    /// Hash of the previous configuration.
+    pub previous_cfg_hash: Hash,
+    /// The height starting from which this configuration becomes actual.
+    pub actual_from: u64,
+    /// Public keys of the validators.
+    pub validator_keys: Vec<ValidatorKeys>,
+    /// Consensus algorithm parameters.
+    pub consensus: ConsensusConfig,
+    /// Configuration of the services.
+    #[serde(default)]
+    pub services: BTreeMap<String, Value>,
+}
+
+impl StoredConfiguration {
+    /// Returns the number of precommits required to approve a block.
+    pub fn majority_count(&self) -> usize {
+        self.validator_keys.len() * 2 / 3 + 1
+    }
+}
+
+impl CryptoHash for StoredConfiguration {
+    fn hash(&self) -> Hash {
+        let bytes = serde_json::to_vec(self).expect("Cannot serialize configuration");
+        crypto::hash(&bytes)
+    }
+}
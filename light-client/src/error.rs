@@ -0,0 +1,68 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Errors returned by the light client.
+
+use std::{error, fmt};
+
+/// An error returned when the data received from a full node cannot be verified.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// A precommit message is malformed or has an invalid signature.
+    MalformedPrecommit(String),
+    /// Precommits do not authenticate the block header.
+    InvalidPrecommit(String),
+    /// The block header is not approved by the supermajority of validators.
+    NoConsensus {
+        /// Number of valid precommits for the block.
+        precommits: usize,
+        /// Number of precommits required for the consensus.
+        required: usize,
+    },
+    /// A Merkle proof is malformed or does not match the expected root hash.
+    InvalidProof(String),
+    /// A configuration cannot follow the latest known configuration.
+    InvalidConfiguration(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::MalformedPrecommit(ref msg) => write!(f, "Malformed precommit: {}", msg),
+            Error::InvalidPrecommit(ref msg) => write!(f, "Invalid precommit: {}", msg),
+            Error::NoConsensus {
+                precommits,
+                required,
+            } => write!(
+                f,
+                "Block has no consensus: {} precommits, {} required",
+                precommits, required
+            ),
+            Error::InvalidProof(ref msg) => write!(f, "Invalid proof: {}", msg),
+            Error::InvalidConfiguration(ref msg) => write!(f, "Invalid configuration: {}", msg),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::MalformedPrecommit(_) => "Malformed precommit",
+            Error::InvalidPrecommit(_) => "Invalid precommit",
+            Error::NoConsensus { .. } => "Block has no consensus",
+            Error::InvalidProof(_) => "Invalid proof",
+            Error::InvalidConfiguration(_) => "Invalid configuration",
+        }
+    }
+}
@@ -0,0 +1,257 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Light client for Exonum blockchains.
+//!
+//! The light client verifies the data received from an untrusted full node without
+//! replicating the blockchain. It depends only on `exonum-crypto`, so it can be used
+//! in environments where the storage and the networking stacks of the node are unavailable.
+//!
+//! The client is initialized with a trusted configuration (usually, the genesis one) and
+//! tracks the validator set through the subsequent `StoredConfiguration` changes, which
+//! are applied with [`apply_configuration`]. Given the validator set, the client can
+//!
+//! - verify that a block header is approved by the supermajority of the validators
+//!   ([`verify_block`]),
+//! - verify a chain of proofs from the `state_hash` of the block down to a service table
+//!   ([`verify_table`]) and to a single entry of this table ([`verify_entry`]).
+//!
+//! All the data is expected in the JSON format used by the API of a full node.
+//!
+//! [`apply_configuration`]: struct.LightClient.html#method.apply_configuration
+//! [`verify_block`]: struct.LightClient.html#method.verify_block
+//! [`verify_table`]: struct.LightClient.html#method.verify_table
+//! [`verify_entry`]: struct.LightClient.html#method.verify_entry
+
+#![deny(missing_debug_implementations, missing_docs, unsafe_code, bare_trait_objects)]
+
+extern crate exonum_crypto as crypto;
+extern crate hex;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+
+pub use block::{Block, BlockProof, Precommit};
+pub use config::{ConsensusConfig, StoredConfiguration, ValidatorKeys};
+pub use error::Error;
+pub use proof::{CheckedMapProof, MapProof, ProofMapKey};
+
+use std::collections::HashSet;
+
+use crypto::{CryptoHash, Hash};
+
+mod block;
+mod config;
+mod error;
+mod proof;
+
+/// Identifier of the core tables in the state hash aggregator.
+pub const CORE_SERVICE: u16 = 0;
+/// Index of the `configs` table among the core tables.
+pub const CORE_CONFIGS_TABLE: usize = 0;
+
+/// Returns the key of a service table in the state hash aggregator.
+///
+/// Mirrors `Blockchain::service_table_unique_key` of the `exonum` crate.
+pub fn service_table_unique_key(service_id: u16, table_idx: usize) -> Hash {
+    debug_assert!(table_idx <= u16::max_value() as usize);
+    let mut bytes = [0; 4];
+    bytes[..2].copy_from_slice(&[service_id as u8, (service_id >> 8) as u8]);
+    bytes[2..].copy_from_slice(&[table_idx as u8, (table_idx >> 8) as u8]);
+    crypto::hash(&bytes)
+}
+
+/// Light client tracking the validator set of the blockchain.
+#[derive(Debug, Clone)]
+pub struct LightClient {
+    // Known configurations in the increasing order of `actual_from`.
+    configs: Vec<StoredConfiguration>,
+}
+
+impl LightClient {
+    /// Creates a light client from the trusted configuration.
+    pub fn new(config: StoredConfiguration) -> Self {
+        Self {
+            configs: vec![config],
+        }
+    }
+
+    /// Returns the configuration actual at the given height.
+    pub fn actual_configuration(&self, height: u64) -> &StoredConfiguration {
+        self.configs
+            .iter()
+            .rev()
+            .find(|config| config.actual_from <= height)
+            .unwrap_or(&self.configs[0])
+    }
+
+    /// Returns the latest known configuration, which may become actual in the future.
+    pub fn latest_configuration(&self) -> &StoredConfiguration {
+        self.configs.last().unwrap()
+    }
+
+    /// Verifies that the block is approved by the supermajority of the validators
+    /// specified in the configuration actual at the block height.
+    ///
+    /// Configurations which have become actual before the block height must be applied
+    /// beforehand, otherwise the block is checked against an outdated validator set.
+    pub fn verify_block(&self, proof: &BlockProof) -> Result<(), Error> {
+        let block = &proof.block;
+        let block_hash = block.hash();
+        let config = self.actual_configuration(block.height);
+
+        let required = config.majority_count();
+        if proof.precommits.len() < required {
+            return Err(Error::NoConsensus {
+                precommits: proof.precommits.len(),
+                required,
+            });
+        }
+
+        let round = proof.precommits[0].round();
+        let mut validators = HashSet::with_capacity(proof.precommits.len());
+        for precommit in &proof.precommits {
+            let keys = config
+                .validator_keys
+                .get(precommit.validator() as usize)
+                .ok_or_else(|| invalid_precommit("precommit from unknown validator"))?;
+            if keys.consensus_key != *precommit.author() {
+                return Err(invalid_precommit("precommit is signed by a wrong key"));
+            }
+            if !validators.insert(precommit.validator()) {
+                return Err(invalid_precommit("several precommits from one validator"));
+            }
+            if *precommit.block_hash() != block_hash
+                || precommit.height() != block.height
+                || precommit.round() != round
+            {
+                return Err(invalid_precommit(
+                    "precommit does not correspond to the block",
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Verifies the block and the proof from its `state_hash` to a service table.
+    ///
+    /// Returns the root hash of the table.
+    pub fn verify_table(
+        &self,
+        block_proof: &BlockProof,
+        service_id: u16,
+        table_idx: usize,
+        to_table: MapProof<Hash, Hash>,
+    ) -> Result<Hash, Error> {
+        self.verify_block(block_proof)?;
+
+        let to_table = to_table.check()?;
+        if to_table.merkle_root() != block_proof.block.state_hash {
+            return Err(Error::InvalidProof(
+                "proof to the table does not match the state hash of the block".to_owned(),
+            ));
+        }
+        to_table
+            .get(&service_table_unique_key(service_id, table_idx))?
+            .cloned()
+            .ok_or_else(|| Error::InvalidProof("table is absent from the state".to_owned()))
+    }
+
+    /// Verifies the block and the chain of proofs from its `state_hash` down to the entry
+    /// of a service table with the given key.
+    ///
+    /// Returns the value of the entry, or `None` if the proof asserts that the key is
+    /// absent from the table.
+    pub fn verify_entry<K, V>(
+        &self,
+        block_proof: &BlockProof,
+        service_id: u16,
+        table_idx: usize,
+        to_table: MapProof<Hash, Hash>,
+        to_entry: MapProof<K, V>,
+        key: &K,
+    ) -> Result<Option<V>, Error>
+    where
+        K: ProofMapKey + PartialEq,
+        V: CryptoHash,
+    {
+        let table_hash = self.verify_table(block_proof, service_id, table_idx, to_table)?;
+
+        let to_entry = to_entry.check()?;
+        if to_entry.merkle_root() != table_hash {
+            return Err(Error::InvalidProof(
+                "proof to the entry does not match the table hash".to_owned(),
+            ));
+        }
+        to_entry
+            .into_entries()
+            .into_iter()
+            .find(|(entry_key, _)| entry_key == key)
+            .map(|(_, value)| value)
+            .ok_or_else(|| {
+                Error::InvalidProof("requested key is not covered by the proof".to_owned())
+            })
+    }
+
+    /// Applies the next configuration of the blockchain.
+    ///
+    /// The configuration must be contained in the core `configs` table at the height of
+    /// the given block, and it must reference the latest configuration known to the client.
+    pub fn apply_configuration(
+        &mut self,
+        block_proof: &BlockProof,
+        to_table: MapProof<Hash, Hash>,
+        to_config: MapProof<Hash, StoredConfiguration>,
+    ) -> Result<(), Error> {
+        let table_hash =
+            self.verify_table(block_proof, CORE_SERVICE, CORE_CONFIGS_TABLE, to_table)?;
+
+        let to_config = to_config.check()?;
+        if to_config.merkle_root() != table_hash {
+            return Err(Error::InvalidProof(
+                "proof to the configuration does not match the table hash".to_owned(),
+            ));
+        }
+        let config = {
+            let mut entries = to_config.entries();
+            match (entries.next(), entries.next()) {
+                (Some((hash, config)), None) if *hash == config.hash() => config.clone(),
+                _ => {
+                    return Err(Error::InvalidProof(
+                        "proof must contain a single configuration".to_owned(),
+                    ))
+                }
+            }
+        };
+
+        let latest = self.latest_configuration();
+        if config.previous_cfg_hash != latest.hash() {
+            return Err(Error::InvalidConfiguration(
+                "configuration does not follow the latest known one".to_owned(),
+            ));
+        }
+        if config.actual_from <= latest.actual_from {
+            return Err(Error::InvalidConfiguration(
+                "configuration becomes actual before the latest known one".to_owned(),
+            ));
+        }
+        self.configs.push(config);
+        Ok(())
+    }
+}
+
+fn invalid_precommit(msg: &str) -> Error {
+    Error::InvalidPrecommit(msg.to_owned())
+}
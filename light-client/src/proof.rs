@@ -0,0 +1,446 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Verification of proofs for `ProofMapIndex` entries.
+//!
+//! The proofs have the same JSON format as `MapProof` in the `exonum` crate. Only keys
+//! which are mapped into the proof paths directly (`Hash` and `PublicKey`) are supported.
+
+use serde::{de, Deserialize, Deserializer};
+
+use std::{cmp::Ordering, fmt};
+
+use crypto::{CryptoHash, Hash, HashStream, PublicKey, HASH_SIZE};
+use error::Error;
+
+const BRANCH_KEY_PREFIX: u8 = 0;
+const LEAF_KEY_PREFIX: u8 = 1;
+const KEY_SIZE: usize = HASH_SIZE;
+const PROOF_PATH_SIZE: usize = KEY_SIZE + 2;
+const PROOF_PATH_LEN_POS: usize = KEY_SIZE + 1;
+
+/// A key of the `ProofMapIndex`.
+pub trait ProofMapKey {
+    /// Returns the bytes of the key, which form the path to the leaf in the Merkle Patricia tree.
+    fn key_bytes(&self) -> [u8; KEY_SIZE];
+}
+
+impl ProofMapKey for Hash {
+    fn key_bytes(&self) -> [u8; KEY_SIZE] {
+        let mut bytes = [0; KEY_SIZE];
+        bytes.copy_from_slice(self.as_ref());
+        bytes
+    }
+}
+
+impl ProofMapKey for PublicKey {
+    fn key_bytes(&self) -> [u8; KEY_SIZE] {
+        let mut bytes = [0; KEY_SIZE];
+        bytes.copy_from_slice(self.as_ref());
+        bytes
+    }
+}
+
+/// Path to a node of the Merkle Patricia tree.
+///
+/// Unlike the `ProofPath` of the `exonum` crate, the path always starts from the root of
+/// the tree, and the bits after its end are always zeroed.
+#[derive(Clone, Copy)]
+struct ProofPath {
+    bytes: [u8; PROOF_PATH_SIZE],
+}
+
+impl ProofPath {
+    fn new(key: &[u8; KEY_SIZE]) -> Self {
+        let mut bytes = [0; PROOF_PATH_SIZE];
+        bytes[0] = LEAF_KEY_PREFIX;
+        bytes[1..=KEY_SIZE].copy_from_slice(key);
+        Self { bytes }
+    }
+
+    fn is_leaf(&self) -> bool {
+        self.bytes[0] == LEAF_KEY_PREFIX
+    }
+
+    fn len(&self) -> u16 {
+        if self.is_leaf() {
+            KEY_SIZE as u16 * 8
+        } else {
+            u16::from(self.bytes[PROOF_PATH_LEN_POS])
+        }
+    }
+
+    fn bit(&self, idx: u16) -> bool {
+        let byte = self.bytes[1 + (idx / 8) as usize];
+        byte & (1 << (idx % 8)) != 0
+    }
+
+    /// Returns the branch path consisting of the first `len` bits of this path.
+    fn prefix(&self, len: u16) -> Self {
+        debug_assert!(len < KEY_SIZE as u16 * 8);
+
+        let mut bytes = [0; PROOF_PATH_SIZE];
+        bytes[0] = BRANCH_KEY_PREFIX;
+        let full_bytes = (len / 8) as usize;
+        bytes[1..=full_bytes].copy_from_slice(&self.bytes[1..=full_bytes]);
+        if len % 8 != 0 {
+            bytes[1 + full_bytes] = self.bytes[1 + full_bytes] & !(255_u8 << (len % 8));
+        }
+        bytes[PROOF_PATH_LEN_POS] = len as u8;
+        Self { bytes }
+    }
+
+    fn common_prefix_len(&self, other: &Self) -> u16 {
+        let max_len = self.len().min(other.len());
+        (0..max_len)
+            .find(|&i| self.bit(i) != other.bit(i))
+            .unwrap_or(max_len)
+    }
+
+    fn starts_with(&self, other: &Self) -> bool {
+        self.common_prefix_len(other) == other.len()
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl PartialEq for ProofPath {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.starts_with(other)
+    }
+}
+
+impl Eq for ProofPath {}
+
+impl PartialOrd for ProofPath {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ProofPath {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let common_len = self.common_prefix_len(other);
+        if common_len < self.len() && common_len < other.len() {
+            self.bit(common_len).cmp(&other.bit(common_len))
+        } else {
+            self.len().cmp(&other.len())
+        }
+    }
+}
+
+impl fmt::Debug for ProofPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for i in 0..self.len() {
+            f.write_str(if self.bit(i) { "1" } else { "0" })?;
+        }
+        Ok(())
+    }
+}
+
+impl<'de> Deserialize<'de> for ProofPath {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = String::deserialize(deserializer)?;
+        let len = value.len();
+        if len == 0 || len > 8 * KEY_SIZE {
+            return Err(de::Error::custom("invalid proof path length"));
+        }
+
+        let mut bytes = [0_u8; KEY_SIZE];
+        for (i, ch) in value.chars().enumerate() {
+            match ch {
+                '0' => {}
+                '1' => bytes[i / 8] |= 1 << (i % 8),
+                _ => return Err(de::Error::custom("invalid character in proof path")),
+            }
+        }
+
+        let path = Self::new(&bytes);
+        Ok(if len == 8 * KEY_SIZE {
+            path
+        } else {
+            path.prefix(len as u16)
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+struct MapProofEntry {
+    path: ProofPath,
+    hash: Hash,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum OptionalEntry<K, V> {
+    Missing { missing: K },
+    KV { key: K, value: V },
+}
+
+impl<K, V> OptionalEntry<K, V> {
+    fn key(&self) -> &K {
+        match *self {
+            OptionalEntry::Missing { ref missing } => missing,
+            OptionalEntry::KV { ref key, .. } => key,
+        }
+    }
+}
+
+/// Proof of existence or absence of entries in a `ProofMapIndex`.
+///
+/// The proof is deserialized from the JSON produced by a full node and verified with
+/// the [`check()`] method.
+///
+/// [`check()`]: #method.check
+#[derive(Debug, Clone, Deserialize)]
+pub struct MapProof<K, V> {
+    entries: Vec<OptionalEntry<K, V>>,
+    proof: Vec<MapProofEntry>,
+}
+
+/// Version of `MapProof` obtained after verification.
+#[derive(Debug, Clone)]
+pub struct CheckedMapProof<K, V> {
+    entries: Vec<(K, Option<V>)>,
+    hash: Hash,
+}
+
+impl<K, V> MapProof<K, V>
+where
+    K: ProofMapKey,
+    V: CryptoHash,
+{
+    /// Checks the consistency of the proof and calculates the root hash of the index.
+    ///
+    /// The values are hashed with their `CryptoHash` implementation, which must coincide
+    /// with the implementation of the values stored in the index.
+    pub fn check(self) -> Result<CheckedMapProof<K, V>, Error> {
+        for window in self.proof.windows(2) {
+            let (prev_path, path) = (&window[0].path, &window[1].path);
+            if prev_path >= path {
+                return Err(invalid_proof("invalid path ordering"));
+            }
+            if path.starts_with(prev_path) {
+                return Err(invalid_proof("embedded paths in proof"));
+            }
+        }
+
+        let mut proof = self.proof;
+        let proof_len = proof.len();
+        for entry in &self.entries {
+            let path = ProofPath::new(&entry.key().key_bytes());
+            if proof[..proof_len].iter().any(|e| path.starts_with(&e.path)) {
+                return Err(invalid_proof("embedded paths in proof"));
+            }
+            if let OptionalEntry::KV { ref value, .. } = *entry {
+                proof.push(MapProofEntry {
+                    path,
+                    hash: value.hash(),
+                });
+            }
+        }
+
+        proof.sort_unstable_by(|x, y| x.path.cmp(&y.path));
+        for window in proof.windows(2) {
+            if window[0].path == window[1].path {
+                return Err(invalid_proof("duplicate path in proof"));
+            }
+        }
+
+        let hash = collect(&proof)?;
+        let entries = self
+            .entries
+            .into_iter()
+            .map(|entry| match entry {
+                OptionalEntry::Missing { missing } => (missing, None),
+                OptionalEntry::KV { key, value } => (key, Some(value)),
+            }).collect();
+        Ok(CheckedMapProof { entries, hash })
+    }
+}
+
+impl<K, V> CheckedMapProof<K, V> {
+    /// Returns the entries which exist in the index according to the proof.
+    pub fn entries(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.entries
+            .iter()
+            .filter_map(|(key, value)| value.as_ref().map(|value| (key, value)))
+    }
+
+    /// Returns the keys which are absent from the index according to the proof.
+    pub fn missing_keys(&self) -> impl Iterator<Item = &K> {
+        self.entries
+            .iter()
+            .filter_map(|(key, value)| if value.is_none() { Some(key) } else { None })
+    }
+
+    /// Returns the root hash of the index calculated from the proof.
+    pub fn merkle_root(&self) -> Hash {
+        self.hash
+    }
+
+    /// Converts the proof into the list of entries; absent keys have `None` values.
+    pub fn into_entries(self) -> Vec<(K, Option<V>)> {
+        self.entries
+    }
+
+    /// Returns the value for the given key, checking that the key is covered by the proof.
+    pub fn get(&self, key: &K) -> Result<Option<&V>, Error>
+    where
+        K: PartialEq,
+    {
+        self.entries
+            .iter()
+            .find(|(entry_key, _)| entry_key == key)
+            .map(|(_, value)| value.as_ref())
+            .ok_or_else(|| invalid_proof("requested key is not covered by the proof"))
+    }
+}
+
+fn invalid_proof(msg: &str) -> Error {
+    Error::InvalidProof(msg.to_owned())
+}
+
+/// Computes the root hash of the Merkle Patricia tree from the entries sorted
+/// by their paths. See `exonum::storage::proof_map_index` for the description
+/// of the algorithm.
+fn collect(entries: &[MapProofEntry]) -> Result<Hash, Error> {
+    fn hash_branch(left: &MapProofEntry, right: &MapProofEntry) -> Hash {
+        HashStream::new()
+            .update(left.hash.as_ref())
+            .update(right.hash.as_ref())
+            .update(left.path.as_bytes())
+            .update(right.path.as_bytes())
+            .hash()
+    }
+
+    fn common_prefix(x: &ProofPath, y: &ProofPath) -> ProofPath {
+        x.prefix(x.common_prefix_len(y))
+    }
+
+    fn fold(contour: &mut Vec<MapProofEntry>, last_prefix: ProofPath) -> Option<ProofPath> {
+        let last_entry = contour.pop().unwrap();
+        let penultimate_entry = contour.pop().unwrap();
+        contour.push(MapProofEntry {
+            path: last_prefix,
+            hash: hash_branch(&penultimate_entry, &last_entry),
+        });
+
+        if contour.len() > 1 {
+            let penultimate_entry = contour[contour.len() - 2];
+            Some(common_prefix(&penultimate_entry.path, &last_prefix))
+        } else {
+            None
+        }
+    }
+
+    match entries.len() {
+        0 => Ok(Hash::zero()),
+
+        1 => {
+            let entry = &entries[0];
+            if entry.path.is_leaf() {
+                Ok(HashStream::new()
+                    .update(entry.path.as_bytes())
+                    .update(entry.hash.as_ref())
+                    .hash())
+            } else {
+                Err(invalid_proof("non-terminal node as a single key in proof"))
+            }
+        }
+
+        _ => {
+            let mut contour = vec![entries[0], entries[1]];
+            let mut last_prefix = common_prefix(&entries[0].path, &entries[1].path);
+
+            for entry in entries.iter().skip(2) {
+                let new_prefix = common_prefix(&contour.last().unwrap().path, &entry.path);
+                while contour.len() > 1 && new_prefix.len() < last_prefix.len() {
+                    if let Some(prefix) = fold(&mut contour, last_prefix) {
+                        last_prefix = prefix;
+                    }
+                }
+                contour.push(*entry);
+                last_prefix = new_prefix;
+            }
+
+            while contour.len() > 1 {
+                if let Some(prefix) = fold(&mut contour, last_prefix) {
+                    last_prefix = prefix;
+                }
+            }
+            Ok(contour[0].hash)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+
+    use super::*;
+    use crypto::hash;
+
+    #[test]
+    fn proof_path_ordering() {
+        let path = ProofPath::new(&[0b1010_0101; KEY_SIZE]);
+        assert!(path.prefix(3) < path);
+        assert!(path.prefix(3) < path.prefix(4));
+        assert_eq!(path.prefix(5).common_prefix_len(&path), 5);
+
+        let other = ProofPath::new(&[0b1010_0111; KEY_SIZE]);
+        assert!(path < other);
+        assert_eq!(path.common_prefix_len(&other), 1);
+        assert_eq!(other.prefix(1), path.prefix(1));
+    }
+
+    #[test]
+    fn proof_path_deserialization() {
+        let path: ProofPath = serde_json::from_str("\"1011\"").unwrap();
+        assert_eq!(path.len(), 4);
+        assert_eq!(path.as_bytes()[1], 0b1101);
+        assert!(serde_json::from_str::<ProofPath>("\"12\"").is_err());
+        assert!(serde_json::from_str::<ProofPath>("\"\"").is_err());
+    }
+
+    #[test]
+    fn empty_and_single_entry_proofs() {
+        let proof: MapProof<Hash, Hash> =
+            serde_json::from_str(r#"{ "entries": [], "proof": [] }"#).unwrap();
+        assert_eq!(proof.check().unwrap().merkle_root(), Hash::zero());
+
+        let (key, value) = (hash(&[1]), hash(&[2]));
+        let json = format!(
+            r#"{{ "entries": [{{ "key": {}, "value": {} }}], "proof": [] }}"#,
+            serde_json::to_string(&key).unwrap(),
+            serde_json::to_string(&value).unwrap()
+        );
+        let checked = serde_json::from_str::<MapProof<Hash, Hash>>(&json)
+            .unwrap()
+            .check()
+            .unwrap();
+        let expected_root = HashStream::new()
+            .update(ProofPath::new(&key.key_bytes()).as_bytes())
+            .update(value.as_ref())
+            .hash();
+        assert_eq!(checked.merkle_root(), expected_root);
+        assert_eq!(checked.get(&key).unwrap(), Some(&value));
+        assert!(checked.get(&value).is_err());
+    }
+}
@@ -0,0 +1,203 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Checks the light client against the data produced by a full node.
+
+extern crate exonum;
+extern crate exonum_light_client as light_client;
+extern crate exonum_testkit;
+extern crate serde;
+extern crate serde_json;
+
+use exonum::{
+    blockchain::{Blockchain, Schema, CORE_SERVICE},
+    crypto::{self, CryptoHash},
+    helpers::Height,
+};
+use exonum_testkit::{TestKit, TestKitBuilder};
+use serde::{de::DeserializeOwned, Serialize};
+
+use light_client::{BlockProof, Error, LightClient, StoredConfiguration};
+
+/// Passes the value through JSON, like the data received from the node API.
+fn convert<T: Serialize, U: DeserializeOwned>(value: &T) -> U {
+    serde_json::from_value(serde_json::to_value(value).unwrap()).unwrap()
+}
+
+fn genesis_client(testkit: &TestKit) -> LightClient {
+    let config = Schema::new(&testkit.snapshot()).configuration_by_height(Height(0));
+    LightClient::new(convert(&config))
+}
+
+fn block_proof(testkit: &TestKit, height: Height) -> BlockProof {
+    let proof = Schema::new(&testkit.snapshot())
+        .block_and_precommits(height)
+        .unwrap();
+    convert(&proof)
+}
+
+#[test]
+fn hashes_match_full_node() {
+    let mut testkit = TestKitBuilder::validator().with_validators(4).create();
+    testkit.create_blocks_until(Height(2));
+
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    let config = schema.actual_configuration();
+    let light_config: StoredConfiguration = convert(&config);
+    assert_eq!(light_config.hash(), config.hash());
+
+    let block = schema.block_and_precommits(Height(2)).unwrap().block;
+    assert_eq!(block_proof(&testkit, Height(2)).block.hash(), block.hash());
+
+    for idx in 0..2 {
+        assert_eq!(
+            light_client::service_table_unique_key(CORE_SERVICE, idx),
+            Blockchain::service_table_unique_key(CORE_SERVICE, idx)
+        );
+    }
+}
+
+#[test]
+fn verify_block_precommits() {
+    let mut testkit = TestKitBuilder::validator().with_validators(4).create();
+    testkit.create_blocks_until(Height(3));
+    let client = genesis_client(&testkit);
+
+    let mut proof = block_proof(&testkit, Height(3));
+    client.verify_block(&proof).unwrap();
+
+    let mut forged = proof.clone();
+    forged.block.tx_count += 1;
+    match client.verify_block(&forged) {
+        Err(Error::InvalidPrecommit(_)) => {}
+        other => panic!("Unexpected result: {:?}", other),
+    }
+
+    let mut duplicated = proof.clone();
+    duplicated.precommits[1] = duplicated.precommits[0].clone();
+    match client.verify_block(&duplicated) {
+        Err(Error::InvalidPrecommit(_)) => {}
+        other => panic!("Unexpected result: {:?}", other),
+    }
+
+    proof.precommits.truncate(2);
+    assert_eq!(
+        client.verify_block(&proof),
+        Err(Error::NoConsensus {
+            precommits: 2,
+            required: 3,
+        })
+    );
+}
+
+#[test]
+fn verify_table_entry() {
+    let mut testkit = TestKitBuilder::validator().with_validators(4).create();
+    testkit.create_blocks_until(Height(2));
+    let client = genesis_client(&testkit);
+    let proof = block_proof(&testkit, Height(2));
+
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    let config_hash = schema.actual_configuration().hash();
+    let to_table = schema.get_proof_to_service_table(CORE_SERVICE, 0);
+
+    let config: Option<StoredConfiguration> = client
+        .verify_entry(
+            &proof,
+            CORE_SERVICE,
+            0,
+            convert(&to_table),
+            convert(&schema.configs().get_proof(config_hash)),
+            &config_hash,
+        ).unwrap();
+    assert_eq!(config.unwrap().hash(), config_hash);
+
+    let missing_hash = crypto::hash(&[1, 2, 3]);
+    let missing: Option<StoredConfiguration> = client
+        .verify_entry(
+            &proof,
+            CORE_SERVICE,
+            0,
+            convert(&to_table),
+            convert(&schema.configs().get_proof(missing_hash)),
+            &missing_hash,
+        ).unwrap();
+    assert!(missing.is_none());
+
+    // The proof for a different table does not match the entry.
+    let result = client.verify_entry::<_, StoredConfiguration>(
+        &proof,
+        CORE_SERVICE,
+        1,
+        convert(&schema.get_proof_to_service_table(CORE_SERVICE, 1)),
+        convert(&schema.configs().get_proof(config_hash)),
+        &config_hash,
+    );
+    match result {
+        Err(Error::InvalidProof(_)) => {}
+        other => panic!("Unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn track_validator_set_changes() {
+    let mut testkit = TestKitBuilder::auditor().with_validators(4).create();
+    let mut client = genesis_client(&testkit);
+
+    let proposal = {
+        let mut cfg = testkit.configuration_change_proposal();
+        let validators = cfg.validators()[1..].to_vec();
+        cfg.set_validators(validators);
+        cfg.set_actual_from(Height(5));
+        cfg
+    };
+    let stored = proposal.stored_configuration().clone();
+    testkit.commit_configuration_change(proposal);
+    testkit.create_block();
+
+    {
+        let height = testkit.height();
+        let snapshot = testkit.snapshot();
+        let schema = Schema::new(&snapshot);
+        let to_table = convert(&schema.get_proof_to_service_table(CORE_SERVICE, 0));
+        let to_config = convert(&schema.configs().get_proof(stored.hash()));
+        client
+            .apply_configuration(&block_proof(&testkit, height), to_table, to_config)
+            .unwrap();
+    }
+    assert_eq!(client.latest_configuration().hash(), stored.hash());
+    assert_eq!(client.actual_configuration(4).validator_keys.len(), 4);
+    assert_eq!(client.actual_configuration(5).validator_keys.len(), 3);
+
+    testkit.create_blocks_until(Height(6));
+    let proof = block_proof(&testkit, Height(6));
+    client.verify_block(&proof).unwrap();
+    // The validator identifiers have been shifted, so the outdated client rejects the block.
+    assert!(genesis_client(&testkit).verify_block(&proof).is_err());
+
+    // The same configuration cannot be applied twice.
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    let result = client.apply_configuration(
+        &proof,
+        convert(&schema.get_proof_to_service_table(CORE_SERVICE, 0)),
+        convert(&schema.configs().get_proof(stored.hash())),
+    );
+    match result {
+        Err(Error::InvalidConfiguration(_)) => {}
+        other => panic!("Unexpected result: {:?}", other),
+    }
+}
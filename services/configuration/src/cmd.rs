@@ -210,6 +210,9 @@ pub fn generate_testnet_config(
             mempool: Default::default(),
             services_configs: service_config.clone(),
             database: Default::default(),
+            pruning: Default::default(),
+            state_history: Default::default(),
+            state_sync: Default::default(),
             thread_pool_size: Default::default(),
         }).collect::<Vec<_>>()
 }
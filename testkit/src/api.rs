@@ -271,6 +271,11 @@ where
             StatusCode::FORBIDDEN => Err(api::Error::Unauthorized),
             StatusCode::BAD_REQUEST => Err(api::Error::BadRequest(error(response))),
             StatusCode::NOT_FOUND => Err(api::Error::NotFound(error(response))),
+            StatusCode::GONE => Err(api::Error::Gone(error(response))),
+            StatusCode::TOO_MANY_REQUESTS => Err(api::Error::TooManyRequests(error(response))),
+            StatusCode::SERVICE_UNAVAILABLE => {
+                Err(api::Error::ServiceUnavailable(error(response)))
+            }
             s if s.is_server_error() => Err(api::Error::InternalError(format_err!(
                 "{}",
                 error(response)
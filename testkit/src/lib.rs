@@ -180,7 +180,7 @@ use exonum::{
     explorer::{BlockWithTransactions, BlockchainExplorer},
     helpers::{Height, ValidatorId},
     messages::{RawTransaction, Signed},
-    node::{ApiSender, ExternalMessage, State as NodeState},
+    node::{ApiSender, ExternalMessage, State as NodeState, StateHistoryConfig},
     storage::{MemoryDB, Patch, Snapshot},
 };
 
@@ -282,6 +282,7 @@ pub struct TestKitBuilder {
     validator_count: Option<u16>,
     services: Vec<Box<dyn Service>>,
     logger: bool,
+    state_history: Option<StateHistoryConfig>,
 }
 
 impl fmt::Debug for TestKitBuilder {
@@ -301,6 +302,7 @@ impl fmt::Debug for TestKitBuilder {
                     .map(|x| x.service_name())
                     .collect::<Vec<_>>(),
             ).field("logger", &self.logger)
+            .field("state_history", &self.state_history)
             .finish()
     }
 }
@@ -313,6 +315,7 @@ impl TestKitBuilder {
             our_validator_id: Some(ValidatorId(0)),
             services: Vec::new(),
             logger: false,
+            state_history: None,
         }
     }
 
@@ -323,6 +326,7 @@ impl TestKitBuilder {
             our_validator_id: None,
             services: Vec::new(),
             logger: false,
+            state_history: None,
         }
     }
 
@@ -351,6 +355,13 @@ impl TestKitBuilder {
         self
     }
 
+    /// Enables recording of the state history, which allows to test the service endpoints
+    /// reading the blockchain state at past heights with `Blockchain::snapshot_at`.
+    pub fn with_state_history(mut self, config: StateHistoryConfig) -> Self {
+        self.state_history = Some(config);
+        self
+    }
+
     /// Creates the testkit.
     pub fn create(self) -> TestKit {
         if self.logger {
@@ -360,6 +371,7 @@ impl TestKitBuilder {
         TestKit::assemble(
             self.services,
             TestNetwork::with_our_role(self.our_validator_id, self.validator_count.unwrap_or(1)),
+            self.state_history,
         )
     }
 
@@ -406,7 +418,11 @@ impl TestKit {
         TestKitBuilder::validator().with_service(service).create()
     }
 
-    fn assemble(services: Vec<Box<dyn Service>>, network: TestNetwork) -> Self {
+    fn assemble(
+        services: Vec<Box<dyn Service>>,
+        network: TestNetwork,
+        state_history: Option<StateHistoryConfig>,
+    ) -> Self {
         let api_channel = mpsc::channel(1_000);
         let api_sender = ApiSender::new(api_channel.0.clone());
 
@@ -420,6 +436,9 @@ impl TestKit {
             network.us().service_keypair().1.clone(),
             api_sender.clone(),
         );
+        if let Some(state_history) = state_history {
+            blockchain.set_state_history_config(state_history);
+        }
 
         let genesis = network.genesis_config();
         blockchain.initialize(genesis.clone()).unwrap();
@@ -428,20 +447,17 @@ impl TestKit {
             let mut blockchain = blockchain.clone();
             Box::new(api_channel.1.and_then(move |event| {
                 let mut fork = blockchain.fork();
-                {
-                    let mut schema = CoreSchema::new(&mut fork);
-                    match event {
-                        ExternalMessage::Transaction(tx) => {
-                            let hash = tx.hash();
-                            if !schema.transactions().contains(&hash) {
-                                schema.add_transaction_into_pool(tx.clone());
-                            }
+                match event {
+                    ExternalMessage::Transaction(tx) => {
+                        let hash = tx.hash();
+                        if !CoreSchema::new(&fork).transactions().contains(&hash) {
+                            blockchain.add_transaction_into_pool(&mut fork, tx.clone());
                         }
-                        ExternalMessage::PeerAdd(_)
-                        | ExternalMessage::Enable(_)
-                        | ExternalMessage::Rebroadcast
-                        | ExternalMessage::Shutdown => { /* Ignored */ }
                     }
+                    ExternalMessage::PeerAdd(_)
+                    | ExternalMessage::Enable(_)
+                    | ExternalMessage::Rebroadcast
+                    | ExternalMessage::Shutdown => { /* Ignored */ }
                 }
                 blockchain.merge(fork.into_patch()).unwrap();
                 Ok(())
@@ -702,25 +718,27 @@ impl TestKit {
         let tx_hashes: Vec<_> = {
             let blockchain = self.blockchain_mut();
             let mut fork = blockchain.fork();
-            let hashes = {
-                let mut schema = CoreSchema::new(&mut fork);
-
-                txs.into_iter()
-                    .map(|tx| {
-                        let tx_id = tx.hash();
-                        let tx_not_found = !schema.transactions().contains(&tx_id);
-                        let tx_in_pool = schema.transactions_pool().contains(&tx_id);
-                        assert!(
-                            tx_not_found || tx_in_pool,
-                            "Transaction is already committed: {:?}",
-                            tx
-                        );
-                        if tx_not_found {
-                            schema.add_transaction_into_pool(tx.clone());
-                        }
-                        tx_id
-                    }).collect()
-            };
+            let hashes: Vec<_> = txs
+                .into_iter()
+                .map(|tx| {
+                    let tx_id = tx.hash();
+                    let (tx_not_found, tx_in_pool) = {
+                        let schema = CoreSchema::new(&fork);
+                        (
+                            !schema.transactions().contains(&tx_id),
+                            schema.transactions_pool().contains(&tx_id),
+                        )
+                    };
+                    assert!(
+                        tx_not_found || tx_in_pool,
+                        "Transaction is already committed: {:?}",
+                        tx
+                    );
+                    if tx_not_found {
+                        blockchain.add_transaction_into_pool(&mut fork, tx.clone());
+                    }
+                    tx_id
+                }).collect();
             blockchain.merge(fork.into_patch()).unwrap();
             hashes
         };
@@ -772,7 +790,8 @@ impl TestKit {
         self.do_create_block(tx_hashes)
     }
 
-    /// Creates block with all transactions in the pool.
+    /// Creates block with all transactions in the pool. The transactions are ordered
+    /// by their priority in the same way as by the leader of the consensus round.
     ///
     /// # Return value
     ///
@@ -782,8 +801,7 @@ impl TestKit {
 
         let snapshot = self.blockchain.snapshot();
         let schema = CoreSchema::new(&snapshot);
-        let txs = schema.transactions_pool();
-        let tx_hashes: Vec<_> = txs.iter().collect();
+        let tx_hashes = schema.transactions_pool_ordered(schema.transactions_pool_len() as usize);
         {
             let blockchain = self.blockchain_mut();
             let fork = blockchain.fork();
@@ -795,8 +813,7 @@ impl TestKit {
     /// Adds transaction into persistent pool.
     pub fn add_tx(&mut self, transaction: Signed<RawTransaction>) {
         let mut fork = self.blockchain.fork();
-        let mut schema = CoreSchema::new(&mut fork);
-        schema.add_transaction_into_pool(transaction)
+        self.blockchain.add_transaction_into_pool(&mut fork, transaction)
     }
 
     /// Checks if transaction can be found in pool